@@ -13,7 +13,7 @@
 //!
 //! The implementation is heavily inspired by https://github.com/boa-dev/boa/blob/main/boa_runtime/src/console/mod.rs
 
-use std::ops::Deref;
+use std::{cell::RefCell, collections::HashMap, ops::Deref, rc::Rc, str::FromStr};
 
 use boa_engine::{
     js_string,
@@ -31,7 +31,7 @@ use tezos_smart_rollup::prelude::debug_msg;
 
 pub const LOG_PREFIX: &str = "[JSTZ:SMART_FUNCTION:LOG] ";
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LogRecord {
     pub contract_address: PublicKeyHash,
     pub request_id: String,
@@ -49,9 +49,73 @@ impl LogRecord {
     }
 }
 
+/// Collects the log records of the smart functions run by an operation.
+/// Clones share the same records.
+#[derive(Debug, Default, Clone)]
+pub struct LogBuffer(Rc<RefCell<Vec<LogRecord>>>);
+
+impl Finalize for LogBuffer {}
+
+unsafe impl Trace for LogBuffer {
+    empty_trace!();
+}
+
+impl LogBuffer {
+    pub fn push(&self, record: LogRecord) {
+        self.0.borrow_mut().push(record)
+    }
+
+    /// Returns a checkpoint that the buffer can be rolled back to
+    pub fn checkpoint(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    /// Discards the records collected since `checkpoint`
+    pub fn rollback_to(&self, checkpoint: usize) {
+        self.0.borrow_mut().truncate(checkpoint)
+    }
+
+    /// Removes and returns the records collected so far
+    pub fn take(&self) -> Vec<LogRecord> {
+        std::mem::take(&mut self.0.borrow_mut())
+    }
+}
+
+/// The severity of a log message. Messages below the minimum level of a
+/// console are dropped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// `console.debug`
+    #[default]
+    Debug,
+    /// `console.log`, `console.info` and `console.group`
+    Info,
+    /// `console.warn`
+    Warn,
+    /// `console.error` and failed `console.assert`s
+    Error,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(format!(
+                "Invalid log level '{s}', expected one of: debug, info, warn, error"
+            )),
+        }
+    }
+}
+
 /// This represents the different types of log messages.
 #[derive(Debug)]
 enum LogMessage {
+    Debug(String),
     Log(String),
     Info(String),
     Warn(String),
@@ -60,23 +124,37 @@ enum LogMessage {
 
 impl LogMessage {
     fn log(self, rt: &impl HostRuntime, console: &Console) {
+        if !console.is_enabled(self.log_level()) {
+            return;
+        }
+
         match console {
             Console::Proto {
                 groups,
                 contract_address,
                 operation_hash,
+                logs,
+                ..
             } => {
-                let indent = 2 * groups.len();
+                let indent = " ".repeat(2 * groups.len());
+                let text = self
+                    .message()
+                    .split('\n')
+                    .map(|line| indent.clone() + line)
+                    .collect::<Vec<_>>()
+                    .join("\n");
                 let log_record = LogRecord {
                     contract_address: contract_address.clone(),
                     request_id: operation_hash.to_string(),
                     level: self.level().to_string(),
-                    text: " ".repeat(indent) + self.message(),
-                }
-                .to_string();
-                rt.write_debug(&(LOG_PREFIX.to_string() + &log_record + "\n"));
+                    text,
+                };
+                rt.write_debug(
+                    &(LOG_PREFIX.to_string() + &log_record.to_string() + "\n"),
+                );
+                logs.push(log_record);
             }
-            Console::Cli { groups } => {
+            Console::Cli { groups, .. } => {
                 let indent = 2 * groups.len();
                 let symbol = self.symbol();
                 for line in self.message().lines() {
@@ -88,6 +166,7 @@ impl LogMessage {
 
     fn message(&self) -> &str {
         match self {
+            LogMessage::Debug(msg) => msg,
             LogMessage::Error(msg) => msg,
             LogMessage::Warn(msg) => msg,
             LogMessage::Info(msg) => msg,
@@ -101,6 +180,7 @@ impl LogMessage {
             LogMessage::Warn(_) => '🟠',
             LogMessage::Info(_) => '🟢',
             LogMessage::Log(_) => '🪵',
+            LogMessage::Debug(_) => '🐛',
         }
     }
 
@@ -110,6 +190,16 @@ impl LogMessage {
             LogMessage::Warn(_) => "warn",
             LogMessage::Info(_) => "info",
             LogMessage::Log(_) => "log",
+            LogMessage::Debug(_) => "debug",
+        }
+    }
+
+    fn log_level(&self) -> LogLevel {
+        match self {
+            LogMessage::Error(_) => LogLevel::Error,
+            LogMessage::Warn(_) => LogLevel::Warn,
+            LogMessage::Info(_) | LogMessage::Log(_) => LogLevel::Info,
+            LogMessage::Debug(_) => LogLevel::Debug,
         }
     }
 }
@@ -204,13 +294,18 @@ enum Console {
     // Json log
     Proto {
         groups: Vec<String>,
+        counts: HashMap<String, u32>,
         // TODO: Remove these once `Jstz` object is implemented
         contract_address: PublicKeyHash,
         operation_hash: Blake2b,
+        level: LogLevel,
+        logs: LogBuffer,
     },
     // pretty log
     Cli {
         groups: Vec<String>,
+        counts: HashMap<String, u32>,
+        level: LogLevel,
     },
 }
 
@@ -222,9 +317,25 @@ impl Console {
     fn groups(&mut self) -> &mut Vec<String> {
         match self {
             Console::Proto { groups, .. } => groups,
-            Console::Cli { groups } => groups,
+            Console::Cli { groups, .. } => groups,
+        }
+    }
+
+    fn counts(&mut self) -> &mut HashMap<String, u32> {
+        match self {
+            Console::Proto { counts, .. } | Console::Cli { counts, .. } => counts,
         }
     }
+
+    /// Returns `true` if messages of `level` are printed
+    fn is_enabled(&self, level: LogLevel) -> bool {
+        let min_level = match self {
+            Console::Proto { level, .. } | Console::Cli { level, .. } => *level,
+        };
+
+        level >= min_level
+    }
+
     /// `console.clear()`
     ///
     /// Removes all groups and clears console if possible.
@@ -291,7 +402,7 @@ impl Console {
         rt: &impl HostRuntime,
         context: &mut Context<'_>,
     ) -> JsResult<()> {
-        LogMessage::Log(formatter(data, context)?).log(rt, self);
+        LogMessage::Debug(formatter(data, context)?).log(rt, self);
         Ok(())
     }
 
@@ -410,6 +521,51 @@ impl Console {
     fn group_end(&mut self) {
         self.groups().pop();
     }
+
+    /// `console.count(label)`
+    ///
+    /// Prints the number of times `console.count(label)` has been called with
+    /// the given label.
+    ///
+    /// More information:
+    ///  - [MDN documentation][mdn]
+    ///  - [WHATWG `console` specification][spec]
+    ///
+    /// [spec]: https://console.spec.whatwg.org/#count
+    /// [mdn]: https://developer.mozilla.org/en-US/docs/Web/API/console/count
+    fn count(&mut self, label: String, rt: &impl HostRuntime) {
+        let count = self.counts().entry(label.clone()).or_insert(0);
+        *count += 1;
+
+        let message = format!("{label}: {count}");
+        LogMessage::Info(message).log(rt, self)
+    }
+
+    /// `console.countReset(label)`
+    ///
+    /// Resets the counter of `label`.
+    ///
+    /// More information:
+    ///  - [MDN documentation][mdn]
+    ///  - [WHATWG `console` specification][spec]
+    ///
+    /// [spec]: https://console.spec.whatwg.org/#countreset
+    /// [mdn]: https://developer.mozilla.org/en-US/docs/Web/API/console/countReset
+    fn count_reset(&mut self, label: String, rt: &impl HostRuntime) {
+        match self.counts().get_mut(&label) {
+            Some(count) => *count = 0,
+            None => LogMessage::Warn(format!("Count for '{label}' does not exist"))
+                .log(rt, self),
+        }
+    }
+}
+
+/// Returns the label passed to `console.count()` and `console.countReset()`
+fn count_label(args: &[JsValue], context: &mut Context<'_>) -> JsResult<String> {
+    match args.get_or_undefined(0) {
+        JsValue::Undefined => Ok("default".to_string()),
+        label => Ok(label.to_string(context)?.to_std_string_escaped()),
+    }
 }
 
 /// `ConsoleApi` implements `jstz_core::host::Api`, permitting it to be registered
@@ -419,8 +575,52 @@ pub enum ConsoleApi {
     Proto {
         contract_address: PublicKeyHash,
         operation_hash: Blake2b,
+        level: LogLevel,
+        /// The buffer into which log records are collected
+        logs: LogBuffer,
     },
-    Cli,
+    Cli {
+        level: LogLevel,
+    },
+}
+
+/// The kind of console registered for a smart function, chosen by the caller
+/// that runs it. This lets the smart functions called from the CLI keep the CLI
+/// console, rather than reverting to the protocol's console.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleKind {
+    /// A [`ConsoleApi::Proto`] console
+    #[default]
+    Proto,
+    /// A [`ConsoleApi::Cli`] console showing messages from `level` upwards
+    Cli { level: LogLevel },
+}
+
+impl Finalize for ConsoleKind {}
+
+unsafe impl Trace for ConsoleKind {
+    empty_trace!();
+}
+
+impl ConsoleKind {
+    /// Returns the console API of this kind for the smart function at
+    /// `contract_address`, run by the operation `operation_hash`
+    pub fn api(
+        self,
+        contract_address: PublicKeyHash,
+        operation_hash: Blake2b,
+        logs: LogBuffer,
+    ) -> ConsoleApi {
+        match self {
+            ConsoleKind::Proto => ConsoleApi::Proto {
+                contract_address,
+                operation_hash,
+                level: LogLevel::default(),
+                logs,
+            },
+            ConsoleKind::Cli { level } => ConsoleApi::Cli { level },
+        }
+    }
 }
 
 impl Console {
@@ -500,6 +700,30 @@ impl ConsoleApi {
         Ok(JsValue::undefined())
     }
 
+    fn count(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let mut console = Console::from_js_value(this)?;
+        let label = count_label(args, context)?;
+
+        runtime::with_global_host(|rt| console.count(label, rt));
+        Ok(JsValue::undefined())
+    }
+
+    fn count_reset(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let mut console = Console::from_js_value(this)?;
+        let label = count_label(args, context)?;
+
+        runtime::with_global_host(|rt| console.count_reset(label, rt));
+        Ok(JsValue::undefined())
+    }
+
     fn clear(
         this: &JsValue,
         _args: &[JsValue],
@@ -515,13 +739,20 @@ impl ConsoleApi {
             ConsoleApi::Proto {
                 contract_address,
                 operation_hash,
+                level,
+                logs,
             } => Console::Proto {
                 groups: Vec::default(),
+                counts: HashMap::default(),
                 contract_address,
                 operation_hash,
+                level,
+                logs,
             },
-            ConsoleApi::Cli => Console::Cli {
+            ConsoleApi::Cli { level } => Console::Cli {
                 groups: Vec::default(),
+                counts: HashMap::default(),
+                level,
             },
         }
     }
@@ -571,6 +802,16 @@ impl jstz_core::Api for ConsoleApi {
                 js_string!("groupEnd"),
                 0,
             )
+            .function(
+                NativeFunction::from_fn_ptr(Self::count),
+                js_string!("count"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::count_reset),
+                js_string!("countReset"),
+                0,
+            )
             .function(
                 NativeFunction::from_fn_ptr(Self::clear),
                 js_string!("clear"),
@@ -583,3 +824,131 @@ impl jstz_core::Api for ConsoleApi {
             .expect("console api should only be registered once!")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tezos_smart_rollup_mock::MockHost;
+
+    fn cli_console(level: LogLevel) -> Console {
+        ConsoleApi::Cli { level }.to_console()
+    }
+
+    fn proto_console(logs: &LogBuffer) -> Console {
+        ConsoleApi::Proto {
+            contract_address: PublicKeyHash::from_base58(
+                "tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty",
+            )
+            .unwrap(),
+            operation_hash: Blake2b::default(),
+            level: LogLevel::default(),
+            logs: logs.clone(),
+        }
+        .to_console()
+    }
+
+    fn levels_and_texts(logs: &LogBuffer) -> Vec<(String, String)> {
+        logs.take()
+            .into_iter()
+            .map(|log| (log.level, log.text))
+            .collect()
+    }
+
+    #[test]
+    fn passing_assert_logs_nothing() {
+        let hrt = MockHost::default();
+        let context = &mut Context::default();
+        let logs = LogBuffer::default();
+        let console = proto_console(&logs);
+
+        console
+            .assert(true, &[js_string!("unreachable").into()], &hrt, context)
+            .unwrap();
+
+        assert!(logs.take().is_empty());
+    }
+
+    #[test]
+    fn failing_assert_logs_error() {
+        let hrt = MockHost::default();
+        let context = &mut Context::default();
+        let logs = LogBuffer::default();
+        let console = proto_console(&logs);
+
+        console
+            .assert(false, &[js_string!("balance is low").into()], &hrt, context)
+            .unwrap();
+
+        assert_eq!(
+            levels_and_texts(&logs),
+            vec![(
+                "error".to_string(),
+                "Assertion failed: balance is low".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn count_same_label_three_times() {
+        let hrt = MockHost::default();
+        let logs = LogBuffer::default();
+        let mut console = proto_console(&logs);
+
+        for _ in 0..3 {
+            console.count("calls".to_string(), &hrt);
+        }
+        console.count_reset("calls".to_string(), &hrt);
+        console.count("calls".to_string(), &hrt);
+
+        let texts: Vec<_> = levels_and_texts(&logs)
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect();
+        assert_eq!(texts, vec!["calls: 1", "calls: 2", "calls: 3", "calls: 1"]);
+    }
+
+    #[test]
+    fn count_reset_unknown_label_warns() {
+        let hrt = MockHost::default();
+        let logs = LogBuffer::default();
+        let mut console = proto_console(&logs);
+
+        console.count_reset("missing".to_string(), &hrt);
+
+        assert_eq!(
+            levels_and_texts(&logs),
+            vec![(
+                "warn".to_string(),
+                "Count for 'missing' does not exist".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn warn_level_hides_debug_and_shows_warn() {
+        let console = cli_console(LogLevel::Warn);
+
+        let shown = |message: LogMessage| console.is_enabled(message.log_level());
+
+        assert!(!shown(LogMessage::Debug("debug".to_string())));
+        assert!(!shown(LogMessage::Log("log".to_string())));
+        assert!(!shown(LogMessage::Info("info".to_string())));
+        assert!(shown(LogMessage::Warn("warn".to_string())));
+        assert!(shown(LogMessage::Error("error".to_string())));
+    }
+
+    #[test]
+    fn default_level_shows_everything() {
+        let console = cli_console(LogLevel::default());
+
+        assert!(console.is_enabled(LogLevel::Debug));
+        assert!(console.is_enabled(LogLevel::Error));
+    }
+
+    #[test]
+    fn parse_log_level() {
+        assert_eq!("warn".parse::<LogLevel>(), Ok(LogLevel::Warn));
+        assert_eq!("DEBUG".parse::<LogLevel>(), Ok(LogLevel::Debug));
+        assert!("verbose".parse::<LogLevel>().is_err());
+    }
+}
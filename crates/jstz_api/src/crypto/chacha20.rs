@@ -0,0 +1,233 @@
+//! ChaCha20-Poly1305 authenticated encryption ([RFC 8439]), and its
+//! extended-nonce variant XChaCha20-Poly1305.
+//!
+//! Keys are 32 bytes. Nonces are 12 bytes for ChaCha20-Poly1305 and 24 bytes
+//! for XChaCha20-Poly1305, which is long enough for nonces to be chosen at
+//! random. Ciphertexts are followed by the 16-byte Poly1305 tag.
+//!
+//! [RFC 8439]: https://datatracker.ietf.org/doc/html/rfc8439
+
+use boa_engine::{
+    js_string, object::ObjectInitializer, Context, JsArgs, JsNativeError, JsObject,
+    JsResult, JsValue, NativeFunction,
+};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, XChaCha20Poly1305,
+};
+
+use crate::idl::{buffer_source_to_vec, vec_to_uint8_array};
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const XNONCE_LEN: usize = 24;
+
+fn check_len(bytes: &[u8], len: usize, name: &str) -> JsResult<()> {
+    if bytes.len() != len {
+        return Err(JsNativeError::range()
+            .with_message(format!("The {name} must be {len} bytes"))
+            .into());
+    }
+    Ok(())
+}
+
+fn decryption_failed() -> JsNativeError {
+    JsNativeError::error().with_message("Decryption failed")
+}
+
+pub fn encrypt(key: &[u8], nonce: &[u8], msg: &[u8], aad: &[u8]) -> JsResult<Vec<u8>> {
+    check_len(key, KEY_LEN, "key")?;
+    check_len(nonce, NONCE_LEN, "nonce")?;
+
+    ChaCha20Poly1305::new(key.into())
+        .encrypt(nonce.into(), Payload { msg, aad })
+        .map_err(|_| {
+            JsNativeError::error()
+                .with_message("Encryption failed")
+                .into()
+        })
+}
+
+pub fn decrypt(key: &[u8], nonce: &[u8], msg: &[u8], aad: &[u8]) -> JsResult<Vec<u8>> {
+    check_len(key, KEY_LEN, "key")?;
+    check_len(nonce, NONCE_LEN, "nonce")?;
+
+    ChaCha20Poly1305::new(key.into())
+        .decrypt(nonce.into(), Payload { msg, aad })
+        .map_err(|_| decryption_failed().into())
+}
+
+pub fn xencrypt(key: &[u8], nonce: &[u8], msg: &[u8], aad: &[u8]) -> JsResult<Vec<u8>> {
+    check_len(key, KEY_LEN, "key")?;
+    check_len(nonce, XNONCE_LEN, "nonce")?;
+
+    XChaCha20Poly1305::new(key.into())
+        .encrypt(nonce.into(), Payload { msg, aad })
+        .map_err(|_| {
+            JsNativeError::error()
+                .with_message("Encryption failed")
+                .into()
+        })
+}
+
+pub fn xdecrypt(key: &[u8], nonce: &[u8], msg: &[u8], aad: &[u8]) -> JsResult<Vec<u8>> {
+    check_len(key, KEY_LEN, "key")?;
+    check_len(nonce, XNONCE_LEN, "nonce")?;
+
+    XChaCha20Poly1305::new(key.into())
+        .decrypt(nonce.into(), Payload { msg, aad })
+        .map_err(|_| decryption_failed().into())
+}
+
+type Cipher = fn(&[u8], &[u8], &[u8], &[u8]) -> JsResult<Vec<u8>>;
+
+/// Calls `cipher` with the `(key, nonce, data, aad?)` arguments
+fn apply(
+    cipher: Cipher,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let key = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+    let nonce = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+    let data = buffer_source_to_vec(args.get_or_undefined(2), context)?;
+    let aad = match args.get_or_undefined(3) {
+        JsValue::Undefined => Vec::new(),
+        aad => buffer_source_to_vec(aad, context)?,
+    };
+
+    let result = cipher(&key, &nonce, &data, &aad)?;
+
+    Ok(vec_to_uint8_array(result, context)?.into())
+}
+
+fn js_encrypt(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    apply(encrypt, args, context)
+}
+
+fn js_decrypt(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    apply(decrypt, args, context)
+}
+
+fn js_xencrypt(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    apply(xencrypt, args, context)
+}
+
+fn js_xdecrypt(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    apply(xdecrypt, args, context)
+}
+
+/// Builds the `crypto.chacha20` object
+pub(super) fn object(context: &mut Context<'_>) -> JsObject {
+    ObjectInitializer::new(context)
+        .function(
+            NativeFunction::from_fn_ptr(js_encrypt),
+            js_string!("encrypt"),
+            3,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(js_decrypt),
+            js_string!("decrypt"),
+            3,
+        )
+        .build()
+}
+
+/// Builds the `crypto.xchacha20` object
+pub(super) fn xobject(context: &mut Context<'_>) -> JsObject {
+    ObjectInitializer::new(context)
+        .function(
+            NativeFunction::from_fn_ptr(js_xencrypt),
+            js_string!("encrypt"),
+            3,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(js_xdecrypt),
+            js_string!("decrypt"),
+            3,
+        )
+        .build()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // RFC 8439, section 2.8.2
+    const PLAINTEXT: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+    const NONCE: &str = "070000004041424344454647";
+    const AAD: &str = "50515253c0c1c2c3c4c5c6c7";
+    const CIPHERTEXT: &str = concat!(
+        "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6",
+        "3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36",
+        "92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc",
+        "3ff4def08e4b7a9de576d26586cec64b6116",
+    );
+    const TAG: &str = "1ae10b594f09e26a7e902ecbd0600691";
+
+    fn key() -> Vec<u8> {
+        (0x80..=0x9f).collect()
+    }
+
+    #[test]
+    fn rfc8439_vector() {
+        let nonce = hex::decode(NONCE).unwrap();
+        let aad = hex::decode(AAD).unwrap();
+        let expected = hex::decode(format!("{CIPHERTEXT}{TAG}")).unwrap();
+
+        let ciphertext = encrypt(&key(), &nonce, PLAINTEXT, &aad).unwrap();
+        assert_eq!(ciphertext, expected);
+
+        let plaintext = decrypt(&key(), &nonce, &ciphertext, &aad).unwrap();
+        assert_eq!(plaintext, PLAINTEXT);
+    }
+
+    #[test]
+    fn reject_tampered_ciphertext() {
+        let nonce = hex::decode(NONCE).unwrap();
+        let aad = hex::decode(AAD).unwrap();
+
+        let mut ciphertext = encrypt(&key(), &nonce, PLAINTEXT, &aad).unwrap();
+        ciphertext[0] ^= 1;
+
+        assert!(decrypt(&key(), &nonce, &ciphertext, &aad).is_err());
+        assert!(decrypt(&key(), &nonce, &ciphertext, b"").is_err());
+    }
+
+    #[test]
+    fn xchacha20_round_trip() {
+        let nonce = [7; XNONCE_LEN];
+
+        let ciphertext = xencrypt(&key(), &nonce, b"session token", b"").unwrap();
+        assert_eq!(ciphertext.len(), b"session token".len() + 16);
+
+        let plaintext = xdecrypt(&key(), &nonce, &ciphertext, b"").unwrap();
+        assert_eq!(plaintext, b"session token");
+
+        let mut other_nonce = nonce;
+        other_nonce[23] ^= 1;
+        assert!(xdecrypt(&key(), &other_nonce, &ciphertext, b"").is_err());
+    }
+
+    #[test]
+    fn reject_invalid_lengths() {
+        assert!(encrypt(&key()[1..], &[0; NONCE_LEN], b"", b"").is_err());
+        assert!(encrypt(&key(), &[0; XNONCE_LEN], b"", b"").is_err());
+        assert!(xencrypt(&key(), &[0; NONCE_LEN], b"", b"").is_err());
+    }
+}
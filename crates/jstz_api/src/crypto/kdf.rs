@@ -0,0 +1,448 @@
+//! Key derivation functions: password-based (PBKDF2, bcrypt and Argon2) and
+//! HMAC-based (HKDF).
+//!
+//! Password-based key derivation is deliberately expensive, so the work
+//! factors accepted by these functions are capped to bound the cost of a
+//! single call. bcrypt and Argon2 require the `password-hashing` feature.
+
+#[cfg(feature = "password-hashing")]
+use argon2::Argon2;
+use boa_engine::{
+    js_string, object::FunctionObjectBuilder, Context, JsArgs, JsNativeError, JsObject,
+    JsResult, JsValue, NativeFunction,
+};
+use hkdf::Hkdf;
+use sha2::{Sha256, Sha512};
+
+use crate::idl::{buffer_source_to_vec, vec_to_uint8_array};
+
+/// The maximum number of PBKDF2 iterations
+pub const MAX_PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// The maximum bcrypt cost
+#[cfg(feature = "password-hashing")]
+pub const MAX_BCRYPT_COST: u32 = 12;
+
+/// The maximum length (in bytes) of a derived key
+pub const MAX_KEY_LEN: usize = 1024;
+
+fn unsupported_hash(hash: &str) -> JsNativeError {
+    JsNativeError::typ().with_message(format!("Unsupported hash function: {hash}"))
+}
+
+fn invalid_hkdf_length(_: hkdf::InvalidLength) -> JsNativeError {
+    JsNativeError::range().with_message("Invalid HKDF output length")
+}
+
+fn check_key_len(key_len: usize) -> JsResult<()> {
+    if key_len == 0 || key_len > MAX_KEY_LEN {
+        return Err(JsNativeError::range()
+            .with_message(format!("Key length must be between 1 and {MAX_KEY_LEN}"))
+            .into());
+    }
+
+    Ok(())
+}
+
+/// Derives a key of `key_len` bytes from `password` and `salt` using
+/// PBKDF2-HMAC with the hash function named `hash` (`SHA-256` or `SHA-512`).
+pub fn pbkdf2(
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    key_len: usize,
+    hash: &str,
+) -> JsResult<Vec<u8>> {
+    if iterations == 0 || iterations > MAX_PBKDF2_ITERATIONS {
+        return Err(JsNativeError::range()
+            .with_message(format!(
+                "Iterations must be between 1 and {MAX_PBKDF2_ITERATIONS}"
+            ))
+            .into());
+    }
+    check_key_len(key_len)?;
+
+    let mut key = vec![0; key_len];
+    match hash {
+        "SHA-256" => {
+            ::pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, iterations, &mut key)
+        }
+        "SHA-512" => {
+            ::pbkdf2::pbkdf2_hmac::<Sha512>(password, salt, iterations, &mut key)
+        }
+        _ => return Err(unsupported_hash(hash).into()),
+    }
+
+    Ok(key)
+}
+
+/// Hashes `password` with bcrypt, returning the hash in modular crypt format
+/// (`$2b$<cost>$<salt><hash>`).
+#[cfg(feature = "password-hashing")]
+pub fn bcrypt(password: &[u8], salt: &[u8], cost: u32) -> JsResult<String> {
+    if cost > MAX_BCRYPT_COST {
+        return Err(JsNativeError::range()
+            .with_message(format!("Cost must be at most {MAX_BCRYPT_COST}"))
+            .into());
+    }
+
+    let salt: [u8; 16] = salt.try_into().map_err(|_| {
+        JsNativeError::range().with_message("Salt must be exactly 16 bytes")
+    })?;
+
+    let hash = ::bcrypt::hash_with_salt(password, cost, salt).map_err(|err| {
+        JsNativeError::range().with_message(format!("Failed to hash password: {err}"))
+    })?;
+
+    Ok(hash.to_string())
+}
+
+/// Returns `true` if `password` matches the bcrypt `hash`.
+#[cfg(feature = "password-hashing")]
+pub fn bcrypt_verify(password: &[u8], hash: &str) -> JsResult<bool> {
+    ::bcrypt::verify(password, hash).map_err(|err| {
+        JsNativeError::typ()
+            .with_message(format!("Invalid bcrypt hash: {err}"))
+            .into()
+    })
+}
+
+/// Derives a key of `key_len` bytes from `password` and `salt` using
+/// Argon2id with the default parameters.
+#[cfg(feature = "password-hashing")]
+pub fn argon2(password: &[u8], salt: &[u8], key_len: usize) -> JsResult<Vec<u8>> {
+    check_key_len(key_len)?;
+
+    let mut key = vec![0; key_len];
+    Argon2::default()
+        .hash_password_into(password, salt, &mut key)
+        .map_err(|err| {
+            JsNativeError::range().with_message(format!("Failed to derive key: {err}"))
+        })?;
+
+    Ok(key)
+}
+
+/// Derives a key of `key_len` bytes from the input keying material `ikm`
+/// using HKDF (RFC 5869) with the hash function named `hash`.
+pub fn hkdf(
+    ikm: &[u8],
+    salt: &[u8],
+    info: &[u8],
+    key_len: usize,
+    hash: &str,
+) -> JsResult<Vec<u8>> {
+    let mut key = vec![0; key_len];
+    let result = match hash {
+        "SHA-256" => Hkdf::<Sha256>::new(Some(salt), ikm).expand(info, &mut key),
+        "SHA-512" => Hkdf::<Sha512>::new(Some(salt), ikm).expand(info, &mut key),
+        _ => return Err(unsupported_hash(hash).into()),
+    };
+    result.map_err(invalid_hkdf_length)?;
+
+    Ok(key)
+}
+
+/// The HKDF extract step: derives a pseudorandom key from `salt` and the
+/// input keying material `ikm`.
+pub fn hkdf_extract(salt: &[u8], ikm: &[u8], hash: &str) -> JsResult<Vec<u8>> {
+    let prk = match hash {
+        "SHA-256" => Hkdf::<Sha256>::extract(Some(salt), ikm).0.to_vec(),
+        "SHA-512" => Hkdf::<Sha512>::extract(Some(salt), ikm).0.to_vec(),
+        _ => return Err(unsupported_hash(hash).into()),
+    };
+
+    Ok(prk)
+}
+
+/// The HKDF expand step: derives a key of `key_len` bytes from the
+/// pseudorandom key `prk` and `info`.
+pub fn hkdf_expand(
+    prk: &[u8],
+    info: &[u8],
+    key_len: usize,
+    hash: &str,
+) -> JsResult<Vec<u8>> {
+    let invalid_prk = |_| JsNativeError::range().with_message("Invalid HKDF key");
+
+    let mut key = vec![0; key_len];
+    let result = match hash {
+        "SHA-256" => Hkdf::<Sha256>::from_prk(prk)
+            .map_err(invalid_prk)?
+            .expand(info, &mut key),
+        "SHA-512" => Hkdf::<Sha512>::from_prk(prk)
+            .map_err(invalid_prk)?
+            .expand(info, &mut key),
+        _ => return Err(unsupported_hash(hash).into()),
+    };
+    result.map_err(invalid_hkdf_length)?;
+
+    Ok(key)
+}
+
+/// Returns the name of the hash function passed as the `index`-th argument,
+/// defaulting to `SHA-256`.
+fn hash_arg(
+    args: &[JsValue],
+    index: usize,
+    context: &mut Context<'_>,
+) -> JsResult<String> {
+    match args.get(index) {
+        Some(hash) if !hash.is_undefined() => hash.try_js_into(context),
+        _ => Ok("SHA-256".to_string()),
+    }
+}
+
+pub(super) fn js_pbkdf2(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let password = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+    let salt = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+    let iterations: u32 = args.get_or_undefined(2).try_js_into(context)?;
+    let key_len: usize = args.get_or_undefined(3).try_js_into(context)?;
+    let hash = hash_arg(args, 4, context)?;
+
+    let key = pbkdf2(&password, &salt, iterations, key_len, &hash)?;
+
+    Ok(vec_to_uint8_array(key, context)?.into())
+}
+
+#[cfg(feature = "password-hashing")]
+pub(super) fn js_bcrypt(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let password = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+    let salt = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+    let cost: u32 = args.get_or_undefined(2).try_js_into(context)?;
+
+    Ok(boa_engine::JsString::from(bcrypt(&password, &salt, cost)?).into())
+}
+
+#[cfg(feature = "password-hashing")]
+pub(super) fn js_bcrypt_verify(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let password = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+    let hash: String = args.get_or_undefined(1).try_js_into(context)?;
+
+    Ok(bcrypt_verify(&password, &hash)?.into())
+}
+
+#[cfg(feature = "password-hashing")]
+pub(super) fn js_argon2(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let password = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+    let salt = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+    let key_len: usize = args.get_or_undefined(2).try_js_into(context)?;
+
+    let key = argon2(&password, &salt, key_len)?;
+
+    Ok(vec_to_uint8_array(key, context)?.into())
+}
+
+fn js_hkdf(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let ikm = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+    let salt = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+    let info = buffer_source_to_vec(args.get_or_undefined(2), context)?;
+    let key_len: usize = args.get_or_undefined(3).try_js_into(context)?;
+    let hash = hash_arg(args, 4, context)?;
+
+    let key = hkdf(&ikm, &salt, &info, key_len, &hash)?;
+
+    Ok(vec_to_uint8_array(key, context)?.into())
+}
+
+fn js_hkdf_extract(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let salt = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+    let ikm = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+    let hash = hash_arg(args, 2, context)?;
+
+    let prk = hkdf_extract(&salt, &ikm, &hash)?;
+
+    Ok(vec_to_uint8_array(prk, context)?.into())
+}
+
+fn js_hkdf_expand(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let prk = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+    let info = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+    let key_len: usize = args.get_or_undefined(2).try_js_into(context)?;
+    let hash = hash_arg(args, 3, context)?;
+
+    let key = hkdf_expand(&prk, &info, key_len, &hash)?;
+
+    Ok(vec_to_uint8_array(key, context)?.into())
+}
+
+fn function(
+    f: fn(&JsValue, &[JsValue], &mut Context<'_>) -> JsResult<JsValue>,
+    name: &'static str,
+    length: usize,
+    context: &mut Context<'_>,
+) -> JsObject {
+    FunctionObjectBuilder::new(context.realm(), NativeFunction::from_fn_ptr(f))
+        .name(JsString::from(name))
+        .length(length)
+        .build()
+        .into()
+}
+
+/// Builds the `crypto.hkdf` function, with its `extract` and `expand` steps
+/// as properties
+pub(super) fn hkdf_object(context: &mut Context<'_>) -> JsObject {
+    let hkdf = function(js_hkdf, "hkdf", 5, context);
+    let extract = function(js_hkdf_extract, "extract", 3, context);
+    let expand = function(js_hkdf_expand, "expand", 4, context);
+
+    hkdf.create_data_property_or_throw(js_string!("extract"), extract, context)
+        .expect("Failed to define `hkdf.extract`");
+    hkdf.create_data_property_or_throw(js_string!("expand"), expand, context)
+        .expect("Failed to define `hkdf.expand`");
+
+    hkdf
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pbkdf2_sha256_vectors() {
+        let cases = [
+            (
+                1,
+                "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b",
+            ),
+            (
+                2,
+                "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43",
+            ),
+            (
+                4096,
+                "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a",
+            ),
+        ];
+
+        for (iterations, expected) in cases {
+            let key = pbkdf2(b"password", b"salt", iterations, 32, "SHA-256").unwrap();
+            assert_eq!(hex::encode(key), expected);
+        }
+    }
+
+    #[test]
+    fn pbkdf2_sha512_vector() {
+        let key = pbkdf2(b"password", b"salt", 1, 64, "SHA-512").unwrap();
+
+        assert_eq!(
+            hex::encode(key),
+            "867f70cf1ade02cff3752599a3a53dc4af34c7a669815ae5d513554e1c8cf252\
+             c02d470a285a0501bad999bfe943c08f050235d7d68b1da55e63f73b60a57fce"
+        );
+    }
+
+    #[test]
+    fn pbkdf2_rejects_excessive_iterations() {
+        assert!(pbkdf2(
+            b"password",
+            b"salt",
+            MAX_PBKDF2_ITERATIONS + 1,
+            32,
+            "SHA-256"
+        )
+        .is_err());
+        assert!(pbkdf2(b"password", b"salt", 1, 32, "MD5").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "password-hashing")]
+    fn bcrypt_verify_known_hash() {
+        assert!(bcrypt_verify(
+            b"U*U",
+            "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW"
+        )
+        .unwrap());
+        assert!(!bcrypt_verify(
+            b"U*V",
+            "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW"
+        )
+        .unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "password-hashing")]
+    fn bcrypt_roundtrip() {
+        let hash = bcrypt(b"hunter2", &[7; 16], 4).unwrap();
+
+        assert!(hash.starts_with("$2b$04$"));
+        assert!(bcrypt_verify(b"hunter2", &hash).unwrap());
+        assert!(bcrypt(b"hunter2", &[7; 16], MAX_BCRYPT_COST + 1).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "password-hashing")]
+    fn argon2_is_deterministic() {
+        let key = argon2(b"password", b"somesalt", 32).unwrap();
+
+        assert_eq!(key.len(), 32);
+        assert_eq!(key, argon2(b"password", b"somesalt", 32).unwrap());
+        assert_ne!(key, argon2(b"password", b"othersalt", 32).unwrap());
+    }
+
+    #[test]
+    fn hkdf_rfc5869_vectors() {
+        // RFC 5869, Appendix A, test cases 1 and 3
+        let ikm = [0x0b; 22];
+        let salt = hex::decode("000102030405060708090a0b0c").unwrap();
+        let info = hex::decode("f0f1f2f3f4f5f6f7f8f9").unwrap();
+
+        let prk = hkdf_extract(&salt, &ikm, "SHA-256").unwrap();
+        assert_eq!(
+            hex::encode(&prk),
+            "077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5"
+        );
+
+        let okm = "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf\
+                   34007208d5b887185865";
+        assert_eq!(
+            hex::encode(hkdf_expand(&prk, &info, 42, "SHA-256").unwrap()),
+            okm
+        );
+        assert_eq!(
+            hex::encode(hkdf(&ikm, &salt, &info, 42, "SHA-256").unwrap()),
+            okm
+        );
+
+        assert_eq!(
+            hex::encode(hkdf(&ikm, &[], &[], 42, "SHA-256").unwrap()),
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d\
+             9d201395faa4b61a96c8"
+        );
+    }
+
+    #[test]
+    fn hkdf_rejects_invalid_input() {
+        assert!(hkdf(b"ikm", b"salt", b"info", 255 * 32 + 1, "SHA-256").is_err());
+        assert!(hkdf(b"ikm", b"salt", b"info", 32, "MD5").is_err());
+        assert!(hkdf_expand(&[0; 16], b"info", 32, "SHA-256").is_err());
+    }
+}
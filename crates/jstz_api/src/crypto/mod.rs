@@ -0,0 +1,74 @@
+use boa_engine::{
+    js_string, object::ObjectInitializer, property::Attribute, Context, NativeFunction,
+};
+
+use jstz_crypto::hash::Blake2b;
+
+pub mod chacha20;
+pub mod kdf;
+pub mod random;
+pub mod secp256k1;
+pub mod subtle;
+
+pub struct CryptoApi {
+    /// The seed of `crypto.getRandomValues()`. Smart functions are seeded with
+    /// their operation, so that random values are deterministic per operation.
+    pub seed: Blake2b,
+}
+
+impl CryptoApi {
+    const NAME: &'static str = "crypto";
+}
+
+impl jstz_core::Api for CryptoApi {
+    fn init(self, context: &mut Context<'_>) {
+        let secp256k1 = secp256k1::object(context);
+        let hkdf = kdf::hkdf_object(context);
+        let chacha20 = chacha20::object(context);
+        let xchacha20 = chacha20::xobject(context);
+        let subtle = subtle::object(context);
+
+        let mut crypto =
+            ObjectInitializer::with_native(random::RandomSource::new(self.seed), context);
+        crypto
+            .property(js_string!("secp256k1"), secp256k1, Attribute::all())
+            .property(js_string!("hkdf"), hkdf, Attribute::all())
+            .property(js_string!("chacha20"), chacha20, Attribute::all())
+            .property(js_string!("xchacha20"), xchacha20, Attribute::all())
+            .property(js_string!("subtle"), subtle, Attribute::all())
+            .function(
+                NativeFunction::from_fn_ptr(random::get_random_values),
+                js_string!("getRandomValues"),
+                1,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(kdf::js_pbkdf2),
+                js_string!("pbkdf2"),
+                5,
+            );
+
+        #[cfg(feature = "password-hashing")]
+        crypto
+            .function(
+                NativeFunction::from_fn_ptr(kdf::js_bcrypt),
+                js_string!("bcrypt"),
+                3,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(kdf::js_bcrypt_verify),
+                js_string!("bcryptVerify"),
+                2,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(kdf::js_argon2),
+                js_string!("argon2"),
+                3,
+            );
+
+        let crypto = crypto.build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), crypto, Attribute::all())
+            .expect("The crypto object shouldn't exist yet");
+    }
+}
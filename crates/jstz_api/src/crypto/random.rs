@@ -0,0 +1,241 @@
+//! `crypto.getRandomValues(typedArray)`
+//!
+//! Smart functions must compute the same result on every node, so random
+//! values cannot be drawn from the entropy of the host. Instead, they are
+//! derived from the seed of the `crypto` object (the hash of the operation and
+//! the address of the contract) and the number of previous calls. The values
+//! are deterministic per operation: anyone who knows the operation can compute
+//! them, so they must not be used for secrets.
+//!
+//! More information:
+//!  - [MDN documentation][mdn]
+//!
+//! [mdn]: https://developer.mozilla.org/en-US/docs/Web/API/Crypto/getRandomValues
+
+use boa_engine::{
+    js_string,
+    object::{builtins::JsTypedArray, Object},
+    Context, JsArgs, JsNativeError, JsResult, JsSymbol, JsValue,
+};
+use boa_gc::{empty_trace, Finalize, GcRefMut, Trace};
+use jstz_crypto::hash::Blake2b;
+
+use crate::idl::{ArrayBufferLike, JsArrayBufferData};
+
+/// The maximum number of bytes filled by a single call
+pub const MAX_BYTE_LENGTH: usize = 65536;
+
+const DIGEST_LENGTH: usize = 32;
+
+/// The integer typed arrays accepted by `getRandomValues`
+const INTEGER_ARRAYS: [&str; 9] = [
+    "Int8Array",
+    "Uint8Array",
+    "Uint8ClampedArray",
+    "Int16Array",
+    "Uint16Array",
+    "Int32Array",
+    "Uint32Array",
+    "BigInt64Array",
+    "BigUint64Array",
+];
+
+/// A deterministic source of random bytes
+pub struct RandomSource {
+    seed: Blake2b,
+    counter: u64,
+}
+
+impl Finalize for RandomSource {}
+
+unsafe impl Trace for RandomSource {
+    empty_trace!();
+}
+
+impl RandomSource {
+    pub fn new(seed: Blake2b) -> Self {
+        Self { seed, counter: 0 }
+    }
+
+    pub(crate) fn from_js_value<'a>(
+        value: &'a JsValue,
+    ) -> JsResult<GcRefMut<'a, Object, Self>> {
+        value
+            .as_object()
+            .and_then(|obj| obj.downcast_mut::<Self>())
+            .ok_or_else(|| {
+                JsNativeError::typ()
+                    .with_message("Failed to convert js value into rust type `Crypto`")
+                    .into()
+            })
+    }
+
+    /// Fills `dst` with the bytes of the next call. The `i`-th block of 32
+    /// bytes is the digest of the seed, the call counter and `i`.
+    pub fn fill(&mut self, dst: &mut [u8]) {
+        for (i, chunk) in dst.chunks_mut(DIGEST_LENGTH).enumerate() {
+            let input = [
+                self.seed.as_ref(),
+                &self.counter.to_le_bytes(),
+                &(i as u64).to_le_bytes(),
+            ]
+            .concat();
+
+            let digest = Blake2b::from(input.as_slice());
+            chunk.copy_from_slice(&digest.as_ref()[..chunk.len()]);
+        }
+
+        self.counter += 1;
+    }
+}
+
+/// Returns the name of the typed array, such as `Uint8Array`
+fn typed_array_name(array: &JsTypedArray, context: &mut Context<'_>) -> JsResult<String> {
+    let name = array.get(JsSymbol::to_string_tag(), context)?;
+
+    Ok(name
+        .as_string()
+        .map(|name| name.to_std_string_escaped())
+        .unwrap_or_default())
+}
+
+/// `crypto.getRandomValues(typedArray)`
+pub fn get_random_values(
+    this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let value = args.get_or_undefined(0);
+    let array = value
+        .as_object()
+        .filter(|obj| obj.is_typed_array())
+        .map(|obj| JsTypedArray::from_object(obj.clone()))
+        .transpose()?
+        .ok_or_else(|| JsNativeError::typ().with_message("Expected a typed array"))?;
+
+    let name = typed_array_name(&array, context)?;
+    if !INTEGER_ARRAYS.contains(&name.as_str()) {
+        return Err(JsNativeError::typ()
+            .with_message(format!("Unsupported typed array `{name}`"))
+            .into());
+    }
+
+    let byte_offset = array
+        .get(js_string!("byteOffset"), context)?
+        .to_length(context)? as usize;
+    let byte_length = array
+        .get(js_string!("byteLength"), context)?
+        .to_length(context)? as usize;
+
+    if byte_length > MAX_BYTE_LENGTH {
+        return Err(JsNativeError::typ()
+            .with_message(format!(
+                "The byte length of the array ({byte_length}) exceeds the maximum of {MAX_BYTE_LENGTH}"
+            ))
+            .into());
+    }
+
+    let data = JsArrayBufferData::from_array_buffer_like(&array, context)?;
+    if let Some(mut bytes) = data.as_slice_mut() {
+        let mut source = RandomSource::from_js_value(this)?;
+        source.fill(&mut bytes[byte_offset..byte_offset + byte_length]);
+    }
+
+    Ok(value.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::Api;
+
+    use super::*;
+    use crate::crypto::CryptoApi;
+
+    fn eval(seed: &[u8], code: &str) -> JsResult<JsValue> {
+        let context = &mut Context::default();
+        CryptoApi {
+            seed: Blake2b::from(seed),
+        }
+        .init(context);
+
+        context.eval(Source::from_bytes(code))
+    }
+
+    fn eval_to_string(seed: &[u8], code: &str) -> String {
+        eval(seed, code)
+            .expect("Could not evaluate code")
+            .as_string()
+            .expect("Expected a string")
+            .to_std_string_escaped()
+    }
+
+    const RANDOM_BYTES: &str = r#"
+        const first = crypto.getRandomValues(new Uint8Array(40));
+        const second = crypto.getRandomValues(new Uint8Array(40));
+        [...first, ...second].join(",")
+    "#;
+
+    #[test]
+    fn same_seed_produces_identical_bytes() {
+        let first_run = eval_to_string(b"operation", RANDOM_BYTES);
+        let second_run = eval_to_string(b"operation", RANDOM_BYTES);
+
+        assert_eq!(first_run, second_run);
+        assert_ne!(first_run, eval_to_string(b"other operation", RANDOM_BYTES));
+    }
+
+    #[test]
+    fn each_call_produces_new_bytes() {
+        let mut source = RandomSource::new(Blake2b::from(b"operation".as_slice()));
+        let (mut first, mut second) = ([0u8; 40], [0u8; 40]);
+
+        source.fill(&mut first);
+        source.fill(&mut second);
+
+        assert_ne!(first, second);
+        assert_ne!(first[..32], first[32..]);
+    }
+
+    #[test]
+    fn fills_views_of_a_buffer() {
+        let result = eval_to_string(
+            b"operation",
+            r#"
+            const buffer = new ArrayBuffer(8);
+            crypto.getRandomValues(new Uint16Array(buffer, 2, 2));
+            new Uint8Array(buffer).map((byte) => byte === 0 ? 0 : 1).join(",")
+            "#,
+        );
+
+        // Only the viewed bytes are filled
+        assert!(result.starts_with("0,0,"), "{result}");
+        assert!(result.ends_with(",0,0"), "{result}");
+    }
+
+    #[test]
+    fn rejects_unsupported_arrays() {
+        for code in [
+            "crypto.getRandomValues(new Float64Array(1))",
+            "crypto.getRandomValues([1, 2, 3])",
+            "crypto.getRandomValues(new Uint8Array(65537))",
+        ] {
+            let error = eval(b"operation", code).expect_err("Expected an error");
+
+            assert!(
+                error.to_string().starts_with("TypeError"),
+                "{code}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_the_maximum_length() {
+        let result = eval_to_string(
+            b"operation",
+            "String(crypto.getRandomValues(new Uint32Array(16384)).length)",
+        );
+
+        assert_eq!(result, "16384");
+    }
+}
@@ -0,0 +1,207 @@
+//! ECDSA over secp256k1, compatible with Ethereum wallets.
+//!
+//! Messages are hashed with Keccak-256 before signing, as in Ethereum.
+//! Signatures are 64 bytes (`r || s`), optionally followed by a recovery byte
+//! `v`, which is ignored by `verify`.
+
+use boa_engine::{
+    js_string, object::ObjectInitializer, Context, JsArgs, JsNativeError, JsObject,
+    JsResult, JsString, JsValue, NativeFunction,
+};
+use k256::{
+    ecdsa::{signature::hazmat::PrehashVerifier, RecoveryId, Signature, VerifyingKey},
+    EncodedPoint,
+};
+use sha3::{Digest, Keccak256};
+
+use crate::idl::{buffer_source_to_vec, vec_to_uint8_array};
+
+fn parse_signature(signature: &[u8]) -> JsResult<Signature> {
+    let signature = match signature.len() {
+        64 | 65 => Signature::from_slice(&signature[..64]).ok(),
+        _ => None,
+    };
+
+    signature.ok_or_else(|| {
+        JsNativeError::typ()
+            .with_message("Invalid secp256k1 signature")
+            .into()
+    })
+}
+
+fn parse_public_key(public_key: &[u8]) -> JsResult<VerifyingKey> {
+    VerifyingKey::from_sec1_bytes(public_key).map_err(|_| {
+        JsNativeError::typ()
+            .with_message("Invalid secp256k1 public key")
+            .into()
+    })
+}
+
+/// Returns `true` if `signature` is a valid signature of `message` by
+/// `public_key`.
+pub fn verify(message: &[u8], signature: &[u8], public_key: &[u8]) -> JsResult<bool> {
+    let signature = parse_signature(signature)?;
+    let public_key = parse_public_key(public_key)?;
+
+    // Ethereum signatures are low-S normalized, but be lenient with high-S ones
+    let signature = signature.normalize_s().unwrap_or(signature);
+
+    Ok(public_key
+        .verify_prehash(&Keccak256::digest(message), &signature)
+        .is_ok())
+}
+
+/// Recovers the uncompressed (65 bytes) public key that produced `signature`
+/// for `message`.
+///
+/// `recovery_id` is either `0..=3` or Ethereum's `v` value (`27` or `28`).
+pub fn recover(message: &[u8], signature: &[u8], recovery_id: u8) -> JsResult<Vec<u8>> {
+    let signature = parse_signature(signature)?;
+
+    let recovery_id = match recovery_id {
+        27 | 28 => recovery_id - 27,
+        _ => recovery_id,
+    };
+    let recovery_id = RecoveryId::from_byte(recovery_id).ok_or_else(|| {
+        JsNativeError::range().with_message("Invalid secp256k1 recovery id")
+    })?;
+
+    let public_key = VerifyingKey::recover_from_prehash(
+        &Keccak256::digest(message),
+        &signature,
+        recovery_id,
+    )
+    .map_err(|_| {
+        JsNativeError::error().with_message("Failed to recover secp256k1 public key")
+    })?;
+
+    Ok(public_key.to_encoded_point(false).as_bytes().to_vec())
+}
+
+/// Returns the EIP-55 checksummed Ethereum address (`0x…`) of `public_key`.
+pub fn eth_address(public_key: &[u8]) -> JsResult<String> {
+    let public_key: EncodedPoint = parse_public_key(public_key)?.to_encoded_point(false);
+
+    // The address is the last 20 bytes of the Keccak-256 hash of the
+    // uncompressed public key (without the `0x04` tag)
+    let hash = Keccak256::digest(&public_key.as_bytes()[1..]);
+    let address = hex::encode(&hash[12..]);
+
+    // EIP-55: uppercase the i-th hex digit if the i-th nibble of the hash of
+    // the lowercase address is >= 8
+    let checksum = Keccak256::digest(address.as_bytes());
+    let address: String = address
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (checksum[i / 2] >> (4 * (1 - i % 2))) & 0xf;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    Ok(format!("0x{address}"))
+}
+
+fn js_verify(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let message = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+    let signature = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+    let public_key = buffer_source_to_vec(args.get_or_undefined(2), context)?;
+
+    Ok(verify(&message, &signature, &public_key)?.into())
+}
+
+fn js_recover(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let message = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+    let signature = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+    let recovery_id: u8 = args.get_or_undefined(2).try_js_into(context)?;
+
+    let public_key = recover(&message, &signature, recovery_id)?;
+
+    Ok(vec_to_uint8_array(public_key, context)?.into())
+}
+
+fn js_eth_address(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let public_key = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+
+    Ok(JsString::from(eth_address(&public_key)?).into())
+}
+
+/// Builds the `crypto.secp256k1` object
+pub(super) fn object(context: &mut Context<'_>) -> JsObject {
+    ObjectInitializer::new(context)
+        .function(
+            NativeFunction::from_fn_ptr(js_verify),
+            js_string!("verify"),
+            3,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(js_recover),
+            js_string!("recover"),
+            3,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(js_eth_address),
+            js_string!("ethAddress"),
+            1,
+        )
+        .build()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // From the `web3.eth.accounts.sign` documentation
+    const ADDRESS: &str = "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23";
+    const SIGNATURE: &str = "b91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c";
+
+    /// EIP-191 (version `0x45`) encoding of a personal message
+    fn eip191(message: &str) -> Vec<u8> {
+        format!("\x19Ethereum Signed Message:\n{}{}", message.len(), message).into_bytes()
+    }
+
+    #[test]
+    fn recover_eip191_signature() {
+        let signature = hex::decode(SIGNATURE).unwrap();
+
+        let public_key =
+            recover(&eip191("Some data"), &signature, signature[64]).unwrap();
+
+        assert_eq!(public_key.len(), 65);
+        assert_eq!(eth_address(&public_key).unwrap(), ADDRESS);
+    }
+
+    #[test]
+    fn verify_eip191_signature() {
+        let signature = hex::decode(SIGNATURE).unwrap();
+        let public_key =
+            recover(&eip191("Some data"), &signature, signature[64]).unwrap();
+
+        assert!(verify(&eip191("Some data"), &signature, &public_key).unwrap());
+        assert!(verify(&eip191("Some data"), &signature[..64], &public_key).unwrap());
+        assert!(!verify(&eip191("Other data"), &signature, &public_key).unwrap());
+    }
+
+    #[test]
+    fn reject_malformed_input() {
+        assert!(verify(b"data", &[0; 10], &[0; 33]).is_err());
+        assert!(recover(b"data", &[1; 64], 4).is_err());
+        assert!(eth_address(&[0; 10]).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! `crypto.subtle`, the hashing subset of the Web Crypto API.
+//!
+//! More information:
+//!  - [MDN documentation][mdn]
+//!  - [W3C `SubtleCrypto` specification][spec]
+//!
+//! [mdn]: https://developer.mozilla.org/en-US/docs/Web/API/SubtleCrypto
+//! [spec]: https://w3c.github.io/webcrypto/#subtlecrypto-interface
+
+use boa_engine::{
+    js_string,
+    object::{
+        builtins::{JsArrayBuffer, JsPromise},
+        ObjectInitializer,
+    },
+    Context, JsArgs, JsError, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::idl::buffer_source_to_vec;
+
+/// The digest algorithms supported by `crypto.subtle.digest()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl Algorithm {
+    /// Parses an algorithm name. Names are case-insensitive.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "SHA-256" => Some(Self::Sha256),
+            "SHA-384" => Some(Self::Sha384),
+            "SHA-512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha256 => Sha256::digest(data).to_vec(),
+            Self::Sha384 => Sha384::digest(data).to_vec(),
+            Self::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+/// Returns an error named `NotSupportedError`, mirroring the `DOMException`
+/// thrown by browsers
+fn not_supported_error(message: &str, context: &mut Context<'_>) -> JsError {
+    let error = JsError::from_native(JsNativeError::error().with_message(message))
+        .to_opaque(context);
+
+    if let Some(object) = error.as_object() {
+        // Setting a property of a fresh error object cannot fail
+        let _ = object.set(
+            js_string!("name"),
+            js_string!("NotSupportedError"),
+            false,
+            context,
+        );
+    }
+
+    JsError::from_opaque(error)
+}
+
+/// Reads the name of an `AlgorithmIdentifier`: either a string or an object
+/// with a `name` property
+fn algorithm_name(value: &JsValue, context: &mut Context<'_>) -> JsResult<String> {
+    let name = match value.as_object() {
+        Some(algorithm) => algorithm.get(js_string!("name"), context)?,
+        None => value.clone(),
+    };
+
+    Ok(name.to_string(context)?.to_std_string_escaped())
+}
+
+fn digest(args: &[JsValue], context: &mut Context<'_>) -> JsResult<JsValue> {
+    let name = algorithm_name(args.get_or_undefined(0), context)?;
+    let algorithm = Algorithm::from_name(&name).ok_or_else(|| {
+        not_supported_error(&format!("Unrecognized algorithm name `{name}`"), context)
+    })?;
+    let data = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+
+    let array_buffer = JsArrayBuffer::from_byte_block(algorithm.digest(&data), context)?;
+
+    Ok(array_buffer.into())
+}
+
+/// `crypto.subtle.digest(algorithm, data)`
+///
+/// Resolves to an `ArrayBuffer` with the digest of `data`. Supports
+/// `SHA-256`, `SHA-384` and `SHA-512`, and rejects with a `NotSupportedError`
+/// otherwise.
+///
+/// [spec]: https://w3c.github.io/webcrypto/#SubtleCrypto-method-digest
+fn js_digest(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let promise = match digest(args, context) {
+        Ok(digest) => JsPromise::resolve(digest, context)?,
+        Err(error) => {
+            let error = error.to_opaque(context);
+            JsPromise::reject(JsError::from_opaque(error), context)?
+        }
+    };
+
+    Ok(promise.into())
+}
+
+/// Builds the `crypto.subtle` object
+pub(super) fn object(context: &mut Context<'_>) -> JsObject {
+    ObjectInitializer::new(context)
+        .function(
+            NativeFunction::from_fn_ptr(js_digest),
+            js_string!("digest"),
+            2,
+        )
+        .build()
+}
+
+#[cfg(test)]
+mod test {
+    use boa_engine::{builtins::promise::PromiseState, Source};
+
+    use super::*;
+
+    // FIPS 180-2, appendix B.1 and the digest of the empty message
+    const SHA256_EMPTY: &str =
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+    const SHA256_ABC: &str =
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn sha256_vectors() {
+        let sha256 = Algorithm::from_name("SHA-256").unwrap();
+
+        assert_eq!(hex::encode(sha256.digest(b"")), SHA256_EMPTY);
+        assert_eq!(hex::encode(sha256.digest(b"abc")), SHA256_ABC);
+    }
+
+    #[test]
+    fn algorithm_names_are_case_insensitive() {
+        assert_eq!(Algorithm::from_name("sha-512"), Some(Algorithm::Sha512));
+        assert_eq!(Algorithm::from_name("SHA-1"), None);
+    }
+
+    fn eval_digest(code: &str) -> PromiseState {
+        let context = &mut Context::default();
+        let subtle = object(context);
+        context
+            .register_global_property(
+                js_string!("subtle"),
+                subtle,
+                boa_engine::property::Attribute::all(),
+            )
+            .unwrap();
+
+        let promise = context
+            .eval(Source::from_bytes(code))
+            .expect("Could not evaluate code");
+
+        JsPromise::from_object(promise.as_object().unwrap().clone())
+            .unwrap()
+            .state()
+            .unwrap()
+    }
+
+    #[test]
+    fn digest_resolves_to_array_buffer() {
+        let PromiseState::Fulfilled(digest) = eval_digest(
+            r#"subtle.digest({ name: "SHA-256" }, new Uint8Array([97, 98, 99]))"#,
+        ) else {
+            panic!("Expected the digest to resolve");
+        };
+
+        let digest = JsArrayBuffer::from_object(digest.as_object().unwrap().clone())
+            .unwrap()
+            .take()
+            .unwrap();
+        assert_eq!(hex::encode(digest), SHA256_ABC);
+    }
+
+    #[test]
+    fn digest_rejects_unknown_algorithm() {
+        let PromiseState::Rejected(error) =
+            eval_digest(r#"subtle.digest("MD5", new Uint8Array())"#)
+        else {
+            panic!("Expected the digest to reject");
+        };
+
+        let context = &mut Context::default();
+        let name = error
+            .as_object()
+            .unwrap()
+            .get(js_string!("name"), context)
+            .unwrap();
+        assert_eq!(name.as_string().unwrap(), "NotSupportedError");
+    }
+}
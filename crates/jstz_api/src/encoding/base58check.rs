@@ -0,0 +1,229 @@
+//! `jstz`'s implementation of Tezos' base58check encoding.
+//!
+//! Tezos encodes hashes, keys and signatures as base58check strings, where the
+//! payload is preceded by a 'prefix' that determines the human-readable prefix of
+//! the encoded string (e.g. `tz1`, `edpk` or `o`), and is followed by a 4-byte
+//! checksum (the first 4 bytes of the double SHA-256 of the prefixed payload).
+//!
+//! More information:
+//!  - [Tezos base58 prefixes][prefixes]
+//!
+//! [prefixes]: https://gitlab.com/tezos/tezos/-/blob/master/src/lib_crypto/base58.ml
+
+use boa_engine::{
+    js_string, object::ObjectInitializer, property::Attribute, Context, JsArgs, JsError,
+    JsNativeError, JsResult, JsString, JsValue, NativeFunction,
+};
+
+use crate::idl::{buffer_source_to_vec, vec_to_uint8_array};
+
+/// A known Tezos base58check prefix.
+pub struct Prefix {
+    /// The name of the constant exposed on the `Base58Check` object
+    pub name: &'static str,
+    /// The raw bytes of the prefix
+    pub bytes: &'static [u8],
+    /// The length of the payload (in bytes) encoded with this prefix
+    pub payload_len: usize,
+}
+
+macro_rules! prefix {
+    ($name:literal, [$($byte:literal),*], $payload_len:literal) => {
+        Prefix {
+            name: $name,
+            bytes: &[$($byte),*],
+            payload_len: $payload_len,
+        }
+    };
+}
+
+pub const PREFIXES: [Prefix; 17] = [
+    prefix!("PREFIX_BLOCK_HASH", [1, 52], 32),
+    prefix!("PREFIX_OPERATION_HASH", [5, 116], 32),
+    prefix!("PREFIX_OPERATION_LIST_LIST_HASH", [29, 159, 109], 32),
+    prefix!("PREFIX_PROTOCOL_HASH", [2, 170], 32),
+    prefix!("PREFIX_CONTEXT_HASH", [79, 199], 32),
+    prefix!("PREFIX_CHAIN_ID", [87, 82, 0], 4),
+    prefix!("PREFIX_ED25519_PUBLIC_KEY_HASH", [6, 161, 159], 20),
+    prefix!("PREFIX_SECP256K1_PUBLIC_KEY_HASH", [6, 161, 161], 20),
+    prefix!("PREFIX_P256_PUBLIC_KEY_HASH", [6, 161, 164], 20),
+    prefix!("PREFIX_CONTRACT_HASH", [2, 90, 121], 20),
+    prefix!("PREFIX_ED25519_SEED", [13, 15, 58, 7], 32),
+    prefix!("PREFIX_ED25519_PUBLIC_KEY", [13, 15, 37, 217], 32),
+    prefix!("PREFIX_SECP256K1_PUBLIC_KEY", [3, 254, 226, 86], 33),
+    prefix!("PREFIX_P256_PUBLIC_KEY", [3, 178, 139, 127], 33),
+    prefix!("PREFIX_ED25519_SIGNATURE", [9, 245, 205, 134, 18], 64),
+    prefix!("PREFIX_SECP256K1_SIGNATURE", [13, 115, 101, 19, 63], 64),
+    prefix!("PREFIX_GENERIC_SIGNATURE", [4, 130, 43], 64),
+];
+
+/// Encodes `payload` prefixed with `prefix` as a base58check string.
+pub fn encode(payload: &[u8], prefix: &[u8]) -> String {
+    let mut data = Vec::with_capacity(prefix.len() + payload.len());
+    data.extend_from_slice(prefix);
+    data.extend_from_slice(payload);
+
+    bs58::encode(data).with_check().into_string()
+}
+
+/// Decodes a base58check string, verifying its checksum.
+///
+/// The decoded bytes are split into a `(prefix, payload)` pair using the known
+/// Tezos prefixes. If no known prefix matches, the prefix is empty and the
+/// payload contains all of the decoded bytes.
+pub fn decode(data: &str) -> JsResult<(Vec<u8>, Vec<u8>)> {
+    let bytes =
+        bs58::decode(data)
+            .with_check(None)
+            .into_vec()
+            .map_err(|err| -> JsError {
+                match err {
+                    bs58::decode::Error::InvalidChecksum { .. }
+                    | bs58::decode::Error::NoChecksum => JsNativeError::range()
+                        .with_message("Invalid checksum")
+                        .into(),
+                    err => JsNativeError::typ()
+                        .with_message(format!("Invalid base58 string: {err}"))
+                        .into(),
+                }
+            })?;
+
+    let prefix_len = PREFIXES
+        .iter()
+        .find(|prefix| {
+            bytes.len() == prefix.bytes.len() + prefix.payload_len
+                && bytes.starts_with(prefix.bytes)
+        })
+        .map_or(0, |prefix| prefix.bytes.len());
+
+    let (prefix, payload) = bytes.split_at(prefix_len);
+
+    Ok((prefix.to_vec(), payload.to_vec()))
+}
+
+pub struct Base58CheckApi;
+
+impl Base58CheckApi {
+    const NAME: &'static str = "Base58Check";
+
+    fn encode(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let payload = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+        let prefix = match args.get(1) {
+            Some(prefix) if !prefix.is_undefined() => {
+                buffer_source_to_vec(prefix, context)?
+            }
+            _ => Vec::new(),
+        };
+
+        Ok(JsString::from(encode(&payload, &prefix)).into())
+    }
+
+    fn decode(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let data: String = args.get_or_undefined(0).try_js_into(context)?;
+
+        let (prefix, payload) = decode(&data)?;
+
+        let prefix = vec_to_uint8_array(prefix, context)?;
+        let payload = vec_to_uint8_array(payload, context)?;
+
+        Ok(ObjectInitializer::new(context)
+            .property(js_string!("prefix"), prefix, Attribute::all())
+            .property(js_string!("payload"), payload, Attribute::all())
+            .build()
+            .into())
+    }
+}
+
+impl jstz_core::Api for Base58CheckApi {
+    fn init(self, context: &mut Context<'_>) {
+        let mut constants = Vec::with_capacity(PREFIXES.len());
+        for prefix in PREFIXES.iter() {
+            let bytes = vec_to_uint8_array(prefix.bytes.to_vec(), context)
+                .expect("Failed to construct prefix `Uint8Array`");
+            constants.push((prefix.name, bytes));
+        }
+
+        let mut base58check = ObjectInitializer::new(context);
+        base58check
+            .function(
+                NativeFunction::from_fn_ptr(Self::encode),
+                js_string!("encode"),
+                2,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::decode),
+                js_string!("decode"),
+                1,
+            );
+        for (name, bytes) in constants {
+            base58check.property(
+                js_string!(name),
+                bytes,
+                Attribute::READONLY | Attribute::NON_ENUMERABLE,
+            );
+        }
+        let base58check = base58check.build();
+
+        context
+            .register_global_property(
+                js_string!(Self::NAME),
+                base58check,
+                Attribute::all(),
+            )
+            .expect("The base58check object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_operation_hash() {
+        let (prefix, payload) =
+            decode("oosWs8Q8E5vvX2DWjM6u1TQCZfsHZREVM5yz31J5N2oF1YSx1zf").unwrap();
+
+        assert_eq!(prefix, vec![5, 116]);
+        assert_eq!(
+            payload
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>(),
+            "a1e727ce45a1118e95bfbdf1ceb230166016485dcc47c33427386c5ea60ae790"
+        );
+    }
+
+    #[test]
+    fn decode_public_key_hash() {
+        let (prefix, payload) = decode("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty").unwrap();
+
+        assert_eq!(prefix, vec![6, 161, 159]);
+        assert_eq!(payload.len(), 20);
+    }
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let encoded = encode(&[0; 32], &[13, 15, 37, 217]);
+        assert_eq!(
+            encoded,
+            "edpkteDwHwoNPB18tKToFKeSCykvr1ExnoMV5nawTJy9Y9nLTfQ541"
+        );
+
+        let (prefix, payload) = decode(&encoded).unwrap();
+        assert_eq!(prefix, vec![13, 15, 37, 217]);
+        assert_eq!(payload, vec![0; 32]);
+    }
+
+    #[test]
+    fn decode_invalid_checksum() {
+        assert!(decode("oosWs8Q8E5vvX2DWjM6u1TQCZfsHZREVM5yz31J5N2oF1YSx1zg").is_err());
+    }
+}
@@ -1,52 +1,128 @@
-use std::error::Error;
-
-use base64::prelude::{Engine as _, BASE64_URL_SAFE as DEFAULT_ENGINE};
+//! `jstz`'s implementation of the global `atob` and `btoa` functions.
+//!
+//! More information:
+//!  - [MDN documentation][mdn]
+//!  - [WHATWG specification][spec]
+//!
+//! [mdn]: https://developer.mozilla.org/en-US/docs/Web/API/btoa
+//! [spec]: https://html.spec.whatwg.org/multipage/webappapis.html#atob
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    prelude::{Engine as _, BASE64_STANDARD},
+};
 use boa_engine::{
     js_string, Context, JsArgs, JsError, JsNativeError, JsResult, JsString, JsValue,
     NativeFunction,
 };
 use boa_gc::{Finalize, Trace};
 
+/// Decodes base64 after padding has been stripped, discarding any trailing
+/// bits (as in the forgiving-base64 decode algorithm)
+const FORGIVING_ENGINE: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_decode_allow_trailing_bits(true)
+        .with_decode_padding_mode(DecodePaddingMode::RequireNone),
+);
+
+/// Returns an error named `InvalidCharacterError`, mirroring the
+/// `DOMException` thrown by browsers
+fn invalid_character_error(message: &str, context: &mut Context<'_>) -> JsError {
+    let error = JsError::from_native(JsNativeError::error().with_message(message))
+        .to_opaque(context);
+
+    if let Some(object) = error.as_object() {
+        // Setting a property of a fresh error object cannot fail
+        let _ = object.set(
+            js_string!("name"),
+            js_string!("InvalidCharacterError"),
+            false,
+            context,
+        );
+    }
+
+    JsError::from_opaque(error)
+}
+
 #[derive(Trace, Finalize)]
 struct Global;
 
 impl Global {
-    fn atob(data: &JsString) -> JsResult<JsString> {
-        fn on_err(err: impl Error) -> JsError {
-            JsNativeError::eval().with_message(err.to_string()).into()
+    /// [spec] https://infra.spec.whatwg.org/#forgiving-base64-decode
+    fn atob(data: &JsString, context: &mut Context<'_>) -> JsResult<JsString> {
+        let invalid = |context: &mut Context<'_>| {
+            invalid_character_error(
+                "The string to be decoded is not correctly encoded",
+                context,
+            )
+        };
+
+        // 1. Remove all ASCII whitespace from `data`
+        let mut data: String = char::decode_utf16(data.iter().copied())
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .filter(|c| !matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' '))
+            .collect();
+
+        // 2. If `data`’s length divides by 4 leaving no remainder, then remove
+        //    one or two `=` code points from the end of `data`
+        if data.len() % 4 == 0 {
+            for _ in 0..2 {
+                if data.ends_with('=') {
+                    data.pop();
+                }
+            }
         }
-        let str = data.to_std_string_escaped();
-        let encoded = DEFAULT_ENGINE.decode(&str).map_err(on_err)?;
-        let encoded_str = core::str::from_utf8(encoded.as_slice()).map_err(on_err)?;
 
-        Ok(encoded_str.into())
+        // 3. If `data`’s length divides by 4 leaving a remainder of 1, return failure
+        // 4. If `data` contains a code point that is not base64, return failure
+        if data.len() % 4 == 1
+            || !data
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/')
+        {
+            return Err(invalid(context));
+        }
+
+        // 5-9. Decode, returning each byte as a code unit
+        let bytes = FORGIVING_ENGINE
+            .decode(&data)
+            .map_err(|_| invalid(context))?;
+        let code_units: Vec<u16> = bytes.into_iter().map(u16::from).collect();
+
+        Ok(JsString::from(code_units.as_slice()))
     }
-    fn btoa(data: &JsString) -> JsResult<JsString> {
-        let str = data.to_std_string_escaped();
-        let encoded = DEFAULT_ENGINE.encode(&str);
-        Ok(encoded.into())
+
+    /// [spec] https://html.spec.whatwg.org/multipage/webappapis.html#dom-btoa
+    fn btoa(data: &JsString, context: &mut Context<'_>) -> JsResult<JsString> {
+        // 1. If `data` contains any code point greater than U+00FF, then throw
+        //    an "InvalidCharacterError" DOMException
+        let bytes = data
+            .iter()
+            .map(|&code_unit| u8::try_from(code_unit))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| {
+                invalid_character_error(
+                    "The string to be encoded contains characters outside of the Latin1 range",
+                    context,
+                )
+            })?;
+
+        // 2-3. Return the base64 encoding of the bytes of `data`
+        Ok(BASE64_STANDARD.encode(bytes).as_str().into())
     }
 }
 
 pub struct GlobalApi;
 impl GlobalApi {
-    fn atob(_: &JsValue, args: &[JsValue], _context: &mut Context) -> JsResult<JsValue> {
-        let data: &JsString = args
-            .get_or_undefined(0)
-            .as_string()
-            .ok_or_else(|| JsNativeError::typ().with_message("expected string"))?;
-        // TODO: https://github.com/trilitech/jstz/pull/197#discussion_r1413836707
-        // let data: JsString = args.get_or_undefined(0).try_js_into(context);
-        let result = Global::atob(&data)?;
+    fn atob(_: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+        let data = args.get_or_undefined(0).to_string(context)?;
+        let result = Global::atob(&data, context)?;
         Ok(result.into())
     }
-    fn btoa(_: &JsValue, args: &[JsValue], _context: &mut Context) -> JsResult<JsValue> {
-        let data: &JsString = args
-            .get_or_undefined(0)
-            .as_string()
-            .ok_or_else(|| JsNativeError::typ().with_message("expected string"))?;
-        // TODO: https://github.com/trilitech/jstz/pull/197#discussion_r1413836707
-        let result = Global::btoa(&data)?;
+    fn btoa(_: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+        let data = args.get_or_undefined(0).to_string(context)?;
+        let result = Global::btoa(&data, context)?;
         Ok(result.into())
     }
 }
@@ -69,3 +145,70 @@ impl jstz_core::Api for GlobalApi {
             .expect("btoa should only be registered once");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::Api;
+
+    use super::*;
+
+    fn eval(code: &str) -> JsResult<String> {
+        let context = &mut Context::default();
+        GlobalApi.init(context);
+
+        let value = context.eval(Source::from_bytes(code))?;
+        Ok(value.to_string(context)?.to_std_string_escaped())
+    }
+
+    fn error_name(code: &str) -> String {
+        eval(&format!(
+            "try {{ {code}; 'no error' }} catch (error) {{ error.name }}"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn btoa_padding() {
+        assert_eq!(eval(r#"btoa("")"#).unwrap(), "");
+        assert_eq!(eval(r#"btoa("a")"#).unwrap(), "YQ==");
+        assert_eq!(eval(r#"btoa("ab")"#).unwrap(), "YWI=");
+        assert_eq!(eval(r#"btoa("abc")"#).unwrap(), "YWJj");
+    }
+
+    #[test]
+    fn atob_padding() {
+        assert_eq!(eval(r#"atob("")"#).unwrap(), "");
+        assert_eq!(eval(r#"atob("YQ==")"#).unwrap(), "a");
+        assert_eq!(eval(r#"atob("YQ")"#).unwrap(), "a");
+        assert_eq!(eval(r#"atob("YWI=")"#).unwrap(), "ab");
+        assert_eq!(eval(r#"atob("YWI")"#).unwrap(), "ab");
+        assert_eq!(eval(r#"atob("YWJj")"#).unwrap(), "abc");
+        assert_eq!(eval(r#"atob(" YW\nJj ")"#).unwrap(), "abc");
+    }
+
+    #[test]
+    fn round_trip_binary_string() {
+        let code = r#"
+            const binary = String.fromCharCode(...Array.from({ length: 256 }, (_, i) => i));
+            atob(btoa(binary)) === binary
+        "#;
+
+        assert_eq!(eval(code).unwrap(), "true");
+        assert_eq!(eval(r#"btoa("\xff\xfe")"#).unwrap(), "//4=");
+    }
+
+    #[test]
+    fn atob_rejects_invalid_input() {
+        assert_eq!(error_name(r#"atob("Y")"#), "InvalidCharacterError");
+        assert_eq!(error_name(r#"atob("YQ=")"#), "InvalidCharacterError");
+        assert_eq!(error_name(r#"atob("YQ===")"#), "InvalidCharacterError");
+        assert_eq!(error_name(r#"atob("Y-_Q")"#), "InvalidCharacterError");
+    }
+
+    #[test]
+    fn btoa_rejects_code_points_above_0xff() {
+        assert_eq!(error_name(r#"btoa("Ā")"#), "InvalidCharacterError");
+        assert_eq!(error_name(r#"btoa("🦀")"#), "InvalidCharacterError");
+    }
+}
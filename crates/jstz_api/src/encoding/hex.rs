@@ -0,0 +1,115 @@
+//! `jstz`'s implementation of hexadecimal encoding.
+//!
+//! Exposes the global `Hex` object, which encodes byte buffers as lowercase
+//! hexadecimal strings and decodes (case-insensitive) hexadecimal strings
+//! into `Uint8Array`s.
+
+use boa_engine::{
+    js_string, object::ObjectInitializer, property::Attribute, Context, JsArgs,
+    JsNativeError, JsResult, JsString, JsValue, NativeFunction,
+};
+
+use crate::idl::{buffer_source_to_vec, vec_to_uint8_array};
+
+/// Encodes `bytes` as a lowercase hexadecimal string.
+pub fn encode(bytes: &[u8]) -> String {
+    hex::encode(bytes)
+}
+
+/// Decodes a hexadecimal string. Both lower and upper case digits are
+/// accepted.
+pub fn decode(data: &str) -> JsResult<Vec<u8>> {
+    hex::decode(data).map_err(|err| {
+        let message = match err {
+            hex::FromHexError::OddLength => "Odd number of hex digits".to_string(),
+            err => format!("Invalid hex string: {err}"),
+        };
+
+        JsNativeError::typ().with_message(message).into()
+    })
+}
+
+pub struct HexApi;
+
+impl HexApi {
+    const NAME: &'static str = "Hex";
+
+    fn encode(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let bytes = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+
+        Ok(JsString::from(encode(&bytes)).into())
+    }
+
+    fn decode(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let data: String = args.get_or_undefined(0).try_js_into(context)?;
+
+        Ok(vec_to_uint8_array(decode(&data)?, context)?.into())
+    }
+}
+
+impl jstz_core::Api for HexApi {
+    fn init(self, context: &mut Context<'_>) {
+        let hex = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::encode),
+                js_string!("encode"),
+                1,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::decode),
+                js_string!("decode"),
+                1,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), hex, Attribute::all())
+            .expect("The hex object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_emits_lowercase() {
+        assert_eq!(encode(&[]), "");
+        assert_eq!(encode(&[0x00, 0xab, 0xcd, 0xef, 0xff]), "00abcdefff");
+    }
+
+    #[test]
+    fn decode_accepts_both_cases() {
+        assert_eq!(
+            decode("00abCDefFF").unwrap(),
+            vec![0x00, 0xab, 0xcd, 0xef, 0xff]
+        );
+        assert_eq!(decode("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn decode_rejects_odd_length() {
+        assert!(decode("abc").is_err());
+    }
+
+    #[test]
+    fn decode_rejects_non_hex_digits() {
+        assert!(decode("zz").is_err());
+        assert!(decode("0x00").is_err());
+    }
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+
+        assert_eq!(decode(&encode(&bytes)).unwrap(), bytes);
+    }
+}
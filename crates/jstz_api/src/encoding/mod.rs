@@ -1,10 +1,18 @@
 use boa_engine::Context;
 
 use self::{
-    global::GlobalApi, text_decoder::TextDecoderApi, text_encoder::TextEncoderApi,
+    base58check::Base58CheckApi, global::GlobalApi, hex::HexApi, multibase::MultibaseApi,
+    multihash::MultihashApi, protobuf::ProtobufApi, rlp::RlpApi,
+    text_decoder::TextDecoderApi, text_encoder::TextEncoderApi,
 };
 
+pub mod base58check;
 pub mod global;
+pub mod hex;
+pub mod multibase;
+pub mod multihash;
+pub mod protobuf;
+pub mod rlp;
 pub mod text_decoder;
 pub mod text_encoder;
 
@@ -15,5 +23,11 @@ impl jstz_core::Api for EncodingApi {
         TextEncoderApi.init(context);
         TextDecoderApi.init(context);
         GlobalApi.init(context);
+        Base58CheckApi.init(context);
+        HexApi.init(context);
+        MultibaseApi.init(context);
+        MultihashApi.init(context);
+        RlpApi.init(context);
+        ProtobufApi.init(context);
     }
 }
@@ -0,0 +1,149 @@
+//! `jstz`'s implementation of the multibase encoding.
+//!
+//! Multibase strings are prefixed by a single character identifying the base
+//! used to encode the data, as used by IPFS and libp2p content identifiers.
+//!
+//! More information:
+//!  - [Multibase specification][spec]
+//!
+//! [spec]: https://github.com/multiformats/multibase
+
+use boa_engine::{
+    js_string, object::ObjectInitializer, property::Attribute, Context, JsArgs,
+    JsNativeError, JsResult, JsString, JsValue, NativeFunction,
+};
+use multibase::Base;
+
+use crate::idl::{buffer_source_to_vec, vec_to_uint8_array};
+
+fn base_from_name(name: &str) -> JsResult<Base> {
+    match name {
+        "base64" => Ok(Base::Base64),
+        "base32" => Ok(Base::Base32Lower),
+        "base16" => Ok(Base::Base16Lower),
+        "base58btc" => Ok(Base::Base58Btc),
+        _ => Err(JsNativeError::typ()
+            .with_message(format!("Unsupported multibase encoding: {name}"))
+            .into()),
+    }
+}
+
+fn base_to_name(base: Base) -> JsResult<&'static str> {
+    match base {
+        Base::Base64 | Base::Base64Pad => Ok("base64"),
+        Base::Base32Lower | Base::Base32Upper => Ok("base32"),
+        Base::Base16Lower | Base::Base16Upper => Ok("base16"),
+        Base::Base58Btc => Ok("base58btc"),
+        base => Err(JsNativeError::typ()
+            .with_message(format!("Unsupported multibase encoding: {base:?}"))
+            .into()),
+    }
+}
+
+/// Encodes `data` as a multibase string using the base named `base`.
+pub fn encode(data: &[u8], base: &str) -> JsResult<String> {
+    Ok(multibase::encode(base_from_name(base)?, data))
+}
+
+/// Decodes a multibase string, returning the decoded data and the name of the
+/// base it was encoded with.
+pub fn decode(encoded: &str) -> JsResult<(Vec<u8>, &'static str)> {
+    let (base, data) = multibase::decode(encoded).map_err(|err| {
+        JsNativeError::typ().with_message(format!("Invalid multibase string: {err}"))
+    })?;
+
+    Ok((data, base_to_name(base)?))
+}
+
+pub struct MultibaseApi;
+
+impl MultibaseApi {
+    const NAME: &'static str = "Multibase";
+
+    fn encode(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let data = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+        let base: String = args.get_or_undefined(1).try_js_into(context)?;
+
+        Ok(JsString::from(encode(&data, &base)?).into())
+    }
+
+    fn decode(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let encoded: String = args.get_or_undefined(0).try_js_into(context)?;
+
+        let (data, base) = decode(&encoded)?;
+
+        let data = vec_to_uint8_array(data, context)?;
+
+        Ok(ObjectInitializer::new(context)
+            .property(js_string!("data"), data, Attribute::all())
+            .property(js_string!("base"), js_string!(base), Attribute::all())
+            .build()
+            .into())
+    }
+}
+
+impl jstz_core::Api for MultibaseApi {
+    fn init(self, context: &mut Context<'_>) {
+        let multibase = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::encode),
+                js_string!("encode"),
+                2,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::decode),
+                js_string!("decode"),
+                1,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), multibase, Attribute::all())
+            .expect("The multibase object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_uses_prefix() {
+        assert_eq!(
+            encode(b"yes mani !", "base16").unwrap(),
+            "f796573206d616e692021"
+        );
+        assert_eq!(
+            encode(b"yes mani !", "base32").unwrap(),
+            "bpfsxgidnmfxgsibb"
+        );
+        assert_eq!(
+            encode(b"yes mani !", "base58btc").unwrap(),
+            "z7paNL19xttacUY"
+        );
+        assert_eq!(encode(b"yes mani !", "base64").unwrap(), "meWVzIG1hbmkgIQ");
+    }
+
+    #[test]
+    fn decode_roundtrip() {
+        for base in ["base64", "base32", "base16", "base58btc"] {
+            let encoded = encode(b"hello jstz", base).unwrap();
+
+            assert_eq!(decode(&encoded).unwrap(), (b"hello jstz".to_vec(), base));
+        }
+    }
+
+    #[test]
+    fn unsupported_base() {
+        assert!(encode(b"data", "base36").is_err());
+        assert!(decode("k2lcpzo5yikidynfl").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! `jstz`'s implementation of the multihash format.
+//!
+//! A multihash is a self-describing digest: `<hash function code><digest
+//! length><digest>`, where the code and length are unsigned varints. It is the
+//! building block of IPFS content identifiers (CIDs).
+//!
+//! More information:
+//!  - [Multihash specification][spec]
+//!
+//! [spec]: https://github.com/multiformats/multihash
+
+use boa_engine::{
+    js_string, object::ObjectInitializer, property::Attribute, Context, JsArgs,
+    JsNativeError, JsResult, JsValue, NativeFunction,
+};
+use jstz_crypto::hash::Blake2b;
+use sha2::{Digest, Sha256};
+
+use crate::idl::{buffer_source_to_vec, vec_to_uint8_array};
+
+const SHA2_256: u64 = 0x12;
+const BLAKE2B_256: u64 = 0xb220;
+
+fn write_varint(mut value: u64, buf: &mut Vec<u8>) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Hashes `data` with the hash function named `hash_fn` and returns the
+/// resulting multihash.
+pub fn encode(data: &[u8], hash_fn: &str) -> JsResult<Vec<u8>> {
+    let (code, digest) = match hash_fn {
+        "sha2-256" => (SHA2_256, Sha256::digest(data).to_vec()),
+        "blake2b-256" => (BLAKE2B_256, Blake2b::from(data).as_ref().to_vec()),
+        _ => {
+            return Err(JsNativeError::typ()
+                .with_message(format!("Unsupported multihash function: {hash_fn}"))
+                .into())
+        }
+    };
+
+    let mut multihash = Vec::with_capacity(digest.len() + 4);
+    write_varint(code, &mut multihash);
+    write_varint(digest.len() as u64, &mut multihash);
+    multihash.extend_from_slice(&digest);
+
+    Ok(multihash)
+}
+
+pub struct MultihashApi;
+
+impl MultihashApi {
+    const NAME: &'static str = "Multihash";
+
+    fn encode(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let data = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+        let hash_fn: String = args.get_or_undefined(1).try_js_into(context)?;
+
+        Ok(vec_to_uint8_array(encode(&data, &hash_fn)?, context)?.into())
+    }
+}
+
+impl jstz_core::Api for MultihashApi {
+    fn init(self, context: &mut Context<'_>) {
+        let multihash = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::encode),
+                js_string!("encode"),
+                2,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), multihash, Attribute::all())
+            .expect("The multihash object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_sha2_256() {
+        let multihash = encode(b"hello world", "sha2-256").unwrap();
+
+        assert_eq!(&multihash[..2], &[0x12, 0x20]);
+        assert_eq!(
+            &multihash[2..6],
+            &[0xb9, 0x4d, 0x27, 0xb9],
+            "digest should be sha2-256(\"hello world\")"
+        );
+        assert_eq!(multihash.len(), 34);
+    }
+
+    #[test]
+    fn encode_blake2b_256() {
+        let multihash = encode(b"hello world", "blake2b-256").unwrap();
+
+        assert_eq!(&multihash[..4], &[0xa0, 0xe4, 0x02, 0x20]);
+        assert_eq!(multihash.len(), 36);
+    }
+
+    #[test]
+    fn unsupported_hash_function() {
+        assert!(encode(b"hello world", "md5").is_err());
+    }
+}
@@ -0,0 +1,1205 @@
+//! `jstz`'s implementation of Protocol Buffers serialization.
+//!
+//! Messages are described by a proto3 schema, given as source text. The first
+//! top-level message of the schema is the one being encoded or decoded; any
+//! other messages and enums it refers to must be defined in the same schema.
+//!
+//! Messages are represented in JavaScript as plain objects keyed by field
+//! name, with `oneof` members appearing directly on the message. Following
+//! the proto3 JSON mapping, 64-bit integers are decoded as decimal strings (as
+//! they may not fit in a `number`), `bytes` fields are `Uint8Array`s and enums
+//! are decoded as the name of their value.
+//!
+//! Supported: scalar types, nested messages, enums, `repeated` fields (packed
+//! by default), `oneof` and `optional`. Not supported: `map` fields, groups,
+//! imports and extensions.
+//!
+//! More information:
+//!  - [Protocol Buffers encoding][encoding]
+//!  - [Proto3 language guide][proto3]
+//!
+//! [encoding]: https://protobuf.dev/programming-guides/encoding/
+//! [proto3]: https://protobuf.dev/programming-guides/proto3/
+
+use std::collections::BTreeMap;
+
+use boa_engine::{
+    js_string,
+    object::{builtins::JsArray, ObjectInitializer},
+    property::Attribute,
+    Context, JsArgs, JsNativeError, JsObject, JsResult, JsString, JsValue,
+    NativeFunction,
+};
+use bytes::Buf;
+use prost::encoding::{decode_key, decode_varint, encode_key, encode_varint, WireType};
+
+use crate::idl::{buffer_source_to_vec, vec_to_uint8_array};
+
+/// The maximum nesting depth of messages
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FieldType {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    UInt32,
+    UInt64,
+    SInt32,
+    SInt64,
+    Fixed32,
+    Fixed64,
+    SFixed32,
+    SFixed64,
+    Bool,
+    String,
+    Bytes,
+    Message(String),
+    Enum(String),
+    /// A message or enum type that has not been resolved yet
+    Named(String),
+}
+
+impl FieldType {
+    fn scalar(name: &str) -> Option<Self> {
+        let ty = match name {
+            "double" => Self::Double,
+            "float" => Self::Float,
+            "int32" => Self::Int32,
+            "int64" => Self::Int64,
+            "uint32" => Self::UInt32,
+            "uint64" => Self::UInt64,
+            "sint32" => Self::SInt32,
+            "sint64" => Self::SInt64,
+            "fixed32" => Self::Fixed32,
+            "fixed64" => Self::Fixed64,
+            "sfixed32" => Self::SFixed32,
+            "sfixed64" => Self::SFixed64,
+            "bool" => Self::Bool,
+            "string" => Self::String,
+            "bytes" => Self::Bytes,
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    fn wire_type(&self) -> WireType {
+        match self {
+            Self::Double | Self::Fixed64 | Self::SFixed64 => WireType::SixtyFourBit,
+            Self::Float | Self::Fixed32 | Self::SFixed32 => WireType::ThirtyTwoBit,
+            Self::String | Self::Bytes | Self::Message(_) | Self::Named(_) => {
+                WireType::LengthDelimited
+            }
+            _ => WireType::Varint,
+        }
+    }
+
+    /// Repeated fields of packable types are packed by default in proto3
+    fn is_packable(&self) -> bool {
+        self.wire_type() != WireType::LengthDelimited
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+    name: String,
+    number: u32,
+    ty: FieldType,
+    repeated: bool,
+    /// The `oneof` the field belongs to, if any
+    oneof: Option<String>,
+    /// Whether the field tracks presence (i.e. is `optional` or part of a
+    /// `oneof`), in which case it is encoded even if it has its default value
+    presence: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Message {
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Enum {
+    values: Vec<(String, i32)>,
+}
+
+/// A parsed proto3 schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    messages: BTreeMap<String, Message>,
+    enums: BTreeMap<String, Enum>,
+    root: String,
+}
+
+fn invalid_schema(message: impl Into<String>) -> JsNativeError {
+    JsNativeError::syntax().with_message(format!("Invalid schema: {}", message.into()))
+}
+
+fn invalid_data(message: impl std::fmt::Display) -> JsNativeError {
+    JsNativeError::typ().with_message(format!("Invalid protobuf data: {message}"))
+}
+
+fn tokenize(src: &str) -> JsResult<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = src.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '/' {
+            chars.next();
+            match chars.next() {
+                Some('/') => {
+                    for c in chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                Some('*') => {
+                    let mut last = ' ';
+                    for c in chars.by_ref() {
+                        if last == '*' && c == '/' {
+                            break;
+                        }
+                        last = c;
+                    }
+                }
+                _ => return Err(invalid_schema("unexpected `/`").into()),
+            }
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut token = String::from(c);
+            for next in chars.by_ref() {
+                token.push(next);
+                if next == c {
+                    break;
+                }
+            }
+            tokens.push(token);
+        } else if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_' || c == '.' || c == '-') {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        } else {
+            tokens.push(c.to_string());
+            chars.next();
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+    schema: Schema,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> JsResult<String> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| invalid_schema("unexpected end of input"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> JsResult<()> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(invalid_schema(format!(
+                "expected `{expected}`, found `{token}`"
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    fn skip_statement(&mut self) -> JsResult<()> {
+        while self.next()? != ";" {}
+        Ok(())
+    }
+
+    fn parse(mut self) -> JsResult<Schema> {
+        while let Some(token) = self.peek() {
+            match token {
+                "syntax" | "package" | "import" | "option" => self.skip_statement()?,
+                ";" => self.pos += 1,
+                "message" => {
+                    self.pos += 1;
+                    let name = self.parse_message("")?;
+                    if self.schema.root.is_empty() {
+                        self.schema.root = name;
+                    }
+                }
+                "enum" => {
+                    self.pos += 1;
+                    self.parse_enum("")?;
+                }
+                token => {
+                    return Err(invalid_schema(format!("unexpected `{token}`")).into())
+                }
+            }
+        }
+
+        if self.schema.root.is_empty() {
+            return Err(invalid_schema("no message defined").into());
+        }
+
+        self.resolve()?;
+
+        Ok(self.schema)
+    }
+
+    fn parse_message(&mut self, scope: &str) -> JsResult<String> {
+        let name = qualify(scope, &self.next()?);
+        self.expect("{")?;
+
+        let mut message = Message::default();
+        loop {
+            let token = self.next()?;
+            match token.as_str() {
+                "}" => break,
+                ";" => {}
+                "message" => {
+                    self.parse_message(&name)?;
+                }
+                "enum" => self.parse_enum(&name)?,
+                "option" | "reserved" | "extensions" => self.skip_statement()?,
+                "map" => {
+                    return Err(invalid_schema("`map` fields are not supported").into())
+                }
+                "oneof" => {
+                    let oneof = self.next()?;
+                    self.expect("{")?;
+                    loop {
+                        let token = self.next()?;
+                        match token.as_str() {
+                            "}" => break,
+                            ";" => {}
+                            "option" => self.skip_statement()?,
+                            _ => message.fields.push(self.parse_field(
+                                token,
+                                false,
+                                Some(oneof.clone()),
+                            )?),
+                        }
+                    }
+                }
+                "repeated" => {
+                    let ty = self.next()?;
+                    message.fields.push(self.parse_field(ty, true, None)?);
+                }
+                "optional" => {
+                    let ty = self.next()?;
+                    let mut field = self.parse_field(ty, false, None)?;
+                    field.presence = true;
+                    message.fields.push(field);
+                }
+                _ => message.fields.push(self.parse_field(token, false, None)?),
+            }
+        }
+
+        self.schema.messages.insert(name.clone(), message);
+        Ok(name)
+    }
+
+    fn parse_field(
+        &mut self,
+        ty: String,
+        repeated: bool,
+        oneof: Option<String>,
+    ) -> JsResult<Field> {
+        let name = self.next()?;
+        self.expect("=")?;
+        let number = self.next()?;
+        let number: u32 = number
+            .parse()
+            .ok()
+            .filter(|number| (1..(1 << 29)).contains(number))
+            .ok_or_else(|| invalid_schema(format!("invalid field number `{number}`")))?;
+
+        if self.peek() == Some("[") {
+            while self.next()? != "]" {}
+        }
+        self.expect(";")?;
+
+        Ok(Field {
+            name,
+            number,
+            ty: FieldType::scalar(&ty).unwrap_or(FieldType::Named(ty)),
+            repeated,
+            presence: oneof.is_some(),
+            oneof,
+        })
+    }
+
+    fn parse_enum(&mut self, scope: &str) -> JsResult<()> {
+        let name = qualify(scope, &self.next()?);
+        self.expect("{")?;
+
+        let mut values = Vec::new();
+        loop {
+            let token = self.next()?;
+            match token.as_str() {
+                "}" => break,
+                ";" => {}
+                "option" | "reserved" => self.skip_statement()?,
+                _ => {
+                    self.expect("=")?;
+                    let value = self.next()?;
+                    let value: i32 = value.parse().map_err(|_| {
+                        invalid_schema(format!("invalid enum value `{value}`"))
+                    })?;
+
+                    if self.peek() == Some("[") {
+                        while self.next()? != "]" {}
+                    }
+                    self.expect(";")?;
+
+                    values.push((token, value));
+                }
+            }
+        }
+
+        self.schema.enums.insert(name, Enum { values });
+        Ok(())
+    }
+
+    /// Resolves named field types, searching the scope of the message
+    /// outwards, as protoc does
+    fn resolve(&mut self) -> JsResult<()> {
+        let mut resolved = Vec::new();
+
+        for (name, message) in &self.schema.messages {
+            for (index, field) in message.fields.iter().enumerate() {
+                let FieldType::Named(ty) = &field.ty else {
+                    continue;
+                };
+
+                let ty = self.lookup(name, ty).ok_or_else(|| {
+                    invalid_schema(format!(
+                        "unknown type `{ty}` of field `{}`",
+                        field.name
+                    ))
+                })?;
+
+                resolved.push((name.clone(), index, ty));
+            }
+        }
+
+        for (name, index, ty) in resolved {
+            if let Some(message) = self.schema.messages.get_mut(&name) {
+                message.fields[index].ty = ty;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lookup(&self, scope: &str, ty: &str) -> Option<FieldType> {
+        let find = |name: &str| {
+            if self.schema.messages.contains_key(name) {
+                Some(FieldType::Message(name.to_string()))
+            } else if self.schema.enums.contains_key(name) {
+                Some(FieldType::Enum(name.to_string()))
+            } else {
+                None
+            }
+        };
+
+        if let Some(ty) = ty.strip_prefix('.') {
+            return find(ty);
+        }
+
+        let mut scope = scope;
+        loop {
+            if let Some(ty) = find(&qualify(scope, ty)) {
+                return Some(ty);
+            }
+            if scope.is_empty() {
+                return None;
+            }
+            scope = scope.rsplit_once('.').map_or("", |(outer, _)| outer);
+        }
+    }
+}
+
+fn qualify(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{scope}.{name}")
+    }
+}
+
+impl Schema {
+    pub fn parse(src: &str) -> JsResult<Self> {
+        Parser {
+            tokens: tokenize(src)?,
+            pos: 0,
+            schema: Schema {
+                messages: BTreeMap::new(),
+                enums: BTreeMap::new(),
+                root: String::new(),
+            },
+        }
+        .parse()
+    }
+
+    fn message(&self, name: &str) -> &Message {
+        &self.messages[name]
+    }
+
+    /// Encodes the JavaScript object `value` as the schema's root message
+    pub fn encode(
+        &self,
+        value: &JsValue,
+        context: &mut Context<'_>,
+    ) -> JsResult<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.encode_message(&self.root, value, 0, &mut buf, context)?;
+        Ok(buf)
+    }
+
+    /// Decodes `data` as the schema's root message into a JavaScript object
+    pub fn decode(&self, data: &[u8], context: &mut Context<'_>) -> JsResult<JsValue> {
+        self.decode_message(&self.root, data, 0, context)
+    }
+
+    fn encode_message(
+        &self,
+        name: &str,
+        value: &JsValue,
+        depth: usize,
+        buf: &mut Vec<u8>,
+        context: &mut Context<'_>,
+    ) -> JsResult<()> {
+        if depth >= MAX_DEPTH {
+            return Err(too_deep().into());
+        }
+
+        let object = value.as_object().ok_or_else(|| {
+            JsNativeError::typ()
+                .with_message(format!("Expected `{name}` to be an object"))
+        })?;
+
+        let mut oneofs: Vec<&str> = Vec::new();
+        for field in &self.message(name).fields {
+            let value = object.get(js_string!(field.name.as_str()), context)?;
+            if value.is_null_or_undefined() {
+                continue;
+            }
+
+            if let Some(oneof) = &field.oneof {
+                if oneofs.contains(&oneof.as_str()) {
+                    return Err(JsNativeError::typ()
+                        .with_message(format!("More than one field of `{oneof}` is set"))
+                        .into());
+                }
+                oneofs.push(oneof);
+            }
+
+            if field.repeated {
+                self.encode_repeated(field, &value, depth, buf, context)?;
+                continue;
+            }
+
+            if !field.presence && self.is_default(&field.ty, &value, context)? {
+                continue;
+            }
+
+            encode_key(field.number, field.ty.wire_type(), buf);
+            self.encode_value(&field.ty, &value, depth, buf, context)?;
+        }
+
+        Ok(())
+    }
+
+    fn encode_repeated(
+        &self,
+        field: &Field,
+        value: &JsValue,
+        depth: usize,
+        buf: &mut Vec<u8>,
+        context: &mut Context<'_>,
+    ) -> JsResult<()> {
+        let array = value
+            .as_object()
+            .filter(|obj| obj.is_array())
+            .ok_or_else(|| {
+                JsNativeError::typ()
+                    .with_message(format!("Expected `{}` to be an array", field.name))
+            })?;
+        let array = JsArray::from_object(array.clone())?;
+        let length = array.length(context)?;
+
+        if length == 0 {
+            return Ok(());
+        }
+
+        if field.ty.is_packable() {
+            let mut packed = Vec::new();
+            for i in 0..length {
+                let value = array.get(i, context)?;
+                self.encode_value(&field.ty, &value, depth, &mut packed, context)?;
+            }
+
+            encode_key(field.number, WireType::LengthDelimited, buf);
+            encode_varint(packed.len() as u64, buf);
+            buf.extend_from_slice(&packed);
+        } else {
+            for i in 0..length {
+                let value = array.get(i, context)?;
+                encode_key(field.number, field.ty.wire_type(), buf);
+                self.encode_value(&field.ty, &value, depth, buf, context)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_default(
+        &self,
+        ty: &FieldType,
+        value: &JsValue,
+        context: &mut Context<'_>,
+    ) -> JsResult<bool> {
+        let is_default = match ty {
+            FieldType::Message(_) | FieldType::Named(_) => false,
+            FieldType::String => value.to_string(context)?.is_empty(),
+            FieldType::Bytes => buffer_source_to_vec(value, context)?.is_empty(),
+            FieldType::Bool => !value.to_boolean(),
+            FieldType::Double | FieldType::Float => value.to_number(context)? == 0.0,
+            FieldType::Enum(name) => self.enum_value(name, value, context)? == 0,
+            FieldType::UInt32
+            | FieldType::UInt64
+            | FieldType::Fixed32
+            | FieldType::Fixed64 => to_u64(value, context)? == 0,
+            _ => to_i64(value, context)? == 0,
+        };
+
+        Ok(is_default)
+    }
+
+    fn encode_value(
+        &self,
+        ty: &FieldType,
+        value: &JsValue,
+        depth: usize,
+        buf: &mut Vec<u8>,
+        context: &mut Context<'_>,
+    ) -> JsResult<()> {
+        match ty {
+            FieldType::Double => {
+                buf.extend_from_slice(&value.to_number(context)?.to_le_bytes())
+            }
+            FieldType::Float => {
+                buf.extend_from_slice(&(value.to_number(context)? as f32).to_le_bytes())
+            }
+            FieldType::Int32 => encode_varint(to_i32(value, context)? as i64 as u64, buf),
+            FieldType::Int64 => encode_varint(to_i64(value, context)? as u64, buf),
+            FieldType::UInt32 => encode_varint(to_u32(value, context)? as u64, buf),
+            FieldType::UInt64 => encode_varint(to_u64(value, context)?, buf),
+            FieldType::SInt32 => {
+                let n = to_i32(value, context)?;
+                encode_varint(((n << 1) ^ (n >> 31)) as u32 as u64, buf)
+            }
+            FieldType::SInt64 => {
+                let n = to_i64(value, context)?;
+                encode_varint(((n << 1) ^ (n >> 63)) as u64, buf)
+            }
+            FieldType::Fixed32 => {
+                buf.extend_from_slice(&to_u32(value, context)?.to_le_bytes())
+            }
+            FieldType::Fixed64 => {
+                buf.extend_from_slice(&to_u64(value, context)?.to_le_bytes())
+            }
+            FieldType::SFixed32 => {
+                buf.extend_from_slice(&to_i32(value, context)?.to_le_bytes())
+            }
+            FieldType::SFixed64 => {
+                buf.extend_from_slice(&to_i64(value, context)?.to_le_bytes())
+            }
+            FieldType::Bool => encode_varint(value.to_boolean() as u64, buf),
+            FieldType::Enum(name) => {
+                encode_varint(self.enum_value(name, value, context)? as i64 as u64, buf)
+            }
+            FieldType::String => {
+                let string = value.to_string(context)?.to_std_string_escaped();
+                encode_varint(string.len() as u64, buf);
+                buf.extend_from_slice(string.as_bytes());
+            }
+            FieldType::Bytes => {
+                let bytes = buffer_source_to_vec(value, context)?;
+                encode_varint(bytes.len() as u64, buf);
+                buf.extend_from_slice(&bytes);
+            }
+            FieldType::Message(name) => {
+                let mut message = Vec::new();
+                self.encode_message(name, value, depth + 1, &mut message, context)?;
+                encode_varint(message.len() as u64, buf);
+                buf.extend_from_slice(&message);
+            }
+            FieldType::Named(name) => {
+                return Err(invalid_schema(format!("unknown type `{name}`")).into())
+            }
+        }
+
+        Ok(())
+    }
+
+    fn enum_value(
+        &self,
+        name: &str,
+        value: &JsValue,
+        context: &mut Context<'_>,
+    ) -> JsResult<i32> {
+        if let Some(string) = value.as_string() {
+            let string = string.to_std_string_escaped();
+            return self.enums[name]
+                .values
+                .iter()
+                .find(|(value, _)| *value == string)
+                .map(|(_, number)| *number)
+                .ok_or_else(|| {
+                    JsNativeError::typ()
+                        .with_message(format!("`{string}` is not a value of `{name}`"))
+                        .into()
+                });
+        }
+
+        to_i32(value, context)
+    }
+
+    fn decode_message(
+        &self,
+        name: &str,
+        mut data: &[u8],
+        depth: usize,
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        if depth >= MAX_DEPTH {
+            return Err(too_deep().into());
+        }
+
+        let message = self.message(name);
+        let mut values: Vec<Vec<JsValue>> = vec![Vec::new(); message.fields.len()];
+
+        while data.has_remaining() {
+            let (number, wire_type) = decode_key(&mut data).map_err(invalid_data)?;
+
+            let Some(index) = message.fields.iter().position(|f| f.number == number)
+            else {
+                skip_field(wire_type, &mut data)?;
+                continue;
+            };
+            let field = &message.fields[index];
+
+            if field.repeated
+                && field.ty.is_packable()
+                && wire_type == WireType::LengthDelimited
+            {
+                let mut packed = split_length_delimited(&mut data)?;
+                while packed.has_remaining() {
+                    let value =
+                        self.decode_value(&field.ty, &mut packed, depth, context)?;
+                    values[index].push(value);
+                }
+                continue;
+            }
+
+            if wire_type != field.ty.wire_type() {
+                return Err(invalid_data(format!(
+                    "unexpected wire type for field `{}`",
+                    field.name
+                ))
+                .into());
+            }
+
+            let value = self.decode_value(&field.ty, &mut data, depth, context)?;
+
+            if !field.repeated {
+                // The last member of a `oneof` on the wire wins
+                if let Some(oneof) = &field.oneof {
+                    for (i, other) in message.fields.iter().enumerate() {
+                        if other.oneof.as_ref() == Some(oneof) {
+                            values[i].clear();
+                        }
+                    }
+                }
+                values[index].clear();
+            }
+            values[index].push(value);
+        }
+
+        let object = JsObject::with_object_proto(context.intrinsics());
+        for (field, mut values) in message.fields.iter().zip(values) {
+            let value = if field.repeated {
+                JsArray::from_iter(values, context).into()
+            } else if let Some(value) = values.pop() {
+                value
+            } else if field.presence || matches!(field.ty, FieldType::Message(_)) {
+                continue;
+            } else {
+                self.default_value(&field.ty, context)?
+            };
+
+            object.create_data_property_or_throw(
+                js_string!(field.name.as_str()),
+                value,
+                context,
+            )?;
+        }
+
+        Ok(object.into())
+    }
+
+    fn decode_value(
+        &self,
+        ty: &FieldType,
+        data: &mut &[u8],
+        depth: usize,
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let value = match ty {
+            FieldType::Double => f64::from_le_bytes(read_fixed(data)?).into(),
+            FieldType::Float => (f32::from_le_bytes(read_fixed(data)?) as f64).into(),
+            FieldType::Int32 => (read_varint(data)? as i32).into(),
+            FieldType::Int64 => {
+                JsString::from((read_varint(data)? as i64).to_string()).into()
+            }
+            FieldType::UInt32 => (read_varint(data)? as u32).into(),
+            FieldType::UInt64 => JsString::from(read_varint(data)?.to_string()).into(),
+            FieldType::SInt32 => {
+                let n = read_varint(data)? as u32;
+                (((n >> 1) as i32) ^ -((n & 1) as i32)).into()
+            }
+            FieldType::SInt64 => {
+                let n = read_varint(data)?;
+                let n = ((n >> 1) as i64) ^ -((n & 1) as i64);
+                JsString::from(n.to_string()).into()
+            }
+            FieldType::Fixed32 => u32::from_le_bytes(read_fixed(data)?).into(),
+            FieldType::Fixed64 => {
+                JsString::from(u64::from_le_bytes(read_fixed(data)?).to_string()).into()
+            }
+            FieldType::SFixed32 => i32::from_le_bytes(read_fixed(data)?).into(),
+            FieldType::SFixed64 => {
+                JsString::from(i64::from_le_bytes(read_fixed(data)?).to_string()).into()
+            }
+            FieldType::Bool => (read_varint(data)? != 0).into(),
+            FieldType::Enum(name) => self.enum_name(name, read_varint(data)? as i32),
+            FieldType::String => {
+                let bytes = split_length_delimited(data)?;
+                let string = std::str::from_utf8(bytes).map_err(invalid_data)?;
+                JsString::from(string).into()
+            }
+            FieldType::Bytes => {
+                let bytes = split_length_delimited(data)?;
+                vec_to_uint8_array(bytes.to_vec(), context)?.into()
+            }
+            FieldType::Message(name) => {
+                let bytes = split_length_delimited(data)?;
+                self.decode_message(name, bytes, depth + 1, context)?
+            }
+            FieldType::Named(name) => {
+                return Err(invalid_schema(format!("unknown type `{name}`")).into())
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn enum_name(&self, name: &str, number: i32) -> JsValue {
+        self.enums[name]
+            .values
+            .iter()
+            .find(|(_, value)| *value == number)
+            .map_or(number.into(), |(name, _)| {
+                JsString::from(name.as_str()).into()
+            })
+    }
+
+    fn default_value(
+        &self,
+        ty: &FieldType,
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let value = match ty {
+            FieldType::Int64
+            | FieldType::UInt64
+            | FieldType::SInt64
+            | FieldType::Fixed64
+            | FieldType::SFixed64 => js_string!("0").into(),
+            FieldType::Bool => false.into(),
+            FieldType::String => js_string!().into(),
+            FieldType::Bytes => vec_to_uint8_array(Vec::new(), context)?.into(),
+            FieldType::Enum(name) => self.enum_name(name, 0),
+            _ => 0.into(),
+        };
+
+        Ok(value)
+    }
+}
+
+fn too_deep() -> JsNativeError {
+    JsNativeError::range().with_message(format!(
+        "Protobuf messages cannot be nested more than {MAX_DEPTH} deep"
+    ))
+}
+
+fn read_varint(data: &mut &[u8]) -> JsResult<u64> {
+    Ok(decode_varint(data).map_err(invalid_data)?)
+}
+
+fn read_fixed<const N: usize>(data: &mut &[u8]) -> JsResult<[u8; N]> {
+    if data.len() < N {
+        return Err(invalid_data("unexpected end of buffer").into());
+    }
+
+    let mut bytes = [0; N];
+    data.copy_to_slice(&mut bytes);
+    Ok(bytes)
+}
+
+fn split_length_delimited<'a>(data: &mut &'a [u8]) -> JsResult<&'a [u8]> {
+    let len = read_varint(data)? as usize;
+    if data.len() < len {
+        return Err(invalid_data("unexpected end of buffer").into());
+    }
+
+    let (bytes, rest) = data.split_at(len);
+    *data = rest;
+    Ok(bytes)
+}
+
+fn skip_field(wire_type: WireType, data: &mut &[u8]) -> JsResult<()> {
+    match wire_type {
+        WireType::Varint => {
+            read_varint(data)?;
+        }
+        WireType::SixtyFourBit => {
+            read_fixed::<8>(data)?;
+        }
+        WireType::ThirtyTwoBit => {
+            read_fixed::<4>(data)?;
+        }
+        WireType::LengthDelimited => {
+            split_length_delimited(data)?;
+        }
+        WireType::StartGroup | WireType::EndGroup => {
+            return Err(invalid_data("groups are not supported").into())
+        }
+    }
+
+    Ok(())
+}
+
+fn to_i64(value: &JsValue, context: &mut Context<'_>) -> JsResult<i64> {
+    if value.is_bigint() {
+        return value.to_big_int64(context);
+    }
+
+    if let Some(string) = value.as_string() {
+        return string.to_std_string_escaped().parse().map_err(|_| {
+            JsNativeError::typ()
+                .with_message("Expected an integer")
+                .into()
+        });
+    }
+
+    let number = value.to_number(context)?;
+    if !number.is_finite() || number.fract() != 0.0 {
+        return Err(JsNativeError::typ()
+            .with_message("Expected an integer")
+            .into());
+    }
+
+    Ok(number as i64)
+}
+
+fn to_u64(value: &JsValue, context: &mut Context<'_>) -> JsResult<u64> {
+    if value.is_bigint() {
+        return value.to_big_uint64(context);
+    }
+
+    if let Some(string) = value.as_string() {
+        return string.to_std_string_escaped().parse().map_err(|_| {
+            JsNativeError::typ()
+                .with_message("Expected an unsigned integer")
+                .into()
+        });
+    }
+
+    u64::try_from(to_i64(value, context)?).map_err(|_| {
+        JsNativeError::range()
+            .with_message("Expected an unsigned integer")
+            .into()
+    })
+}
+
+fn to_i32(value: &JsValue, context: &mut Context<'_>) -> JsResult<i32> {
+    i32::try_from(to_i64(value, context)?).map_err(|_| {
+        JsNativeError::range()
+            .with_message("Integer out of range for a 32-bit field")
+            .into()
+    })
+}
+
+fn to_u32(value: &JsValue, context: &mut Context<'_>) -> JsResult<u32> {
+    u32::try_from(to_u64(value, context)?).map_err(|_| {
+        JsNativeError::range()
+            .with_message("Integer out of range for a 32-bit field")
+            .into()
+    })
+}
+
+pub struct ProtobufApi;
+
+impl ProtobufApi {
+    const NAME: &'static str = "Protobuf";
+
+    fn encode(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let schema = args
+            .get_or_undefined(0)
+            .to_string(context)?
+            .to_std_string_escaped();
+        let schema = Schema::parse(&schema)?;
+
+        let bytes = schema.encode(args.get_or_undefined(1), context)?;
+
+        Ok(vec_to_uint8_array(bytes, context)?.into())
+    }
+
+    fn decode(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let schema = args
+            .get_or_undefined(0)
+            .to_string(context)?
+            .to_std_string_escaped();
+        let schema = Schema::parse(&schema)?;
+
+        let data = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+
+        schema.decode(&data, context)
+    }
+}
+
+impl jstz_core::Api for ProtobufApi {
+    fn init(self, context: &mut Context<'_>) {
+        let protobuf = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::encode),
+                js_string!("encode"),
+                2,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::decode),
+                js_string!("decode"),
+                2,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), protobuf, Attribute::all())
+            .expect("The protobuf object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+
+    use super::*;
+
+    fn eval(code: &str) -> JsValue {
+        let context = &mut Context::default();
+        ProtobufApi.init(context);
+
+        context
+            .eval(Source::from_bytes(code))
+            .expect("Could not evaluate code")
+    }
+
+    fn encode(schema: &str, message: &str) -> Vec<u8> {
+        let context = &mut Context::default();
+        let message = context
+            .eval(Source::from_bytes(&format!("({message})")))
+            .expect("Could not evaluate message");
+
+        Schema::parse(schema)
+            .unwrap()
+            .encode(&message, context)
+            .unwrap()
+    }
+
+    const SCHEMA: &str = r#"
+        syntax = "proto3";
+
+        // A user of the service
+        message User {
+            enum Role {
+                GUEST = 0;
+                ADMIN = 1;
+            }
+
+            message Address {
+                string city = 1;
+                uint32 zip = 2;
+            }
+
+            int32 id = 1;
+            string name = 2;
+            bool active = 3;
+            double score = 4;
+            sint64 balance = 5;
+            bytes avatar = 6;
+            repeated int32 tags = 7;
+            repeated Address addresses = 8;
+            Role role = 9;
+            oneof contact {
+                string email = 10;
+                string phone = 11;
+            }
+            fixed32 flags = 12;
+            optional int32 age = 13;
+        }
+    "#;
+
+    #[test]
+    fn encode_spec_examples() {
+        let schema = "message Test { int32 a = 1; string b = 2; repeated int32 d = 4; }";
+
+        assert_eq!(encode(schema, "{ a: 150 }"), vec![0x08, 0x96, 0x01]);
+        assert_eq!(
+            encode(schema, r#"{ b: "testing" }"#),
+            vec![0x12, 0x07, 0x74, 0x65, 0x73, 0x74, 0x69, 0x6e, 0x67]
+        );
+        assert_eq!(
+            encode(schema, "{ d: [3, 270, 86942] }"),
+            vec![0x22, 0x06, 0x03, 0x8e, 0x02, 0x9e, 0xa7, 0x05]
+        );
+        assert_eq!(
+            encode(schema, "{ a: -1 }"),
+            vec![0x08, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]
+        );
+
+        // Default values are not encoded
+        assert_eq!(encode(schema, r#"{ a: 0, b: "", d: [] }"#), vec![]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let code = format!(
+            r#"
+            const schema = `{SCHEMA}`;
+            const user = {{
+                id: 42,
+                name: "Alice",
+                active: true,
+                score: 99.5,
+                balance: "-9007199254740993",
+                avatar: new Uint8Array([1, 2, 3]),
+                tags: [1, -2, 300],
+                addresses: [{{ city: "Paris", zip: 75001 }}, {{ city: "Lyon", zip: 0 }}],
+                role: "ADMIN",
+                phone: "555-0100",
+                flags: 4294967295,
+                age: 0,
+            }};
+
+            const decoded = Protobuf.decode(schema, Protobuf.encode(schema, user));
+            JSON.stringify({{ ...decoded, avatar: Array.from(decoded.avatar) }});
+            "#
+        );
+
+        let result = eval(&code).as_string().unwrap().to_std_string_escaped();
+        let result: serde_json::Value = serde_json::from_str(&result).unwrap();
+
+        assert_eq!(
+            result,
+            serde_json::json!({
+                "id": 42,
+                "name": "Alice",
+                "active": true,
+                "score": 99.5,
+                "balance": "-9007199254740993",
+                "avatar": [1, 2, 3],
+                "tags": [1, -2, 300],
+                "addresses": [{ "city": "Paris", "zip": 75001 }, { "city": "Lyon", "zip": 0 }],
+                "role": "ADMIN",
+                "phone": "555-0100",
+                "flags": 4294967295u32,
+                "age": 0,
+            })
+        );
+    }
+
+    #[test]
+    fn decode_defaults() {
+        let code = format!(
+            r#"
+            const schema = `{SCHEMA}`;
+            JSON.stringify(Protobuf.decode(schema, new Uint8Array([])));
+            "#
+        );
+
+        let result = eval(&code).as_string().unwrap().to_std_string_escaped();
+        let result: serde_json::Value = serde_json::from_str(&result).unwrap();
+
+        assert_eq!(
+            result,
+            serde_json::json!({
+                "id": 0,
+                "name": "",
+                "active": false,
+                "score": 0,
+                "balance": "0",
+                "avatar": {},
+                "tags": [],
+                "addresses": [],
+                "role": "GUEST",
+                "flags": 0,
+            })
+        );
+    }
+
+    #[test]
+    fn reject_invalid_input() {
+        let context = &mut Context::default();
+        let schema = Schema::parse(SCHEMA).unwrap();
+
+        let both = context
+            .eval(Source::from_bytes(
+                r#"({ email: "a@b.c", phone: "555-0100" })"#,
+            ))
+            .unwrap();
+        assert!(schema.encode(&both, context).is_err());
+
+        assert!(schema.decode(&[0x12, 0x05, 0x41], context).is_err());
+        assert!(Schema::parse("message A { Unknown b = 1; }").is_err());
+        assert!(Schema::parse("message A { map<string, int32> b = 1; }").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! `jstz`'s implementation of Ethereum's Recursive Length Prefix (RLP)
+//! encoding.
+//!
+//! An RLP value is either a byte string (a `Uint8Array` in JavaScript) or a
+//! list of RLP values (an array).
+//!
+//! More information:
+//!  - [Ethereum Yellow Paper, Appendix B][spec]
+//!
+//! [spec]: https://ethereum.github.io/yellowpaper/paper.pdf
+
+use boa_engine::{
+    js_string,
+    object::{builtins::JsArray, ObjectInitializer},
+    property::Attribute,
+    Context, JsArgs, JsNativeError, JsResult, JsValue, NativeFunction,
+};
+use rlp::{DecoderError, Encodable, Rlp, RlpStream};
+
+use crate::idl::{buffer_source_to_vec, vec_to_uint8_array};
+
+/// The maximum nesting depth of lists
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Bytes(Vec<u8>),
+    List(Vec<Item>),
+}
+
+impl Encodable for Item {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        match self {
+            Item::Bytes(bytes) => {
+                s.append(bytes);
+            }
+            Item::List(items) => {
+                s.begin_list(items.len());
+                for item in items {
+                    s.append(item);
+                }
+            }
+        }
+    }
+}
+
+fn too_deep() -> JsNativeError {
+    JsNativeError::range().with_message(format!(
+        "RLP lists cannot be nested more than {MAX_DEPTH} deep"
+    ))
+}
+
+fn invalid(err: DecoderError) -> JsNativeError {
+    JsNativeError::typ().with_message(format!("Invalid RLP data: {err}"))
+}
+
+fn decode_item(rlp: &Rlp, depth: usize) -> JsResult<Item> {
+    if !rlp.is_list() {
+        return Ok(Item::Bytes(rlp.data().map_err(invalid)?.to_vec()));
+    }
+
+    if depth >= MAX_DEPTH {
+        return Err(too_deep().into());
+    }
+
+    let count = rlp.item_count().map_err(invalid)?;
+    let mut items = Vec::with_capacity(count);
+    for i in 0..count {
+        items.push(decode_item(&rlp.at(i).map_err(invalid)?, depth + 1)?);
+    }
+
+    Ok(Item::List(items))
+}
+
+/// Encodes `item` as RLP.
+pub fn encode(item: &Item) -> Vec<u8> {
+    rlp::encode(item).to_vec()
+}
+
+/// Decodes a single RLP item spanning the whole of `data`.
+pub fn decode(data: &[u8]) -> JsResult<Item> {
+    let rlp = Rlp::new(data);
+    if rlp.payload_info().map_err(invalid)?.total() != data.len() {
+        return Err(invalid(DecoderError::RlpInconsistentLengthAndData).into());
+    }
+
+    decode_item(&rlp, 0)
+}
+
+fn item_from_js(
+    value: &JsValue,
+    depth: usize,
+    context: &mut Context<'_>,
+) -> JsResult<Item> {
+    match value.as_object() {
+        Some(obj) if obj.is_array() => {
+            if depth >= MAX_DEPTH {
+                return Err(too_deep().into());
+            }
+
+            let array = JsArray::from_object(obj.clone())?;
+            let mut items = Vec::new();
+            for i in 0..array.length(context)? {
+                let value = array.get(i, context)?;
+                items.push(item_from_js(&value, depth + 1, context)?);
+            }
+
+            Ok(Item::List(items))
+        }
+        _ => Ok(Item::Bytes(buffer_source_to_vec(value, context)?)),
+    }
+}
+
+fn item_to_js(item: Item, context: &mut Context<'_>) -> JsResult<JsValue> {
+    match item {
+        Item::Bytes(bytes) => Ok(vec_to_uint8_array(bytes, context)?.into()),
+        Item::List(items) => {
+            let values = items
+                .into_iter()
+                .map(|item| item_to_js(item, context))
+                .collect::<JsResult<Vec<_>>>()?;
+
+            Ok(JsArray::from_iter(values, context).into())
+        }
+    }
+}
+
+pub struct RlpApi;
+
+impl RlpApi {
+    const NAME: &'static str = "Rlp";
+
+    fn encode(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let item = item_from_js(args.get_or_undefined(0), 0, context)?;
+
+        Ok(vec_to_uint8_array(encode(&item), context)?.into())
+    }
+
+    fn decode(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let data = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+
+        item_to_js(decode(&data)?, context)
+    }
+
+    fn decode_list(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let data = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+
+        match decode(&data)? {
+            item @ Item::List(_) => item_to_js(item, context),
+            Item::Bytes(_) => Err(JsNativeError::typ()
+                .with_message("Expected an RLP list")
+                .into()),
+        }
+    }
+
+    fn decode_bytes(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let data = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+
+        match decode(&data)? {
+            item @ Item::Bytes(_) => item_to_js(item, context),
+            Item::List(_) => Err(JsNativeError::typ()
+                .with_message("Expected an RLP byte string")
+                .into()),
+        }
+    }
+}
+
+impl jstz_core::Api for RlpApi {
+    fn init(self, context: &mut Context<'_>) {
+        let rlp = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::encode),
+                js_string!("encode"),
+                1,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::decode),
+                js_string!("decode"),
+                1,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::decode_list),
+                js_string!("decodeList"),
+                1,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::decode_bytes),
+                js_string!("decodeBytes"),
+                1,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), rlp, Attribute::all())
+            .expect("The rlp object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn bytes(data: &[u8]) -> Item {
+        Item::Bytes(data.to_vec())
+    }
+
+    fn list(items: Vec<Item>) -> Item {
+        Item::List(items)
+    }
+
+    #[test]
+    fn yellow_paper_vectors() {
+        let lorem = b"Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+        let mut encoded_lorem = vec![0xb8, 0x38];
+        encoded_lorem.extend_from_slice(lorem);
+
+        let cases = vec![
+            (bytes(b"dog"), hex::decode("83646f67").unwrap()),
+            (
+                list(vec![bytes(b"cat"), bytes(b"dog")]),
+                hex::decode("c88363617483646f67").unwrap(),
+            ),
+            (bytes(b""), vec![0x80]),
+            (list(vec![]), vec![0xc0]),
+            (bytes(&[0x00]), vec![0x00]),
+            (bytes(&[0x0f]), vec![0x0f]),
+            (bytes(&[0x04, 0x00]), vec![0x82, 0x04, 0x00]),
+            (
+                // The set theoretical representation of three
+                list(vec![
+                    list(vec![]),
+                    list(vec![list(vec![])]),
+                    list(vec![list(vec![]), list(vec![list(vec![])])]),
+                ]),
+                hex::decode("c7c0c1c0c3c0c1c0").unwrap(),
+            ),
+            (bytes(lorem), encoded_lorem),
+        ];
+
+        for (item, expected) in cases {
+            assert_eq!(encode(&item), expected);
+            assert_eq!(decode(&expected).unwrap(), item);
+        }
+    }
+
+    #[test]
+    fn decode_rejects_malformed_data() {
+        // Trailing bytes
+        assert!(decode(&[0x83, b'd', b'o', b'g', 0x00]).is_err());
+        // Truncated payload
+        assert!(decode(&[0x83, b'd', b'o']).is_err());
+        // Non-canonical single byte
+        assert!(decode(&[0x81, 0x00]).is_err());
+        // Too deeply nested
+        let mut nested = list(vec![]);
+        for _ in 0..MAX_DEPTH {
+            nested = list(vec![nested]);
+        }
+        assert!(decode(&encode(&nested)).is_err());
+    }
+}
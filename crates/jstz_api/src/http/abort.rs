@@ -0,0 +1,488 @@
+//! `jstz`'s implementation of JavaScript's `AbortController` and `AbortSignal`
+//! Web API Classes.
+//!
+//! Only the `abort` event is dispatched. Errors thrown by listeners are
+//! ignored, as browsers report them without interrupting the dispatch.
+//!
+//! More information:
+//!  - [MDN documentation of `AbortController`][mdn-controller]
+//!  - [MDN documentation of `AbortSignal`][mdn-signal]
+//!  - [WHATWG `AbortController` specification][spec]
+//!
+//! [mdn-controller]: https://developer.mozilla.org/en-US/docs/Web/API/AbortController
+//! [mdn-signal]: https://developer.mozilla.org/en-US/docs/Web/API/AbortSignal
+//! [spec]: https://dom.spec.whatwg.org/#interface-abortcontroller
+
+use boa_engine::{
+    js_string,
+    object::{Object, ObjectInitializer},
+    property::Attribute,
+    Context, JsArgs, JsError, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{custom_trace, Finalize, GcRefMut, Trace};
+use jstz_core::{
+    accessor,
+    native::{
+        register_global_class, Accessor, ClassBuilder, JsNativeObject, NativeClass,
+    },
+};
+
+/// Returns an error named `AbortError`, the default reason of an aborted signal
+pub fn abort_error(context: &mut Context<'_>) -> JsValue {
+    let error = JsError::from_native(
+        JsNativeError::error().with_message("This operation was aborted"),
+    )
+    .to_opaque(context);
+
+    if let Some(object) = error.as_object() {
+        // Setting a property of a fresh error object cannot fail
+        let _ = object.set(js_string!("name"), js_string!("AbortError"), false, context);
+    }
+
+    error
+}
+
+#[derive(Default)]
+pub struct AbortSignal {
+    aborted: bool,
+    reason: JsValue,
+    /// The event listeners of the signal, with the type of event they listen to
+    listeners: Vec<(String, JsObject)>,
+}
+
+impl Finalize for AbortSignal {}
+
+unsafe impl Trace for AbortSignal {
+    custom_trace!(this, {
+        mark(&this.reason);
+        for (_, listener) in this.listeners.iter() {
+            mark(listener);
+        }
+    });
+}
+
+impl AbortSignal {
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    pub fn reason(&self) -> &JsValue {
+        &self.reason
+    }
+
+    /// [spec] https://dom.spec.whatwg.org/#dom-abortsignal-throwifaborted
+    pub fn throw_if_aborted(&self) -> JsResult<()> {
+        if self.aborted {
+            return Err(JsError::from_opaque(self.reason.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// [spec] https://dom.spec.whatwg.org/#dom-eventtarget-addeventlistener
+    pub fn add_event_listener(&mut self, event_type: String, listener: JsObject) {
+        let exists = self
+            .listeners
+            .iter()
+            .any(|(t, l)| *t == event_type && *l == listener);
+
+        if !exists {
+            self.listeners.push((event_type, listener));
+        }
+    }
+
+    /// [spec] https://dom.spec.whatwg.org/#dom-eventtarget-removeeventlistener
+    pub fn remove_event_listener(&mut self, event_type: &str, listener: &JsObject) {
+        self.listeners
+            .retain(|(t, l)| !(t == event_type && l == listener));
+    }
+
+    /// Aborts `signal` with `reason`, or an `AbortError` if `reason` is
+    /// undefined, and dispatches the `abort` event to its listeners
+    ///
+    /// [spec] https://dom.spec.whatwg.org/#abortsignal-signal-abort
+    pub fn abort(
+        signal: &JsNativeObject<Self>,
+        reason: JsValue,
+        context: &mut Context<'_>,
+    ) -> JsResult<()> {
+        // 1. If `signal` is aborted, then return
+        if signal.deref().aborted {
+            return Ok(());
+        }
+
+        // 2. Set `signal`'s abort reason to `reason` if it is given; otherwise
+        //    to a new "AbortError" `DOMException`
+        let reason = if reason.is_undefined() {
+            abort_error(context)
+        } else {
+            reason
+        };
+
+        let listeners = {
+            let mut signal = signal.deref_mut();
+            signal.aborted = true;
+            signal.reason = reason;
+
+            // The event is dispatched at most once, so the listeners are no
+            // longer needed
+            std::mem::take(&mut signal.listeners)
+        };
+
+        // 3-5. Fire an event named `abort` at `signal`
+        let event = ObjectInitializer::new(context)
+            .property(js_string!("type"), js_string!("abort"), Attribute::READONLY)
+            .property(js_string!("target"), signal.to_inner(), Attribute::READONLY)
+            .build();
+
+        for (_, listener) in listeners.iter().filter(|(t, _)| t == "abort") {
+            let _ = Self::call_listener(signal.inner(), listener, &event, context);
+        }
+
+        Ok(())
+    }
+
+    /// Calls `listener`, which is either a function or an object with a
+    /// `handleEvent` method
+    fn call_listener(
+        this: &JsValue,
+        listener: &JsObject,
+        event: &JsObject,
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let args = [JsValue::from(event.clone())];
+
+        if listener.is_callable() {
+            return listener.call(this, &args, context);
+        }
+
+        let handle_event = listener.get(js_string!("handleEvent"), context)?;
+        match handle_event.as_callable() {
+            Some(handle_event) => {
+                handle_event.call(&listener.clone().into(), &args, context)
+            }
+            None => Err(JsNativeError::typ()
+                .with_message("The event listener has no `handleEvent` method")
+                .into()),
+        }
+    }
+
+    fn try_from_js<'a>(value: &'a JsValue) -> JsResult<GcRefMut<'a, Object, Self>> {
+        value
+            .as_object()
+            .and_then(|obj| obj.downcast_mut::<Self>())
+            .ok_or_else(|| {
+                JsNativeError::typ()
+                    .with_message(
+                        "Failed to convert js value into rust type `AbortSignal`",
+                    )
+                    .into()
+            })
+    }
+}
+
+pub struct AbortSignalClass;
+
+impl AbortSignalClass {
+    fn aborted(context: &mut Context<'_>) -> Accessor {
+        accessor!(
+            context,
+            AbortSignal,
+            "aborted",
+            get:((signal, _context) => Ok(signal.aborted().into()))
+        )
+    }
+
+    fn reason(context: &mut Context<'_>) -> Accessor {
+        accessor!(
+            context,
+            AbortSignal,
+            "reason",
+            get:((signal, _context) => Ok(signal.reason().clone()))
+        )
+    }
+
+    fn throw_if_aborted(
+        this: &JsValue,
+        _args: &[JsValue],
+        _context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        AbortSignal::try_from_js(this)?.throw_if_aborted()?;
+
+        Ok(JsValue::undefined())
+    }
+
+    /// Returns the type and the listener of `addEventListener` and
+    /// `removeEventListener`, if the listener is not null
+    fn listener_args(
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<Option<(String, JsObject)>> {
+        let event_type = args
+            .get_or_undefined(0)
+            .to_string(context)?
+            .to_std_string_escaped();
+
+        match args.get_or_undefined(1) {
+            JsValue::Null | JsValue::Undefined => Ok(None),
+            JsValue::Object(listener) => Ok(Some((event_type, listener.clone()))),
+            _ => Err(JsNativeError::typ()
+                .with_message("The event listener must be an object")
+                .into()),
+        }
+    }
+
+    fn add_event_listener(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        if let Some((event_type, listener)) = Self::listener_args(args, context)? {
+            AbortSignal::try_from_js(this)?.add_event_listener(event_type, listener);
+        }
+
+        Ok(JsValue::undefined())
+    }
+
+    fn remove_event_listener(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        if let Some((event_type, listener)) = Self::listener_args(args, context)? {
+            AbortSignal::try_from_js(this)?.remove_event_listener(&event_type, &listener);
+        }
+
+        Ok(JsValue::undefined())
+    }
+
+    /// `AbortSignal.abort(reason)`, which returns an already aborted signal
+    fn abort(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let signal = JsNativeObject::new::<Self>(AbortSignal::default(), context)?;
+        AbortSignal::abort(&signal, args.get_or_undefined(0).clone(), context)?;
+
+        Ok(signal.inner().clone())
+    }
+}
+
+impl NativeClass for AbortSignalClass {
+    type Instance = AbortSignal;
+
+    const NAME: &'static str = "AbortSignal";
+
+    fn constructor(
+        _this: &JsNativeObject<AbortSignal>,
+        _args: &[JsValue],
+        _context: &mut Context<'_>,
+    ) -> JsResult<AbortSignal> {
+        Err(JsNativeError::typ()
+            .with_message("Illegal constructor")
+            .into())
+    }
+
+    fn init(class: &mut ClassBuilder<'_, '_>) -> JsResult<()> {
+        let aborted = Self::aborted(class.context());
+        let reason = Self::reason(class.context());
+
+        class
+            .accessor(js_string!("aborted"), aborted, Attribute::all())
+            .accessor(js_string!("reason"), reason, Attribute::all())
+            .method(
+                js_string!("throwIfAborted"),
+                0,
+                NativeFunction::from_fn_ptr(Self::throw_if_aborted),
+            )
+            .method(
+                js_string!("addEventListener"),
+                2,
+                NativeFunction::from_fn_ptr(Self::add_event_listener),
+            )
+            .method(
+                js_string!("removeEventListener"),
+                2,
+                NativeFunction::from_fn_ptr(Self::remove_event_listener),
+            )
+            .static_method(
+                js_string!("abort"),
+                1,
+                NativeFunction::from_fn_ptr(Self::abort),
+            );
+
+        Ok(())
+    }
+}
+
+pub struct AbortController {
+    signal: JsNativeObject<AbortSignal>,
+}
+
+impl Finalize for AbortController {}
+
+unsafe impl Trace for AbortController {
+    custom_trace!(this, {
+        mark(&this.signal);
+    });
+}
+
+impl AbortController {
+    pub fn signal(&self) -> &JsNativeObject<AbortSignal> {
+        &self.signal
+    }
+
+    fn try_from_js<'a>(value: &'a JsValue) -> JsResult<GcRefMut<'a, Object, Self>> {
+        value
+            .as_object()
+            .and_then(|obj| obj.downcast_mut::<Self>())
+            .ok_or_else(|| {
+                JsNativeError::typ()
+                    .with_message(
+                        "Failed to convert js value into rust type `AbortController`",
+                    )
+                    .into()
+            })
+    }
+}
+
+pub struct AbortControllerClass;
+
+impl AbortControllerClass {
+    fn signal(context: &mut Context<'_>) -> Accessor {
+        accessor!(
+            context,
+            AbortController,
+            "signal",
+            get:((controller, _context) => Ok(controller.signal().to_inner()))
+        )
+    }
+
+    fn abort(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        // The controller is released before listeners run, as they may use it
+        let signal = AbortController::try_from_js(this)?.signal().clone();
+        AbortSignal::abort(&signal, args.get_or_undefined(0).clone(), context)?;
+
+        Ok(JsValue::undefined())
+    }
+}
+
+impl NativeClass for AbortControllerClass {
+    type Instance = AbortController;
+
+    const NAME: &'static str = "AbortController";
+
+    fn constructor(
+        _this: &JsNativeObject<AbortController>,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<AbortController> {
+        let signal =
+            JsNativeObject::new::<AbortSignalClass>(AbortSignal::default(), context)?;
+
+        Ok(AbortController { signal })
+    }
+
+    fn init(class: &mut ClassBuilder<'_, '_>) -> JsResult<()> {
+        let signal = Self::signal(class.context());
+
+        class
+            .accessor(js_string!("signal"), signal, Attribute::all())
+            .method(
+                js_string!("abort"),
+                0,
+                NativeFunction::from_fn_ptr(Self::abort),
+            );
+
+        Ok(())
+    }
+}
+
+pub struct AbortApi;
+
+impl jstz_core::Api for AbortApi {
+    fn init(self, context: &mut Context<'_>) {
+        register_global_class::<AbortSignalClass>(context)
+            .expect("The `AbortSignal` class shouldn't exist yet");
+        register_global_class::<AbortControllerClass>(context)
+            .expect("The `AbortController` class shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::Api;
+
+    use super::*;
+
+    fn eval(code: &str) -> String {
+        let context = &mut Context::default();
+        AbortApi.init(context);
+
+        context
+            .eval(Source::from_bytes(code))
+            .expect("Could not evaluate code")
+            .to_string(context)
+            .unwrap()
+            .to_std_string_escaped()
+    }
+
+    #[test]
+    fn abort_sets_reason_and_fires_listeners() {
+        let result = eval(
+            r#"
+            const controller = new AbortController();
+            const events = [];
+            const removed = () => events.push("removed");
+            controller.signal.addEventListener("abort", (event) =>
+                events.push(`${event.type}:${event.target.reason}`)
+            );
+            controller.signal.addEventListener("abort", {
+                handleEvent: () => events.push("handleEvent"),
+            });
+            controller.signal.addEventListener("abort", removed);
+            controller.signal.removeEventListener("abort", removed);
+
+            controller.abort("stop");
+            controller.abort("again");
+            [controller.signal.aborted, controller.signal.reason, ...events].join(",")
+            "#,
+        );
+
+        assert_eq!(result, "true,stop,abort:stop,handleEvent");
+    }
+
+    #[test]
+    fn default_reason_is_abort_error() {
+        let result = eval(
+            r#"
+            const signal = AbortSignal.abort();
+            let name;
+            try {
+                signal.throwIfAborted();
+            } catch (error) {
+                name = error.name;
+            }
+            [signal.reason.name, name].join(",")
+            "#,
+        );
+
+        assert_eq!(result, "AbortError,AbortError");
+    }
+
+    #[test]
+    fn signals_cannot_be_constructed() {
+        assert_eq!(
+            eval(
+                "try { new AbortSignal(); } catch (error) { error instanceof TypeError }"
+            ),
+            "true"
+        );
+    }
+}
@@ -6,7 +6,6 @@
 //! The following is missing:
 //!  - Support for streams
 //!  - Support for blobs
-//!  - Support for form data
 //!
 //! More information:
 //!  - [WHATWG `Headers` specification][spec]
@@ -19,6 +18,9 @@ use boa_engine::{
     Context, JsError, JsNativeError, JsResult, JsString, JsValue,
 };
 use boa_gc::{Finalize, Trace};
+use jstz_core::native::JsNativeObject;
+
+use super::form_data::{FormData, FormDataClass};
 
 pub type HttpBody = Option<Vec<u8>>;
 
@@ -69,13 +71,14 @@ impl Inner {
 #[derive(Trace, Finalize, Clone)]
 pub struct Body {
     inner: Option<Inner>,
+    used: bool,
 }
 
 impl Body {
     pub fn from_http_body(body: HttpBody, _context: &mut Context<'_>) -> JsResult<Self> {
         let inner = body.map(Inner::Bytes);
 
-        Ok(Self { inner })
+        Ok(Self { inner, used: false })
     }
 
     pub fn to_http_body(&self) -> HttpBody {
@@ -88,22 +91,52 @@ impl Body {
 
 impl Body {
     fn new(inner: Inner) -> Self {
-        Self { inner: Some(inner) }
+        Self {
+            inner: Some(inner),
+            used: false,
+        }
     }
 
-    fn inner(&mut self) -> JsResult<Inner> {
-        // Consumes the body
-        match self.inner.take() {
-            Some(inner) => Ok(inner),
-            None => Err(JsError::from_native(
-                JsNativeError::typ().with_message("Body is null or has been used"),
-            )),
+    /// Consumes the body, returning its content (`None` for a `null` body)
+    ///
+    /// More information:
+    ///  - [WHATWG specification][spec]
+    ///
+    /// [spec] https://fetch.spec.whatwg.org/#concept-body-consume-body
+    fn consume(&mut self) -> JsResult<Option<Inner>> {
+        // 1. If object is unusable, then return a promise rejected with a TypeError
+        if self.used {
+            return Err(JsError::from_native(
+                JsNativeError::typ().with_message("Body has already been used"),
+            ));
+        }
+
+        // A `null` body is never disturbed, so it may be consumed again
+        let inner = self.inner.take();
+        self.used = inner.is_some();
+
+        Ok(inner)
+    }
+
+    /// Consumes the body with `f`, rejecting the returned promise (rather
+    /// than throwing) on failure
+    fn consume_with(
+        &mut self,
+        context: &mut Context<'_>,
+        f: impl FnOnce(Option<Inner>, &mut Context<'_>) -> JsResult<JsValue>,
+    ) -> JsResult<JsPromise> {
+        match self.consume().and_then(|inner| f(inner, context)) {
+            Ok(value) => JsPromise::resolve(value, context),
+            Err(err) => JsPromise::reject(err, context),
         }
     }
 
     /// Returns a `null` body
     pub fn null() -> Self {
-        Self { inner: None }
+        Self {
+            inner: None,
+            used: false,
+        }
     }
 
     /// Returns whether the body has been read from.
@@ -116,11 +149,11 @@ impl Body {
         // 1. Return true if this’s `body` is non-null and this’s
         //    body’s stream is disturbed; otherwise false.
         // FIXME: Support streams
-        self.inner.is_none()
+        self.used
     }
 
     pub fn is_null(&self) -> bool {
-        self.is_used()
+        self.inner.is_none()
     }
 
     /// Returns a promise fulfilled with body's content as an ArrayBuffer
@@ -130,8 +163,10 @@ impl Body {
     ///
     /// [spec] https://fetch.spec.whatwg.org/#dom-body-arraybuffer
     pub fn array_buffer(&mut self, context: &mut Context<'_>) -> JsResult<JsPromise> {
-        let inner = self.inner()?;
-        JsPromise::resolve(inner.into_array_buffer(context)?, context)
+        self.consume_with(context, |inner, context| {
+            let inner = inner.unwrap_or(Inner::Bytes(Vec::new()));
+            Ok(inner.into_array_buffer(context)?.into())
+        })
     }
 
     /// Returns a promise fulfilled with body's content as a string
@@ -141,8 +176,10 @@ impl Body {
     ///
     /// [spec] https://fetch.spec.whatwg.org/#dom-body-text
     pub fn text(&mut self, context: &mut Context<'_>) -> JsResult<JsPromise> {
-        let inner = self.inner()?;
-        JsPromise::resolve(inner.text()?, context)
+        self.consume_with(context, |inner, _| match inner {
+            Some(inner) => Ok(inner.text()?.into()),
+            None => Ok(JsString::default().into()),
+        })
     }
 
     /// Returns a promise fulfilled with body's content parsed as JSON
@@ -152,16 +189,42 @@ impl Body {
     ///
     /// [spec] https://fetch.spec.whatwg.org/#dom-body-json
     pub fn json(&mut self, context: &mut Context<'_>) -> JsResult<JsPromise> {
-        let inner = self.inner()?;
-        let json: serde_json::Value =
-            serde_json::from_str(&inner.string()?).map_err(|_| {
-                JsError::from_native(
-                    JsNativeError::typ()
-                        .with_message("Failed to convert `Body` to `serde_json::Value`"),
-                )
-            })?;
-
-        JsPromise::resolve(JsValue::from_json(&json, context)?, context)
+        self.consume_with(context, |inner, context| {
+            let string = match inner {
+                Some(inner) => inner.string()?,
+                None => String::new(),
+            };
+
+            let json: serde_json::Value =
+                serde_json::from_str(&string).map_err(|err| {
+                    JsError::from_native(
+                        JsNativeError::syntax()
+                            .with_message(format!("Invalid JSON: {err}")),
+                    )
+                })?;
+
+            JsValue::from_json(&json, context)
+        })
+    }
+
+    /// Returns a promise fulfilled with body's content parsed as form data,
+    /// according to `content_type`
+    ///
+    /// More information:
+    ///  - [WHATWG specification][spec]
+    ///
+    /// [spec] https://fetch.spec.whatwg.org/#dom-body-formdata
+    pub fn form_data(
+        &mut self,
+        content_type: Option<&str>,
+        context: &mut Context<'_>,
+    ) -> JsResult<JsPromise> {
+        self.consume_with(context, |inner, context| {
+            let bytes = inner.map(|inner| inner.bytes()).unwrap_or_default();
+            let form_data = FormData::parse(content_type, &bytes)?;
+
+            Ok(JsNativeObject::new::<FormDataClass>(form_data, context)?.to_inner())
+        })
     }
 }
 
@@ -261,3 +324,81 @@ impl TryFromJs for BodyWithType {
         BodyWithType::from_init(init)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use boa_engine::{builtins::promise::PromiseState, js_string};
+
+    use super::*;
+
+    fn text_body(text: &str) -> Body {
+        Body::new(Inner::Text(JsString::from(text)))
+    }
+
+    fn error_name(promise: &JsPromise, context: &mut Context<'_>) -> String {
+        let PromiseState::Rejected(error) = promise.state().unwrap() else {
+            panic!("Expected a rejected promise");
+        };
+
+        error
+            .as_object()
+            .expect("Expected an error object")
+            .get(js_string!("name"), context)
+            .unwrap()
+            .to_string(context)
+            .unwrap()
+            .to_std_string_escaped()
+    }
+
+    #[test]
+    fn json_parses_valid_json() {
+        let context = &mut Context::default();
+        let mut body = text_body(r#"{"answer": 42}"#);
+
+        let promise = body.json(context).unwrap();
+
+        let PromiseState::Fulfilled(value) = promise.state().unwrap() else {
+            panic!("Expected a fulfilled promise");
+        };
+        assert_eq!(
+            value.to_json(context).unwrap(),
+            serde_json::json!({ "answer": 42 })
+        );
+        assert!(body.is_used());
+    }
+
+    #[test]
+    fn json_rejects_invalid_json_with_syntax_error() {
+        let context = &mut Context::default();
+        let mut body = text_body("{ not json");
+
+        let promise = body.json(context).unwrap();
+
+        assert_eq!(error_name(&promise, context), "SyntaxError");
+    }
+
+    #[test]
+    fn json_after_text_rejects_with_type_error() {
+        let context = &mut Context::default();
+        let mut body = text_body("{}");
+
+        body.text(context).unwrap();
+        let promise = body.json(context).unwrap();
+
+        assert_eq!(error_name(&promise, context), "TypeError");
+    }
+
+    #[test]
+    fn null_body_is_never_used() {
+        let context = &mut Context::default();
+        let mut body = Body::null();
+
+        let promise = body.text(context).unwrap();
+
+        assert!(matches!(
+            promise.state().unwrap(),
+            PromiseState::Fulfilled(_)
+        ));
+        assert!(!body.is_used());
+    }
+}
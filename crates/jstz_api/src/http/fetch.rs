@@ -0,0 +1,152 @@
+//! `jstz`'s implementation of JavaScript's `fetch` Web API.
+//!
+//! Only `jstz://<address>/<path>` URLs are supported: the request is
+//! dispatched to the smart function at `<address>` by the [`FetchHandler`]
+//! of the runtime.
+//!
+//! A `signal` option aborts the call: `fetch` rejects with the reason of the
+//! signal. Aborting only rejects the promise returned by `fetch`; the smart
+//! function that was called still runs to completion.
+//!
+//! More information:
+//!  - [MDN documentation][mdn]
+//!  - [WHATWG `fetch` specification][spec]
+//!
+//! [mdn]: https://developer.mozilla.org/en-US/docs/Web/API/fetch
+//! [spec]: https://fetch.spec.whatwg.org/#fetch-method
+use boa_engine::{
+    js_string,
+    object::{builtins::JsPromise, FunctionObjectBuilder},
+    Context, JsArgs, JsNativeError, JsResult, JsValue, NativeFunction,
+};
+use jstz_core::native::JsNativeObject;
+use jstz_crypto::public_key_hash::PublicKeyHash;
+
+use super::{
+    abort::AbortSignal,
+    request::{Request, RequestClass, RequestInfo, RequestOptions},
+};
+
+const SCHEME: &str = "jstz";
+
+/// Dispatches `request` to the smart function at the given address, returning
+/// its response (or a promise of it)
+pub type FetchHandler =
+    fn(&PublicKeyHash, &JsNativeObject<Request>, &mut Context<'_>) -> JsResult<JsValue>;
+
+pub struct FetchApi {
+    pub handler: FetchHandler,
+}
+
+/// Returns the `signal` option of `fetch`, if any
+fn signal(
+    options: Option<&JsValue>,
+    context: &mut Context<'_>,
+) -> JsResult<Option<JsNativeObject<AbortSignal>>> {
+    let Some(options) = options.and_then(JsValue::as_object) else {
+        return Ok(None);
+    };
+
+    match options.get(js_string!("signal"), context)? {
+        JsValue::Null | JsValue::Undefined => Ok(None),
+        signal => Ok(Some(signal.try_into()?)),
+    }
+}
+
+/// Rejects the response with the reason of `signal` once it is aborted
+fn abortable(
+    response: JsValue,
+    signal: &JsNativeObject<AbortSignal>,
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let (promise, resolvers) = JsPromise::new_pending(context);
+
+    JsPromise::resolve(response, context)?.then(
+        Some(resolvers.resolve.clone()),
+        Some(resolvers.reject.clone()),
+        context,
+    )?;
+
+    // Listeners are called with the signal as `this`
+    let on_abort = FunctionObjectBuilder::new(
+        context.realm(),
+        NativeFunction::from_copy_closure_with_captures(
+            |this, _, reject, context| {
+                let signal = JsNativeObject::<AbortSignal>::try_from(this.clone())?;
+                let reason = signal.deref().reason().clone();
+
+                reject.call(&JsValue::undefined(), &[reason], context)
+            },
+            resolvers.reject,
+        ),
+    )
+    .build();
+    signal
+        .deref_mut()
+        .add_event_listener("abort".to_string(), on_abort.into());
+
+    Ok(promise.into())
+}
+
+fn fetch(
+    handler: FetchHandler,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let info: RequestInfo = args.get_or_undefined(0).try_js_into(context)?;
+    let options: RequestOptions = match args.get(1) {
+        Some(value) if !value.is_undefined() => value.try_js_into(context)?,
+        _ => Default::default(),
+    };
+    let signal = signal(args.get(1), context)?;
+
+    // Aborted calls are never dispatched
+    if let Some(signal) = &signal {
+        signal.deref().throw_if_aborted()?;
+    }
+
+    let request = Request::new(info, options, context)?;
+
+    if request.url().scheme() != SCHEME {
+        return Err(JsNativeError::typ()
+            .with_message(format!("Unsupported scheme: {}", request.url().scheme()))
+            .into());
+    }
+
+    let address = request
+        .url()
+        .domain()
+        .and_then(|domain| PublicKeyHash::from_base58(domain).ok())
+        .ok_or_else(|| JsNativeError::typ().with_message("Invalid host"))?;
+
+    let request = JsNativeObject::new::<RequestClass>(request, context)?;
+
+    let response = handler(&address, &request, context)?;
+
+    match signal {
+        Some(signal) => abortable(response, &signal, context),
+        None => Ok(response),
+    }
+}
+
+impl jstz_core::Api for FetchApi {
+    fn init(self, context: &mut Context<'_>) {
+        let handler = self.handler;
+
+        context
+            .register_global_callable(
+                js_string!("fetch"),
+                1,
+                NativeFunction::from_copy_closure(move |_, args, context| {
+                    // `fetch` reports failures by rejecting, never by throwing
+                    let promise = match fetch(handler, args, context) {
+                        Ok(response) => JsPromise::resolve(response, context),
+                        Err(err) => JsPromise::reject(err, context),
+                    }?;
+
+                    Ok(promise.into())
+                }),
+            )
+            .expect("The `fetch` function shouldn't exist yet")
+    }
+}
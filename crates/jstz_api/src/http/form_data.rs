@@ -0,0 +1,703 @@
+//! `jstz`'s implementation of JavaScript's `FormData` Web API class, and the
+//! parsing of `multipart/form-data` and `application/x-www-form-urlencoded`
+//! bodies.
+//!
+//! FIXME: There is no `Blob` or `File` class, so file entries are plain
+//! objects with `name`, `type` and `size` properties and `text()` and
+//! `arrayBuffer()` methods, and only strings can be appended.
+//!
+//! More information:
+//!  - [MDN documentation][mdn]
+//!  - [WHATWG `FormData` specification][spec]
+//!  - [RFC 7578: Returning Values from Forms: multipart/form-data][rfc]
+//!
+//! [mdn]: https://developer.mozilla.org/en-US/docs/Web/API/FormData
+//! [spec]: https://xhr.spec.whatwg.org/#interface-formdata
+//! [rfc]: https://datatracker.ietf.org/doc/html/rfc7578
+
+use boa_engine::{
+    js_string,
+    object::{
+        builtins::{JsArray, JsArrayBuffer, JsPromise},
+        Object, ObjectInitializer,
+    },
+    property::Attribute,
+    value::TryFromJs,
+    Context, JsArgs, JsError, JsNativeError, JsResult, JsString, JsValue, NativeFunction,
+};
+use boa_gc::{empty_trace, Finalize, GcRefMut, Trace};
+use jstz_core::{
+    iterators::{PairIterable, PairIterableMethods, PairIteratorClass, PairValue},
+    native::{register_global_class, ClassBuilder, JsNativeObject, NativeClass},
+    value::IntoJs,
+};
+
+/// A file entry of a form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    pub name: String,
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// The value of a form entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormDataEntryValue {
+    Text(String),
+    File(File),
+}
+
+fn file_text(
+    _this: &JsValue,
+    _args: &[JsValue],
+    bytes: &Vec<u8>,
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let text = JsString::from(String::from_utf8_lossy(bytes).as_ref());
+
+    Ok(JsPromise::resolve(text, context)?.into())
+}
+
+fn file_array_buffer(
+    _this: &JsValue,
+    _args: &[JsValue],
+    bytes: &Vec<u8>,
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let array_buffer = JsArrayBuffer::from_byte_block(bytes.clone(), context)?;
+
+    Ok(JsPromise::resolve(array_buffer, context)?.into())
+}
+
+impl FormDataEntryValue {
+    fn to_js(&self, context: &mut Context<'_>) -> JsValue {
+        match self {
+            Self::Text(text) => text.clone().into_js(context),
+            Self::File(file) => ObjectInitializer::new(context)
+                .property(
+                    js_string!("name"),
+                    JsString::from(file.name.as_str()),
+                    Attribute::READONLY | Attribute::ENUMERABLE,
+                )
+                .property(
+                    js_string!("type"),
+                    JsString::from(file.content_type.as_str()),
+                    Attribute::READONLY | Attribute::ENUMERABLE,
+                )
+                .property(
+                    js_string!("size"),
+                    file.bytes.len(),
+                    Attribute::READONLY | Attribute::ENUMERABLE,
+                )
+                .function(
+                    NativeFunction::from_copy_closure_with_captures(
+                        file_text,
+                        file.bytes.clone(),
+                    ),
+                    js_string!("text"),
+                    0,
+                )
+                .function(
+                    NativeFunction::from_copy_closure_with_captures(
+                        file_array_buffer,
+                        file.bytes.clone(),
+                    ),
+                    js_string!("arrayBuffer"),
+                    0,
+                )
+                .build()
+                .into(),
+        }
+    }
+}
+
+/// An ordered list of form entries
+///
+/// [spec] https://xhr.spec.whatwg.org/#interface-formdata
+#[derive(Debug, Default, Clone)]
+pub struct FormData {
+    entries: Vec<(String, FormDataEntryValue)>,
+}
+
+impl Finalize for FormData {}
+
+unsafe impl Trace for FormData {
+    empty_trace!();
+}
+
+fn type_error(message: &str) -> JsError {
+    JsError::from_native(JsNativeError::typ().with_message(message))
+}
+
+impl FormData {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn append(&mut self, name: String, value: FormDataEntryValue) {
+        self.entries.push((name, value))
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.entries.retain(|(entry_name, _)| entry_name != name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&FormDataEntryValue> {
+        self.entries
+            .iter()
+            .find(|(entry_name, _)| entry_name == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn get_all(&self, name: &str) -> Vec<&FormDataEntryValue> {
+        self.entries
+            .iter()
+            .filter(|(entry_name, _)| entry_name == name)
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Replaces the first entry named `name` with `value` and removes the
+    /// others, or appends an entry if there is none
+    pub fn set(&mut self, name: String, value: FormDataEntryValue) {
+        match self
+            .entries
+            .iter()
+            .position(|(entry_name, _)| *entry_name == name)
+        {
+            Some(index) => {
+                self.entries[index].1 = value;
+                let mut position = 0;
+                self.entries.retain(|(entry_name, _)| {
+                    position += 1;
+                    position - 1 <= index || *entry_name != name
+                });
+            }
+            None => self.append(name, value),
+        }
+    }
+
+    /// Parses a body of type `content_type` into form entries. Supports
+    /// `multipart/form-data` and `application/x-www-form-urlencoded` bodies.
+    ///
+    /// More information:
+    ///  - [WHATWG specification][spec]
+    ///
+    /// [spec] https://fetch.spec.whatwg.org/#dom-body-formdata
+    pub fn parse(content_type: Option<&str>, body: &[u8]) -> JsResult<Self> {
+        let content_type = content_type.unwrap_or_default();
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        match essence.as_str() {
+            "multipart/form-data" => {
+                let boundary = boundary(content_type)
+                    .ok_or_else(|| type_error("Invalid multipart/form-data boundary"))?;
+                let entries = parse_multipart(body, boundary)
+                    .ok_or_else(|| type_error("Malformed multipart/form-data body"))?;
+
+                Ok(Self { entries })
+            }
+            "application/x-www-form-urlencoded" => {
+                let entries = url::form_urlencoded::parse(body)
+                    .map(|(name, value)| {
+                        (
+                            name.into_owned(),
+                            FormDataEntryValue::Text(value.into_owned()),
+                        )
+                    })
+                    .collect();
+
+                Ok(Self { entries })
+            }
+            _ => Err(type_error(&format!(
+                "Cannot parse a body of type `{content_type}` as form data"
+            ))),
+        }
+    }
+}
+
+/// Returns the `boundary` parameter of a `multipart/form-data` content type,
+/// if it is valid as per RFC 2046
+fn boundary(content_type: &str) -> Option<&str> {
+    let boundary = content_type.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"'))
+    })?;
+
+    let is_valid = (1..=70).contains(&boundary.len())
+        && !boundary.ends_with(' ')
+        && boundary
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "'()+_,-./:=? ".contains(c));
+
+    is_valid.then_some(boundary)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Parses the entries of a `multipart/form-data` body. Returns `None` if the
+/// body is malformed.
+fn parse_multipart(
+    body: &[u8],
+    boundary: &str,
+) -> Option<Vec<(String, FormDataEntryValue)>> {
+    let delimiter = [b"--", boundary.as_bytes()].concat();
+    let part_delimiter = [b"\r\n", delimiter.as_slice()].concat();
+
+    // The preamble before the first delimiter is ignored
+    let mut rest = &body[find(body, &delimiter)? + delimiter.len()..];
+    let mut entries = Vec::new();
+
+    loop {
+        // The close delimiter ends the body; the epilogue is ignored
+        if rest.starts_with(b"--") {
+            return Some(entries);
+        }
+
+        rest = rest.strip_prefix(b"\r\n")?;
+        let end = find(rest, &part_delimiter)?;
+        entries.push(parse_part(&rest[..end])?);
+        rest = &rest[end + part_delimiter.len()..];
+    }
+}
+
+/// Parses a body part, made of headers and content separated by an empty
+/// line. The `Content-Disposition` header names the entry, and the entry is
+/// a file if it has a `filename`.
+fn parse_part(part: &[u8]) -> Option<(String, FormDataEntryValue)> {
+    let headers_end = find(part, b"\r\n\r\n")?;
+    let headers = std::str::from_utf8(&part[..headers_end]).ok()?;
+    let content = &part[headers_end + 4..];
+
+    let mut name = None;
+    let mut filename = None;
+    let mut content_type = None;
+
+    for header in headers.split("\r\n") {
+        let (key, value) = header.split_once(':')?;
+
+        match key.trim().to_ascii_lowercase().as_str() {
+            "content-disposition" => {
+                let mut params = value.split(';');
+                if params.next()?.trim() != "form-data" {
+                    return None;
+                }
+
+                for param in params {
+                    let (key, value) = param.split_once('=')?;
+                    let value = value.trim().trim_matches('"').to_string();
+
+                    match key.trim() {
+                        "name" => name = Some(value),
+                        "filename" => filename = Some(value),
+                        _ => (),
+                    }
+                }
+            }
+            "content-type" => content_type = Some(value.trim().to_string()),
+            _ => (),
+        }
+    }
+
+    let value = match filename {
+        Some(filename) => FormDataEntryValue::File(File {
+            name: filename,
+            content_type: content_type
+                .unwrap_or_else(|| "application/octet-stream".to_string()),
+            bytes: content.to_vec(),
+        }),
+        None => FormDataEntryValue::Text(String::from_utf8_lossy(content).into_owned()),
+    };
+
+    Some((name?, value))
+}
+
+impl FormData {
+    fn try_from_js<'a>(value: &'a JsValue) -> JsResult<GcRefMut<'a, Object, Self>> {
+        value
+            .as_object()
+            .and_then(|obj| obj.downcast_mut::<Self>())
+            .ok_or_else(|| {
+                JsNativeError::typ()
+                    .with_message("Failed to convert js value into rust type `FormData`")
+                    .into()
+            })
+    }
+}
+
+impl TryFromJs for FormData {
+    fn try_from_js(value: &JsValue, _context: &mut Context<'_>) -> JsResult<Self> {
+        Ok(FormData::try_from_js(value)?.clone())
+    }
+}
+
+pub struct FormDataClass;
+
+impl FormDataClass {
+    fn name_and_value(
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<(String, FormDataEntryValue)> {
+        let name: String = args.get_or_undefined(0).try_js_into(context)?;
+        let value = args
+            .get_or_undefined(1)
+            .to_string(context)?
+            .to_std_string_escaped();
+
+        Ok((name, FormDataEntryValue::Text(value)))
+    }
+
+    fn append(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let (name, value) = Self::name_and_value(args, context)?;
+
+        FormData::try_from_js(this)?.append(name, value);
+
+        Ok(JsValue::undefined())
+    }
+
+    fn delete(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let name: String = args.get_or_undefined(0).try_js_into(context)?;
+
+        FormData::try_from_js(this)?.remove(&name);
+
+        Ok(JsValue::undefined())
+    }
+
+    fn get(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let form_data = FormData::try_from_js(this)?;
+        let name: String = args.get_or_undefined(0).try_js_into(context)?;
+
+        match form_data.get(&name) {
+            Some(value) => Ok(value.to_js(context)),
+            None => Ok(JsValue::null()),
+        }
+    }
+
+    fn get_all(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let form_data = FormData::try_from_js(this)?;
+        let name: String = args.get_or_undefined(0).try_js_into(context)?;
+
+        let values: Vec<JsValue> = form_data
+            .get_all(&name)
+            .into_iter()
+            .map(|value| value.to_js(context))
+            .collect();
+
+        Ok(JsArray::from_iter(values, context).into())
+    }
+
+    fn has(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let form_data = FormData::try_from_js(this)?;
+        let name: String = args.get_or_undefined(0).try_js_into(context)?;
+
+        Ok(form_data.contains(&name).into())
+    }
+
+    fn set(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let (name, value) = Self::name_and_value(args, context)?;
+
+        FormData::try_from_js(this)?.set(name, value);
+
+        Ok(JsValue::undefined())
+    }
+}
+
+impl NativeClass for FormDataClass {
+    type Instance = FormData;
+
+    const NAME: &'static str = "FormData";
+
+    fn constructor(
+        _this: &JsNativeObject<FormData>,
+        _args: &[JsValue],
+        _context: &mut Context<'_>,
+    ) -> JsResult<FormData> {
+        Ok(FormData::new())
+    }
+
+    fn init(class: &mut ClassBuilder<'_, '_>) -> JsResult<()> {
+        class
+            .method(
+                js_string!("append"),
+                2,
+                NativeFunction::from_fn_ptr(FormDataClass::append),
+            )
+            .method(
+                js_string!("delete"),
+                1,
+                NativeFunction::from_fn_ptr(FormDataClass::delete),
+            )
+            .method(
+                js_string!("get"),
+                1,
+                NativeFunction::from_fn_ptr(FormDataClass::get),
+            )
+            .method(
+                js_string!("getAll"),
+                1,
+                NativeFunction::from_fn_ptr(FormDataClass::get_all),
+            )
+            .method(
+                js_string!("has"),
+                1,
+                NativeFunction::from_fn_ptr(FormDataClass::has),
+            )
+            .method(
+                js_string!("set"),
+                2,
+                NativeFunction::from_fn_ptr(FormDataClass::set),
+            );
+
+        PairIterableMethods::<FormDataIteratorClass>::define_pair_iterable_methods(
+            class,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl PairIterable for FormData {
+    fn pair_iterable_len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn pair_iterable_get(
+        &self,
+        index: usize,
+        context: &mut Context<'_>,
+    ) -> JsResult<PairValue> {
+        let (name, value) = self.entries.get(index).ok_or::<JsError>(
+            JsNativeError::typ()
+                .with_message("index out of bounds in FormData Iterator")
+                .into(),
+        )?;
+        let key = name.clone().into_js(context);
+        let value = value.to_js(context);
+        Ok(PairValue { key, value })
+    }
+}
+
+struct FormDataIteratorClass;
+
+impl PairIteratorClass for FormDataIteratorClass {
+    type Iterable = FormData;
+    const NAME: &'static str = "FormData Iterator";
+}
+
+pub struct FormDataApi;
+
+impl jstz_core::Api for FormDataApi {
+    fn init(self, context: &mut Context<'_>) {
+        register_global_class::<FormDataClass>(context)
+            .expect("The `FormData` class shouldn't exist yet");
+        // TODO should not really be a global class, remove from
+        // global object when possible
+        register_global_class::<FormDataIteratorClass>(context)
+            .expect("The `FormData Iterator` class shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::Api;
+
+    use super::*;
+    use crate::http::HttpApi;
+
+    const TWO_FIELDS: &str = "preamble\r\n\
+        --xyz\r\n\
+        Content-Disposition: form-data; name=\"a\"\r\n\
+        \r\n\
+        1\r\n\
+        --xyz\r\n\
+        Content-Disposition: form-data; name=\"b\"\r\n\
+        \r\n\
+        two\r\nlines\r\n\
+        --xyz--\r\n";
+
+    const WITH_FILE: &str = "--xyz\r\n\
+        Content-Disposition: form-data; name=\"a\"\r\n\
+        \r\n\
+        1\r\n\
+        --xyz\r\n\
+        Content-Disposition: form-data; name=\"upload\"; filename=\"hello.txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        hello\r\n\
+        --xyz--";
+
+    fn text(value: &str) -> FormDataEntryValue {
+        FormDataEntryValue::Text(value.to_string())
+    }
+
+    #[test]
+    fn parse_two_fields() {
+        let form_data = FormData::parse(
+            Some("multipart/form-data; boundary=xyz"),
+            TWO_FIELDS.as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(form_data.get("a"), Some(&text("1")));
+        assert_eq!(form_data.get("b"), Some(&text("two\r\nlines")));
+        assert!(!form_data.contains("c"));
+    }
+
+    #[test]
+    fn parse_file() {
+        let form_data = FormData::parse(
+            Some("multipart/form-data; boundary=\"xyz\""),
+            WITH_FILE.as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            form_data.get_all("upload"),
+            [&FormDataEntryValue::File(File {
+                name: "hello.txt".to_string(),
+                content_type: "text/plain".to_string(),
+                bytes: b"hello".to_vec(),
+            })]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed_bodies() {
+        let body = TWO_FIELDS.as_bytes();
+
+        assert!(FormData::parse(Some("multipart/form-data"), body).is_err());
+        assert!(
+            FormData::parse(Some("multipart/form-data; boundary=x{z"), body).is_err()
+        );
+        assert!(
+            FormData::parse(Some("multipart/form-data; boundary=abc"), body).is_err()
+        );
+        assert!(FormData::parse(
+            Some("multipart/form-data; boundary=xyz"),
+            TWO_FIELDS.trim_end_matches("--xyz--\r\n").as_bytes()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn set_replaces_all_entries() {
+        let mut form_data = FormData::new();
+        form_data.append("a".to_string(), text("1"));
+        form_data.append("b".to_string(), text("2"));
+        form_data.append("a".to_string(), text("3"));
+
+        form_data.set("a".to_string(), text("4"));
+
+        assert_eq!(form_data.get_all("a"), [&text("4")]);
+        assert_eq!(form_data.entries[1], ("b".to_string(), text("2")));
+    }
+
+    /// Evaluates `code`, runs the pending jobs, then returns the global
+    /// `result`
+    fn eval(code: &str) -> String {
+        let context = &mut Context::default();
+        HttpApi.init(context);
+
+        context
+            .eval(Source::from_bytes(code))
+            .expect("Could not evaluate code");
+        context.run_jobs();
+
+        context
+            .eval(Source::from_bytes("result"))
+            .unwrap()
+            .to_string(context)
+            .unwrap()
+            .to_std_string_escaped()
+    }
+
+    fn request(content_type: &str, body: &str) -> String {
+        format!(
+            r#"
+            const request = new Request("tezos://tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/", {{
+                method: "POST",
+                headers: {{ "Content-Type": {content_type:?} }},
+                body: {body:?},
+            }});
+            "#
+        )
+    }
+
+    #[test]
+    fn request_form_data() {
+        let result = eval(&format!(
+            r#"
+            var result;
+            {}
+            request.formData().then(async (form) => {{
+                const upload = form.get("upload");
+                result = [
+                    [...form.keys()].join(" "),
+                    form.has("a"),
+                    upload.name,
+                    upload.type,
+                    upload.size,
+                    await upload.text(),
+                ].join(",");
+            }});
+            "#,
+            request("multipart/form-data; boundary=xyz", WITH_FILE)
+        ));
+
+        assert_eq!(result, "a upload,true,hello.txt,text/plain,5,hello");
+    }
+
+    #[test]
+    fn request_form_data_rejects_malformed_boundary() {
+        let result = eval(&format!(
+            r#"
+            var result;
+            {}
+            request.formData().catch((error) => {{ result = error.name; }});
+            "#,
+            request("multipart/form-data; boundary=", WITH_FILE)
+        ));
+
+        assert_eq!(result, "TypeError");
+    }
+}
@@ -20,8 +20,12 @@ use boa_engine::{
 };
 use boa_gc::{empty_trace, Finalize, GcRefMut, Trace};
 use derive_more::{Deref, DerefMut};
-use http::{header::Entry, HeaderMap, HeaderName, HeaderValue};
+use http::{
+    header::{Entry, SET_COOKIE},
+    HeaderMap, HeaderName, HeaderValue,
+};
 use jstz_core::{
+    iterators::{PairIterable, PairIterableMethods, PairIteratorClass, PairValue},
     native::{register_global_class, ClassBuilder, JsNativeObject, NativeClass},
     value::IntoJs,
 };
@@ -165,6 +169,32 @@ impl Headers {
         self.headers.insert(name, value);
         Ok(())
     }
+
+    /// Returns the headers as (name, value) pairs sorted by lowercase name,
+    /// where the values of a header are combined into a single
+    /// comma-separated value. `Set-Cookie` values are never combined, since
+    /// a cookie may itself contain a comma, and yield one pair each.
+    ///
+    /// More information:
+    ///  - [WHATWG specification][spec]
+    ///
+    /// [spec] https://fetch.spec.whatwg.org/#concept-header-list-sort-and-combine
+    pub fn sort_and_combine(&self) -> JsResult<Vec<(String, String)>> {
+        let mut names: Vec<&HeaderName> = self.headers.keys().collect();
+        names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        let mut pairs = Vec::with_capacity(names.len());
+        for name in names {
+            let values = Header::try_from_iter(self.headers.get_all(name))?.headers;
+            if *name == SET_COOKIE {
+                pairs.extend(values.into_iter().map(|value| (name.to_string(), value)));
+            } else {
+                pairs.push((name.to_string(), values.join(", ")));
+            }
+        }
+
+        Ok(pairs)
+    }
 }
 
 pub struct HeadersClass;
@@ -425,15 +455,178 @@ impl NativeClass for HeadersClass {
                 NativeFunction::from_fn_ptr(HeadersClass::set),
             );
 
+        PairIterableMethods::<HeadersIteratorClass>::define_pair_iterable_methods(class)?;
+
         Ok(())
     }
 }
 
+impl PairIterable for Headers {
+    fn pair_iterable_len(&self) -> usize {
+        // `Set-Cookie` values are yielded separately, all other names once
+        self.headers.keys_len() - usize::from(self.headers.contains_key(SET_COOKIE))
+            + self.headers.get_all(SET_COOKIE).iter().count()
+    }
+
+    fn pair_iterable_get(
+        &self,
+        index: usize,
+        context: &mut Context<'_>,
+    ) -> JsResult<PairValue> {
+        // The pairs are recomputed on each step, so that an iterator reflects
+        // changes made to the headers during iteration, as per the spec
+        let (name, value) = self
+            .sort_and_combine()?
+            .into_iter()
+            .nth(index)
+            .ok_or::<JsError>(
+                JsNativeError::typ()
+                    .with_message("index out of bounds in Headers Iterator")
+                    .into(),
+            )?;
+        let key = name.into_js(context);
+        let value = value.into_js(context);
+        Ok(PairValue { key, value })
+    }
+}
+
+struct HeadersIteratorClass;
+
+impl PairIteratorClass for HeadersIteratorClass {
+    type Iterable = Headers;
+    const NAME: &'static str = "Headers Iterator";
+}
+
 pub struct HeadersApi;
 
 impl jstz_core::Api for HeadersApi {
     fn init(self, context: &mut Context<'_>) {
         register_global_class::<HeadersClass>(context)
-            .expect("The `Headers` class shouldn't exist yet")
+            .expect("The `Headers` class shouldn't exist yet");
+        // TODO should not really be a global class, remove from
+        // global object when possible
+        register_global_class::<HeadersIteratorClass>(context)
+            .expect("The `Headers Iterator` class shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::Api;
+
+    use super::*;
+
+    fn pairs(headers: &Headers) -> Vec<(String, String)> {
+        headers.sort_and_combine().unwrap()
+    }
+
+    fn pair(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn sort_and_combine_orders_by_lowercase_name() {
+        let mut headers = Headers::new();
+        headers.append("X-Zeta", "1").unwrap();
+        headers.append("Accept", "text/html").unwrap();
+        headers.append("content-type", "text/plain").unwrap();
+
+        assert_eq!(
+            pairs(&headers),
+            [
+                pair("accept", "text/html"),
+                pair("content-type", "text/plain"),
+                pair("x-zeta", "1"),
+            ]
+        );
+        assert_eq!(headers.pair_iterable_len(), 3);
+    }
+
+    #[test]
+    fn sort_and_combine_joins_duplicate_headers() {
+        let mut headers = Headers::new();
+        headers.append("Accept", "text/html").unwrap();
+        headers.append("X-Custom", "a").unwrap();
+        headers.append("accept", "application/json").unwrap();
+
+        assert_eq!(
+            pairs(&headers),
+            [
+                pair("accept", "text/html, application/json"),
+                pair("x-custom", "a"),
+            ]
+        );
+        assert_eq!(headers.pair_iterable_len(), 2);
+    }
+
+    #[test]
+    fn sort_and_combine_keeps_set_cookie_values_separate() {
+        let mut headers = Headers::new();
+        headers
+            .append("Set-Cookie", "a=1; Expires=Wed, 21 Oct 2015")
+            .unwrap();
+        headers.append("Vary", "Accept").unwrap();
+        headers.append("Set-Cookie", "b=2").unwrap();
+        headers.append("Age", "3").unwrap();
+
+        assert_eq!(
+            pairs(&headers),
+            [
+                pair("age", "3"),
+                pair("set-cookie", "a=1; Expires=Wed, 21 Oct 2015"),
+                pair("set-cookie", "b=2"),
+                pair("vary", "Accept"),
+            ]
+        );
+        assert_eq!(headers.pair_iterable_len(), 4);
+    }
+
+    fn eval(code: &str) -> String {
+        let context = &mut Context::default();
+        HeadersApi.init(context);
+
+        context
+            .eval(Source::from_bytes(code))
+            .expect("Could not evaluate code")
+            .to_string(context)
+            .unwrap()
+            .to_std_string_escaped()
+    }
+
+    #[test]
+    fn iteration() {
+        let result = eval(
+            r#"
+            const headers = new Headers([
+                ["B", "2"],
+                ["a", "1"],
+                ["b", "3"],
+            ]);
+            const seen = [];
+            headers.forEach((value, name) => seen.push(`${name}=${value}`));
+            [
+                JSON.stringify([...headers]),
+                [...headers.keys()].join(" "),
+                [...headers.values()].join(" | "),
+                seen.join(" "),
+            ].join("
+")
+            "#,
+        );
+
+        assert_eq!(
+            result,
+            [
+                r#"[["a","1"],["b","2, 3"]]"#,
+                "a b",
+                "1 | 2, 3",
+                "a=1 b=2, 3",
+            ]
+            .join(
+                "
+"
+            )
+        );
     }
 }
@@ -1,8 +1,14 @@
 use boa_engine::Context;
 
-use self::{header::HeadersApi, request::RequestApi, response::ResponseApi};
+use self::{
+    abort::AbortApi, form_data::FormDataApi, header::HeadersApi, request::RequestApi,
+    response::ResponseApi,
+};
 
+pub mod abort;
 pub mod body;
+pub mod fetch;
+pub mod form_data;
 pub mod header;
 pub mod request;
 pub mod response;
@@ -11,6 +17,8 @@ pub struct HttpApi;
 
 impl jstz_core::Api for HttpApi {
     fn init(self, context: &mut Context<'_>) {
+        AbortApi.init(context);
+        FormDataApi.init(context);
         HeadersApi.init(context);
         RequestApi.init(context);
         ResponseApi.init(context);
@@ -137,7 +137,7 @@ impl Request {
     }
 
     fn check_url_scheme(url: &Url) -> JsResult<()> {
-        if url.scheme() != "tezos" {
+        if !matches!(url.scheme(), "tezos" | "jstz") {
             return Err(JsError::from_native(
                 JsNativeError::typ().with_message("Invalid scheme"),
             ));
@@ -196,7 +196,7 @@ impl Request {
             }
         };
 
-        // TEZOS SPECIFIC: Check if URL scheme is "tezos"
+        // TEZOS SPECIFIC: Check if URL scheme is "tezos" or "jstz"
         Request::check_url_scheme(&request.url)?;
 
         // 7-24. (FIXME:) SKIPPED
@@ -291,9 +291,48 @@ impl Request {
         self.request.body_mut().text(context)
     }
 
+    pub fn form_data(&mut self, context: &mut Context<'_>) -> JsResult<JsPromise> {
+        let content_type = self
+            .headers
+            .deref()
+            .get("Content-Type")?
+            .headers
+            .first()
+            .cloned();
+
+        self.request
+            .body_mut()
+            .form_data(content_type.as_deref(), context)
+    }
+
     pub fn body_used(&self) -> bool {
         self.request.body().is_used()
     }
+
+    /// Returns a copy of the request with its own headers and body, which
+    /// can be read independently of this request's
+    ///
+    /// [spec] https://fetch.spec.whatwg.org/#dom-request-clone
+    pub fn try_clone(&self, context: &mut Context<'_>) -> JsResult<Self> {
+        // 1. If this is unusable, then throw a TypeError
+        if self.body_used() {
+            return Err(JsError::from_native(
+                JsNativeError::typ().with_message("Request body has already been used"),
+            ));
+        }
+
+        // 2. Let `cloned_request` be the result of cloning this's request
+        // 4. Let `cloned_request_object` be the result of creating a Request
+        //    object with a copy of this's headers
+        let headers = Headers::clone(&self.headers.deref());
+        let headers = JsNativeObject::new::<HeadersClass>(headers, context)?;
+
+        Ok(Self {
+            request: clone_inner_request(&self.request),
+            headers,
+            url: self.url.clone(),
+        })
+    }
 }
 
 pub struct RequestClass;
@@ -377,6 +416,26 @@ impl RequestClass {
 
         Ok(request.json(context)?.into())
     }
+
+    fn form_data(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let mut request = Request::try_from_js(this)?;
+
+        Ok(request.form_data(context)?.into())
+    }
+
+    fn clone(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let request = Request::try_from_js(this)?.try_clone(context)?;
+
+        Ok(JsNativeObject::new::<Self>(request, context)?.to_inner())
+    }
 }
 
 impl TryFromJs for RequestInfo {
@@ -463,11 +522,21 @@ impl NativeClass for RequestClass {
             .accessor(js_string!("headers"), headers, Attribute::all())
             .accessor(js_string!("method"), method, Attribute::all())
             .accessor(js_string!("url"), url, Attribute::all())
+            .method(
+                js_string!("clone"),
+                0,
+                NativeFunction::from_fn_ptr(Self::clone),
+            )
             .method(
                 js_string!("arrayBuffer"),
                 0,
                 NativeFunction::from_fn_ptr(Self::array_buffer),
             )
+            .method(
+                js_string!("formData"),
+                0,
+                NativeFunction::from_fn_ptr(Self::form_data),
+            )
             .method(
                 js_string!("json"),
                 0,
@@ -491,3 +560,106 @@ impl jstz_core::Api for RequestApi {
             .expect("The `Request` class shouldn't exist yet")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::Api;
+
+    use super::*;
+    use crate::http::HttpApi;
+
+    /// Evaluates `code`, runs the pending jobs, then returns the global
+    /// `result`
+    fn eval(code: &str) -> String {
+        let context = &mut Context::default();
+        HttpApi.init(context);
+
+        context
+            .eval(Source::from_bytes(code))
+            .expect("Could not evaluate code");
+        context.run_jobs();
+
+        context
+            .eval(Source::from_bytes("result"))
+            .unwrap()
+            .to_string(context)
+            .unwrap()
+            .to_std_string_escaped()
+    }
+
+    #[test]
+    fn clone_before_reading() {
+        let result = eval(
+            r#"
+            var result;
+            const request = new Request("tezos://tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/", {
+                method: "POST",
+                headers: { "X-Tag": "a" },
+                body: "hello",
+            });
+            const clone = request.clone();
+            Promise.all([request.text(), clone.text()]).then((bodies) => {
+                result = [
+                    ...bodies,
+                    clone.method,
+                    clone.url,
+                    clone.headers.get("X-Tag"),
+                ].join(",");
+            });
+            "#,
+        );
+
+        assert_eq!(
+            result,
+            "hello,hello,POST,tezos://tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/,a"
+        );
+    }
+
+    #[test]
+    fn clone_after_reading_throws() {
+        let result = eval(
+            r#"
+            var result;
+            const request = new Request("tezos://tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/", {
+                method: "POST",
+                body: "hello",
+            });
+            request.text();
+            try {
+                request.clone();
+            } catch (error) {
+                result = error.name;
+            }
+            "#,
+        );
+
+        assert_eq!(result, "TypeError");
+    }
+
+    #[test]
+    fn clone_is_independent() {
+        let result = eval(
+            r#"
+            var result;
+            const request = new Request("tezos://tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/", {
+                method: "PUT",
+                headers: { "X-Tag": "a" },
+                body: "hello",
+            });
+            const clone = request.clone();
+            clone.headers.set("X-Tag", "b");
+            clone.text().then((body) => {
+                result = [
+                    body,
+                    clone.bodyUsed,
+                    request.bodyUsed,
+                    request.headers.get("X-Tag"),
+                ].join(",");
+            });
+            "#,
+        );
+
+        assert_eq!(result, "hello,true,false,a");
+    }
+}
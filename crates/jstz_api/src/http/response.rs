@@ -179,13 +179,18 @@ impl Response {
     }
 
     /// Returns the status message corresponding to the status code. (e.g., OK for 200).
+    /// This is the empty string for a status without a canonical reason phrase.
     ///
     /// More information:
     ///  - [WHATWG specification][spec]
     ///
     /// [spec] https://fetch.spec.whatwg.org/#dom-response-statustext
-    pub fn status_text(&self) -> Option<String> {
-        self.response.status().canonical_reason().map(String::from)
+    pub fn status_text(&self) -> String {
+        self.response
+            .status()
+            .canonical_reason()
+            .unwrap_or_default()
+            .to_string()
     }
 
     /// Returns whether or not the response is the result of a redirect
@@ -239,6 +244,14 @@ impl Response {
     }
 }
 
+/// The statuses allowed by `Response.redirect`
+///
+/// More information:
+///  - [WHATWG specification][spec]
+///
+/// [spec] https://fetch.spec.whatwg.org/#redirect-status
+const REDIRECT_STATUSES: [u16; 5] = [301, 302, 303, 307, 308];
+
 pub struct ResponseBuilder;
 
 impl ResponseBuilder {
@@ -269,6 +282,21 @@ impl ResponseBuilder {
         })
     }
 
+    /// Returns a new Response object with the given status and a null body.
+    pub fn empty(status: u16, context: &mut Context<'_>) -> JsResult<Response> {
+        Response::new(
+            BodyWithType {
+                body: Body::null(),
+                content_type: None,
+            },
+            ResponseOptions {
+                status,
+                headers: Headers::new(),
+            },
+            context,
+        )
+    }
+
     /// Returns a new response with a different URL.
     ///
     /// More information:
@@ -286,17 +314,15 @@ impl ResponseBuilder {
             JsError::from_native(JsNativeError::typ().with_message("Invalid URL"))
         })?;
 
-        // 2. If status is not a redirect status, then row a RangeError
-        let status = StatusCode::from_u16(status.unwrap_or(302)).map_err(|_| {
-            JsError::from_native(
-                JsNativeError::range().with_message("Invalid status code"),
-            )
-        })?;
-        if !status.is_redirection() {
-            return Err(JsError::from_native(
-                JsNativeError::range().with_message("Expected a redirect status"),
-            ));
+        // 3. If status is not a redirect status, then throw a RangeError
+        let status = status.unwrap_or(302);
+        if !REDIRECT_STATUSES.contains(&status) {
+            return Err(JsError::from_native(JsNativeError::range().with_message(
+                "Expected a redirect status (301, 302, 303, 307 or 308)",
+            )));
         };
+        let status =
+            StatusCode::from_u16(status).expect("Expected a valid redirect status");
 
         let mut headers = Headers::new();
 
@@ -427,7 +453,7 @@ impl ResponseClass {
         accessor!(
             context,
             Response,
-            "redirected",
+            "status",
             get:((response, _context) => Ok(response.status().into()))
         )
     }
@@ -602,3 +628,81 @@ impl jstz_core::Api for ResponseApi {
             .expect("The `Response` class shouldn't exist yet")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::Api;
+
+    use super::*;
+    use crate::http::header::HeadersApi;
+
+    fn eval(code: &str) -> String {
+        let context = &mut Context::default();
+        HeadersApi.init(context);
+        ResponseApi.init(context);
+
+        context
+            .eval(Source::from_bytes(code))
+            .expect("Could not evaluate code")
+            .to_string(context)
+            .unwrap()
+            .to_std_string_escaped()
+    }
+
+    #[test]
+    fn redirect() {
+        let result = eval(
+            r#"
+            const response = Response.redirect("tezos://tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/home", 301);
+            [response.status, response.headers.get("Location"), response.statusText].join(",")
+            "#,
+        );
+
+        assert_eq!(
+            result,
+            "301,tezos://tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/home,Moved Permanently"
+        );
+    }
+
+    #[test]
+    fn redirect_defaults_to_302() {
+        let response = ResponseBuilder::redirect(
+            "tezos://tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/".to_string(),
+            None,
+            &mut Context::default(),
+        )
+        .unwrap();
+
+        assert_eq!(response.status(), 302);
+    }
+
+    #[test]
+    fn redirect_rejects_non_redirect_statuses() {
+        for status in [200, 300, 304, 399, 1000] {
+            let result = eval(&format!(
+                r#"
+                try {{
+                    Response.redirect("tezos://tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/", {status});
+                    "no error"
+                }} catch (error) {{
+                    error.name
+                }}
+                "#
+            ));
+
+            assert_eq!(result, "RangeError", "status {status}");
+        }
+    }
+
+    #[test]
+    fn status_text() {
+        let context = &mut Context::default();
+
+        let not_found = ResponseBuilder::empty(404, context).unwrap();
+        let unknown = ResponseBuilder::empty(299, context).unwrap();
+
+        assert_eq!(not_found.status_text(), "Not Found");
+        assert_eq!(unknown.status_text(), "");
+    }
+}
@@ -2,7 +2,7 @@ use std::ops::Deref;
 
 use boa_engine::{
     object::{
-        builtins::{JsArrayBuffer, JsDataView, JsTypedArray},
+        builtins::{JsArrayBuffer, JsDataView, JsTypedArray, JsUint8Array},
         Object,
     },
     value::TryFromJs,
@@ -182,3 +182,29 @@ impl ArrayBufferLike for JsBufferSource {
         }
     }
 }
+
+/// Copies the contents of a `BufferSource` into a byte vector.
+pub fn buffer_source_to_vec(
+    value: &JsValue,
+    context: &mut Context<'_>,
+) -> JsResult<Vec<u8>> {
+    let buffer_source: JsBufferSource = value.try_js_into(context)?;
+    let array_buffer_data = buffer_source.to_array_buffer_data(context)?;
+    let bytes = array_buffer_data
+        .as_slice()
+        .map(|slice| slice.to_vec())
+        .unwrap_or_default();
+
+    Ok(bytes)
+}
+
+/// Creates a new `Uint8Array` backed by `bytes`.
+pub fn vec_to_uint8_array(
+    bytes: Vec<u8>,
+    context: &mut Context<'_>,
+) -> JsResult<JsUint8Array> {
+    JsUint8Array::from_array_buffer(
+        JsArrayBuffer::from_byte_block(bytes, context)?,
+        context,
+    )
+}
@@ -1,21 +1,93 @@
-use std::ops::Deref;
+use std::{collections::BTreeSet, ops::Deref};
 
 use boa_engine::{
-    js_string, object::ObjectInitializer, property::Attribute, Context, JsArgs, JsError,
-    JsNativeError, JsResult, JsString, JsValue, NativeFunction,
+    js_string,
+    object::{
+        builtins::{JsArray, JsPromise},
+        FunctionObjectBuilder, Object, ObjectInitializer,
+    },
+    property::Attribute,
+    Context, JsArgs, JsError, JsNativeError, JsResult, JsString, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, GcRefMut, Trace};
+use jstz_core::{
+    host::HostRuntime,
+    host_defined,
+    kv::{SavepointId, Transaction},
+    runtime, Result,
 };
-use boa_gc::{Finalize, Trace};
-use jstz_core::{host::HostRuntime, host_defined, kv::Transaction, runtime, Result};
 use jstz_crypto::public_key_hash::PublicKeyHash;
 use serde::{Deserialize, Serialize};
-use tezos_smart_rollup::storage::path::{self, OwnedPath, RefPath};
+use tezos_smart_rollup::storage::path::{self, OwnedPath, Path, RefPath};
+
+use crate::time;
 
 #[derive(Debug, Trace, Finalize)]
 pub struct Kv {
     prefix: String,
+    dump_enabled: bool,
+    max_value_size: Option<usize>,
 }
 
 const KV_PATH: RefPath = RefPath::assert_from(b"/jstz_kv");
+const KV_EXPIRY_PATH: RefPath = RefPath::assert_from(b"/jstz_kv_expiry");
+
+/// The key under which the address of the account that deployed a smart
+/// function is stored. Only the owner may copy entries into its storage.
+pub const OWNER_KEY: &str = "__owner__";
+
+/// Registered in `HostDefined` with the address of the immediate caller of
+/// the smart function. `Kv.exportJSON()` and `Kv.importJSON()` may only be
+/// used in requests sent by the owner (see [`OWNER_KEY`]).
+#[derive(Debug, Clone, Trace, Finalize)]
+pub struct KvCaller(pub String);
+
+/// The key under which the subscriptions to a smart function's keys are
+/// stored (see [`Subscription`]).
+pub const SUBSCRIPTIONS_KEY: &str = "__subscriptions__";
+
+/// The key under which the smart functions allowed to watch the keys of a
+/// smart function are stored. Only these may subscribe with `Kv.watchGlobal()`.
+pub const WATCHERS_KEY: &str = "__watchers__";
+
+/// The prefix of the keys under which the locks taken by a smart function with
+/// `Contract.lock()` are stored
+pub const LOCK_KEY_PREFIX: &str = "__lock__/";
+
+/// Returns `true` if `key` is reserved for entries managed by `jstz`, such as
+/// [`OWNER_KEY`]. Reserved keys are of the form `__name__` or start with
+/// [`LOCK_KEY_PREFIX`], and smart functions may read them but not write or
+/// delete them.
+pub fn is_reserved_key(key: &str) -> bool {
+    (key.len() > 4 && key.starts_with("__") && key.ends_with("__"))
+        || key.starts_with(LOCK_KEY_PREFIX)
+}
+
+/// The maximum number of subscriptions to the keys of a smart function
+pub const MAX_SUBSCRIPTIONS: usize = 16;
+
+/// A subscription to a key of a smart function's storage, made with
+/// `Kv.watchGlobal()`. Whenever an operation sent to the smart function
+/// changes the value of `key`, the executor calls `callback` with the old
+/// and new values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub key: String,
+    pub callback: String,
+    /// The smart function that made the subscription, and which alone may
+    /// remove it
+    pub subscriber: String,
+}
+
+/// The maximum number of entries copied by a single `Kv.copyTo()` call
+pub const MAX_COPY_ENTRIES: usize = 1000;
+
+/// The default maximum size (in bytes) of a value written by a smart function,
+/// as serialized to JSON
+pub const MAX_VALUE_SIZE: usize = 4 * 1024;
+
+/// The maximum length (in bytes) of a key written by a smart function
+pub const MAX_KEY_LENGTH: usize = 128;
 
 // TODO: Figure out a more effective way of serializing values using json
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,8 +109,63 @@ impl TryFrom<String> for KvValue {
 }
 
 impl Kv {
+    fn try_from_js<'a>(value: &'a JsValue) -> JsResult<GcRefMut<'a, Object, Self>> {
+        value
+            .as_object()
+            .and_then(|obj| obj.downcast_mut::<Self>())
+            .ok_or_else(|| {
+                JsNativeError::typ()
+                    .with_message("Failed to convert js value into rust type `Kv`")
+                    .into()
+            })
+    }
+
+    /// Creates the `Kv` of the smart function `prefix`.
+    ///
+    /// The rollup host cannot list the keys of its durable storage, so the
+    /// transactions that write to a `Kv` index its keys (see
+    /// [`Transaction::index_prefix`]), allowing them to be enumerated with
+    /// [`Kv::keys`]. Keys written before they were indexed are not listed.
     pub fn new(prefix: String) -> Self {
-        Self { prefix }
+        Self {
+            prefix,
+            dump_enabled: false,
+            max_value_size: None,
+        }
+    }
+
+    /// Enables `Kv.dump()`, for debugging and testing
+    pub fn with_dump(mut self) -> Self {
+        self.dump_enabled = true;
+        self
+    }
+
+    /// Limits the values written with [`Kv::set`] to `limit` bytes (as
+    /// serialized to JSON). Keys are always limited to [`MAX_KEY_LENGTH`]
+    /// bytes.
+    pub fn with_max_value_size(mut self, limit: usize) -> Self {
+        self.max_value_size = Some(limit);
+        self
+    }
+
+    /// Fails with `Error::KeyTooLong` or `Error::ValueTooLarge` if the write
+    /// of `value` at `key` exceeds the limits of this `Kv`.
+    fn check_limits(&self, key: &str, value: &KvValue) -> Result<()> {
+        if key.len() > MAX_KEY_LENGTH {
+            return Err(jstz_core::Error::KeyTooLong {
+                length: key.len(),
+                limit: MAX_KEY_LENGTH,
+            });
+        }
+
+        if let Some(limit) = self.max_value_size {
+            let size = value.0.to_string().len();
+            if size > limit {
+                return Err(jstz_core::Error::ValueTooLarge { size, limit });
+            }
+        }
+
+        Ok(())
     }
 
     fn key_path(&self, key: &str) -> jstz_core::Result<OwnedPath> {
@@ -47,10 +174,119 @@ impl Kv {
         Ok(path::concat(&KV_PATH, &key_path)?)
     }
 
-    pub fn set(&self, tx: &mut Transaction, key: &str, value: KvValue) -> Result<()> {
+    /// The path under which the entries of this `Kv` are stored
+    fn root_path(&self) -> jstz_core::Result<OwnedPath> {
+        let root_path = OwnedPath::try_from(format!("/{}", self.prefix))?;
+
+        Ok(path::concat(&KV_PATH, &root_path)?)
+    }
+
+    /// The expiry of a key is stored under its own path, so that keys without
+    /// one are never read or written with it
+    fn expiry_path(&self, key: &str) -> jstz_core::Result<OwnedPath> {
+        let expiry_path = OwnedPath::try_from(format!("/{}/{}", self.prefix, key))?;
+
+        Ok(path::concat(&KV_EXPIRY_PATH, &expiry_path)?)
+    }
+
+    /// Returns the expiry of `key`, if it was set with one.
+    fn expiry(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+    ) -> Result<Option<i64>> {
+        let expires_at = tx.get::<i64>(hrt, self.expiry_path(key)?)?;
+
+        Ok(expires_at.copied())
+    }
+
+    /// Returns `true` if `key` was set with an expiry that is at or before
+    /// `now`
+    fn is_expired(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+        now: i64,
+    ) -> Result<bool> {
+        let expired = self
+            .expiry(hrt, tx, key)?
+            .map_or(false, |expires_at| expires_at <= now);
+
+        Ok(expired)
+    }
+
+    pub fn set(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+        value: KvValue,
+    ) -> Result<()> {
+        self.check_limits(key, &value)?;
+        self.set_unchecked(hrt, tx, key, value)
+    }
+
+    /// Sets `key` to `value` regardless of the limits of this `Kv`, for
+    /// entries that are managed by `jstz` rather than by the smart function
+    fn set_unchecked(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+        value: KvValue,
+    ) -> Result<()> {
+        tx.ensure_writable()?;
+
+        tx.index_prefix(&self.root_path()?);
+        tx.remove(hrt, &self.expiry_path(key)?)?;
+
+        tx.insert(self.key_path(key)?, value)
+    }
+
+    /// Sets `key` to `value` until `expires_at` (in seconds since the Unix
+    /// epoch). The entry is then deleted by [`Kv::expire`].
+    pub fn set_with_expiry(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+        value: KvValue,
+        expires_at: i64,
+    ) -> Result<()> {
+        self.check_limits(key, &value)?;
+        tx.ensure_writable()?;
+
+        tx.index_prefix(&self.root_path()?);
+        tx.insert(self.expiry_path(key)?, expires_at)?;
+
         tx.insert(self.key_path(key)?, value)
     }
 
+    /// Returns `true` if `key` was set with an expiry that is at or before
+    /// `now`, deleting it unless `tx` is read-only.
+    ///
+    /// Expired entries are deleted lazily: until this is called for the key,
+    /// [`Kv::get`] still returns them. They are skipped when enumerating the
+    /// entries of the `Kv`.
+    pub fn expire(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+        now: i64,
+    ) -> Result<bool> {
+        if !self.is_expired(hrt, tx, key, now)? {
+            return Ok(false);
+        }
+
+        if !tx.is_read_only() {
+            self.delete(hrt, tx, key)?;
+        }
+        Ok(true)
+    }
+
     pub fn get<'a>(
         &self,
         hrt: &impl HostRuntime,
@@ -66,6 +302,11 @@ impl Kv {
         tx: &mut Transaction,
         key: &str,
     ) -> Result<()> {
+        tx.ensure_writable()?;
+
+        tx.index_prefix(&self.root_path()?);
+        tx.remove(hrt, &self.expiry_path(key)?)?;
+
         tx.remove(hrt, &self.key_path(key)?)
     }
 
@@ -77,6 +318,319 @@ impl Kv {
     ) -> Result<bool> {
         tx.contains_key(hrt, &self.key_path(key)?)
     }
+
+    /// Sets `key` to `new` if its current value equals `expected`, where an
+    /// `expected` of `None` requires the key to be absent. Returns whether
+    /// the value was swapped.
+    ///
+    /// The value is compared and written within `tx`, so that nested calls
+    /// cannot interleave a write between the two.
+    pub fn cas(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+        expected: Option<&serde_json::Value>,
+        new: KvValue,
+    ) -> Result<bool> {
+        tx.ensure_writable()?;
+
+        let current = self.get(hrt, tx, key)?.map(|value| &value.0);
+        if current != expected {
+            return Ok(false);
+        }
+
+        self.set(hrt, tx, key, new)?;
+
+        Ok(true)
+    }
+
+    /// Deletes all entries.
+    pub fn clear(&self, tx: &mut Transaction) -> Result<()> {
+        let prefix_path = OwnedPath::try_from(format!("/{}", self.prefix))?;
+        let root_path = self.root_path()?;
+
+        tx.index_prefix(&root_path);
+        tx.remove_prefix(&root_path)?;
+        tx.remove_prefix(&path::concat(&KV_EXPIRY_PATH, &prefix_path)?)?;
+
+        Ok(())
+    }
+
+    /// Returns all keys, including expired ones.
+    fn all_keys(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+    ) -> Result<BTreeSet<String>> {
+        let root_path = self.root_path()?;
+        let root_len = root_path.as_bytes().len();
+
+        let keys = tx
+            .keys_with_prefix(hrt, &root_path)?
+            .into_iter()
+            .map(|key_path| {
+                String::from_utf8_lossy(&key_path.as_bytes()[root_len + 1..]).into_owned()
+            })
+            .collect();
+
+        Ok(keys)
+    }
+
+    /// Returns the keys that have not expired at `now`.
+    pub fn keys(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        now: i64,
+    ) -> Result<BTreeSet<String>> {
+        let mut keys = BTreeSet::new();
+        for key in self.all_keys(hrt, tx)? {
+            if !self.is_expired(hrt, tx, &key, now)? {
+                keys.insert(key);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Returns the entries that have not expired at `now`, in key order, as
+    /// scanned by `tx` (see [`Transaction::scan_prefix`]). Uncommitted writes
+    /// and deletions in `tx` are reflected.
+    pub fn entries(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        now: i64,
+    ) -> Result<Vec<(String, KvValue)>> {
+        let root_path = self.root_path()?;
+        let root_len = root_path.as_bytes().len();
+
+        let mut entries = Vec::new();
+        for (key_path, value) in tx.scan_prefix::<KvValue>(hrt, &root_path)? {
+            let key = String::from_utf8_lossy(&key_path.as_bytes()[root_len + 1..])
+                .into_owned();
+            if !self.is_expired(hrt, tx, &key, now)? {
+                entries.push((key, value));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns the entries whose keys start with `prefix` and that have not
+    /// expired at `now`, in key order.
+    pub fn scan_prefix(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        prefix: &str,
+        now: i64,
+    ) -> Result<Vec<(String, KvValue)>> {
+        let entries = self
+            .entries(hrt, tx, now)?
+            .into_iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Copies the entries for `keys` into `target`, returning the number of
+    /// entries copied. Missing keys and reserved keys are skipped.
+    ///
+    /// The entries are checked against the limits of `target` before any of
+    /// them is written, so that no entry exceeding them is copied.
+    pub fn copy_to(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        target: &Kv,
+        keys: &[String],
+    ) -> Result<usize> {
+        let mut entries = Vec::with_capacity(keys.len());
+        for key in keys.iter().filter(|key| !is_reserved_key(key)) {
+            if let Some(value) = self.get(hrt, tx, key)? {
+                target.check_limits(key, value)?;
+                entries.push((key, value.clone()));
+            }
+        }
+
+        let count = entries.len();
+        for (key, value) in entries {
+            target.set(hrt, tx, key, value)?;
+        }
+
+        Ok(count)
+    }
+
+    /// Returns `true` if `owner` deployed the smart function owning this
+    /// storage.
+    pub fn is_owned_by(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        owner: &str,
+    ) -> Result<bool> {
+        let is_owner = self
+            .get(hrt, tx, OWNER_KEY)?
+            .map_or(false, |value| value.0.as_str() == Some(owner));
+
+        Ok(is_owner)
+    }
+
+    /// Throws unless `caller` is the owner of this storage, or `Kv.dump()` is
+    /// enabled (as in the REPL, which has no owner). `method` names the JS
+    /// method in the error message.
+    fn ensure_owner(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        caller: Option<&str>,
+        method: &str,
+    ) -> JsResult<()> {
+        if self.dump_enabled {
+            return Ok(());
+        }
+
+        let is_owner = match caller {
+            Some(caller) => self.is_owned_by(hrt, tx, caller)?,
+            None => false,
+        };
+
+        if !is_owner {
+            return Err(JsNativeError::error()
+                .with_message(format!("{method} may only be called by the owner"))
+                .into());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the entries that have not expired at `now` as a JSON object,
+    /// excluding reserved keys.
+    pub fn export_json(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        now: i64,
+    ) -> Result<serde_json::Map<String, serde_json::Value>> {
+        let entries = self
+            .entries(hrt, tx, now)?
+            .into_iter()
+            .filter(|(key, _)| !is_reserved_key(key))
+            .map(|(key, value)| (key, value.0))
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Populates the storage from the entries of a JSON object. Unless
+    /// `merge` is set, all existing entries are deleted first. Reserved keys
+    /// are neither deleted nor imported.
+    pub fn import_json(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        entries: serde_json::Map<String, serde_json::Value>,
+        merge: bool,
+    ) -> Result<()> {
+        if !merge {
+            for key in self.all_keys(hrt, tx)? {
+                if !is_reserved_key(&key) {
+                    self.delete(hrt, tx, &key)?;
+                }
+            }
+        }
+
+        for (key, value) in entries.into_iter().filter(|(key, _)| !is_reserved_key(key)) {
+            self.set(hrt, tx, &key, KvValue(value))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the subscriptions to the keys of this storage. A malformed
+    /// [`SUBSCRIPTIONS_KEY`] entry is treated as empty.
+    pub fn subscriptions(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+    ) -> Result<Vec<Subscription>> {
+        let subscriptions = self
+            .get(hrt, tx, SUBSCRIPTIONS_KEY)?
+            .and_then(|value| serde_json::from_value(value.0.clone()).ok())
+            .unwrap_or_default();
+
+        Ok(subscriptions)
+    }
+
+    pub fn set_subscriptions(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        subscriptions: &[Subscription],
+    ) -> Result<()> {
+        if subscriptions.is_empty() {
+            return self.delete(hrt, tx, SUBSCRIPTIONS_KEY);
+        }
+
+        let value = serde_json::to_value(subscriptions)
+            .expect("Subscriptions should serialize to JSON");
+
+        self.set_unchecked(hrt, tx, SUBSCRIPTIONS_KEY, KvValue(value))
+    }
+
+    /// Returns the addresses of the smart functions allowed to watch the keys
+    /// of this storage. A malformed [`WATCHERS_KEY`] entry is treated as empty.
+    pub fn watchers(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+    ) -> Result<Vec<String>> {
+        let watchers = self
+            .get(hrt, tx, WATCHERS_KEY)?
+            .and_then(|value| serde_json::from_value(value.0.clone()).ok())
+            .unwrap_or_default();
+
+        Ok(watchers)
+    }
+
+    pub fn set_watchers(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        watchers: &[String],
+    ) -> Result<()> {
+        if watchers.is_empty() {
+            return self.delete(hrt, tx, WATCHERS_KEY);
+        }
+
+        let value =
+            serde_json::to_value(watchers).expect("Watchers should serialize to JSON");
+
+        self.set_unchecked(hrt, tx, WATCHERS_KEY, KvValue(value))
+    }
+
+    /// Returns the entries that have not expired at `now`, keyed by their raw
+    /// (namespaced) key.
+    pub fn dump(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        now: i64,
+    ) -> Result<Vec<(String, KvValue)>> {
+        let root_path =
+            String::from_utf8_lossy(self.root_path()?.as_bytes()).into_owned();
+
+        let entries = self
+            .entries(hrt, tx, now)?
+            .into_iter()
+            .map(|(key, value)| (format!("{root_path}/{key}"), value))
+            .collect();
+
+        Ok(entries)
+    }
 }
 
 macro_rules! preamble {
@@ -107,8 +661,40 @@ macro_rules! preamble {
     };
 }
 
+/// Rejects writes to reserved keys made by smart functions
+fn ensure_not_reserved(key: &str) -> JsResult<()> {
+    if is_reserved_key(key) {
+        return Err(JsNativeError::typ()
+            .with_message(format!("The key `{key}` is reserved"))
+            .into());
+    }
+
+    Ok(())
+}
+
+fn rollback_to_savepoint(savepoint: SavepointId, context: &mut Context) -> JsResult<()> {
+    host_defined!(context, host_defined);
+    let mut tx = host_defined
+        .get_mut::<Transaction>()
+        .expect("Curent transaction undefined");
+
+    // The savepoint is gone if an enclosing savepoint was rolled back, in
+    // which case its writes have already been discarded
+    if tx.has_savepoint(savepoint) {
+        tx.rollback_to(savepoint)?;
+        tx.release_savepoint(savepoint)?;
+    }
+
+    Ok(())
+}
+
 pub struct KvApi {
     pub contract_address: PublicKeyHash,
+    /// The maximum size (in bytes) of the values written by the smart
+    /// function, as serialized to JSON. Usually [`MAX_VALUE_SIZE`].
+    pub max_value_size: usize,
+    /// Enables `Kv.dump()`. Should only be set when debugging or testing.
+    pub enable_dump: bool,
 }
 
 impl KvApi {
@@ -116,18 +702,51 @@ impl KvApi {
 
     fn set(this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
         preamble!(this, args, context, key, tx);
+        ensure_not_reserved(&key)?;
+
+        let value = KvValue(args.get_or_undefined(1).to_json(context)?);
+
+        runtime::with_global_host(|hrt| this.set(hrt.deref(), &mut tx, &key, value))?;
+
+        Ok(JsValue::undefined())
+    }
+
+    fn set_with_ttl(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let ttl = args.get_or_undefined(2).to_number(context)?;
+        if !(ttl.is_finite() && ttl >= 0.0) {
+            return Err(JsNativeError::range()
+                .with_message("The TTL must be a non-negative number of seconds")
+                .into());
+        }
+        let expires_at = time::now_seconds(context).saturating_add(ttl.ceil() as i64);
+
+        preamble!(this, args, context, key, tx);
+        ensure_not_reserved(&key)?;
 
         let value = KvValue(args.get_or_undefined(1).to_json(context)?);
 
-        this.set(&mut tx, &key, value)?;
+        runtime::with_global_host(|hrt| {
+            this.set_with_expiry(hrt.deref(), &mut tx, &key, value, expires_at)
+        })?;
 
         Ok(JsValue::undefined())
     }
 
     fn get(this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+        let now = time::now_seconds(context);
+
         preamble!(this, args, context, key, tx);
 
-        let result = runtime::with_global_host(|rt| this.get(rt.deref(), &mut tx, &key))?;
+        let result = runtime::with_global_host(|rt| {
+            if this.expire(rt.deref(), &mut tx, &key, now)? {
+                return Ok(None);
+            }
+            this.get(rt.deref(), &mut tx, &key)
+        })?;
 
         match result {
             Some(value) => JsValue::from_json(&value.0, context),
@@ -141,6 +760,7 @@ impl KvApi {
         context: &mut Context,
     ) -> JsResult<JsValue> {
         preamble!(this, args, context, key, tx);
+        ensure_not_reserved(&key)?;
 
         runtime::with_global_host(|hrt| this.delete(hrt.deref(), &mut tx, &key))?;
 
@@ -148,33 +768,1201 @@ impl KvApi {
     }
 
     fn has(this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+        let now = time::now_seconds(context);
+
         preamble!(this, args, context, key, tx);
 
-        let result =
-            runtime::with_global_host(|hrt| this.has(hrt.deref(), &mut tx, &key))?;
+        let result = runtime::with_global_host(|hrt| {
+            Ok::<_, jstz_core::Error>(
+                !this.expire(hrt.deref(), &mut tx, &key, now)?
+                    && this.has(hrt.deref(), &mut tx, &key)?,
+            )
+        })?;
 
         Ok(result.into())
     }
-}
 
-impl jstz_core::Api for KvApi {
-    fn init(self, context: &mut boa_engine::Context<'_>) {
-        let storage = ObjectInitializer::with_native(
-            Kv::new(self.contract_address.to_string()),
-            context,
-        )
-        .function(NativeFunction::from_fn_ptr(Self::set), js_string!("set"), 2)
-        .function(NativeFunction::from_fn_ptr(Self::get), js_string!("get"), 1)
-        .function(
-            NativeFunction::from_fn_ptr(Self::delete),
-            js_string!("delete"),
-            1,
-        )
-        .function(NativeFunction::from_fn_ptr(Self::has), js_string!("has"), 1)
-        .build();
+    fn compare_and_swap(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let now = time::now_seconds(context);
 
-        context
-            .register_global_property(js_string!(Self::NAME), storage, Attribute::all())
-            .expect("The storage object shouldn't exist yet");
+        preamble!(this, args, context, key, tx);
+        ensure_not_reserved(&key)?;
+
+        let expected = match args.get_or_undefined(1) {
+            value if value.is_null_or_undefined() => None,
+            value => Some(value.to_json(context)?),
+        };
+        let new = KvValue(args.get_or_undefined(2).to_json(context)?);
+
+        let swapped = runtime::with_global_host(|hrt| {
+            this.expire(hrt.deref(), &mut tx, &key, now)?;
+            this.cas(hrt.deref(), &mut tx, &key, expected.as_ref(), new)
+        })?;
+
+        Ok(swapped.into())
+    }
+
+    fn dump(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let now = time::now_seconds(context);
+
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let this = this
+            .as_object()
+            .and_then(|obj| obj.downcast_mut::<Kv>())
+            .ok_or_else(|| {
+                JsError::from_native(
+                    JsNativeError::typ()
+                        .with_message("Failed to convert js value into rust type `Kv`"),
+                )
+            })?;
+
+        if !this.dump_enabled {
+            return Err(JsNativeError::error()
+                .with_message("Kv.dump() is disabled in production")
+                .into());
+        }
+
+        let entries =
+            runtime::with_global_host(|hrt| this.dump(hrt.deref(), &mut tx, now))?;
+
+        let dump = ObjectInitializer::new(context).build();
+        for (key, value) in entries {
+            let value = JsValue::from_json(&value.0, context)?;
+            dump.create_data_property_or_throw(js_string!(key.as_str()), value, context)?;
+        }
+
+        Ok(dump.into())
+    }
+
+    fn scan_prefix(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let now = time::now_seconds(context);
+
+        preamble!(this, args, context, prefix, tx);
+
+        let entries = runtime::with_global_host(|hrt| {
+            this.scan_prefix(hrt.deref(), &mut tx, &prefix, now)
+        })?;
+
+        let mut pairs = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let value = JsValue::from_json(&value.0, context)?;
+            let pair =
+                JsArray::from_iter([js_string!(key.as_str()).into(), value], context);
+            pairs.push(pair.into());
+        }
+
+        Ok(JsArray::from_iter(pairs, context).into())
+    }
+
+    fn export_json(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let now = time::now_seconds(context);
+
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let caller = host_defined
+            .get::<KvCaller>()
+            .map(|caller| caller.0.clone());
+
+        let this = Kv::try_from_js(this)?;
+        let entries = runtime::with_global_host(|hrt| {
+            this.ensure_owner(
+                hrt.deref(),
+                &mut tx,
+                caller.as_deref(),
+                "Kv.exportJSON()",
+            )?;
+            this.export_json(hrt.deref(), &mut tx, now)
+                .map_err(JsError::from)
+        })?;
+
+        JsValue::from_json(&serde_json::Value::Object(entries), context)
+    }
+
+    fn import_json(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let caller = host_defined
+            .get::<KvCaller>()
+            .map(|caller| caller.0.clone());
+
+        let this = Kv::try_from_js(this)?;
+
+        let serde_json::Value::Object(entries) =
+            args.get_or_undefined(0).to_json(context)?
+        else {
+            return Err(JsNativeError::typ()
+                .with_message("Expected an object")
+                .into());
+        };
+
+        let merge = match args.get_or_undefined(1).as_object() {
+            Some(options) => options.get(js_string!("merge"), context)?.to_boolean(),
+            None => false,
+        };
+
+        runtime::with_global_host(|hrt| {
+            this.ensure_owner(
+                hrt.deref(),
+                &mut tx,
+                caller.as_deref(),
+                "Kv.importJSON()",
+            )?;
+            this.import_json(hrt.deref(), &mut tx, entries, merge)
+                .map_err(JsError::from)
+        })?;
+
+        Ok(JsValue::undefined())
+    }
+
+    /// Runs `fn(kv)`, rolling back its writes if it throws or rejects. Reads
+    /// made by `fn` always see its own writes.
+    fn transaction(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let f = args
+            .get_or_undefined(0)
+            .as_callable()
+            .cloned()
+            .ok_or_else(|| JsNativeError::typ().with_message("Expected a function"))?;
+
+        let savepoint = {
+            host_defined!(context, host_defined);
+            let mut tx = host_defined
+                .get_mut::<Transaction>()
+                .expect("Curent transaction undefined");
+
+            tx.savepoint()
+        };
+
+        let result = match f.call(&JsValue::undefined(), &[this.clone()], context) {
+            Ok(result) => result,
+            Err(err) => {
+                rollback_to_savepoint(savepoint, context)?;
+                return Err(err);
+            }
+        };
+
+        let promise = match result.as_promise() {
+            Some(promise) => JsPromise::from_object(promise.clone())?,
+            None => JsPromise::resolve(result, context)?,
+        };
+
+        let promise = promise.then(
+            Some(
+                FunctionObjectBuilder::new(
+                    context.realm(),
+                    NativeFunction::from_copy_closure(move |_, args, context| {
+                        host_defined!(context, host_defined);
+                        let mut tx = host_defined
+                            .get_mut::<Transaction>()
+                            .expect("Curent transaction undefined");
+
+                        // A savepoint that is already gone counts as released
+                        if tx.has_savepoint(savepoint) {
+                            tx.release_savepoint(savepoint)?;
+                        }
+
+                        Ok(args.get_or_undefined(0).clone())
+                    }),
+                )
+                .build(),
+            ),
+            Some(
+                FunctionObjectBuilder::new(
+                    context.realm(),
+                    NativeFunction::from_copy_closure(move |_, args, context| {
+                        rollback_to_savepoint(savepoint, context)?;
+
+                        Err(JsError::from_opaque(args.get_or_undefined(0).clone()))
+                    }),
+                )
+                .build(),
+            ),
+            context,
+        )?;
+
+        Ok(promise.into())
+    }
+
+    fn copy_to(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let now = time::now_seconds(context);
+
+        preamble!(this, args, context, target, tx);
+
+        let target = PublicKeyHash::from_base58(&target).map_err(|_| {
+            JsNativeError::typ().with_message(format!("Invalid address: {target}"))
+        })?;
+
+        let keys = match args.get_or_undefined(1) {
+            JsValue::Undefined => {
+                runtime::with_global_host(|hrt| this.entries(hrt.deref(), &mut tx, now))?
+                    .into_iter()
+                    .map(|(key, _)| key)
+                    .filter(|key| !is_reserved_key(key))
+                    .collect()
+            }
+            keys => {
+                let keys = keys
+                    .as_object()
+                    .and_then(|obj| JsArray::from_object(obj.clone()).ok())
+                    .ok_or_else(|| {
+                        JsNativeError::typ().with_message("Expected an array of keys")
+                    })?;
+
+                // Reading one key past the cap is enough to reject the call below
+                let len = keys.length(context)?.min(MAX_COPY_ENTRIES as u64 + 1);
+                let mut result = Vec::new();
+                for i in 0..len {
+                    result.push(keys.get(i, context)?.try_js_into::<String>(context)?);
+                }
+                result
+            }
+        };
+
+        if keys.len() > MAX_COPY_ENTRIES {
+            return Err(JsNativeError::range()
+                .with_message(format!("Cannot copy more than {MAX_COPY_ENTRIES} entries"))
+                .into());
+        }
+
+        let mut target = Kv::new(target.to_string());
+        target.max_value_size = this.max_value_size;
+
+        let count = runtime::with_global_host(|hrt| {
+            if !target.is_owned_by(hrt.deref(), &mut tx, &this.prefix)? {
+                return Err(JsNativeError::error()
+                    .with_message("Kv.copyTo() requires ownership of the target")
+                    .into());
+            }
+
+            this.copy_to(hrt.deref(), &mut tx, &target, &keys)
+                .map_err(JsError::from)
+        })?;
+
+        Ok(count.into())
+    }
+}
+
+impl KvApi {
+    fn subscription_from_js(
+        this: &Kv,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<(Kv, Subscription)> {
+        let contract: String = args.get_or_undefined(0).try_js_into(context)?;
+        let key: String = args.get_or_undefined(1).try_js_into(context)?;
+        let callback: String = args.get_or_undefined(2).try_js_into(context)?;
+
+        for address in [&contract, &callback] {
+            PublicKeyHash::from_base58(address).map_err(|_| {
+                JsNativeError::typ().with_message(format!("Invalid address: {address}"))
+            })?;
+        }
+
+        let subscription = Subscription {
+            key,
+            callback,
+            subscriber: this.prefix.clone(),
+        };
+
+        Ok((Kv::new(contract), subscription))
+    }
+
+    fn watch_global(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let this = Kv::try_from_js(this)?;
+        let (target, subscription) = Self::subscription_from_js(&this, args, context)?;
+
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        runtime::with_global_host(|hrt| {
+            // The watched smart function must opt in, since it pays for the
+            // notifications and its subscriptions are capped
+            if !target
+                .watchers(hrt.deref(), &mut tx)?
+                .contains(&subscription.subscriber)
+            {
+                return Err(JsNativeError::error()
+                    .with_message(format!(
+                        "Kv.watchGlobal() requires {} to allow {} as a watcher",
+                        target.prefix, subscription.subscriber
+                    ))
+                    .into());
+            }
+
+            let mut subscriptions = target.subscriptions(hrt.deref(), &mut tx)?;
+            if subscriptions.contains(&subscription) {
+                return Ok(());
+            }
+
+            if subscriptions.len() >= MAX_SUBSCRIPTIONS {
+                return Err(JsNativeError::range()
+                    .with_message(format!(
+                        "Cannot watch more than {MAX_SUBSCRIPTIONS} keys of a smart function"
+                    ))
+                    .into());
+            }
+
+            subscriptions.push(subscription);
+            target
+                .set_subscriptions(hrt.deref(), &mut tx, &subscriptions)
+                .map_err(JsError::from)
+        })?;
+
+        Ok(JsValue::undefined())
+    }
+
+    fn unwatch_global(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let this = Kv::try_from_js(this)?;
+        let (target, subscription) = Self::subscription_from_js(&this, args, context)?;
+
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        runtime::with_global_host(|hrt| {
+            let mut subscriptions = target.subscriptions(hrt.deref(), &mut tx)?;
+            subscriptions.retain(|other| *other != subscription);
+
+            target.set_subscriptions(hrt.deref(), &mut tx, &subscriptions)
+        })?;
+
+        Ok(JsValue::undefined())
+    }
+}
+
+impl KvApi {
+    fn watcher_from_js(args: &[JsValue], context: &mut Context) -> JsResult<String> {
+        let address: String = args.get_or_undefined(0).try_js_into(context)?;
+        PublicKeyHash::from_base58(&address).map_err(|_| {
+            JsNativeError::typ().with_message(format!("Invalid address: {address}"))
+        })?;
+
+        Ok(address)
+    }
+
+    /// Allows the smart function at `address` to watch the keys of this
+    /// smart function with `Kv.watchGlobal()`
+    fn allow_watcher(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let this = Kv::try_from_js(this)?;
+        let watcher = Self::watcher_from_js(args, context)?;
+
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        runtime::with_global_host(|hrt| {
+            let mut watchers = this.watchers(hrt.deref(), &mut tx)?;
+            if !watchers.contains(&watcher) {
+                watchers.push(watcher);
+                this.set_watchers(hrt.deref(), &mut tx, &watchers)?;
+            }
+
+            Ok::<_, jstz_core::Error>(())
+        })?;
+
+        Ok(JsValue::undefined())
+    }
+
+    /// Revokes the permission of the smart function at `address` to watch the
+    /// keys of this smart function, removing its subscriptions
+    fn revoke_watcher(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let this = Kv::try_from_js(this)?;
+        let watcher = Self::watcher_from_js(args, context)?;
+
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        runtime::with_global_host(|hrt| {
+            let mut watchers = this.watchers(hrt.deref(), &mut tx)?;
+            watchers.retain(|other| *other != watcher);
+            this.set_watchers(hrt.deref(), &mut tx, &watchers)?;
+
+            let mut subscriptions = this.subscriptions(hrt.deref(), &mut tx)?;
+            subscriptions.retain(|subscription| subscription.subscriber != watcher);
+            this.set_subscriptions(hrt.deref(), &mut tx, &subscriptions)
+        })?;
+
+        Ok(JsValue::undefined())
+    }
+}
+
+impl jstz_core::Api for KvApi {
+    fn init(self, context: &mut boa_engine::Context<'_>) {
+        let mut kv = Kv::new(self.contract_address.to_string())
+            .with_max_value_size(self.max_value_size);
+        if self.enable_dump {
+            kv = kv.with_dump();
+        }
+
+        let storage = ObjectInitializer::with_native(kv, context)
+            .function(NativeFunction::from_fn_ptr(Self::set), js_string!("set"), 2)
+            .function(NativeFunction::from_fn_ptr(Self::get), js_string!("get"), 1)
+            .function(
+                NativeFunction::from_fn_ptr(Self::set_with_ttl),
+                js_string!("setWithTtl"),
+                3,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::delete),
+                js_string!("delete"),
+                1,
+            )
+            .function(NativeFunction::from_fn_ptr(Self::has), js_string!("has"), 1)
+            .function(
+                NativeFunction::from_fn_ptr(Self::compare_and_swap),
+                js_string!("compareAndSwap"),
+                3,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::dump),
+                js_string!("dump"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::scan_prefix),
+                js_string!("scanPrefix"),
+                1,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::export_json),
+                js_string!("exportJSON"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::import_json),
+                js_string!("importJSON"),
+                2,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::transaction),
+                js_string!("transaction"),
+                1,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::copy_to),
+                js_string!("copyTo"),
+                2,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::watch_global),
+                js_string!("watchGlobal"),
+                3,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::unwatch_global),
+                js_string!("unwatchGlobal"),
+                3,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::allow_watcher),
+                js_string!("allowWatcher"),
+                1,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::revoke_watcher),
+                js_string!("revokeWatcher"),
+                1,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), storage, Attribute::all())
+            .expect("The storage object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tezos_smart_rollup_mock::MockHost;
+
+    #[test]
+    fn test_dump_matches_set_values() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let storage = Kv::new("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty".to_string());
+
+        storage
+            .set(hrt, &mut tx, "a", KvValue(serde_json::json!(1)))
+            .unwrap();
+        storage
+            .set(hrt, &mut tx, "b", KvValue(serde_json::json!({ "c": true })))
+            .unwrap();
+        storage
+            .set(hrt, &mut tx, "d", KvValue(serde_json::json!("e")))
+            .unwrap();
+        storage.delete(hrt, &mut tx, "d").unwrap();
+
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let dump: Vec<_> = storage
+            .dump(hrt, &mut tx, 0)
+            .unwrap()
+            .into_iter()
+            .map(|(key, value)| (key, value.0))
+            .collect();
+
+        // Assert
+        assert_eq!(
+            dump,
+            vec![
+                (
+                    "/jstz_kv/tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/a".to_string(),
+                    serde_json::json!(1)
+                ),
+                (
+                    "/jstz_kv/tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/b".to_string(),
+                    serde_json::json!({ "c": true })
+                ),
+            ]
+        );
+    }
+
+    fn committed_storage(hrt: &mut MockHost, kv: &mut jstz_core::kv::Kv) -> Kv {
+        let storage = Kv::new("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty".to_string());
+
+        let mut tx = kv.begin_transaction();
+        storage
+            .set(hrt, &mut tx, "a", KvValue(serde_json::json!(1)))
+            .unwrap();
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        storage
+    }
+
+    #[test]
+    fn test_cas_swaps_matching_value() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let storage = committed_storage(hrt, &mut kv);
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let swapped = storage
+            .cas(
+                hrt,
+                &mut tx,
+                "a",
+                Some(&serde_json::json!(1)),
+                KvValue(serde_json::json!(2)),
+            )
+            .unwrap();
+
+        // Assert
+        assert!(swapped);
+        let value = storage.get(hrt, &mut tx, "a").unwrap().unwrap();
+        assert_eq!(value.0, serde_json::json!(2));
+    }
+
+    #[test]
+    fn test_cas_rejects_mismatched_value() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let storage = committed_storage(hrt, &mut kv);
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let mismatched = storage
+            .cas(
+                hrt,
+                &mut tx,
+                "a",
+                Some(&serde_json::json!(3)),
+                KvValue(serde_json::json!(2)),
+            )
+            .unwrap();
+        let present = storage
+            .cas(hrt, &mut tx, "a", None, KvValue(serde_json::json!(2)))
+            .unwrap();
+
+        // Assert
+        assert!(!mismatched);
+        assert!(!present);
+        let value = storage.get(hrt, &mut tx, "a").unwrap().unwrap();
+        assert_eq!(value.0, serde_json::json!(1));
+    }
+
+    #[test]
+    fn test_cas_creates_absent_key() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let storage = committed_storage(hrt, &mut kv);
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let created = storage
+            .cas(hrt, &mut tx, "b", None, KvValue(serde_json::json!("b")))
+            .unwrap();
+        let recreated = storage
+            .cas(hrt, &mut tx, "b", None, KvValue(serde_json::json!("c")))
+            .unwrap();
+
+        // Assert
+        assert!(created);
+        assert!(!recreated);
+        kv.commit_transaction(hrt, tx).unwrap();
+        let mut tx = kv.begin_transaction();
+        let value = storage.get(hrt, &mut tx, "b").unwrap().unwrap();
+        assert_eq!(value.0, serde_json::json!("b"));
+    }
+
+    #[test]
+    fn test_value_expires() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let storage = committed_storage(hrt, &mut kv);
+
+        let mut tx = kv.begin_transaction();
+        storage
+            .set_with_expiry(hrt, &mut tx, "session", KvValue(serde_json::json!(1)), 100)
+            .unwrap();
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        // Before its expiry, the value is read as usual
+        let mut tx = kv.begin_transaction();
+        assert!(!storage.expire(hrt, &mut tx, "session", 99).unwrap());
+        let value = storage.get(hrt, &mut tx, "session").unwrap().unwrap();
+        assert_eq!(value.0, serde_json::json!(1));
+
+        // At its expiry, the value is deleted
+        assert!(storage.expire(hrt, &mut tx, "session", 100).unwrap());
+        assert!(storage.get(hrt, &mut tx, "session").unwrap().is_none());
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        let mut tx = kv.begin_transaction();
+        assert!(!storage.has(hrt, &mut tx, "session").unwrap());
+        assert!(!storage.expire(hrt, &mut tx, "session", 200).unwrap());
+        assert!(storage.has(hrt, &mut tx, "a").unwrap());
+    }
+
+    #[test]
+    fn test_set_clears_expiry() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let storage = committed_storage(hrt, &mut kv);
+
+        let mut tx = kv.begin_transaction();
+        storage
+            .set_with_expiry(hrt, &mut tx, "session", KvValue(serde_json::json!(1)), 100)
+            .unwrap();
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        let mut tx = kv.begin_transaction();
+        storage
+            .set(hrt, &mut tx, "session", KvValue(serde_json::json!(2)))
+            .unwrap();
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        let mut tx = kv.begin_transaction();
+        assert!(!storage.expire(hrt, &mut tx, "session", 200).unwrap());
+        let value = storage.get(hrt, &mut tx, "session").unwrap().unwrap();
+        assert_eq!(value.0, serde_json::json!(2));
+    }
+
+    #[test]
+    fn test_expiry_is_stored_per_key() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let storage = committed_storage(hrt, &mut kv);
+
+        let mut tx = kv.begin_transaction();
+        storage
+            .set_with_expiry(hrt, &mut tx, "session", KvValue(serde_json::json!(1)), 100)
+            .unwrap();
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        let mut tx = kv.begin_transaction();
+        assert_eq!(storage.expiry(hrt, &mut tx, "session").unwrap(), Some(100));
+        assert_eq!(storage.expiry(hrt, &mut tx, "a").unwrap(), None);
+
+        storage.delete(hrt, &mut tx, "session").unwrap();
+        assert_eq!(storage.expiry(hrt, &mut tx, "session").unwrap(), None);
+    }
+
+    #[test]
+    fn test_expired_keys_are_not_enumerated() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let storage = Kv::new("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty".to_string());
+
+        storage
+            .set(hrt, &mut tx, "a", KvValue(serde_json::json!(1)))
+            .unwrap();
+        storage
+            .set_with_expiry(hrt, &mut tx, "session", KvValue(serde_json::json!(2)), 100)
+            .unwrap();
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        // Act
+        let mut tx = kv.begin_read_only_transaction();
+        let live = storage.export_json(hrt, &mut tx, 99).unwrap();
+        let expired = storage.export_json(hrt, &mut tx, 100).unwrap();
+
+        // Assert
+        assert_eq!(
+            serde_json::Value::Object(live),
+            serde_json::json!({ "a": 1, "session": 2 })
+        );
+        assert_eq!(
+            serde_json::Value::Object(expired),
+            serde_json::json!({ "a": 1 })
+        );
+        assert_eq!(
+            storage.keys(hrt, &mut tx, 100).unwrap(),
+            BTreeSet::from(["a".to_string()])
+        );
+        assert!(storage
+            .scan_prefix(hrt, &mut tx, "s", 100)
+            .unwrap()
+            .is_empty());
+        assert_eq!(storage.dump(hrt, &mut tx, 100).unwrap().len(), 1);
+        assert!(storage.has(hrt, &mut tx, "session").unwrap());
+    }
+
+    #[test]
+    fn test_expired_value_is_kept_in_read_only_transaction() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let storage = committed_storage(hrt, &mut kv);
+
+        let mut tx = kv.begin_transaction();
+        storage
+            .set_with_expiry(hrt, &mut tx, "session", KvValue(serde_json::json!(1)), 100)
+            .unwrap();
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        let mut tx = kv.begin_read_only_transaction();
+        assert!(storage.expire(hrt, &mut tx, "session", 100).unwrap());
+        assert!(storage.has(hrt, &mut tx, "session").unwrap());
+    }
+
+    #[test]
+    fn test_value_size_limit() {
+        let hrt = &mut MockHost::default();
+        let kv = jstz_core::kv::Kv::new();
+        let storage = Kv::new("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty".to_string())
+            .with_max_value_size(MAX_VALUE_SIZE);
+        let mut tx = kv.begin_transaction();
+
+        // A JSON string is serialized with its 2 quotes
+        let at_limit = KvValue(serde_json::json!("a".repeat(MAX_VALUE_SIZE - 2)));
+        let over_limit = KvValue(serde_json::json!("a".repeat(MAX_VALUE_SIZE - 1)));
+
+        storage.set(hrt, &mut tx, "a", at_limit).unwrap();
+        assert!(matches!(
+            storage.set(hrt, &mut tx, "b", over_limit),
+            Err(jstz_core::Error::ValueTooLarge {
+                size,
+                limit: MAX_VALUE_SIZE,
+            }) if size == MAX_VALUE_SIZE + 1
+        ));
+        assert!(storage.has(hrt, &mut tx, "a").unwrap());
+        assert!(!storage.has(hrt, &mut tx, "b").unwrap());
+    }
+
+    #[test]
+    fn test_key_length_limit() {
+        let hrt = &mut MockHost::default();
+        let kv = jstz_core::kv::Kv::new();
+        let storage = Kv::new("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty".to_string())
+            .with_max_value_size(MAX_VALUE_SIZE);
+        let mut tx = kv.begin_transaction();
+
+        let at_limit = "k".repeat(MAX_KEY_LENGTH);
+        let over_limit = "k".repeat(MAX_KEY_LENGTH + 1);
+
+        storage
+            .set(hrt, &mut tx, &at_limit, KvValue(serde_json::json!(1)))
+            .unwrap();
+        assert!(matches!(
+            storage.set(hrt, &mut tx, &over_limit, KvValue(serde_json::json!(1))),
+            Err(jstz_core::Error::KeyTooLong {
+                length,
+                limit: MAX_KEY_LENGTH,
+            }) if length == MAX_KEY_LENGTH + 1
+        ));
+    }
+
+    #[test]
+    fn test_delete_then_get() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let storage = committed_storage(hrt, &mut kv);
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        storage.delete(hrt, &mut tx, "a").unwrap();
+
+        // Assert
+        assert!(storage.get(hrt, &mut tx, "a").unwrap().is_none());
+        assert!(!storage.has(hrt, &mut tx, "a").unwrap());
+
+        kv.commit_transaction(hrt, tx).unwrap();
+        let mut tx = kv.begin_transaction();
+        assert!(!storage.has(hrt, &mut tx, "a").unwrap());
+    }
+
+    #[test]
+    fn test_delete_then_set() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let storage = committed_storage(hrt, &mut kv);
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        storage.delete(hrt, &mut tx, "a").unwrap();
+        storage
+            .set(hrt, &mut tx, "a", KvValue(serde_json::json!(2)))
+            .unwrap();
+
+        // Assert
+        let value = storage.get(hrt, &mut tx, "a").unwrap().unwrap();
+        assert_eq!(value.0, serde_json::json!(2));
+
+        kv.commit_transaction(hrt, tx).unwrap();
+        let mut tx = kv.begin_transaction();
+        let value = storage.get(hrt, &mut tx, "a").unwrap().unwrap();
+        assert_eq!(value.0, serde_json::json!(2));
+    }
+
+    #[test]
+    fn test_delete_nonexistent_key() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let storage = committed_storage(hrt, &mut kv);
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        storage.delete(hrt, &mut tx, "b").unwrap();
+
+        // Assert
+        assert!(storage.get(hrt, &mut tx, "b").unwrap().is_none());
+        assert!(storage.has(hrt, &mut tx, "a").unwrap());
+    }
+
+    #[test]
+    fn test_delete_rolled_back_to_savepoint() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let storage = committed_storage(hrt, &mut kv);
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let savepoint = tx.savepoint();
+        storage.delete(hrt, &mut tx, "a").unwrap();
+        assert!(storage.get(hrt, &mut tx, "a").unwrap().is_none());
+        tx.rollback_to(savepoint).unwrap();
+
+        // Assert
+        let value = storage.get(hrt, &mut tx, "a").unwrap().unwrap();
+        assert_eq!(value.0, serde_json::json!(1));
+    }
+
+    #[test]
+    fn test_scan_prefix_reads_uncommitted_writes() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let storage = Kv::new("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty".to_string());
+
+        storage
+            .set(hrt, &mut tx, "users/deleted", KvValue(serde_json::json!(0)))
+            .unwrap();
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        for (key, value) in [("users/1", 1), ("users/2", 2), ("users/3", 3)] {
+            storage
+                .set(hrt, &mut tx, key, KvValue(serde_json::json!(value)))
+                .unwrap();
+        }
+        storage
+            .set(hrt, &mut tx, "usersettings", KvValue(serde_json::json!({})))
+            .unwrap();
+        storage.delete(hrt, &mut tx, "users/deleted").unwrap();
+
+        let entries: Vec<_> = storage
+            .scan_prefix(hrt, &mut tx, "users/", 0)
+            .unwrap()
+            .into_iter()
+            .map(|(key, value)| (key, value.0))
+            .collect();
+
+        // Assert
+        assert_eq!(
+            entries,
+            vec![
+                ("users/1".to_string(), serde_json::json!(1)),
+                ("users/2".to_string(), serde_json::json!(2)),
+                ("users/3".to_string(), serde_json::json!(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_copy_to_migrates_entries() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let owner = "tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty";
+        let source = Kv::new(owner.to_string());
+        let target = Kv::new("tz1ZXxxkNYSUutm5VZvfudakRxx2mjpWko4J".to_string());
+
+        source
+            .set(hrt, &mut tx, "a", KvValue(serde_json::json!(1)))
+            .unwrap();
+        source
+            .set(hrt, &mut tx, "b", KvValue(serde_json::json!({ "c": true })))
+            .unwrap();
+        source
+            .set(
+                hrt,
+                &mut tx,
+                OWNER_KEY,
+                KvValue(serde_json::json!("tz1other")),
+            )
+            .unwrap();
+        target
+            .set(hrt, &mut tx, OWNER_KEY, KvValue(serde_json::json!(owner)))
+            .unwrap();
+
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        assert!(target.is_owned_by(hrt, &mut tx, owner).unwrap());
+        assert!(!source.is_owned_by(hrt, &mut tx, owner).unwrap());
+
+        let keys = ["a", "b", "missing", OWNER_KEY].map(String::from);
+        let count = source.copy_to(hrt, &mut tx, &target, &keys).unwrap();
+
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        // Assert
+        let mut tx = kv.begin_transaction();
+        assert_eq!(count, 2);
+        assert_eq!(
+            target.get(hrt, &mut tx, "a").unwrap().unwrap().0,
+            serde_json::json!(1)
+        );
+        assert_eq!(
+            target.get(hrt, &mut tx, "b").unwrap().unwrap().0,
+            serde_json::json!({ "c": true })
+        );
+        assert_eq!(
+            target.get(hrt, &mut tx, OWNER_KEY).unwrap().unwrap().0,
+            serde_json::json!(owner)
+        );
+    }
+
+    #[test]
+    fn test_copy_to_copies_all_committed_entries() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = Kv::new("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty".to_string());
+        let target = Kv::new("tz1ZXxxkNYSUutm5VZvfudakRxx2mjpWko4J".to_string());
+
+        source
+            .set(hrt, &mut tx, "a", KvValue(serde_json::json!(1)))
+            .unwrap();
+        source
+            .set(hrt, &mut tx, "users/b", KvValue(serde_json::json!(2)))
+            .unwrap();
+        source
+            .set(
+                hrt,
+                &mut tx,
+                OWNER_KEY,
+                KvValue(serde_json::json!("tz1other")),
+            )
+            .unwrap();
+
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        // Act: copy without explicit keys, as `Kv.copyTo(target)` does
+        let mut tx = kv.begin_transaction();
+        let keys: Vec<_> = source
+            .entries(hrt, &mut tx, 0)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .filter(|key| !is_reserved_key(key))
+            .collect();
+        let count = source.copy_to(hrt, &mut tx, &target, &keys).unwrap();
+
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        // Assert
+        let mut tx = kv.begin_transaction();
+        assert_eq!(count, 2);
+        assert_eq!(
+            target
+                .entries(hrt, &mut tx, 0)
+                .unwrap()
+                .into_iter()
+                .map(|(key, value)| (key, value.0))
+                .collect::<Vec<_>>(),
+            vec![
+                ("a".to_string(), serde_json::json!(1)),
+                ("users/b".to_string(), serde_json::json!(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_copy_to_rejects_oversized_entries() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = Kv::new("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty".to_string());
+        let target = Kv::new("tz1ZXxxkNYSUutm5VZvfudakRxx2mjpWko4J".to_string())
+            .with_max_value_size(MAX_VALUE_SIZE);
+
+        // Written before the limit, or by a `Kv` without one
+        let oversized = KvValue(serde_json::json!("a".repeat(MAX_VALUE_SIZE)));
+        source
+            .set(hrt, &mut tx, "a", KvValue(serde_json::json!(1)))
+            .unwrap();
+        source.set(hrt, &mut tx, "b", oversized).unwrap();
+
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let keys = ["a", "b"].map(String::from);
+        let result = source.copy_to(hrt, &mut tx, &target, &keys);
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(jstz_core::Error::ValueTooLarge {
+                size,
+                limit: MAX_VALUE_SIZE,
+            }) if size == MAX_VALUE_SIZE + 2
+        ));
+        assert!(!target.has(hrt, &mut tx, "a").unwrap());
+        assert!(!target.has(hrt, &mut tx, "b").unwrap());
+    }
+
+    #[test]
+    fn test_export_import_migration() {
+        let hrt = &mut MockHost::default();
+        let mut kv = jstz_core::kv::Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let owner = "tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty";
+        let storage = Kv::new("tz1ZXxxkNYSUutm5VZvfudakRxx2mjpWko4J".to_string());
+
+        // v1 schema: balances stored under `balance_<name>`
+        storage
+            .set(hrt, &mut tx, OWNER_KEY, KvValue(serde_json::json!(owner)))
+            .unwrap();
+        storage
+            .set(
+                hrt,
+                &mut tx,
+                "balance_alice",
+                KvValue(serde_json::json!(10)),
+            )
+            .unwrap();
+        storage
+            .set(hrt, &mut tx, "balance_bob", KvValue(serde_json::json!(20)))
+            .unwrap();
+
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let v1 = storage.export_json(hrt, &mut tx, 0).unwrap();
+        assert_eq!(v1.len(), 2);
+
+        // v2 schema: balances stored under `balances/<name>`
+        let v2 = v1
+            .into_iter()
+            .map(|(key, value)| (key.replace("balance_", "balances/"), value))
+            .collect();
+        storage.import_json(hrt, &mut tx, v2, false).unwrap();
+
+        kv.commit_transaction(hrt, tx).unwrap();
+
+        // Assert
+        let mut tx = kv.begin_transaction();
+        assert_eq!(
+            serde_json::Value::Object(storage.export_json(hrt, &mut tx, 0).unwrap()),
+            serde_json::json!({ "balances/alice": 10, "balances/bob": 20 })
+        );
+        assert!(!storage.has(hrt, &mut tx, "balance_alice").unwrap());
+        assert!(storage.is_owned_by(hrt, &mut tx, owner).unwrap());
     }
 }
@@ -1,12 +1,25 @@
 mod console;
 mod kv;
+mod map;
 
+pub mod crypto;
 pub mod encoding;
 pub mod http;
 pub mod idl;
+pub mod merkle;
+pub mod net;
+pub mod tezos;
+pub mod time;
 pub mod url;
 pub mod urlpattern;
-pub use console::{ConsoleApi, LogRecord, LOG_PREFIX};
+#[cfg(feature = "zk")]
+pub mod zk;
+pub use console::{ConsoleApi, ConsoleKind, LogBuffer, LogLevel, LogRecord, LOG_PREFIX};
 pub use kv::Kv;
 pub use kv::KvApi;
+pub use kv::KvCaller;
 pub use kv::KvValue;
+pub use kv::MAX_VALUE_SIZE;
+pub use kv::{Subscription, SUBSCRIPTIONS_KEY};
+pub use kv::{LOCK_KEY_PREFIX, OWNER_KEY};
+pub use map::{KvMapApi, PersistentMap};
@@ -0,0 +1,1014 @@
+//! Ordered persistent maps, stored as a B-tree in a smart function's KV store.
+//!
+//! Each node of the tree is stored as a separate KV entry (a shard) under
+//! `__map__/<name>/<id>`, alongside a `__map__/<name>/meta` entry holding the
+//! root node id, the next free node id and the number of entries. Only the
+//! nodes along a root-to-leaf path are read or written by a single operation,
+//! allowing efficient range queries over sorted keys.
+//!
+//! Exposes the global `KvMap` object.
+
+use std::ops::Deref;
+
+use boa_engine::{
+    js_string,
+    object::{builtins::JsArray, ObjectInitializer},
+    property::Attribute,
+    Context, JsArgs, JsError, JsNativeError, JsResult, JsString, JsValue, NativeFunction,
+};
+use boa_gc::{Finalize, Trace};
+use jstz_core::{host::HostRuntime, host_defined, kv::Transaction, runtime, Result};
+use jstz_crypto::public_key_hash::PublicKeyHash;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Kv, KvValue};
+
+/// The minimum degree of the B-tree. Every node other than the root holds
+/// between `MIN_DEGREE - 1` and `2 * MIN_DEGREE - 1` keys.
+const MIN_DEGREE: usize = 16;
+const MAX_KEYS: usize = 2 * MIN_DEGREE - 1;
+
+/// The default and maximum number of entries returned by a range query
+pub const DEFAULT_RANGE_LIMIT: usize = 100;
+pub const MAX_RANGE_LIMIT: usize = 1000;
+
+type NodeId = u64;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Node {
+    keys: Vec<String>,
+    values: Vec<serde_json::Value>,
+    /// Empty for leaves
+    children: Vec<NodeId>,
+}
+
+impl Node {
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Meta {
+    root: NodeId,
+    next_id: NodeId,
+    len: u64,
+}
+
+impl Default for Meta {
+    fn default() -> Self {
+        Self {
+            root: 0,
+            next_id: 1,
+            len: 0,
+        }
+    }
+}
+
+#[derive(Debug, Trace, Finalize)]
+pub struct PersistentMap {
+    storage: Kv,
+    name: String,
+}
+
+impl PersistentMap {
+    pub fn new(prefix: String, name: String) -> Self {
+        Self {
+            storage: Kv::new(prefix),
+            name,
+        }
+    }
+
+    fn read<T: Default + DeserializeOwned>(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+    ) -> Result<T> {
+        match self
+            .storage
+            .get(hrt, tx, &format!("__map__/{}/{}", self.name, key))?
+        {
+            Some(value) => Ok(serde_json::from_value(value.0.clone()).map_err(|_| {
+                JsNativeError::error()
+                    .with_message(format!("Corrupted persistent map: {}", self.name))
+            })?),
+            None => Ok(T::default()),
+        }
+    }
+
+    fn write<T: Serialize>(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+        value: &T,
+    ) -> Result<()> {
+        let value = serde_json::to_value(value).expect("Map shards should serialize");
+
+        self.storage.set(
+            hrt,
+            tx,
+            &format!("__map__/{}/{}", self.name, key),
+            KvValue(value),
+        )
+    }
+
+    fn read_meta(&self, hrt: &impl HostRuntime, tx: &mut Transaction) -> Result<Meta> {
+        self.read(hrt, tx, "meta")
+    }
+
+    fn write_meta(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        meta: &Meta,
+    ) -> Result<()> {
+        self.write(hrt, tx, "meta", meta)
+    }
+
+    fn read_node(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        id: NodeId,
+    ) -> Result<Node> {
+        self.read(hrt, tx, &id.to_string())
+    }
+
+    fn write_node(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        id: NodeId,
+        node: &Node,
+    ) -> Result<()> {
+        self.write(hrt, tx, &id.to_string(), node)
+    }
+
+    fn free_node(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        id: NodeId,
+    ) -> Result<()> {
+        self.storage
+            .delete(hrt, tx, &format!("__map__/{}/{}", self.name, id))
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self, hrt: &impl HostRuntime, tx: &mut Transaction) -> Result<u64> {
+        Ok(self.read_meta(hrt, tx)?.len)
+    }
+
+    pub fn get(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+    ) -> Result<Option<serde_json::Value>> {
+        let mut id = self.read_meta(hrt, tx)?.root;
+
+        loop {
+            let mut node = self.read_node(hrt, tx, id)?;
+            match node.keys.binary_search_by(|k| k.as_str().cmp(key)) {
+                Ok(i) => return Ok(Some(node.values.swap_remove(i))),
+                Err(_) if node.is_leaf() => return Ok(None),
+                Err(i) => id = node.children[i],
+            }
+        }
+    }
+
+    /// Splits the full `i`-th child of `parent` in two, moving its median
+    /// entry up into `parent`. The caller is responsible for writing `parent`.
+    fn split_child(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        meta: &mut Meta,
+        parent: &mut Node,
+        i: usize,
+    ) -> Result<()> {
+        let child_id = parent.children[i];
+        let mut child = self.read_node(hrt, tx, child_id)?;
+
+        let right = Node {
+            keys: child.keys.split_off(MIN_DEGREE),
+            values: child.values.split_off(MIN_DEGREE),
+            children: if child.is_leaf() {
+                vec![]
+            } else {
+                child.children.split_off(MIN_DEGREE)
+            },
+        };
+        let median_key = child.keys.pop().expect("Full nodes are not empty");
+        let median_value = child.values.pop().expect("Full nodes are not empty");
+
+        let right_id = meta.next_id;
+        meta.next_id += 1;
+
+        parent.keys.insert(i, median_key);
+        parent.values.insert(i, median_value);
+        parent.children.insert(i + 1, right_id);
+
+        self.write_node(hrt, tx, child_id, &child)?;
+        self.write_node(hrt, tx, right_id, &right)
+    }
+
+    /// Inserts `key` into the map, returning the previous value, if any.
+    pub fn insert(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: String,
+        value: serde_json::Value,
+    ) -> Result<Option<serde_json::Value>> {
+        let mut meta = self.read_meta(hrt, tx)?;
+
+        let root = self.read_node(hrt, tx, meta.root)?;
+        if root.keys.len() == MAX_KEYS {
+            let mut new_root = Node {
+                children: vec![meta.root],
+                ..Default::default()
+            };
+            self.split_child(hrt, tx, &mut meta, &mut new_root, 0)?;
+
+            meta.root = meta.next_id;
+            meta.next_id += 1;
+            self.write_node(hrt, tx, meta.root, &new_root)?;
+        }
+
+        // Descend, splitting full nodes on the way down so that a leaf
+        // always has room for the new key
+        let mut id = meta.root;
+        let previous = loop {
+            let mut node = self.read_node(hrt, tx, id)?;
+            let mut i = match node.keys.binary_search(&key) {
+                Ok(i) => {
+                    let previous = std::mem::replace(&mut node.values[i], value);
+                    self.write_node(hrt, tx, id, &node)?;
+                    break Some(previous);
+                }
+                Err(i) => i,
+            };
+
+            if node.is_leaf() {
+                node.keys.insert(i, key);
+                node.values.insert(i, value);
+                self.write_node(hrt, tx, id, &node)?;
+                break None;
+            }
+
+            let child = self.read_node(hrt, tx, node.children[i])?;
+            if child.keys.len() == MAX_KEYS {
+                self.split_child(hrt, tx, &mut meta, &mut node, i)?;
+                self.write_node(hrt, tx, id, &node)?;
+
+                match key.cmp(&node.keys[i]) {
+                    std::cmp::Ordering::Equal => {
+                        let previous = std::mem::replace(&mut node.values[i], value);
+                        self.write_node(hrt, tx, id, &node)?;
+                        break Some(previous);
+                    }
+                    std::cmp::Ordering::Greater => i += 1,
+                    std::cmp::Ordering::Less => (),
+                }
+            }
+
+            id = node.children[i];
+        };
+
+        if previous.is_none() {
+            meta.len += 1;
+        }
+        self.write_meta(hrt, tx, &meta)?;
+
+        Ok(previous)
+    }
+
+    /// Merges the `i + 1`-th child of `parent` and the `i`-th key of `parent`
+    /// into the `i`-th child. The caller is responsible for writing `parent`.
+    fn merge_children(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        parent: &mut Node,
+        i: usize,
+    ) -> Result<()> {
+        let left_id = parent.children[i];
+        let right_id = parent.children.remove(i + 1);
+        let mut left = self.read_node(hrt, tx, left_id)?;
+        let right = self.read_node(hrt, tx, right_id)?;
+
+        left.keys.push(parent.keys.remove(i));
+        left.values.push(parent.values.remove(i));
+        left.keys.extend(right.keys);
+        left.values.extend(right.values);
+        left.children.extend(right.children);
+
+        self.write_node(hrt, tx, left_id, &left)?;
+        self.free_node(hrt, tx, right_id)
+    }
+
+    /// Ensures the `i`-th child of `parent` has at least `MIN_DEGREE` keys,
+    /// by borrowing a key from a sibling or merging with one. Returns the
+    /// index of the child that now covers the original child's keys. The
+    /// caller is responsible for writing `parent`.
+    fn fill_child(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        parent: &mut Node,
+        i: usize,
+    ) -> Result<usize> {
+        let child_id = parent.children[i];
+
+        if i > 0 {
+            let left_id = parent.children[i - 1];
+            let mut left = self.read_node(hrt, tx, left_id)?;
+            if left.keys.len() >= MIN_DEGREE {
+                let mut child = self.read_node(hrt, tx, child_id)?;
+
+                let key = left.keys.pop().expect("Left sibling is not empty");
+                let value = left.values.pop().expect("Left sibling is not empty");
+                child
+                    .keys
+                    .insert(0, std::mem::replace(&mut parent.keys[i - 1], key));
+                child
+                    .values
+                    .insert(0, std::mem::replace(&mut parent.values[i - 1], value));
+                if let Some(grandchild) = left.children.pop() {
+                    child.children.insert(0, grandchild);
+                }
+
+                self.write_node(hrt, tx, left_id, &left)?;
+                self.write_node(hrt, tx, child_id, &child)?;
+                return Ok(i);
+            }
+        }
+
+        if i < parent.keys.len() {
+            let right_id = parent.children[i + 1];
+            let mut right = self.read_node(hrt, tx, right_id)?;
+            if right.keys.len() >= MIN_DEGREE {
+                let mut child = self.read_node(hrt, tx, child_id)?;
+
+                let key = right.keys.remove(0);
+                let value = right.values.remove(0);
+                child.keys.push(std::mem::replace(&mut parent.keys[i], key));
+                child
+                    .values
+                    .push(std::mem::replace(&mut parent.values[i], value));
+                if !right.is_leaf() {
+                    child.children.push(right.children.remove(0));
+                }
+
+                self.write_node(hrt, tx, right_id, &right)?;
+                self.write_node(hrt, tx, child_id, &child)?;
+                return Ok(i);
+            }
+
+            self.merge_children(hrt, tx, parent, i)?;
+            return Ok(i);
+        }
+
+        self.merge_children(hrt, tx, parent, i - 1)?;
+        Ok(i - 1)
+    }
+
+    /// Removes and returns the greatest (if `last`) or least entry of the
+    /// subtree rooted at `id`, which must have at least `MIN_DEGREE` keys.
+    fn remove_extreme(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        mut id: NodeId,
+        last: bool,
+    ) -> Result<(String, serde_json::Value)> {
+        loop {
+            let mut node = self.read_node(hrt, tx, id)?;
+
+            if node.is_leaf() {
+                let entry = if last {
+                    (node.keys.pop(), node.values.pop())
+                } else {
+                    (Some(node.keys.remove(0)), Some(node.values.remove(0)))
+                };
+                self.write_node(hrt, tx, id, &node)?;
+
+                match entry {
+                    (Some(key), Some(value)) => return Ok((key, value)),
+                    _ => unreachable!("Non-root nodes are not empty"),
+                }
+            }
+
+            let mut i = if last { node.keys.len() } else { 0 };
+            let child = self.read_node(hrt, tx, node.children[i])?;
+            if child.keys.len() < MIN_DEGREE {
+                i = self.fill_child(hrt, tx, &mut node, i)?;
+                self.write_node(hrt, tx, id, &node)?;
+            }
+
+            id = node.children[i];
+        }
+    }
+
+    /// Removes `key` from the map, returning its value, if any.
+    pub fn remove(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+    ) -> Result<Option<serde_json::Value>> {
+        let mut meta = self.read_meta(hrt, tx)?;
+
+        // Descend, ensuring every node visited below the root has at least
+        // `MIN_DEGREE` keys so that a key can be removed without underflow
+        let mut id = meta.root;
+        let removed = loop {
+            let mut node = self.read_node(hrt, tx, id)?;
+
+            match node.keys.binary_search_by(|k| k.as_str().cmp(key)) {
+                Ok(i) if node.is_leaf() => {
+                    node.keys.remove(i);
+                    let value = node.values.remove(i);
+                    self.write_node(hrt, tx, id, &node)?;
+                    break Some(value);
+                }
+                Ok(i) => {
+                    let left = self.read_node(hrt, tx, node.children[i])?;
+                    if left.keys.len() >= MIN_DEGREE {
+                        let (k, v) =
+                            self.remove_extreme(hrt, tx, node.children[i], true)?;
+                        node.keys[i] = k;
+                        let value = std::mem::replace(&mut node.values[i], v);
+                        self.write_node(hrt, tx, id, &node)?;
+                        break Some(value);
+                    }
+
+                    let right = self.read_node(hrt, tx, node.children[i + 1])?;
+                    if right.keys.len() >= MIN_DEGREE {
+                        let (k, v) =
+                            self.remove_extreme(hrt, tx, node.children[i + 1], false)?;
+                        node.keys[i] = k;
+                        let value = std::mem::replace(&mut node.values[i], v);
+                        self.write_node(hrt, tx, id, &node)?;
+                        break Some(value);
+                    }
+
+                    // Both children are minimal: merge them around the key
+                    // and continue removing from the merged child
+                    self.merge_children(hrt, tx, &mut node, i)?;
+                    self.write_node(hrt, tx, id, &node)?;
+                    id = node.children[i];
+                }
+                Err(_) if node.is_leaf() => break None,
+                Err(mut i) => {
+                    let child = self.read_node(hrt, tx, node.children[i])?;
+                    if child.keys.len() < MIN_DEGREE {
+                        i = self.fill_child(hrt, tx, &mut node, i)?;
+                        self.write_node(hrt, tx, id, &node)?;
+                    }
+                    id = node.children[i];
+                }
+            }
+        };
+
+        // Shrink the tree if the root has been emptied by a merge
+        let root = self.read_node(hrt, tx, meta.root)?;
+        if root.keys.is_empty() && !root.is_leaf() {
+            self.free_node(hrt, tx, meta.root)?;
+            meta.root = root.children[0];
+        }
+
+        if removed.is_some() {
+            meta.len -= 1;
+        }
+        self.write_meta(hrt, tx, &meta)?;
+
+        Ok(removed)
+    }
+
+    fn range_from(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        id: NodeId,
+        from: Option<&str>,
+        to: Option<&str>,
+        limit: usize,
+        entries: &mut Vec<(String, serde_json::Value)>,
+    ) -> Result<bool> {
+        let node = self.read_node(hrt, tx, id)?;
+        let start = from.map_or(0, |from| {
+            node.keys.partition_point(|key| key.as_str() < from)
+        });
+
+        for i in start..=node.keys.len() {
+            if !node.is_leaf()
+                && !self.range_from(
+                    hrt,
+                    tx,
+                    node.children[i],
+                    from,
+                    to,
+                    limit,
+                    entries,
+                )?
+            {
+                return Ok(false);
+            }
+
+            if entries.len() >= limit {
+                return Ok(false);
+            }
+
+            if let Some(key) = node.keys.get(i) {
+                if to.map_or(false, |to| key.as_str() >= to) {
+                    return Ok(false);
+                }
+                entries.push((key.clone(), node.values[i].clone()));
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Returns up to `limit` entries with keys in `[from, to)`, in ascending
+    /// order of keys. Missing bounds are unbounded.
+    pub fn range(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        from: Option<&str>,
+        to: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(String, serde_json::Value)>> {
+        let root = self.read_meta(hrt, tx)?.root;
+
+        let mut entries = Vec::new();
+        self.range_from(hrt, tx, root, from, to, limit, &mut entries)?;
+
+        Ok(entries)
+    }
+
+    /// Returns the greatest key less than or equal to `key`.
+    pub fn floor_key(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+    ) -> Result<Option<String>> {
+        let mut id = self.read_meta(hrt, tx)?.root;
+        let mut floor = None;
+
+        loop {
+            let mut node = self.read_node(hrt, tx, id)?;
+            match node.keys.binary_search_by(|k| k.as_str().cmp(key)) {
+                Ok(i) => return Ok(Some(node.keys.swap_remove(i))),
+                Err(i) => {
+                    if i > 0 {
+                        floor = Some(node.keys.swap_remove(i - 1));
+                    }
+                    if node.is_leaf() {
+                        return Ok(floor);
+                    }
+                    id = node.children[i];
+                }
+            }
+        }
+    }
+
+    /// Returns the least key greater than or equal to `key`.
+    pub fn ceiling_key(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        key: &str,
+    ) -> Result<Option<String>> {
+        let mut id = self.read_meta(hrt, tx)?.root;
+        let mut ceiling = None;
+
+        loop {
+            let mut node = self.read_node(hrt, tx, id)?;
+            match node.keys.binary_search_by(|k| k.as_str().cmp(key)) {
+                Ok(i) => return Ok(Some(node.keys.swap_remove(i))),
+                Err(i) => {
+                    if i < node.keys.len() {
+                        ceiling = Some(node.keys.swap_remove(i));
+                    }
+                    if node.is_leaf() {
+                        return Ok(ceiling);
+                    }
+                    id = node.children[i];
+                }
+            }
+        }
+    }
+}
+
+macro_rules! preamble {
+    ($this:ident, $context:ident, $tx:ident) => {
+        host_defined!($context, host_defined);
+        let mut $tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let $this = $this
+            .as_object()
+            .and_then(|obj| obj.downcast_ref::<PersistentMap>())
+            .ok_or_else(|| {
+                JsError::from_native(JsNativeError::typ().with_message(
+                    "Failed to convert js value into rust type `PersistentMap`",
+                ))
+            })?;
+    };
+}
+
+fn string_arg(args: &[JsValue], index: usize) -> JsResult<String> {
+    args.get_or_undefined(index)
+        .as_string()
+        .map(JsString::to_std_string_escaped)
+        .ok_or_else(|| {
+            JsNativeError::typ()
+                .with_message("Failed to convert js value into rust type `String`")
+                .into()
+        })
+}
+
+fn optional_string_arg(args: &[JsValue], index: usize) -> JsResult<Option<String>> {
+    match args.get_or_undefined(index) {
+        JsValue::Undefined | JsValue::Null => Ok(None),
+        _ => string_arg(args, index).map(Some),
+    }
+}
+
+fn key_to_js(key: Option<String>) -> JsValue {
+    key.map_or(JsValue::null(), |key| JsString::from(key).into())
+}
+
+/// The native data of the `KvMap` object
+#[derive(Trace, Finalize)]
+struct Namespace {
+    prefix: String,
+}
+
+pub struct KvMapApi {
+    pub contract_address: PublicKeyHash,
+}
+
+impl KvMapApi {
+    const NAME: &'static str = "KvMap";
+
+    fn get(this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+        preamble!(this, context, tx);
+        let key = string_arg(args, 0)?;
+
+        let value =
+            runtime::with_global_host(|hrt| this.get(hrt.deref(), &mut tx, &key))?;
+
+        match value {
+            Some(value) => JsValue::from_json(&value, context),
+            None => Ok(JsValue::null()),
+        }
+    }
+
+    fn set(this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+        preamble!(this, context, tx);
+        let key = string_arg(args, 0)?;
+        let value = args.get_or_undefined(1).to_json(context)?;
+
+        runtime::with_global_host(|hrt| this.insert(hrt.deref(), &mut tx, key, value))?;
+
+        Ok(JsValue::undefined())
+    }
+
+    fn delete(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        preamble!(this, context, tx);
+        let key = string_arg(args, 0)?;
+
+        let removed =
+            runtime::with_global_host(|hrt| this.remove(hrt.deref(), &mut tx, &key))?;
+
+        Ok(removed.is_some().into())
+    }
+
+    fn has(this: &JsValue, args: &[JsValue], context: &mut Context) -> JsResult<JsValue> {
+        preamble!(this, context, tx);
+        let key = string_arg(args, 0)?;
+
+        let value =
+            runtime::with_global_host(|hrt| this.get(hrt.deref(), &mut tx, &key))?;
+
+        Ok(value.is_some().into())
+    }
+
+    fn size(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        preamble!(this, context, tx);
+
+        let len = runtime::with_global_host(|hrt| this.len(hrt.deref(), &mut tx))?;
+
+        Ok(len.into())
+    }
+
+    fn range_get(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        preamble!(this, context, tx);
+        let from = optional_string_arg(args, 0)?;
+        let to = optional_string_arg(args, 1)?;
+        let limit = match args.get_or_undefined(2) {
+            JsValue::Undefined => DEFAULT_RANGE_LIMIT,
+            limit => limit.try_js_into::<u32>(context)? as usize,
+        };
+
+        if limit > MAX_RANGE_LIMIT {
+            return Err(JsNativeError::range()
+                .with_message(format!("Limit cannot exceed {MAX_RANGE_LIMIT}"))
+                .into());
+        }
+
+        let entries = runtime::with_global_host(|hrt| {
+            this.range(hrt.deref(), &mut tx, from.as_deref(), to.as_deref(), limit)
+        })?;
+
+        let mut values = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let value = JsValue::from_json(&value, context)?;
+            let entry = ObjectInitializer::new(context)
+                .property(js_string!("key"), JsString::from(key), Attribute::all())
+                .property(js_string!("value"), value, Attribute::all())
+                .build();
+            values.push(entry.into());
+        }
+
+        Ok(JsArray::from_iter(values, context).into())
+    }
+
+    fn floor_key(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        preamble!(this, context, tx);
+        let key = string_arg(args, 0)?;
+
+        let floor =
+            runtime::with_global_host(|hrt| this.floor_key(hrt.deref(), &mut tx, &key))?;
+
+        Ok(key_to_js(floor))
+    }
+
+    fn ceiling_key(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        preamble!(this, context, tx);
+        let key = string_arg(args, 0)?;
+
+        let ceiling = runtime::with_global_host(|hrt| {
+            this.ceiling_key(hrt.deref(), &mut tx, &key)
+        })?;
+
+        Ok(key_to_js(ceiling))
+    }
+
+    fn persistent(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        let prefix =
+            this.as_object()
+                .and_then(|obj| {
+                    obj.downcast_ref::<Namespace>()
+                        .map(|namespace| namespace.prefix.clone())
+                })
+                .ok_or_else(|| {
+                    JsError::from_native(JsNativeError::typ().with_message(
+                        "Failed to convert js value into rust type `KvMap`",
+                    ))
+                })?;
+
+        let name = string_arg(args, 0)?;
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(JsNativeError::typ()
+                .with_message(format!("Invalid map name: {name}"))
+                .into());
+        }
+
+        if let Some(options) = args.get_or_undefined(1).as_object() {
+            let ordered = options.get(js_string!("ordered"), context)?;
+            if !ordered.is_undefined() && !ordered.to_boolean() {
+                return Err(JsNativeError::typ()
+                    .with_message("Only ordered persistent maps are supported")
+                    .into());
+            }
+        }
+
+        let map =
+            ObjectInitializer::with_native(PersistentMap::new(prefix, name), context)
+                .function(NativeFunction::from_fn_ptr(Self::get), js_string!("get"), 1)
+                .function(NativeFunction::from_fn_ptr(Self::set), js_string!("set"), 2)
+                .function(
+                    NativeFunction::from_fn_ptr(Self::delete),
+                    js_string!("delete"),
+                    1,
+                )
+                .function(NativeFunction::from_fn_ptr(Self::has), js_string!("has"), 1)
+                .function(
+                    NativeFunction::from_fn_ptr(Self::size),
+                    js_string!("size"),
+                    0,
+                )
+                .function(
+                    NativeFunction::from_fn_ptr(Self::range_get),
+                    js_string!("rangeGet"),
+                    3,
+                )
+                .function(
+                    NativeFunction::from_fn_ptr(Self::floor_key),
+                    js_string!("floorKey"),
+                    1,
+                )
+                .function(
+                    NativeFunction::from_fn_ptr(Self::ceiling_key),
+                    js_string!("ceilingKey"),
+                    1,
+                )
+                .build();
+
+        Ok(map.into())
+    }
+}
+
+impl jstz_core::Api for KvMapApi {
+    fn init(self, context: &mut Context<'_>) {
+        let namespace = Namespace {
+            prefix: self.contract_address.to_string(),
+        };
+
+        let kv_map = ObjectInitializer::with_native(namespace, context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::persistent),
+                js_string!("persistent"),
+                2,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), kv_map, Attribute::all())
+            .expect("The KvMap object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tezos_smart_rollup_mock::MockHost;
+
+    fn key(i: usize) -> String {
+        format!("key-{i:03}")
+    }
+
+    /// Checks the B-tree invariants of the subtree rooted at `id`, returning
+    /// its height and number of keys.
+    fn check_node(
+        map: &PersistentMap,
+        hrt: &MockHost,
+        tx: &mut Transaction,
+        id: NodeId,
+        is_root: bool,
+    ) -> (usize, usize) {
+        let node = map.read_node(hrt, tx, id).unwrap();
+
+        assert!(node.keys.windows(2).all(|w| w[0] < w[1]));
+        assert!(node.keys.len() <= MAX_KEYS);
+        assert!(is_root || node.keys.len() >= MIN_DEGREE - 1);
+
+        if node.is_leaf() {
+            return (1, node.keys.len());
+        }
+
+        assert_eq!(node.children.len(), node.keys.len() + 1);
+        let subtrees: Vec<_> = node
+            .children
+            .iter()
+            .map(|child| check_node(map, hrt, tx, *child, false))
+            .collect();
+        assert!(subtrees.iter().all(|(height, _)| *height == subtrees[0].0));
+
+        let len = node.keys.len() + subtrees.iter().map(|(_, len)| len).sum::<usize>();
+        (subtrees[0].0 + 1, len)
+    }
+
+    fn check_tree(map: &PersistentMap, hrt: &MockHost, tx: &mut Transaction) -> usize {
+        let meta = map.read_meta(hrt, tx).unwrap();
+        let (height, len) = check_node(map, hrt, tx, meta.root, true);
+
+        assert_eq!(len as u64, meta.len);
+        height
+    }
+
+    #[test]
+    fn test_ordered_map_range_queries() {
+        let hrt = &mut MockHost::default();
+        let kv = jstz_core::kv::Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let map = PersistentMap::new(
+            "tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty".to_string(),
+            "orders".to_string(),
+        );
+
+        // Insert the even keys 0, 2, ..., 198 in a scrambled order
+        for i in 0..100 {
+            let k = (i * 37) % 100 * 2;
+            let previous = map
+                .insert(hrt, &mut tx, key(k), serde_json::json!(k))
+                .unwrap();
+            assert_eq!(previous, None);
+        }
+
+        assert_eq!(map.len(hrt, &mut tx).unwrap(), 100);
+        assert!(check_tree(&map, hrt, &mut tx) > 1);
+
+        // Overwriting doesn't change the size
+        let previous = map
+            .insert(hrt, &mut tx, key(10), serde_json::json!("ten"))
+            .unwrap();
+        assert_eq!(previous, Some(serde_json::json!(10)));
+        assert_eq!(map.len(hrt, &mut tx).unwrap(), 100);
+        assert_eq!(
+            map.get(hrt, &mut tx, &key(10)).unwrap(),
+            Some(serde_json::json!("ten"))
+        );
+        assert_eq!(map.get(hrt, &mut tx, &key(11)).unwrap(), None);
+
+        // Range queries
+        let range = map
+            .range(hrt, &mut tx, Some(&key(41)), Some(&key(50)), 100)
+            .unwrap();
+        let keys: Vec<_> = range.iter().map(|(k, _)| k.clone()).collect();
+        assert_eq!(keys, vec![key(42), key(44), key(46), key(48)]);
+
+        let range = map.range(hrt, &mut tx, None, None, 1000).unwrap();
+        let keys: Vec<_> = range.into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, (0..100).map(|i| key(i * 2)).collect::<Vec<_>>());
+
+        let range = map.range(hrt, &mut tx, Some(&key(100)), None, 3).unwrap();
+        let keys: Vec<_> = range.into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![key(100), key(102), key(104)]);
+
+        // Floor and ceiling
+        assert_eq!(
+            map.floor_key(hrt, &mut tx, &key(51)).unwrap(),
+            Some(key(50))
+        );
+        assert_eq!(
+            map.floor_key(hrt, &mut tx, &key(50)).unwrap(),
+            Some(key(50))
+        );
+        assert_eq!(
+            map.ceiling_key(hrt, &mut tx, &key(51)).unwrap(),
+            Some(key(52))
+        );
+        assert_eq!(map.floor_key(hrt, &mut tx, "a").unwrap(), None);
+        assert_eq!(map.ceiling_key(hrt, &mut tx, "z").unwrap(), None);
+
+        // Removing rebalances the tree
+        for i in 0..90 {
+            let k = (i * 37) % 100 * 2;
+            assert!(map.remove(hrt, &mut tx, &key(k)).unwrap().is_some());
+            check_tree(&map, hrt, &mut tx);
+        }
+        assert_eq!(map.remove(hrt, &mut tx, &key(1)).unwrap(), None);
+        assert_eq!(map.len(hrt, &mut tx).unwrap(), 10);
+        assert_eq!(check_tree(&map, hrt, &mut tx), 1);
+
+        let remaining: Vec<_> = map
+            .range(hrt, &mut tx, None, None, 100)
+            .unwrap()
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        let mut expected: Vec<_> = (90..100).map(|i| key((i * 37) % 100 * 2)).collect();
+        expected.sort();
+        assert_eq!(remaining, expected);
+    }
+}
@@ -0,0 +1,317 @@
+//! `jstz`'s Merkle tree API.
+//!
+//! Exposes the global `Merkle` object, allowing contracts to build Merkle
+//! trees (e.g. for airdrops or whitelists) and verify inclusion proofs.
+//!
+//! Trees use SHA-256. Leaves and internal nodes are hashed with distinct
+//! prefixes, so that an internal node can never be passed off as a leaf, and
+//! the children of a node are sorted before hashing, so that proofs do not
+//! need to record whether each sibling is on the left or the right.
+
+use boa_engine::{
+    js_string,
+    object::{builtins::JsArray, Object, ObjectInitializer},
+    property::Attribute,
+    Context, JsArgs, JsNativeError, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{empty_trace, Finalize, GcRef, Trace};
+use sha2::{Digest, Sha256};
+
+use crate::idl::{buffer_source_to_vec, vec_to_uint8_array};
+
+pub type Hash = [u8; 32];
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+pub fn hash_leaf(leaf: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(leaf);
+    hasher.finalize().into()
+}
+
+pub fn hash_pair(a: &Hash, b: &Hash) -> Hash {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// A Merkle tree, stored as its layers from the leaves up to the root. A node
+/// without a sibling is promoted to the next layer unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    layers: Vec<Vec<Hash>>,
+}
+
+impl Finalize for MerkleTree {}
+
+unsafe impl Trace for MerkleTree {
+    empty_trace!();
+}
+
+impl MerkleTree {
+    /// Builds a tree from `leaves`. Returns `None` if there are no leaves.
+    pub fn new<L: AsRef<[u8]>>(leaves: &[L]) -> Option<Self> {
+        if leaves.is_empty() {
+            return None;
+        }
+
+        let mut layers = vec![leaves
+            .iter()
+            .map(|leaf| hash_leaf(leaf.as_ref()))
+            .collect::<Vec<_>>()];
+
+        while let Some(layer) = layers.last().filter(|layer| layer.len() > 1) {
+            let next = layer
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => hash_pair(a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+
+            layers.push(next);
+        }
+
+        Some(Self { layers })
+    }
+
+    pub fn leaf_count(&self) -> usize {
+        self.layers[0].len()
+    }
+
+    pub fn root(&self) -> Hash {
+        self.layers[self.layers.len() - 1][0]
+    }
+
+    /// Returns the proof of inclusion of the leaf at `index`, or `None` if the
+    /// index is out of bounds.
+    pub fn proof(&self, index: usize) -> Option<Vec<Hash>> {
+        if index >= self.leaf_count() {
+            return None;
+        }
+
+        let mut proof = Vec::new();
+        let mut index = index;
+        for layer in &self.layers[..self.layers.len() - 1] {
+            if let Some(sibling) = layer.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+
+        Some(proof)
+    }
+}
+
+/// Verifies that `proof` proves the inclusion of `leaf` in the tree with the
+/// given `root`.
+pub fn verify(root: &[u8], leaf: &[u8], proof: &[Hash]) -> bool {
+    let computed = proof
+        .iter()
+        .fold(hash_leaf(leaf), |node, sibling| hash_pair(&node, sibling));
+
+    computed.as_slice() == root
+}
+
+fn hash_from_js(value: &JsValue, context: &mut Context<'_>) -> JsResult<Hash> {
+    buffer_source_to_vec(value, context)?
+        .try_into()
+        .map_err(|_| {
+            JsNativeError::typ()
+                .with_message("Expected a 32-byte hash")
+                .into()
+        })
+}
+
+fn array_from_js<T>(
+    value: &JsValue,
+    f: fn(&JsValue, &mut Context<'_>) -> JsResult<T>,
+    context: &mut Context<'_>,
+) -> JsResult<Vec<T>> {
+    let array = value
+        .as_object()
+        .filter(|obj| obj.is_array())
+        .ok_or_else(|| JsNativeError::typ().with_message("Expected an array"))?;
+
+    let array = JsArray::from_object(array.clone())?;
+    let mut values = Vec::new();
+    for i in 0..array.length(context)? {
+        let value = array.get(i, context)?;
+        values.push(f(&value, context)?);
+    }
+
+    Ok(values)
+}
+
+fn tree_from_js<'a>(value: &'a JsValue) -> JsResult<GcRef<'a, Object, MerkleTree>> {
+    value
+        .as_object()
+        .and_then(|obj| obj.downcast_ref::<MerkleTree>())
+        .ok_or_else(|| {
+            JsNativeError::typ()
+                .with_message("Failed to convert js value into rust type `MerkleTree`")
+                .into()
+        })
+}
+
+pub struct MerkleApi;
+
+impl MerkleApi {
+    const NAME: &'static str = "Merkle";
+
+    fn tree(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let leaves =
+            array_from_js(args.get_or_undefined(0), buffer_source_to_vec, context)?;
+        let tree = MerkleTree::new(&leaves).ok_or_else(|| {
+            JsNativeError::range().with_message("A Merkle tree needs at least 1 leaf")
+        })?;
+
+        let tree = ObjectInitializer::with_native(tree, context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::root),
+                js_string!("root"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::proof),
+                js_string!("proof"),
+                1,
+            )
+            .build();
+
+        Ok(tree.into())
+    }
+
+    fn root(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let root = tree_from_js(this)?.root();
+
+        Ok(vec_to_uint8_array(root.to_vec(), context)?.into())
+    }
+
+    fn proof(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let index: u32 = args.get_or_undefined(0).try_js_into(context)?;
+        let proof = tree_from_js(this)?
+            .proof(index as usize)
+            .ok_or_else(|| JsNativeError::range().with_message("Index out of bounds"))?;
+
+        let values = proof
+            .into_iter()
+            .map(|hash| Ok(vec_to_uint8_array(hash.to_vec(), context)?.into()))
+            .collect::<JsResult<Vec<JsValue>>>()?;
+
+        Ok(JsArray::from_iter(values, context).into())
+    }
+
+    fn verify(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let root = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+        let leaf = buffer_source_to_vec(args.get_or_undefined(1), context)?;
+        let proof = array_from_js(args.get_or_undefined(2), hash_from_js, context)?;
+
+        Ok(verify(&root, &leaf, &proof).into())
+    }
+}
+
+impl jstz_core::Api for MerkleApi {
+    fn init(self, context: &mut Context<'_>) {
+        let merkle = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::tree),
+                js_string!("tree"),
+                1,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::verify),
+                js_string!("verify"),
+                3,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), merkle, Attribute::all())
+            .expect("The merkle object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn leaves(n: u32) -> Vec<Vec<u8>> {
+        (0..n).map(|i| i.to_be_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn verify_proofs_of_1024_leaves() {
+        let leaves = leaves(1024);
+        let tree = MerkleTree::new(&leaves).unwrap();
+        let root = tree.root();
+
+        // A simple LCG gives a deterministic sample of "random" indices
+        let mut index: usize = 7;
+        for _ in 0..64 {
+            index = (index * 1103515245 + 12345) % leaves.len();
+
+            let proof = tree.proof(index).unwrap();
+            assert_eq!(proof.len(), 10);
+            assert!(verify(&root, &leaves[index], &proof));
+
+            // The proof does not hold for any other leaf
+            let other = (index + 1) % leaves.len();
+            assert!(!verify(&root, &leaves[other], &proof));
+        }
+
+        assert_eq!(tree.proof(1024), None);
+    }
+
+    #[test]
+    fn verify_proofs_of_unbalanced_tree() {
+        let leaves = leaves(13);
+        let tree = MerkleTree::new(&leaves).unwrap();
+
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = tree.proof(index).unwrap();
+            assert!(verify(&tree.root(), leaf, &proof));
+        }
+    }
+
+    #[test]
+    fn internal_nodes_are_not_leaves() {
+        let leaves = leaves(4);
+        let tree = MerkleTree::new(&leaves).unwrap();
+
+        // Concatenating two leaf hashes does not yield a valid leaf
+        let mut node = tree.layers[0][0].to_vec();
+        node.extend_from_slice(&tree.layers[0][1]);
+
+        let proof = vec![tree.layers[1][1]];
+        assert!(!verify(&tree.root(), &node, &proof));
+    }
+
+    #[test]
+    fn empty_tree() {
+        assert_eq!(MerkleTree::new::<Vec<u8>>(&[]), None);
+    }
+}
@@ -0,0 +1,220 @@
+//! `jstz`'s utilities for handling IP addresses.
+//!
+//! Exposes the global `Net` object, allowing contracts to convert IP addresses
+//! to and from their byte representation and to perform CIDR range checks.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use boa_engine::{
+    js_string, object::ObjectInitializer, property::Attribute, Context, JsArgs,
+    JsNativeError, JsResult, JsString, JsValue, NativeFunction,
+};
+use ipnet::IpNet;
+
+use crate::idl::{buffer_source_to_vec, vec_to_uint8_array};
+
+fn parse_ip(ip: &str) -> JsResult<IpAddr> {
+    ip.parse().map_err(|_| {
+        JsNativeError::typ()
+            .with_message(format!("Invalid IP address: {ip}"))
+            .into()
+    })
+}
+
+fn parse_cidr(cidr: &str) -> JsResult<IpNet> {
+    cidr.parse().map_err(|_| {
+        JsNativeError::typ()
+            .with_message(format!("Invalid CIDR notation: {cidr}"))
+            .into()
+    })
+}
+
+/// Returns the network byte order representation of `ip` (4 bytes for IPv4,
+/// 16 bytes for IPv6).
+pub fn ip_to_bytes(ip: &IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+/// Parses an IP address from its network byte order representation.
+pub fn bytes_to_ip(bytes: &[u8]) -> Option<IpAddr> {
+    if let Ok(octets) = <[u8; 4]>::try_from(bytes) {
+        return Some(Ipv4Addr::from(octets).into());
+    }
+
+    if let Ok(octets) = <[u8; 16]>::try_from(bytes) {
+        return Some(Ipv6Addr::from(octets).into());
+    }
+
+    None
+}
+
+/// Returns `true` if `ip` is contained in `net`.
+///
+/// IPv4 addresses and IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) are
+/// treated as equivalent, so that dual-stack addresses match both IPv4 and
+/// IPv4-mapped IPv6 ranges.
+pub fn is_in_range(ip: &IpAddr, net: &IpNet) -> bool {
+    match (ip, net) {
+        (IpAddr::V4(ip), IpNet::V6(net)) => net.contains(&ip.to_ipv6_mapped()),
+        (IpAddr::V6(ip), IpNet::V4(net)) => {
+            ip.to_ipv4_mapped().map_or(false, |ip| net.contains(&ip))
+        }
+        (ip, net) => net.contains(ip),
+    }
+}
+
+pub struct NetApi;
+
+impl NetApi {
+    const NAME: &'static str = "Net";
+
+    fn ip_to_bytes(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let ip: String = args.get_or_undefined(0).try_js_into(context)?;
+        let ip = parse_ip(&ip)?;
+
+        Ok(vec_to_uint8_array(ip_to_bytes(&ip), context)?.into())
+    }
+
+    fn bytes_to_ip(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let bytes = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+
+        let ip = bytes_to_ip(&bytes).ok_or_else(|| {
+            JsNativeError::typ().with_message(format!(
+                "Expected 4 or 16 bytes, but {} provided",
+                bytes.len()
+            ))
+        })?;
+
+        Ok(JsString::from(ip.to_string()).into())
+    }
+
+    fn is_in_range(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let ip: String = args.get_or_undefined(0).try_js_into(context)?;
+        let cidr: String = args.get_or_undefined(1).try_js_into(context)?;
+
+        let ip = parse_ip(&ip)?;
+        let net = parse_cidr(&cidr)?;
+
+        Ok(is_in_range(&ip, &net).into())
+    }
+
+    fn ip_version(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let ip: String = args.get_or_undefined(0).try_js_into(context)?;
+
+        match ip.parse::<IpAddr>() {
+            Ok(IpAddr::V4(_)) => Ok(4.into()),
+            Ok(IpAddr::V6(_)) => Ok(6.into()),
+            Err(_) => Ok(JsValue::null()),
+        }
+    }
+}
+
+impl jstz_core::Api for NetApi {
+    fn init(self, context: &mut Context<'_>) {
+        let net = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::ip_to_bytes),
+                js_string!("ipToBytes"),
+                1,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::bytes_to_ip),
+                js_string!("bytesToIp"),
+                1,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::is_in_range),
+                js_string!("isInRange"),
+                2,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::ip_version),
+                js_string!("ipVersion"),
+                1,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), net, Attribute::all())
+            .expect("The net object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn net(net: &str) -> IpNet {
+        net.parse().unwrap()
+    }
+
+    #[test]
+    fn ipv4_bytes_roundtrip() {
+        let bytes = ip_to_bytes(&ip("192.168.1.1"));
+
+        assert_eq!(bytes, vec![192, 168, 1, 1]);
+        assert_eq!(bytes_to_ip(&bytes), Some(ip("192.168.1.1")));
+    }
+
+    #[test]
+    fn ipv6_bytes_roundtrip() {
+        let bytes = ip_to_bytes(&ip("2001:db8::1"));
+
+        assert_eq!(bytes.len(), 16);
+        assert_eq!(&bytes[..4], &[0x20, 0x01, 0x0d, 0xb8]);
+        assert_eq!(bytes_to_ip(&bytes), Some(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn bytes_to_ip_rejects_invalid_length() {
+        assert_eq!(bytes_to_ip(&[127, 0, 0]), None);
+    }
+
+    #[test]
+    fn ipv4_in_range() {
+        assert!(is_in_range(&ip("10.1.2.3"), &net("10.0.0.0/8")));
+        assert!(!is_in_range(&ip("11.1.2.3"), &net("10.0.0.0/8")));
+    }
+
+    #[test]
+    fn ipv6_in_range() {
+        assert!(is_in_range(&ip("2001:db8::42"), &net("2001:db8::/32")));
+        assert!(!is_in_range(&ip("2001:db9::42"), &net("2001:db8::/32")));
+    }
+
+    #[test]
+    fn dual_stack_in_range() {
+        assert!(is_in_range(
+            &ip("::ffff:192.168.1.1"),
+            &net("192.168.0.0/16")
+        ));
+        assert!(is_in_range(
+            &ip("192.168.1.1"),
+            &net("::ffff:192.168.0.0/112")
+        ));
+        assert!(!is_in_range(&ip("2001:db8::1"), &net("192.168.0.0/16")));
+    }
+}
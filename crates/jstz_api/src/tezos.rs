@@ -0,0 +1,76 @@
+//! `jstz`'s utilities for working with Tezos data.
+//!
+//! Exposes the global `Tezos` object.
+
+use boa_engine::{
+    js_string, object::ObjectInitializer, property::Attribute, Context, JsArgs, JsResult,
+    JsString, JsValue, NativeFunction,
+};
+use jstz_crypto::hash::Blake2b;
+
+use crate::{encoding::base58check, idl::buffer_source_to_vec};
+
+const OPERATION_HASH_PREFIX: &[u8] = &[5, 116];
+
+/// Computes the hash (`o…`) of a Tezos operation, given its binary encoding
+/// (including the signature, as injected).
+pub fn operation_hash(operation: &[u8]) -> String {
+    base58check::encode(Blake2b::from(operation).as_ref(), OPERATION_HASH_PREFIX)
+}
+
+pub struct TezosApi;
+
+impl TezosApi {
+    const NAME: &'static str = "Tezos";
+
+    fn operation_hash(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let operation = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+
+        Ok(JsString::from(operation_hash(&operation)).into())
+    }
+}
+
+impl jstz_core::Api for TezosApi {
+    fn init(self, context: &mut Context<'_>) {
+        let tezos = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::operation_hash),
+                js_string!("operationHash"),
+                1,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), tezos, Attribute::all())
+            .expect("The tezos object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn operation_hash_of_bytes() {
+        assert_eq!(
+            operation_hash(&[]),
+            "onkXbVFSFzwc6r3t49L1aCF3pUaQuL1KxdiTBcLTmDih7jRFkcs"
+        );
+        assert_eq!(
+            operation_hash(&(0..32).collect::<Vec<u8>>()),
+            "opBhMZsLM2wMzP3VuKojHxTKRTchoXhhYdD7cPznQuek7kkBhR2"
+        );
+    }
+
+    #[test]
+    fn operation_hash_is_decodable() {
+        let (prefix, payload) = base58check::decode(&operation_hash(b"jstz")).unwrap();
+
+        assert_eq!(prefix, OPERATION_HASH_PREFIX);
+        assert_eq!(payload, Blake2b::from(&b"jstz"[..]).as_ref());
+    }
+}
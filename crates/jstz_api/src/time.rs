@@ -0,0 +1,248 @@
+//! Deterministic `Date` and `performance.now()`
+//!
+//! Every node must agree on the result of a smart function, so the time cannot
+//! be read from the host. Instead, the time is read from the [`Clock`] of the
+//! current realm, which the executor sets to the timestamp of the current
+//! block, plus the virtual time elapsed on the timers of the runtime.
+//!
+//! More information:
+//!  - [MDN documentation of `Date.now()`][date]
+//!  - [MDN documentation of `performance.now()`][performance]
+//!
+//! [date]: https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Date/now
+//! [performance]: https://developer.mozilla.org/en-US/docs/Web/API/Performance/now
+
+use boa_engine::{
+    js_string,
+    object::{FunctionObjectBuilder, ObjectInitializer},
+    property::{Attribute, PropertyDescriptor},
+    Context, JsNativeError, JsObject, JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{empty_trace, Finalize, Trace};
+use jstz_core::{host_defined, timers::Timers};
+
+/// The time of an execution, from which `Date` and `performance` read
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Clock {
+    /// The time, in milliseconds since the Unix epoch, at which the execution
+    /// started
+    pub time_origin: f64,
+}
+
+impl Finalize for Clock {}
+
+unsafe impl Trace for Clock {
+    empty_trace!();
+}
+
+impl Clock {
+    pub fn from_unix_seconds(seconds: i64) -> Self {
+        Self {
+            time_origin: seconds as f64 * 1000.0,
+        }
+    }
+}
+
+/// Returns the milliseconds elapsed since the start of the execution
+fn elapsed(context: &mut Context<'_>) -> f64 {
+    Timers::from_context(context)
+        .map(|timers| timers.now() as f64)
+        .unwrap_or_default()
+}
+
+/// Returns the current time, in milliseconds since the Unix epoch
+fn now(context: &mut Context<'_>) -> f64 {
+    let time_origin = {
+        host_defined!(context, host_defined);
+        host_defined
+            .get::<Clock>()
+            .map(|clock| clock.time_origin)
+            .unwrap_or_default()
+    };
+
+    time_origin + elapsed(context)
+}
+
+/// Returns the current time, in whole seconds since the Unix epoch
+pub(crate) fn now_seconds(context: &mut Context<'_>) -> i64 {
+    (now(context) / 1000.0).floor() as i64
+}
+
+/// `Date.now()`
+fn date_now(
+    _this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    Ok(now(context).into())
+}
+
+/// The `Date` constructor. Calls without arguments read the current time from
+/// the [`Clock`] rather than the host; all other calls are forwarded to the
+/// builtin `Date`.
+fn date(
+    new_target: &JsValue,
+    args: &[JsValue],
+    builtin: &JsObject,
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let current = [JsValue::from(now(context))];
+
+    match new_target.as_object() {
+        // `Date()` ignores its arguments and returns the current time as a string
+        None => {
+            let date = builtin.construct(&current, None, context)?;
+            Ok(JsValue::from(date).to_string(context)?.into())
+        }
+        Some(new_target) => {
+            let args = if args.is_empty() { &current[..] } else { args };
+            Ok(builtin.construct(args, Some(new_target), context)?.into())
+        }
+    }
+}
+
+/// `performance.now()`
+fn performance_now(
+    _this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    Ok(elapsed(context).into())
+}
+
+pub struct TimeApi;
+
+impl TimeApi {
+    fn replace_date(context: &mut Context<'_>) -> JsResult<()> {
+        let builtin = context
+            .global_object()
+            .get(js_string!("Date"), context)?
+            .as_object()
+            .cloned()
+            .ok_or_else(|| JsNativeError::typ().with_message("Date is not defined"))?;
+
+        let date = FunctionObjectBuilder::new(
+            context.realm(),
+            NativeFunction::from_copy_closure_with_captures(date, builtin.clone()),
+        )
+        .name(js_string!("Date"))
+        .length(7)
+        .constructor(true)
+        .build();
+
+        // Instances keep the builtin prototype, so that `instanceof Date` holds
+        let prototype = builtin.get(js_string!("prototype"), context)?;
+        date.define_property_or_throw(
+            js_string!("prototype"),
+            PropertyDescriptor::builder()
+                .value(prototype.clone())
+                .writable(false)
+                .enumerable(false)
+                .configurable(false),
+            context,
+        )?;
+        if let Some(prototype) = prototype.as_object() {
+            prototype.set(js_string!("constructor"), date.clone(), true, context)?;
+        }
+
+        for name in ["parse", "UTC"] {
+            let function = builtin.get(js_string!(name), context)?;
+            date.set(js_string!(name), function, true, context)?;
+        }
+        let date_now = FunctionObjectBuilder::new(
+            context.realm(),
+            NativeFunction::from_fn_ptr(date_now),
+        )
+        .name(js_string!("now"))
+        .length(0)
+        .build();
+        date.set(js_string!("now"), date_now, true, context)?;
+
+        context
+            .global_object()
+            .set(js_string!("Date"), date, true, context)?;
+
+        Ok(())
+    }
+}
+
+impl jstz_core::Api for TimeApi {
+    fn init(self, context: &mut Context<'_>) {
+        Self::replace_date(context).expect("The builtin Date should be replaceable");
+
+        let performance = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(performance_now),
+                js_string!("now"),
+                0,
+            )
+            .build();
+
+        context
+            .register_global_property(
+                js_string!("performance"),
+                performance,
+                Attribute::all(),
+            )
+            .expect("The performance object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::{future, Api, Runtime};
+
+    use super::*;
+
+    fn eval(code: &str) -> String {
+        let mut rt = Runtime::new().unwrap();
+        {
+            let context = rt.context();
+            host_defined!(context, mut host_defined);
+            host_defined.insert(Clock::from_unix_seconds(1_700_000_000));
+        }
+        TimeApi.init(rt.context());
+
+        let value = rt.eval(Source::from_bytes(code)).unwrap();
+        let value = future::block_on(rt.resolve_value(&value)).unwrap();
+
+        value
+            .to_string(rt.context())
+            .unwrap()
+            .to_std_string_escaped()
+    }
+
+    #[test]
+    fn date_reads_clock() {
+        assert_eq!(eval("Date.now()"), "1700000000000");
+        assert_eq!(eval("new Date().getTime()"), "1700000000000");
+        assert_eq!(eval("new Date().toISOString()"), "2023-11-14T22:13:20.000Z");
+        assert_eq!(eval("Date() === new Date().toString()"), "true");
+    }
+
+    #[test]
+    fn date_keeps_builtin_behaviour() {
+        assert_eq!(
+            eval("new Date(0).toISOString()"),
+            "1970-01-01T00:00:00.000Z"
+        );
+        assert_eq!(eval("Date.UTC(2000, 0)"), "946684800000");
+        assert_eq!(eval("new Date() instanceof Date"), "true");
+        assert_eq!(eval("new Date().constructor === Date"), "true");
+    }
+
+    #[test]
+    fn time_advances_with_timers() {
+        let result = eval(
+            r#"
+            const start = [Date.now(), performance.now()];
+            new Promise((resolve) => setTimeout(resolve, 250)).then(() =>
+                [Date.now() - start[0], performance.now() - start[1]].join(",")
+            )
+            "#,
+        );
+
+        assert_eq!(result, "250,250");
+    }
+}
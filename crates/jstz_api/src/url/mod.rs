@@ -57,12 +57,27 @@ impl JsNativeObjectToString for Url {
 }
 
 impl Url {
+    /// The API URL parser.
+    ///
+    /// More information:
+    ///  - [WHATWG specification][spec]
+    ///
+    /// [spec] https://url.spec.whatwg.org/#api-url-parser
     fn parse_url(url: String, base: Option<String>) -> Option<InnerUrl> {
-        let options = InnerUrl::options();
-        let base = base.as_ref().and_then(|base| InnerUrl::parse(base).ok());
-        options.base_url(base.as_ref());
+        // 1. Let `parsed_base` be null
+        // 2. If `base` is non-null:
+        //    1. Set `parsed_base` to the result of running the basic URL parser on `base`
+        //    2. If `parsed_base` is failure, then return failure
+        let parsed_base = match base {
+            Some(base) => Some(InnerUrl::parse(&base).ok()?),
+            None => None,
+        };
 
-        options.parse(&url).ok()
+        // 3. Return the result of running the basic URL parser on `url` with `parsed_base`
+        InnerUrl::options()
+            .base_url(parsed_base.as_ref())
+            .parse(&url)
+            .ok()
     }
 
     /// Creates and returns a URL object referencing the URL specified using an absolute
@@ -149,12 +164,9 @@ impl Url {
         // 3. Set `self`'s URL to `parsed_url`.
         self.url = parsed_url;
 
-        // 6. If `query` is non-null (not empty)
-        if query.len() > 0 {
-            // 4. Empty `self`'s query object's list
-            // 6. (cont.) then set `self`’s query object’s list to `query`
-            self.search_params.deref_mut().set_values(query)
-        }
+        // 4. Empty `self`'s query object's list
+        // 6. If `query` is non-null, then set `self`’s query object’s list to `query`
+        self.search_params.deref_mut().set_values(query);
 
         Ok(())
     }
@@ -282,8 +294,18 @@ impl Url {
         self.url.query().map(String::from)
     }
 
+    /// [spec] https://url.spec.whatwg.org/#dom-url-search
     pub fn set_search(&mut self, search: Option<&str>) {
+        // An empty search (or a lone "?") removes the query
+        let search = search
+            .map(|search| search.strip_prefix('?').unwrap_or(search))
+            .filter(|search| !search.is_empty());
+
         self.url.set_query(search);
+
+        // Set `self`'s query object's list to the result of parsing `search`
+        let query = search.map(UrlSearchParams::parse).unwrap_or_default();
+        self.search_params.deref_mut().set_values(query);
     }
 
     pub fn search_params(&self) -> JsObject {
@@ -517,3 +539,97 @@ impl jstz_core::Api for UrlApi {
             .expect("The `URL` class shouldn't exist yet")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::Api;
+
+    use super::*;
+
+    fn eval(code: &str) -> String {
+        let context = &mut Context::default();
+        UrlApi.init(context);
+
+        context
+            .eval(Source::from_bytes(code))
+            .expect("Could not evaluate code")
+            .to_string(context)
+            .unwrap()
+            .to_std_string_escaped()
+    }
+
+    #[test]
+    fn resolves_path_references_against_base() {
+        let result = eval(
+            r#"
+            const base = "jstz://addr/a/b/c";
+            [
+                new URL("../x", base).href,
+                new URL("./x", base).href,
+                new URL("x", base).href,
+                new URL("/x/y", base).href,
+                new URL("../../../../x", base).href,
+            ].join(",")
+            "#,
+        );
+
+        assert_eq!(
+            result,
+            "jstz://addr/a/x,jstz://addr/a/b/x,jstz://addr/a/b/x,jstz://addr/x/y,jstz://addr/x"
+        );
+    }
+
+    #[test]
+    fn resolves_query_and_fragment_references_against_base() {
+        let result = eval(
+            r##"
+            const base = "jstz://addr/a/b?c=d#e";
+            const url = new URL("?x=1", base);
+            [
+                url.href,
+                url.searchParams.get("x"),
+                new URL("#f", base).href,
+                new URL("", base).href,
+            ].join(",")
+            "##,
+        );
+
+        assert_eq!(
+            result,
+            "jstz://addr/a/b?x=1,1,jstz://addr/a/b?c=d#f,jstz://addr/a/b?c=d"
+        );
+    }
+
+    #[test]
+    fn absolute_url_ignores_base() {
+        let result = eval(r#"new URL("tezos://tz1/path", "jstz://addr/a/b").href"#);
+
+        assert_eq!(result, "tezos://tz1/path");
+    }
+
+    #[test]
+    fn invalid_base_throws_type_error() {
+        let result = eval(
+            r#"
+            const attempt = (f) => {
+                try {
+                    f();
+                    return "ok";
+                } catch (e) {
+                    return e.name;
+                }
+            };
+            [
+                attempt(() => new URL("../x", "not a url")),
+                attempt(() => new URL("tezos://tz1/path", "not a url")),
+                attempt(() => new URL("../x")),
+                URL.canParse("../x", "not a url"),
+                URL.canParse("../x", "jstz://addr/a/b"),
+            ].join(",")
+            "#,
+        );
+
+        assert_eq!(result, "TypeError,TypeError,TypeError,false,true");
+    }
+}
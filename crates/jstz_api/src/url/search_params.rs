@@ -114,7 +114,7 @@ impl UrlSearchParams {
         // 1. If value is given,
         if let Some(value) = value {
             // 1. (cont.) Then removal all tuples whose name is `name` and value is `value`
-            self.values.retain(|(k, v)| k != &name && v != &value)
+            self.values.retain(|(k, v)| k != &name || v != &value)
         } else {
             // 2. Otherwise, removal all tuples whose name is `name`
             self.values.retain(|(k, _)| k != &name)
@@ -228,12 +228,13 @@ impl UrlSearchParams {
 }
 
 impl ToString for UrlSearchParams {
+    /// Serializes the search params as `application/x-www-form-urlencoded`
+    ///
+    /// [spec] https://url.spec.whatwg.org/#urlsearchparams-stringification-behavior
     fn to_string(&self) -> String {
-        self.values
-            .iter()
-            .map(|(k, v)| format!("{k}={v}"))
-            .collect::<Vec<String>>()
-            .join("&")
+        form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(&self.values)
+            .finish()
     }
 }
 
@@ -265,7 +266,10 @@ fn js_array_into_url_search_params_values(
 }
 
 impl UrlSearchParams {
-    fn parse(params: &str) -> Vec<(Name, Value)> {
+    pub(crate) fn parse(params: &str) -> Vec<(Name, Value)> {
+        // A leading "?" is ignored
+        let params = params.strip_prefix('?').unwrap_or(params);
+
         form_urlencoded::parse(params.as_bytes())
             .into_iter()
             .map(|(k, v)| (k.to_string(), v.to_string()))
@@ -563,3 +567,105 @@ impl jstz_core::Api for UrlSearchParamsApi {
             .expect("The `URLSearchParams Iterator` class shouldn't exist yet");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::Api;
+
+    use super::*;
+    use crate::url::UrlApi;
+
+    fn params(query: &str) -> UrlSearchParams {
+        UrlSearchParams::new(UrlSearchParams::parse(query))
+    }
+
+    #[test]
+    fn repeated_keys() {
+        let params = params("a=1&b=2&a=3");
+
+        assert_eq!(params.get("a".to_string()), Some("1".to_string()));
+        assert_eq!(params.get_all("a".to_string()), vec!["1", "3"]);
+        assert_eq!(params.len(), 3);
+    }
+
+    #[test]
+    fn empty_values() {
+        let params = params("?a=&b&=c");
+
+        assert_eq!(params.get("a".to_string()), Some(String::new()));
+        assert_eq!(params.get("b".to_string()), Some(String::new()));
+        assert_eq!(params.get(String::new()), Some("c".to_string()));
+        assert_eq!(params.to_string(), "a=&b=&=c");
+    }
+
+    #[test]
+    fn plus_and_percent_20_decode_to_space() {
+        let params = params("a=hello+world&b=hello%20world&c=1%2B1");
+
+        assert_eq!(params.get("a".to_string()), Some("hello world".to_string()));
+        assert_eq!(params.get("b".to_string()), Some("hello world".to_string()));
+        assert_eq!(params.get("c".to_string()), Some("1+1".to_string()));
+    }
+
+    #[test]
+    fn to_string_round_trips_encoded_characters() {
+        let params = params("q=a+b%26c%3Dd&emoji=%F0%9F%A6%80");
+
+        let serialized = params.to_string();
+
+        assert_eq!(serialized, "q=a+b%26c%3Dd&emoji=%F0%9F%A6%80");
+        assert_eq!(
+            UrlSearchParams::parse(&serialized),
+            vec![
+                ("q".to_string(), "a b&c=d".to_string()),
+                ("emoji".to_string(), "🦀".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn delete_with_value_only_removes_matching_pairs() {
+        let mut params = params("a=1&a=2&b=1");
+
+        params.remove("a".to_string(), Some("1".to_string()));
+
+        assert_eq!(params.to_string(), "a=2&b=1");
+    }
+
+    #[test]
+    fn search_params_of_url() {
+        let context = &mut Context::default();
+        UrlApi.init(context);
+
+        let result = context
+            .eval(Source::from_bytes(
+                r#"
+                const url = new URL("tezos://tz1/path?a=1&a=2&b=x+y");
+                const params = url.searchParams;
+                params.append("c", "1 2");
+                params.set("b", "z");
+                params.delete("a", "1");
+
+                JSON.stringify({
+                    entries: [...params],
+                    has: params.has("c"),
+                    href: url.href,
+                })
+                "#,
+            ))
+            .expect("Could not evaluate code")
+            .to_string(context)
+            .unwrap()
+            .to_std_string_escaped();
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&result).unwrap(),
+            serde_json::json!({
+                "entries": [["a", "2"], ["b", "z"], ["c", "1 2"]],
+                "has": true,
+                "href": "tezos://tz1/path?a=2&b=z&c=1+2",
+            })
+        );
+    }
+}
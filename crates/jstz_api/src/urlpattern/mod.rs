@@ -10,6 +10,8 @@
 //! [mdn]: https://developer.mozilla.org/en-US/docs/Web/API/URLPattern
 //! [spec]: https://urlpattern.spec.whatwg.org/
 
+mod router;
+
 use boa_engine::{
     js_string,
     object::{builtins::JsArray, Object},
@@ -28,6 +30,7 @@ use jstz_core::{
     value::IntoJs,
 };
 
+pub use router::{Router, RouterClass};
 use urlpattern::{
     quirks, UrlPattern as InnerUrlPattern,
     UrlPatternComponentResult as InnerUrlPatternComponentResult,
@@ -97,6 +100,15 @@ impl UrlPattern {
         base_url: Option<String>,
         _context: &mut Context<'_>,
     ) -> JsResult<Self> {
+        let url_pattern = Self::parse(input, base_url)?;
+
+        Ok(Self { url_pattern })
+    }
+
+    fn parse(
+        input: UrlPatternInput,
+        base_url: Option<String>,
+    ) -> JsResult<InnerUrlPattern> {
         let url_pattern_init =
             quirks::process_construct_pattern_input(input.0, base_url.as_deref())
                 .map_err(|_| {
@@ -105,13 +117,11 @@ impl UrlPattern {
                             .with_message("Failed to build UrlPatternInit"),
                     )
                 })?;
-        let url_pattern = InnerUrlPattern::parse(url_pattern_init).map_err(|_| {
+        InnerUrlPattern::parse(url_pattern_init).map_err(|_| {
             JsError::from_native(
                 JsNativeError::typ().with_message("Failed to parse UrlPatternInit"),
             )
-        })?;
-
-        Ok(Self { url_pattern })
+        })
     }
 
     pub fn protocol(&self) -> String {
@@ -535,7 +545,9 @@ pub struct UrlPatternApi;
 impl jstz_core::Api for UrlPatternApi {
     fn init(self, context: &mut Context<'_>) {
         register_global_class::<UrlPatternClass>(context)
-            .expect("The `URLPattern` class shouldn't exist yet")
+            .expect("The `URLPattern` class shouldn't exist yet");
+        register_global_class::<RouterClass>(context)
+            .expect("The `Router` class shouldn't exist yet");
     }
 }
 
@@ -0,0 +1,379 @@
+//! A small `URLPattern`-based router for contracts.
+//!
+//! Routes are registered with a method (or any method) and a pattern, and
+//! `handle` dispatches a `Request` to the handler of the first matching route,
+//! passing the request and an object of the matched path parameters:
+//!
+//! ```js
+//! const router = new Router()
+//!     .get("/users/:id", (request, params) => Response.json(params.id))
+//!     .post("/files/*", (request, params) => new Response(params[0]));
+//!
+//! export default (request) => router.handle(request);
+//! ```
+//!
+//! If no route matches, `handle` returns a `404 Not Found` response.
+
+use boa_engine::{
+    js_string, object::Object, Context, JsArgs, JsError, JsNativeError, JsObject,
+    JsResult, JsValue, NativeFunction,
+};
+use boa_gc::{custom_trace, Finalize, GcRefMut, Trace};
+use http::Method;
+use jstz_core::native::{ClassBuilder, JsNativeObject, NativeClass};
+
+use super::{quirks, InnerUrlPattern, UrlPattern, UrlPatternInit, UrlPatternInput};
+use crate::http::{
+    request::Request,
+    response::{ResponseBuilder, ResponseClass},
+};
+
+struct Route {
+    /// `None` matches any method
+    method: Option<Method>,
+    pattern: InnerUrlPattern,
+    handler: JsObject,
+}
+
+#[derive(Default, Finalize)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+unsafe impl Trace for Router {
+    custom_trace!(this, {
+        for route in this.routes.iter() {
+            mark(&route.handler);
+        }
+    });
+}
+
+impl Router {
+    /// Registers `handler` for requests with the given `method` whose url
+    /// matches `pattern`.
+    pub fn route(
+        &mut self,
+        method: Option<Method>,
+        pattern: InnerUrlPattern,
+        handler: JsObject,
+    ) {
+        self.routes.push(Route {
+            method,
+            pattern,
+            handler,
+        })
+    }
+
+    /// Returns the handler of the first route matching `method` and `url`,
+    /// along with the path parameters extracted from `url`.
+    pub fn find(
+        &self,
+        method: &Method,
+        url: &str,
+    ) -> JsResult<Option<(JsObject, Vec<(String, String)>)>> {
+        for route in self.routes.iter() {
+            if route.method.as_ref().is_some_and(|m| m != method) {
+                continue;
+            }
+
+            let (input, _) =
+                UrlPattern::process_input(UrlPatternInput::from(url.to_string()), None)?;
+            let result = route
+                .pattern
+                .exec(input)
+                .map_err(|e| JsNativeError::typ().with_message(e.to_string()))?;
+
+            if let Some(result) = result {
+                let params = result.pathname.groups.into_iter().collect();
+                return Ok(Some((route.handler.clone(), params)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn try_from_js<'a>(value: &'a JsValue) -> JsResult<GcRefMut<'a, Object, Self>> {
+        value
+            .as_object()
+            .and_then(|obj| obj.downcast_mut::<Self>())
+            .ok_or_else(|| {
+                JsNativeError::typ()
+                    .with_message("Failed to convert js value into rust type `Router`")
+                    .into()
+            })
+    }
+}
+
+pub struct RouterClass;
+
+impl RouterClass {
+    fn pattern(value: &JsValue, context: &mut Context<'_>) -> JsResult<InnerUrlPattern> {
+        // Plain strings are pathname patterns, e.g. `/users/:id`
+        let input = if value.is_string() {
+            let pathname: String = value.try_js_into(context)?;
+            UrlPatternInput::from(quirks::UrlPatternInit {
+                pathname: Some(pathname),
+                ..Default::default()
+            })
+        } else {
+            let UrlPatternInit(init) = value.try_js_into(context)?;
+            UrlPatternInput::from(init)
+        };
+
+        UrlPattern::parse(input, None)
+    }
+
+    fn handler(value: &JsValue) -> JsResult<JsObject> {
+        value.as_callable().cloned().ok_or_else(|| {
+            JsNativeError::typ()
+                .with_message("Route handler must be a function")
+                .into()
+        })
+    }
+
+    fn add_route(
+        this: &JsValue,
+        method: Option<Method>,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let pattern = Self::pattern(args.get_or_undefined(0), context)?;
+        let handler = Self::handler(args.get_or_undefined(1))?;
+
+        Router::try_from_js(this)?.route(method, pattern, handler);
+
+        Ok(this.clone())
+    }
+
+    fn on(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let method: String = args.get_or_undefined(0).try_js_into(context)?;
+        let method =
+            Method::from_bytes(method.to_uppercase().as_bytes()).map_err(|_| {
+                JsError::from_native(
+                    JsNativeError::typ()
+                        .with_message(format!("Invalid method `{method}`")),
+                )
+            })?;
+
+        Self::add_route(
+            this,
+            Some(method),
+            args.get(1..).unwrap_or_default(),
+            context,
+        )
+    }
+
+    fn all(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        Self::add_route(this, None, args, context)
+    }
+
+    fn get(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        Self::add_route(this, Some(Method::GET), args, context)
+    }
+
+    fn post(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        Self::add_route(this, Some(Method::POST), args, context)
+    }
+
+    fn put(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        Self::add_route(this, Some(Method::PUT), args, context)
+    }
+
+    fn patch(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        Self::add_route(this, Some(Method::PATCH), args, context)
+    }
+
+    fn delete(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        Self::add_route(this, Some(Method::DELETE), args, context)
+    }
+
+    fn handle(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let request = args.get_or_undefined(0);
+        let (method, url) = {
+            let request: JsNativeObject<Request> = request.clone().try_into()?;
+            let request = request.deref();
+            (request.method().clone(), request.url().to_string())
+        };
+
+        // The router must not be borrowed while the handler runs, since the
+        // handler may itself register routes
+        let route = Router::try_from_js(this)?.find(&method, &url)?;
+
+        match route {
+            Some((handler, params)) => {
+                let params_obj = JsObject::with_object_proto(context.intrinsics());
+                for (key, value) in params {
+                    params_obj.create_data_property_or_throw(
+                        js_string!(key),
+                        js_string!(value),
+                        context,
+                    )?;
+                }
+
+                handler.call(
+                    &JsValue::undefined(),
+                    &[request.clone(), params_obj.into()],
+                    context,
+                )
+            }
+            None => Ok(JsNativeObject::new::<ResponseClass>(
+                ResponseBuilder::empty(404, context)?,
+                context,
+            )?
+            .inner()
+            .clone()),
+        }
+    }
+}
+
+impl NativeClass for RouterClass {
+    type Instance = Router;
+
+    const NAME: &'static str = "Router";
+
+    fn constructor(
+        _this: &JsNativeObject<Router>,
+        _args: &[JsValue],
+        _context: &mut Context<'_>,
+    ) -> JsResult<Router> {
+        Ok(Router::default())
+    }
+
+    fn init(class: &mut ClassBuilder<'_, '_>) -> JsResult<()> {
+        class
+            .method(js_string!("on"), 3, NativeFunction::from_fn_ptr(Self::on))
+            .method(js_string!("all"), 2, NativeFunction::from_fn_ptr(Self::all))
+            .method(js_string!("get"), 2, NativeFunction::from_fn_ptr(Self::get))
+            .method(
+                js_string!("post"),
+                2,
+                NativeFunction::from_fn_ptr(Self::post),
+            )
+            .method(js_string!("put"), 2, NativeFunction::from_fn_ptr(Self::put))
+            .method(
+                js_string!("patch"),
+                2,
+                NativeFunction::from_fn_ptr(Self::patch),
+            )
+            .method(
+                js_string!("delete"),
+                2,
+                NativeFunction::from_fn_ptr(Self::delete),
+            )
+            .method(
+                js_string!("handle"),
+                1,
+                NativeFunction::from_fn_ptr(Self::handle),
+            );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::Api;
+
+    use super::*;
+    use crate::{http::HttpApi, urlpattern::UrlPatternApi};
+
+    fn eval(code: &str) -> String {
+        let context = &mut Context::default();
+        HttpApi.init(context);
+        UrlPatternApi.init(context);
+
+        context
+            .eval(Source::from_bytes(code))
+            .expect("Could not evaluate code")
+            .to_string(context)
+            .unwrap()
+            .to_std_string_escaped()
+    }
+
+    const ROUTER: &str = r#"
+        const router = new Router()
+            .get("/users/:id", (_, params) => `get user ${params.id}`)
+            .post("/users/:id", (_, params) => `post user ${params.id}`)
+            .on("put", { pathname: "/users/:id" }, () => "put user")
+            .all("/files/*", (_, params) => `file ${params[0]}`);
+        const request = (method, path) =>
+            new Request(`http://example.com${path}`, { method });
+    "#;
+
+    #[test]
+    fn dispatches_on_method_and_pattern() {
+        let result = eval(&format!(
+            r#"{ROUTER}
+            [
+                router.handle(request("GET", "/users/1")),
+                router.handle(request("POST", "/users/2")),
+                router.handle(request("PUT", "/users/3")),
+            ].join(",")
+            "#
+        ));
+
+        assert_eq!(result, "get user 1,post user 2,put user");
+    }
+
+    #[test]
+    fn extracts_path_params() {
+        let result = eval(&format!(
+            r#"{ROUTER}
+            [
+                router.handle(request("GET", "/users/alice?verbose=true")),
+                router.handle(request("GET", "/files/a/b.txt")),
+                router.handle(request("DELETE", "/files/c")),
+            ].join(",")
+            "#
+        ));
+
+        assert_eq!(result, "get user alice,file a/b.txt,file c");
+    }
+
+    #[test]
+    fn falls_back_to_not_found() {
+        let result = eval(&format!(
+            r#"{ROUTER}
+            [
+                router.handle(request("GET", "/unknown")).status,
+                router.handle(request("DELETE", "/users/1")).status,
+                router.handle(request("GET", "/users/1/posts")).status,
+            ].join(",")
+            "#
+        ));
+
+        assert_eq!(result, "404,404,404");
+    }
+}
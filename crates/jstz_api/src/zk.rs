@@ -0,0 +1,166 @@
+//! `jstz`'s zero-knowledge proof verification API.
+//!
+//! Exposes the global `Zk` object, allowing contracts to verify Groth16 proofs
+//! over the BLS12-381 curve. Proofs and verification keys are expected in
+//! arkworks' compressed canonical serialization, and public inputs as 32-byte
+//! little-endian scalar field elements.
+
+use ark_bls12_381::{Bls12_381, Fr};
+use ark_groth16::{Groth16, Proof, VerifyingKey};
+use ark_serialize::CanonicalDeserialize;
+use ark_snark::SNARK;
+use boa_engine::{
+    js_string,
+    object::{builtins::JsArray, ObjectInitializer},
+    property::Attribute,
+    Context, JsArgs, JsNativeError, JsResult, JsValue, NativeFunction,
+};
+
+use crate::idl::buffer_source_to_vec;
+
+fn deserialize<T: CanonicalDeserialize>(bytes: &[u8], name: &str) -> JsResult<T> {
+    T::deserialize_compressed(bytes).map_err(|_| {
+        JsNativeError::typ()
+            .with_message(format!("Invalid {name}"))
+            .into()
+    })
+}
+
+/// Verifies a Groth16 `proof` for the given `public_inputs` against the
+/// verification key `vk`.
+pub fn verify_groth16(
+    proof: &[u8],
+    public_inputs: &[Vec<u8>],
+    vk: &[u8],
+) -> JsResult<bool> {
+    let proof: Proof<Bls12_381> = deserialize(proof, "proof")?;
+    let vk: VerifyingKey<Bls12_381> = deserialize(vk, "verification key")?;
+    let public_inputs = public_inputs
+        .iter()
+        .map(|input| deserialize::<Fr>(input, "public input"))
+        .collect::<JsResult<Vec<_>>>()?;
+
+    if public_inputs.len() + 1 != vk.gamma_abc_g1.len() {
+        return Ok(false);
+    }
+
+    Ok(Groth16::<Bls12_381>::verify(&vk, &public_inputs, &proof).unwrap_or(false))
+}
+
+pub struct ZkApi;
+
+impl ZkApi {
+    const NAME: &'static str = "Zk";
+
+    fn verify(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let proof = buffer_source_to_vec(args.get_or_undefined(0), context)?;
+
+        let public_inputs = args
+            .get_or_undefined(1)
+            .as_object()
+            .and_then(|obj| JsArray::from_object(obj.clone()).ok())
+            .ok_or_else(|| {
+                JsNativeError::typ().with_message("Expected an array of public inputs")
+            })?;
+        let public_inputs = (0..public_inputs.length(context)?)
+            .map(|i| buffer_source_to_vec(&public_inputs.get(i, context)?, context))
+            .collect::<JsResult<Vec<_>>>()?;
+
+        let vk = buffer_source_to_vec(args.get_or_undefined(2), context)?;
+
+        Ok(verify_groth16(&proof, &public_inputs, &vk)?.into())
+    }
+}
+
+impl jstz_core::Api for ZkApi {
+    fn init(self, context: &mut Context<'_>) {
+        let zk = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::verify),
+                js_string!("verify"),
+                3,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), zk, Attribute::all())
+            .expect("The zk object shouldn't exist yet");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ark_relations::{
+        lc,
+        r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+    };
+    use ark_serialize::CanonicalSerialize;
+    use ark_snark::CircuitSpecificSetupSNARK;
+
+    use super::*;
+
+    /// Proves knowledge of `a` and `b` such that `a * b = c`, for a public `c`.
+    #[derive(Clone, Copy)]
+    struct MulCircuit {
+        a: Fr,
+        b: Fr,
+    }
+
+    impl ConstraintSynthesizer<Fr> for MulCircuit {
+        fn generate_constraints(
+            self,
+            cs: ConstraintSystemRef<Fr>,
+        ) -> Result<(), SynthesisError> {
+            let a = cs.new_witness_variable(|| Ok(self.a))?;
+            let b = cs.new_witness_variable(|| Ok(self.b))?;
+            let c = cs.new_input_variable(|| Ok(self.a * self.b))?;
+
+            cs.enforce_constraint(lc!() + a, lc!() + b, lc!() + c)
+        }
+    }
+
+    fn serialize(value: impl CanonicalSerialize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        value.serialize_compressed(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn setup() -> (Vec<u8>, Vec<u8>) {
+        let rng = &mut ark_std::test_rng();
+        let circuit = MulCircuit {
+            a: Fr::from(3u64),
+            b: Fr::from(5u64),
+        };
+
+        let (pk, vk) = Groth16::<Bls12_381>::setup(circuit, rng).unwrap();
+        let proof = Groth16::<Bls12_381>::prove(&pk, circuit, rng).unwrap();
+
+        (serialize(proof), serialize(vk))
+    }
+
+    #[test]
+    fn verify_valid_proof() {
+        let (proof, vk) = setup();
+
+        assert!(verify_groth16(&proof, &[serialize(Fr::from(15u64))], &vk).unwrap());
+    }
+
+    #[test]
+    fn verify_invalid_proof() {
+        let (proof, vk) = setup();
+
+        assert!(!verify_groth16(&proof, &[serialize(Fr::from(16u64))], &vk).unwrap());
+        assert!(!verify_groth16(&proof, &[], &vk).unwrap());
+    }
+
+    #[test]
+    fn verify_malformed_proof() {
+        let (_, vk) = setup();
+
+        assert!(verify_groth16(&[0; 8], &[serialize(Fr::from(15u64))], &vk).is_err());
+    }
+}
@@ -130,13 +130,44 @@ pub fn whoami(cfg: &Config) -> Result<()> {
     Ok(())
 }
 
+fn use_account(alias: String, cfg: &mut Config) -> Result<()> {
+    cfg.accounts().set_current(&alias)?;
+    cfg.save()?;
+
+    let address = cfg.accounts.get(&alias)?.address();
+    println!("Using account {} with address {}", alias, address);
+
+    Ok(())
+}
+
+fn show_account(alias: String, cfg: &Config) -> Result<()> {
+    let account = cfg.accounts.get(&alias)?;
+
+    println!("Alias: {}", account.alias());
+    match account {
+        Account::Owned(OwnedAccount { public_key, .. }) => {
+            println!("Type: Owned");
+            println!("Public Key: {}", public_key.to_string());
+        }
+        Account::Alias(_) => println!("Type: Alias"),
+    }
+    println!("Address: {}", account.address());
+    println!("Current: {}", cfg.accounts.is_current(&alias));
+
+    Ok(())
+}
+
 fn list_accounts(long: bool, cfg: &mut Config) -> Result<()> {
+    let current_alias = cfg.accounts.current_alias.clone();
     let accounts = cfg.accounts().list_all();
 
     println!("Accounts:");
     for (alias, account) in accounts {
+        let is_current = current_alias.as_ref() == Some(alias);
+
         if long {
             println!("Alias: {}", alias);
+            println!("  Current: {}", is_current);
             match account {
                 Account::Owned(OwnedAccount {
                     alias: _,
@@ -155,7 +186,8 @@ fn list_accounts(long: bool, cfg: &mut Config) -> Result<()> {
                 }
             }
         } else {
-            println!("{}: {}", account.alias(), account.address());
+            let marker = if is_current { "*" } else { " " };
+            println!("{} {}: {}", marker, account.alias(), account.address());
         }
     }
 
@@ -213,7 +245,19 @@ pub enum Command {
         #[arg(value_name = "ALIAS")]
         alias: String,
     },
-    /// Lists all accounts
+    /// Shows an account
+    Show {
+        /// User alias
+        #[arg(value_name = "ALIAS")]
+        alias: String,
+    },
+    /// Sets the account used by default as the source of operations
+    Use {
+        /// User alias
+        #[arg(value_name = "ALIAS")]
+        alias: String,
+    },
+    /// Lists all accounts. The current account is marked with `*`.
     #[clap(alias = "ls")]
     List {
         /// Option for long format output
@@ -239,6 +283,8 @@ pub async fn exec(command: Command, cfg: &mut Config) -> Result<()> {
         Command::Alias { address, name } => alias(address, name, cfg),
         Command::Create { alias, passphrase } => create_account(passphrase, alias, cfg),
         Command::Delete { alias } => delete_account(alias, cfg),
+        Command::Show { alias } => show_account(alias, cfg),
+        Command::Use { alias } => use_account(alias, cfg),
         Command::List { long } => list_accounts(long, cfg),
         Command::Code { account } => get_code(account, cfg).await,
         Command::Balance { account } => get_balance(account, cfg).await,
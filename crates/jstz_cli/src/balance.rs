@@ -0,0 +1,49 @@
+use anyhow::Result;
+use serde_json::json;
+
+use crate::{config::Config, jstz::JstzClient};
+
+fn format_balance(address: &str, balance: u64, json: bool) -> String {
+    if json {
+        json!({ "address": address, "balance": balance }).to_string()
+    } else {
+        format!("{} ctez", balance)
+    }
+}
+
+pub async fn exec(
+    account: Option<String>,
+    of: Option<String>,
+    json: bool,
+    cfg: &mut Config,
+) -> Result<()> {
+    let jstz_client = JstzClient::new(cfg);
+
+    let address = match of.or(account) {
+        Some(address_or_alias) => cfg.accounts.get_address(&address_or_alias)?,
+        None => cfg.accounts.account_or_current(None)?.address().clone(),
+    }
+    .to_base58();
+
+    let balance = jstz_client.get_balance(&address).await?;
+
+    println!("{}", format_balance(&address, balance, json));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn json_output_is_parseable() {
+        let address = "tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty";
+
+        let output: serde_json::Value =
+            serde_json::from_str(&format_balance(address, 42, true)).unwrap();
+
+        assert_eq!(output, json!({ "address": address, "balance": 42 }));
+        assert_eq!(format_balance(address, 42, false), "42 ctez");
+    }
+}
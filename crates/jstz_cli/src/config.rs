@@ -2,7 +2,7 @@ use std::{
     collections::HashMap,
     fs,
     io::{Error, ErrorKind},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
@@ -89,6 +89,18 @@ impl AccountConfig {
         self.get_mut(&alias)
     }
 
+    pub fn is_current(&self, alias: &str) -> bool {
+        self.current_alias.as_deref() == Some(alias)
+    }
+
+    /// Makes `alias` the account used by default as the source of operations
+    pub fn set_current(&mut self, alias: &str) -> Result<()> {
+        self.get(alias)?.as_owned()?;
+        self.current_alias = Some(alias.to_string());
+
+        Ok(())
+    }
+
     pub fn remove(&mut self, alias: &String) -> Option<Account> {
         if self.current_alias == Some(alias.clone()) {
             self.current_alias = None;
@@ -96,8 +108,11 @@ impl AccountConfig {
         self.accounts.remove(alias)
     }
 
+    /// Returns all accounts, ordered by alias
     pub fn list_all(&self) -> Vec<(&String, &Account)> {
-        self.accounts.iter().collect()
+        let mut accounts: Vec<_> = self.accounts.iter().collect();
+        accounts.sort_by(|(alias1, _), (alias2, _)| alias1.cmp(alias2));
+        accounts
     }
 
     pub fn get_address(&self, address_or_alias: &str) -> Result<Address> {
@@ -182,12 +197,19 @@ impl Config {
         home().join("config.json")
     }
 
+    /// Path to the REPL history file
+    pub fn repl_history_path() -> PathBuf {
+        home().join("repl_history")
+    }
+
     /// Load the configuration from the file
     pub fn load() -> std::io::Result<Self> {
-        let path = Self::path();
+        Self::load_from(&Self::path())
+    }
 
+    fn load_from(path: &Path) -> std::io::Result<Self> {
         let config = if path.exists() {
-            let json = fs::read_to_string(&path)?;
+            let json = fs::read_to_string(path)?;
             serde_json::from_str(&json)
                 .map_err(|e| Error::new(ErrorKind::InvalidData, e))?
         } else {
@@ -199,13 +221,21 @@ impl Config {
 
     /// Save the configuration to the file
     pub fn save(&self) -> std::io::Result<()> {
-        let path = Self::path();
+        self.save_to(&Self::path())
+    }
+
+    /// Writes the configuration to a temporary file which then replaces the
+    /// file at `path`, so that an interrupted save leaves the previous
+    /// configuration intact
+    fn save_to(&self, path: &Path) -> std::io::Result<()> {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
         }
 
         let json = serde_json::to_string_pretty(self)?;
-        fs::write(&path, json)?;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)?;
         Ok(())
     }
 
@@ -219,3 +249,60 @@ impl Config {
         &mut self.accounts
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::account::account::OwnedAccount;
+
+    fn owned(alias: &str) -> OwnedAccount {
+        OwnedAccount::new(format!("{alias} passphrase"), alias.to_string()).unwrap()
+    }
+
+    #[test]
+    fn accounts_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+
+        let mut cfg = Config::default();
+        let alice = owned("alice");
+        cfg.accounts().upsert(owned("bob"));
+        cfg.accounts().upsert(alice.clone());
+        cfg.accounts().set_current("alice").unwrap();
+        cfg.save_to(&path).unwrap();
+
+        let mut cfg = Config::load_from(&path).unwrap();
+        let aliases: Vec<_> = cfg
+            .accounts
+            .list_all()
+            .into_iter()
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        assert_eq!(aliases, ["alice", "bob"]);
+        assert_eq!(cfg.accounts.get("alice").unwrap().address(), &alice.address);
+        assert!(cfg.accounts.is_current("alice"));
+
+        cfg.accounts().set_current("bob").unwrap();
+        cfg.save_to(&path).unwrap();
+
+        let cfg = Config::load_from(&path).unwrap();
+        assert!(cfg.accounts.is_current("bob"));
+        assert!(!cfg.accounts.is_current("alice"));
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn only_owned_accounts_can_be_current() {
+        let mut accounts = AccountConfig::default();
+        accounts
+            .add_alias(
+                "contract".to_string(),
+                "tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty".to_string(),
+            )
+            .unwrap();
+
+        assert!(accounts.set_current("missing").is_err());
+        assert!(accounts.set_current("contract").is_err());
+        assert_eq!(accounts.current_alias, None);
+    }
+}
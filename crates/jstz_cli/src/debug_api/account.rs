@@ -64,6 +64,23 @@ impl AccountApi {
         Ok(JsValue::undefined())
     }
 
+    fn mint(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context,
+    ) -> JsResult<JsValue> {
+        preamble!(args, context, tx);
+
+        let account: String = args.get_or_undefined(0).try_js_into(context)?;
+
+        let amount: u64 = args.get_or_undefined(1).try_js_into(context)?;
+
+        let pkh = get_public_key_hash(account.as_str())?;
+
+        runtime::with_global_host(|rt| Account::mint(rt.deref(), &mut tx, &pkh, amount))?;
+        Ok(JsValue::undefined())
+    }
+
     fn code(
         _this: &JsValue,
         args: &[JsValue],
@@ -116,6 +133,11 @@ impl AccountApi {
                 js_string!("setBalance"),
                 2,
             )
+            .function(
+                NativeFunction::from_fn_ptr(Self::mint),
+                js_string!("mint"),
+                2,
+            )
             .function(
                 NativeFunction::from_fn_ptr(Self::code),
                 js_string!("code"),
@@ -51,7 +51,7 @@ impl KvApi {
 
         let kv = Kv::new(account);
 
-        kv.set(&mut tx, &key, value)?;
+        runtime::with_global_host(|hrt| kv.set(hrt.deref(), &mut tx, &key, value))?;
 
         Ok(JsValue::undefined())
     }
@@ -1,8 +1,16 @@
-use boa_engine::{js_string, object::ObjectInitializer, property::Attribute};
+use boa_engine::{
+    js_string, object::ObjectInitializer, property::Attribute, NativeFunction,
+};
+use jstz_core::host_defined;
 
 mod account;
 mod kv;
+mod transaction;
 
+/// Functions for inspecting and modifying the state of the REPL, such as
+/// setting the balance of an account. These bypass the rules of the protocol,
+/// so they are only registered by the REPL and never by the executor of
+/// `jstz_proto`.
 pub struct DebugApi;
 
 impl DebugApi {
@@ -11,12 +19,32 @@ impl DebugApi {
 
 impl jstz_core::Api for DebugApi {
     fn init(self, context: &mut boa_engine::Context<'_>) {
+        {
+            host_defined!(context, mut host_defined);
+            host_defined.insert(transaction::CommittedKeys::default());
+        }
+
         let kv_api = kv::KvApi::namespace(context);
         let account_api = account::AccountApi::namespace(context);
 
         let storage = ObjectInitializer::new(context)
             .property(js_string!("Kv"), kv_api, Attribute::all())
             .property(js_string!("Account"), account_api, Attribute::all())
+            .function(
+                NativeFunction::from_fn_ptr(transaction::dump_kv),
+                js_string!("dumpKv"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(transaction::commit),
+                js_string!("commit"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(transaction::rollback),
+                js_string!("rollback"),
+                0,
+            )
             .build();
 
         context
@@ -24,3 +52,164 @@ impl jstz_core::Api for DebugApi {
             .expect("The storage object shouldn't exist yet");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+    use jstz_core::{
+        host_defined,
+        kv::Kv,
+        runtime::{self, Runtime},
+    };
+    use jstz_crypto::public_key_hash::PublicKeyHash;
+    use jstz_proto::api::LedgerApi;
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::DebugApi;
+
+    const SELF_ADDRESS: &str = "tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty";
+
+    fn init_runtime() -> Runtime<'static> {
+        let mut rt = Runtime::new().unwrap();
+
+        {
+            let context = rt.context();
+            host_defined!(context, mut host_defined);
+
+            let kv = Kv::new();
+            let tx = kv.begin_transaction();
+
+            host_defined.insert(kv);
+            host_defined.insert(tx);
+        }
+
+        let realm = rt.realm().clone();
+        realm.register_api(
+            LedgerApi {
+                contract_address: PublicKeyHash::from_base58(SELF_ADDRESS).unwrap(),
+            },
+            rt.context(),
+        );
+        realm.register_api(DebugApi, rt.context());
+
+        rt
+    }
+
+    fn eval(rt: &mut Runtime, hrt: &mut MockHost, code: &str) -> String {
+        runtime::with_host_runtime(hrt, || rt.eval(Source::from_bytes(code)))
+            .unwrap()
+            .display()
+            .to_string()
+    }
+
+    #[test]
+    fn dump_kv_reflects_pending_writes() {
+        let mut rt = init_runtime();
+        let mut hrt = MockHost::default();
+
+        let keys = eval(
+            &mut rt,
+            &mut hrt,
+            r#"
+            jstz.Kv.set("tz1", "b", 1);
+            jstz.Kv.set("tz1", "a", 2);
+            jstz.Kv.set("tz1", "c", 3);
+            jstz.Kv.delete("tz1", "c");
+            jstz.dumpKv().join(",")
+            "#,
+        );
+
+        assert_eq!(keys, r#""/jstz_kv/tz1/a,/jstz_kv/tz1/b""#);
+    }
+
+    #[test]
+    fn commit_persists_writes() {
+        let mut rt = init_runtime();
+        let mut hrt = MockHost::default();
+
+        let committed = eval(
+            &mut rt,
+            &mut hrt,
+            r#"jstz.Kv.set("tz1", "a", 1); jstz.commit()"#,
+        );
+        assert_eq!(committed, "true");
+
+        // The new transaction reads the committed value, and a pending
+        // deletion hides it from the dump
+        let result = eval(
+            &mut rt,
+            &mut hrt,
+            r#"
+            const before = jstz.dumpKv().join(",");
+            const value = jstz.Kv.get("tz1", "a");
+            jstz.Kv.delete("tz1", "a");
+            [before, value, jstz.dumpKv().length].join(";")
+            "#,
+        );
+        assert_eq!(result, r#""/jstz_kv/tz1/a;1;0""#);
+    }
+
+    #[test]
+    fn rollback_discards_writes() {
+        let mut rt = init_runtime();
+        let mut hrt = MockHost::default();
+
+        let result = eval(
+            &mut rt,
+            &mut hrt,
+            r#"
+            jstz.Kv.set("tz1", "a", 1);
+            jstz.commit();
+            jstz.Kv.set("tz1", "a", 2);
+            jstz.Kv.set("tz1", "b", 3);
+            jstz.rollback();
+            [jstz.Kv.get("tz1", "a"), jstz.Kv.has("tz1", "b"), jstz.dumpKv()].join(";")
+            "#,
+        );
+
+        assert_eq!(result, r#""1;false;/jstz_kv/tz1/a""#);
+    }
+
+    #[test]
+    fn set_balance_funds_a_transfer() {
+        let mut rt = init_runtime();
+        let mut hrt = MockHost::default();
+
+        let result = eval(
+            &mut rt,
+            &mut hrt,
+            r#"
+            const dst = "tz1ZXxxkNYSUutm5VZvfudakRxx2mjpWko4J";
+            jstz.Account.setBalance(Ledger.selfAddress, 100);
+            Ledger.transfer(dst, 30);
+            [Ledger.balance(Ledger.selfAddress), Ledger.balance(dst)].join(",")
+            "#,
+        );
+
+        assert_eq!(result, r#""70,30""#);
+    }
+
+    #[test]
+    fn mint_funds_a_transfer_and_increases_total_supply() {
+        let mut rt = init_runtime();
+        let mut hrt = MockHost::default();
+
+        let result = eval(
+            &mut rt,
+            &mut hrt,
+            r#"
+            const dst = "tz1ZXxxkNYSUutm5VZvfudakRxx2mjpWko4J";
+            jstz.Account.mint(Ledger.selfAddress, 50);
+            jstz.Account.mint(Ledger.selfAddress, 25);
+            Ledger.transfer(dst, 75);
+            [
+                Ledger.balance(Ledger.selfAddress),
+                Ledger.balance(dst),
+                Ledger.totalSupply(),
+            ].join(",")
+            "#,
+        );
+
+        assert_eq!(result, r#""0,75,75""#);
+    }
+}
@@ -0,0 +1,111 @@
+use std::{collections::BTreeSet, ops::Deref};
+
+use boa_engine::{object::builtins::JsArray, Context, JsResult, JsValue};
+use boa_gc::{empty_trace, Finalize, Trace};
+use jstz_core::{
+    host_defined,
+    kv::{Kv, Transaction},
+    runtime,
+};
+use tezos_smart_rollup::storage::path::{OwnedPath, Path};
+
+/// The keys written by the transactions committed with `jstz.commit()`. The
+/// persistent store cannot be enumerated, so they are recorded for
+/// `jstz.dumpKv()`.
+#[derive(Default, Finalize)]
+pub struct CommittedKeys(BTreeSet<OwnedPath>);
+
+unsafe impl Trace for CommittedKeys {
+    empty_trace!();
+}
+
+/// `jstz.dumpKv()`, returning the sorted keys visible in the current
+/// transaction, including its uncommitted writes
+pub fn dump_kv(
+    _this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    let keys = {
+        host_defined!(context, host_defined);
+        let tx = host_defined
+            .get::<Transaction>()
+            .expect("Curent transaction undefined");
+        let committed_keys = host_defined
+            .get::<CommittedKeys>()
+            .expect("Committed keys undefined");
+
+        let candidates: BTreeSet<OwnedPath> =
+            committed_keys.0.iter().chain(tx.keys()).cloned().collect();
+
+        // Reading through the transaction hides the keys it has removed
+        runtime::with_global_host(|hrt| -> JsResult<Vec<JsValue>> {
+            let mut keys = Vec::new();
+            for key in candidates {
+                if tx.contains_key(hrt.deref(), &key)? {
+                    let key = String::from_utf8_lossy(key.as_bytes()).into_owned();
+                    keys.push(JsValue::String(key.into()));
+                }
+            }
+            Ok(keys)
+        })?
+    };
+
+    Ok(JsArray::from_iter(keys, context).into())
+}
+
+/// `jstz.commit()`, committing the current transaction and beginning a new
+/// one. Returns `true` if the transaction was committed.
+pub fn commit(
+    _this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    host_defined!(context, mut host_defined);
+    let tx = host_defined
+        .remove::<Transaction>()
+        .expect("Curent transaction undefined");
+    let keys: Vec<OwnedPath> = tx.keys().cloned().collect();
+
+    let (result, next_tx) = {
+        let mut kv = host_defined.get_mut::<Kv>().expect("Kv undefined");
+        let result = runtime::with_global_host(|hrt| kv.commit_transaction(hrt, *tx));
+
+        (result, kv.begin_transaction())
+    };
+    host_defined.insert(next_tx);
+
+    let committed = result?;
+    if committed {
+        host_defined
+            .get_mut::<CommittedKeys>()
+            .expect("Committed keys undefined")
+            .0
+            .extend(keys);
+    }
+
+    Ok(committed.into())
+}
+
+/// `jstz.rollback()`, discarding the writes of the current transaction and
+/// beginning a new one
+pub fn rollback(
+    _this: &JsValue,
+    _args: &[JsValue],
+    context: &mut Context,
+) -> JsResult<JsValue> {
+    host_defined!(context, mut host_defined);
+    let tx = host_defined
+        .remove::<Transaction>()
+        .expect("Curent transaction undefined");
+
+    let next_tx = {
+        let mut kv = host_defined.get_mut::<Kv>().expect("Kv undefined");
+        runtime::with_global_host(|hrt| kv.rollback_transaction(hrt, *tx));
+
+        kv.begin_transaction()
+    };
+    host_defined.insert(next_tx);
+
+    Ok(JsValue::undefined())
+}
@@ -1,5 +1,8 @@
+use std::path::Path;
+
 use anyhow::{anyhow, Result};
 use jstz_proto::{
+    executor::contract::deploy::validate,
     operation::{Content, DeployContract, Operation, SignedOperation},
     receipt::Content as ReceiptContent,
 };
@@ -12,6 +15,40 @@ use crate::{
     utils::{from_file_or_id, piped_input},
 };
 
+/// Extensions of files holding function code. An argument with one of these
+/// extensions is read as a file, even if it doesn't exist.
+const CODE_EXTENSIONS: [&str; 3] = ["js", "mjs", "cjs"];
+
+/// Reads the function code from `function_code`, a file path or the code
+/// itself, or from stdin, and checks it is a deployable module
+fn read_code(function_code: Option<String>) -> Result<String> {
+    let contract_code = match function_code {
+        Some(function_code) => {
+            let path = Path::new(&function_code);
+            let is_code_file = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| CODE_EXTENSIONS.contains(&extension));
+
+            if is_code_file && !path.exists() {
+                return Err(anyhow!("File not found: {function_code}"));
+            }
+
+            from_file_or_id(function_code)
+        }
+        None => piped_input().ok_or(anyhow!("No function code supplied"))?,
+    };
+
+    if contract_code.trim().is_empty() {
+        return Err(anyhow!("The function code is empty"));
+    }
+
+    validate(&contract_code)
+        .map_err(|error| anyhow!("The function code cannot be deployed: {error}"))?;
+
+    Ok(contract_code)
+}
+
 pub async fn exec(
     self_address: Option<String>,
     contract_code: Option<String>,
@@ -42,10 +79,7 @@ pub async fn exec(
         ..
     } = account.as_owned()?.clone();
 
-    let contract_code = contract_code
-        .map(from_file_or_id)
-        .or_else(piped_input)
-        .ok_or(anyhow!("No function code supplied"))?;
+    let contract_code = read_code(contract_code)?;
 
     // Create operation TODO nonce
     let op = Operation {
@@ -77,17 +111,17 @@ pub async fn exec(
 
     println!("Receipt: {:?}", receipt);
 
+    let address = match receipt.inner {
+        Ok(ReceiptContent::DeployContract(deploy)) => deploy.contract_address.to_base58(),
+        Ok(_) => return Err(anyhow!("Content is not of type 'DeployContract'")),
+        Err(error) => return Err(anyhow!("Failed to deploy smart function: {error}")),
+    };
+
+    println!("Smart function deployed at address: {address}");
+
     // Create alias
     if let Some(name) = name {
-        cfg.accounts.add_alias(
-            name,
-            match receipt.inner {
-                Ok(ReceiptContent::DeployContract(deploy)) => {
-                    (&deploy.contract_address).to_string()
-                }
-                _ => return Err(anyhow!("Content is not of type 'DeployContract'")),
-            },
-        )?;
+        cfg.accounts.add_alias(name, address)?;
     }
 
     cfg.save()?;
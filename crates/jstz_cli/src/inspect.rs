@@ -0,0 +1,71 @@
+use anyhow::{anyhow, Result};
+use jstz_proto::abi::{Abi, Function, ABI_KEY};
+
+use crate::{config::Config, jstz::JstzClient};
+
+fn signature(function: &Function) -> String {
+    let params: Vec<String> = function
+        .params
+        .iter()
+        .map(|param| match &param.ty {
+            Some(ty) => format!("{}: {}", param.name, ty),
+            None => param.name.clone(),
+        })
+        .collect();
+
+    let returns = match function.returns.as_ref().and_then(|r| r.ty.as_ref()) {
+        Some(ty) => format!(" -> {}", ty),
+        None => String::new(),
+    };
+
+    format!("{}({}){}", function.name, params.join(", "), returns)
+}
+
+fn print_abi(abi: &Abi) {
+    for function in &abi.functions {
+        println!("{}", signature(function));
+
+        if let Some(description) = &function.description {
+            println!("    {}", description);
+        }
+        for param in &function.params {
+            if let Some(description) = &param.description {
+                println!("    @param {} - {}", param.name, description);
+            }
+        }
+        if let Some(description) = function
+            .returns
+            .as_ref()
+            .and_then(|r| r.description.as_ref())
+        {
+            println!("    @returns {}", description);
+        }
+        println!();
+    }
+}
+
+pub async fn exec(address: Option<String>, abi: bool, cfg: &mut Config) -> Result<()> {
+    let jstz_client = JstzClient::new(cfg);
+
+    let address = cfg.accounts.get_address_from(address)?;
+
+    if abi {
+        let value = jstz_client.get_value(&address, ABI_KEY).await?;
+
+        match value {
+            Some(value) => {
+                let abi: Abi = serde_json::from_value(value.0)
+                    .map_err(|_| anyhow!("Malformed ABI for {}", address))?;
+                print_abi(&abi);
+            }
+            None => println!("No ABI found"),
+        }
+    } else {
+        match jstz_client.get_code(&address).await? {
+            Some(code) => println!("{}", code),
+            None => println!("No code found"),
+        }
+    }
+
+    Ok(())
+}
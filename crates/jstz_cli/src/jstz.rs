@@ -84,6 +84,8 @@ impl JstzClient {
                 let balance = response.json::<u64>().await?;
                 Ok(balance)
             }
+            // Accounts that were never credited are not stored
+            StatusCode::NOT_FOUND => Ok(0),
             _ => Err(anyhow!("Failed to get the balance")),
         }
     }
@@ -3,20 +3,24 @@ use clap::Parser;
 use tokio;
 
 mod account;
+mod balance;
 mod bridge;
 mod config;
 mod debug_api;
 mod deploy;
+mod inspect;
 mod jstz;
 mod kv;
 mod logs;
 mod octez;
 mod repl;
+mod response;
 mod run;
 mod sandbox;
 mod utils;
 
 use config::Config;
+use jstz_api::LogLevel;
 
 #[derive(Parser)]
 #[command(author, version)]
@@ -38,7 +42,8 @@ enum Command {
         /// Initial balance
         #[arg(short, long, default_value_t = 0)]
         balance: u64,
-        /// Function code.
+        /// Function code, or the path of a file holding it. Read from stdin
+        /// if omitted.
         #[arg(value_name = "function_code", default_value = None)]
         function_code: Option<String>,
         /// Name
@@ -59,12 +64,30 @@ enum Command {
         /// The JSON data in the request body.
         #[arg(name = "data", short, long, default_value = None)]
         json_data: Option<String>,
+        /// A request header, as `KEY:VALUE`. May be repeated.
+        #[arg(name = "header", short = 'H', long, value_name = "KEY:VALUE")]
+        headers: Vec<String>,
+        /// The amount transferred to the function.
+        #[arg(short, long, default_value_t = 0)]
+        amount: u64,
+        /// The maximum fuel the function may consume.
+        #[arg(name = "gas-limit", short, long, default_value_t = 1_000_000)]
+        gas_limit: u64,
     },
     /// Start a REPL session.
     Repl {
         /// Sets the address of the REPL environment.
         #[arg(short, long)]
         self_address: Option<String>,
+        /// Do not load or save the REPL history.
+        #[arg(long)]
+        no_history: bool,
+        /// The minimum level of console messages to print (debug, info, warn or error).
+        #[arg(long, default_value = "debug")]
+        log_level: LogLevel,
+        /// Print results as JSON. Toggle with `.mode json` and `.mode display`.
+        #[arg(long)]
+        json: bool,
     },
     /// Commands related to the logs.
     #[command(subcommand)]
@@ -83,6 +106,28 @@ enum Command {
     /// Commands realted to the KV store
     #[command(subcommand)]
     Kv(kv::Command),
+    /// Shows the balance of an account
+    Balance {
+        /// Alias of the account. Defaults to the current account.
+        #[arg(short, long, value_name = "ALIAS", conflicts_with = "of")]
+        account: Option<String>,
+        /// Address (or alias) of the account
+        #[arg(long, value_name = "ADDRESS")]
+        of: Option<String>,
+        /// Print the address and balance as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Shows the code or ABI of a smart function
+    Inspect {
+        /// Smart function address or alias
+        #[arg(value_name = "ALIAS|ADDRESS")]
+        address: Option<String>,
+
+        /// Print the smart function's ABI instead of its code
+        #[arg(long)]
+        abi: bool,
+    },
 }
 
 async fn exec(command: Command, cfg: &mut Config) -> Result<()> {
@@ -101,13 +146,40 @@ async fn exec(command: Command, cfg: &mut Config) -> Result<()> {
             referrer,
             http_method,
             json_data,
-        } => run::exec(cfg, referrer, url, http_method, json_data).await,
-        Command::Repl { self_address } => repl::exec(self_address, cfg),
+            headers,
+            amount,
+            gas_limit,
+        } => {
+            let request = run::Request {
+                url,
+                http_method,
+                json_data,
+                headers,
+            };
+            run::exec(cfg, referrer, request, amount, gas_limit).await
+        }
+        Command::Repl {
+            self_address,
+            no_history,
+            log_level,
+            json,
+        } => {
+            let mode = if json {
+                repl::OutputMode::Json
+            } else {
+                repl::OutputMode::Display
+            };
+            repl::exec(self_address, no_history, log_level, mode, cfg)
+        }
         Command::Logs(logs) => logs::exec(logs, cfg).await,
         Command::Login { alias } => account::login(alias, cfg),
         Command::Logout {} => account::logout(cfg),
         Command::WhoAmI {} => account::whoami(cfg),
         Command::Kv(kv_command) => kv::exec(kv_command, cfg).await,
+        Command::Balance { account, of, json } => {
+            balance::exec(account, of, json, cfg).await
+        }
+        Command::Inspect { address, abi } => inspect::exec(address, abi, cfg).await,
     }
 }
 
@@ -1,8 +1,26 @@
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use anyhow::Result;
-use boa_engine::{js_string, JsResult, JsValue, Source};
+use boa_engine::{
+    builtins::object::Object, js_string, Context, JsObject, JsResult, JsValue, Source,
+};
+use boa_interner::Interner;
 use jstz_api::{
-    encoding::EncodingApi, http::HttpApi, url::UrlApi, urlpattern::UrlPatternApi,
-    ConsoleApi, KvApi,
+    crypto::CryptoApi,
+    encoding::EncodingApi,
+    http::HttpApi,
+    merkle::MerkleApi,
+    net::NetApi,
+    tezos::TezosApi,
+    time::{Clock, TimeApi},
+    url::UrlApi,
+    urlpattern::UrlPatternApi,
+    zk::ZkApi,
+    ConsoleApi, ConsoleKind, KvApi, KvMapApi, LogLevel, MAX_VALUE_SIZE,
 };
 use jstz_core::host::HostRuntime;
 use jstz_core::{
@@ -10,16 +28,78 @@ use jstz_core::{
     kv::Kv,
     runtime::{self, Runtime},
 };
-use jstz_proto::api::{ContractApi, LedgerApi};
-use rustyline::{error::ReadlineError, Editor};
+use jstz_proto::{
+    api::{BlockApi, BlockTimeApi, ContractApi, LedgerApi},
+    context::account::Address,
+    executor::contract::{OperationContext, MAX_EVENT_LOOP_TICKS},
+};
+use rustyline::{
+    error::ReadlineError, history::DefaultHistory, Config as EditorConfig, Editor,
+};
+use serde_json::Value as Json;
 use tezos_smart_rollup_mock::MockHost;
 
 use crate::{config::Config, debug_api::DebugApi};
 
-pub fn exec(self_address: Option<String>, cfg: &Config) -> Result<()> {
-    let account = cfg.accounts.account_or_current(self_address)?;
-    let address = account.address();
+/// The maximum number of lines kept in the REPL history file
+const MAX_HISTORY_LEN: usize = 1000;
+
+/// How the REPL prints the result of an evaluation
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Boa's `display()` formatting
+    #[default]
+    Display,
+    /// One line of JSON per result
+    Json,
+}
+
+impl OutputMode {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "display" => Some(Self::Display),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Display => "display",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// Loads the persisted history into `rl`. A missing or unreadable history
+/// file is not an error: the session simply starts with an empty history.
+fn load_history(rl: &mut Editor<(), DefaultHistory>) {
+    let path = Config::repl_history_path();
+
+    match rl.load_history(&path) {
+        Ok(()) => (),
+        Err(ReadlineError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => (),
+        Err(err) => eprintln!("Couldn't load history from {}: {err}", path.display()),
+    }
+}
 
+fn save_history(rl: &mut Editor<(), DefaultHistory>) {
+    let path = Config::repl_history_path();
+
+    let result = match path.parent() {
+        Some(parent) => std::fs::create_dir_all(parent).map_err(ReadlineError::from),
+        None => Ok(()),
+    }
+    .and_then(|()| rl.save_history(&path));
+
+    if let Err(err) = result {
+        eprintln!("Couldn't save history to {}: {err}", path.display());
+    }
+}
+
+/// Creates a fresh runtime (with an empty KV transaction) exposing the
+/// contract APIs of `address`
+fn init_runtime(address: &Address, log_level: LogLevel) -> Runtime {
     let mut rt = Runtime::new().expect("Failed to create a new runtime.");
 
     {
@@ -31,57 +111,147 @@ pub fn exec(self_address: Option<String>, cfg: &Config) -> Result<()> {
 
         host_defined.insert(kv);
         host_defined.insert(tx);
+        host_defined.insert(OperationContext {
+            console: ConsoleKind::Cli { level: log_level },
+            ..Default::default()
+        });
     }
 
-    let mut rl = Editor::<(), _>::new().expect("Failed to create a new editor.");
-
-    let mut mock_hrt = MockHost::default();
-
     let realm_clone = rt.realm().clone();
 
-    realm_clone.register_api(ConsoleApi::Cli {}, rt.context());
+    realm_clone.register_api(ConsoleApi::Cli { level: log_level }, rt.context());
 
     realm_clone.register_api(
         KvApi {
             contract_address: address.clone(),
+            max_value_size: MAX_VALUE_SIZE,
+            enable_dump: true,
+        },
+        rt.context(),
+    );
+    realm_clone.register_api(
+        KvMapApi {
+            contract_address: address.clone(),
         },
         rt.context(),
     );
     realm_clone.register_api(EncodingApi, rt.context());
+    realm_clone.register_api(
+        CryptoApi {
+            seed: Default::default(),
+        },
+        rt.context(),
+    );
     realm_clone.register_api(UrlApi, rt.context());
     realm_clone.register_api(UrlPatternApi, rt.context());
     realm_clone.register_api(HttpApi, rt.context());
+    realm_clone.register_api(NetApi, rt.context());
+    realm_clone.register_api(TezosApi, rt.context());
+    realm_clone.register_api(ZkApi, rt.context());
+    realm_clone.register_api(MerkleApi, rt.context());
+    realm_clone.register_api(TimeApi, rt.context());
     realm_clone.register_api(
         LedgerApi {
             contract_address: address.clone(),
         },
         rt.context(),
     );
+    realm_clone.register_api(BlockApi, rt.context());
+    realm_clone.register_api(BlockTimeApi, rt.context());
     realm_clone.register_api(
         ContractApi {
             contract_address: address.clone(),
-            operation_hash: Default::default(),
         },
         rt.context(),
     );
 
     realm_clone.register_api(DebugApi, rt.context());
 
-    loop {
-        let readline = rl.readline(">> ");
+    rt
+}
+
+pub fn exec(
+    self_address: Option<String>,
+    no_history: bool,
+    log_level: LogLevel,
+    mut mode: OutputMode,
+    cfg: &Config,
+) -> Result<()> {
+    let account = cfg.accounts.account_or_current(self_address)?;
+    let address = account.address();
+
+    let mut rt = init_runtime(address, log_level);
+
+    let editor_config = EditorConfig::builder()
+        .max_history_size(MAX_HISTORY_LEN)?
+        .build();
+    let mut rl = Editor::<(), DefaultHistory>::with_config(editor_config)
+        .expect("Failed to create a new editor.");
+
+    if !no_history {
+        load_history(&mut rl);
+    }
+
+    let mut mock_hrt = MockHost::default();
+
+    // The statement read so far, spanning several lines if incomplete
+    let mut buffer = String::new();
+
+    let result = loop {
+        let prompt = if buffer.is_empty() { ">> " } else { "... " };
+        let readline = rl.readline(prompt);
         match readline {
             Ok(line) => {
                 let input = line.trim();
 
-                // Check for the exit command.
-                if input == "exit" {
-                    break Ok(());
+                // Add the line to history so you can use arrow keys to recall it
+                if let Err(err) = rl.add_history_entry(line.as_str()) {
+                    break Err(err.into());
                 }
 
-                // Add the line to history so you can use arrow keys to recall it
-                rl.add_history_entry(line.as_str())?;
+                // Meta-commands are only recognised at the start of a statement
+                if buffer.is_empty() {
+                    // Check for the exit command.
+                    if input == "exit" {
+                        break Ok(());
+                    }
+
+                    if input == ".reset" {
+                        rt = init_runtime(address, log_level);
+                        mock_hrt = MockHost::default();
+                        continue;
+                    }
+
+                    if let Some(path) = input.strip_prefix(".load ") {
+                        load(Path::new(path.trim()), mode, &mut rt, &mut mock_hrt);
+                        continue;
+                    }
+
+                    if let Some(name) = input.strip_prefix(".mode") {
+                        match name.trim() {
+                            "" => println!("{}", mode.name()),
+                            name => match OutputMode::from_name(name) {
+                                Some(new_mode) => mode = new_mode,
+                                None => eprintln!(
+                                    "Unknown mode `{name}`, expected `display` or `json`"
+                                ),
+                            },
+                        }
+                        continue;
+                    }
+                }
+
+                let continued = push_line(&mut buffer, &line);
+                if continued || is_incomplete(&buffer) {
+                    continue;
+                }
 
-                evaluate(input, &mut rt, &mut mock_hrt);
+                let input = std::mem::take(&mut buffer);
+                evaluate(input.trim(), None, mode, &mut rt, &mut mock_hrt);
+            }
+            // Abandon an incomplete statement without leaving the REPL
+            Err(ReadlineError::Interrupted) if !buffer.is_empty() => {
+                buffer.clear();
             }
             Err(ReadlineError::Interrupted) => {
                 println!("CTRL-C");
@@ -96,29 +266,317 @@ pub fn exec(self_address: Option<String>, cfg: &Config) -> Result<()> {
                 break Ok(());
             }
         }
+    };
+
+    if !no_history {
+        save_history(&mut rl);
+    }
+
+    result
+}
+
+/// The lexical state at the end of some (possibly partial) input
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Scan {
+    /// The number of unclosed brackets, template literals and block comments
+    unclosed: usize,
+    /// Whether the input ends inside a string literal
+    in_string: bool,
+}
+
+/// Scans `input` for delimiters, skipping over strings and comments.
+/// Regular expression literals are not recognised.
+fn scan(input: &str) -> Scan {
+    enum State {
+        Code,
+        Bracket(char),
+        Str(char),
+        Template,
+        LineComment,
+        BlockComment,
+    }
+
+    let mut stack = vec![State::Code];
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let state = stack.last().expect("The stack should never be empty");
+        match state {
+            State::Str(quote) => match c {
+                '\\' => {
+                    chars.next();
+                }
+                // An unterminated string is a syntax error, reported by the parser
+                '\n' => {
+                    stack.pop();
+                }
+                c if c == *quote => {
+                    stack.pop();
+                }
+                _ => (),
+            },
+            State::Template => match c {
+                '\\' => {
+                    chars.next();
+                }
+                '`' => {
+                    stack.pop();
+                }
+                '$' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    stack.push(State::Bracket('{'));
+                }
+                _ => (),
+            },
+            State::LineComment => {
+                if c == '\n' {
+                    stack.pop();
+                }
+            }
+            State::BlockComment => {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    stack.pop();
+                }
+            }
+            State::Code | State::Bracket(_) => match c {
+                '\'' | '"' => stack.push(State::Str(c)),
+                '`' => stack.push(State::Template),
+                '/' if chars.peek() == Some(&'/') => {
+                    chars.next();
+                    stack.push(State::LineComment);
+                }
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    stack.push(State::BlockComment);
+                }
+                '(' | '[' | '{' => stack.push(State::Bracket(c)),
+                ')' | ']' | '}' => {
+                    if let State::Bracket(_) = state {
+                        stack.pop();
+                    }
+                }
+                _ => (),
+            },
+        }
+    }
+
+    let in_string = matches!(stack.last(), Some(State::Str(_)));
+    let unclosed = stack
+        .iter()
+        .filter(|state| !matches!(state, State::Code | State::LineComment))
+        .count()
+        - usize::from(in_string);
+
+    Scan {
+        unclosed,
+        in_string,
+    }
+}
+
+/// Appends `line` to `buffer`, returning `true` if the line ends with a `\`
+/// asking for more input. Within a string literal the `\` is kept, since it
+/// is a JavaScript line continuation.
+fn push_line(buffer: &mut String, line: &str) -> bool {
+    let continued = match line.strip_suffix('\\') {
+        Some(line) => {
+            buffer.push_str(line);
+            if scan(buffer).in_string {
+                buffer.push('\\');
+            }
+            true
+        }
+        None => {
+            buffer.push_str(line);
+            false
+        }
+    };
+    buffer.push('\n');
+
+    continued
+}
+
+/// Returns `true` if `input` is the beginning of a statement, rather than a
+/// complete statement or a genuine syntax error
+fn is_incomplete(input: &str) -> bool {
+    let mut parser = boa_parser::Parser::new(Source::from_bytes(input));
+
+    match parser.parse_script(&mut Interner::default()) {
+        Ok(_) => false,
+        Err(boa_parser::Error::AbruptEnd) => true,
+        // Lexing fails when the input ends within a template literal or
+        // comment
+        Err(boa_parser::Error::Lex { .. }) => scan(input).unclosed > 0,
+        Err(_) => false,
+    }
+}
+
+/// Evaluates the file at `path` in the current runtime
+fn load(
+    path: &Path,
+    mode: OutputMode,
+    rt: &mut Runtime,
+    hrt: &mut (impl HostRuntime + 'static),
+) {
+    if path.is_dir() {
+        eprintln!("{}: Is a directory", path.display());
+        return;
+    }
+
+    match fs::read_to_string(path) {
+        Ok(code) => evaluate(&code, Some(path), mode, rt, hrt),
+        Err(err) => eprintln!("{}: {err}", path.display()),
+    }
+}
+
+/// Sets the clock of `rt` to the system time. Unlike smart functions, the REPL
+/// has no block to read the time from, and the mock host has no clock.
+fn set_clock(rt: &mut Runtime) {
+    let system_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as f64)
+        .unwrap_or_default();
+    // The virtual time elapsed on timers is added back when reading the clock
+    let time_origin = system_time - rt.timers().now() as f64;
+
+    let context = rt.context();
+    host_defined!(context, mut host_defined);
+    host_defined.insert(Clock { time_origin });
+}
+
+/// Converts `value` to JSON. Values without a JSON representation, such as
+/// functions, symbols and circular references, become placeholder strings.
+/// `seen` holds the objects being converted, to detect cycles.
+fn to_json(
+    value: &JsValue,
+    seen: &mut Vec<JsObject>,
+    context: &mut Context<'_>,
+) -> JsResult<Json> {
+    let json = match value {
+        JsValue::Undefined | JsValue::Null => Json::Null,
+        JsValue::Boolean(boolean) => Json::from(*boolean),
+        JsValue::String(string) => Json::from(string.to_std_string_escaped()),
+        JsValue::Integer(integer) => Json::from(*integer),
+        JsValue::Rational(rational)
+            if rational.fract() == 0.0 && rational.abs() < i64::MAX as f64 =>
+        {
+            Json::from(*rational as i64)
+        }
+        // Like `JSON.stringify`, NaN and infinities become `null`
+        JsValue::Rational(rational) => serde_json::Number::from_f64(*rational)
+            .map(Json::Number)
+            .unwrap_or(Json::Null),
+        JsValue::BigInt(bigint) => Json::from(bigint.to_string()),
+        JsValue::Symbol(symbol) => Json::from(format!(
+            "[{}]",
+            symbol.descriptive_string().to_std_string_escaped()
+        )),
+        JsValue::Object(object) if object.is_callable() => {
+            let name = object
+                .get(js_string!("name"), context)?
+                .to_string(context)?
+                .to_std_string_escaped();
+
+            if name.is_empty() {
+                Json::from("[Function (anonymous)]")
+            } else {
+                Json::from(format!("[Function: {name}]"))
+            }
+        }
+        JsValue::Object(object)
+            if seen.iter().any(|seen| JsObject::equals(seen, object)) =>
+        {
+            Json::from("[Circular]")
+        }
+        JsValue::Object(object) => {
+            seen.push(object.clone());
+
+            let json = if object.is_array() {
+                let length = object
+                    .get(js_string!("length"), context)?
+                    .to_length(context)?;
+
+                let mut elements = Vec::new();
+                for index in 0..length {
+                    let element = object.get(index as u32, context)?;
+                    elements.push(to_json(&element, seen, context)?);
+                }
+                Json::Array(elements)
+            } else {
+                let entries =
+                    Object::entries(&JsValue::undefined(), &[value.clone()], context)?
+                        .to_object(context)?;
+                let length = entries
+                    .get(js_string!("length"), context)?
+                    .to_length(context)?;
+
+                let mut members = serde_json::Map::new();
+                for index in 0..length {
+                    let entry = entries.get(index as u32, context)?.to_object(context)?;
+                    let key = entry.get(0u32, context)?.to_string(context)?;
+                    let value = entry.get(1u32, context)?;
+                    members.insert(
+                        key.to_std_string_escaped(),
+                        to_json(&value, seen, context)?,
+                    );
+                }
+                Json::Object(members)
+            };
+
+            seen.pop();
+            json
+        }
+    };
+
+    Ok(json)
+}
+
+/// Formats the result of an evaluation according to `mode`
+fn format_result(
+    value: &JsValue,
+    mode: OutputMode,
+    context: &mut Context<'_>,
+) -> JsResult<String> {
+    match mode {
+        OutputMode::Display if value.is_callable() => {
+            Ok(value.to_string(context)?.to_std_string_escaped())
+        }
+        OutputMode::Display => Ok(value.display().to_string()),
+        OutputMode::Json => Ok(to_json(value, &mut Vec::new(), context)?.to_string()),
     }
 }
 
-fn evaluate(input: &str, rt: &mut Runtime, hrt: &mut (impl HostRuntime + 'static)) {
+/// Evaluates `input`, printing its result. Errors are prefixed with `path`
+/// when evaluating a file.
+fn evaluate(
+    input: &str,
+    path: Option<&Path>,
+    mode: OutputMode,
+    rt: &mut Runtime,
+    hrt: &mut (impl HostRuntime + 'static),
+) {
+    let location = path
+        .map(|path| format!("{}: ", path.display()))
+        .unwrap_or_default();
+
+    set_clock(rt);
+
     let rt_output = runtime::with_host_runtime(hrt, || -> JsResult<JsValue> {
         let value = rt.eval(Source::from_bytes(input))?;
         jstz_core::future::block_on(async {
-            rt.run_event_loop().await;
-            rt.resolve_value(&value).await
+            rt.run_event_loop(Some(MAX_EVENT_LOOP_TICKS)).await?;
+            rt.resolve_value_within(&value, Some(MAX_EVENT_LOOP_TICKS))
+                .await
         })
     });
 
     match rt_output {
         Ok(res) => {
             if !res.is_undefined() {
-                println!(
-                    "{}",
-                    if res.is_callable() {
-                        res.to_string(rt.context()).unwrap().to_std_string_escaped()
-                    } else {
-                        res.display().to_string()
-                    },
-                );
+                match format_result(&res, mode, rt.context()) {
+                    Ok(output) => println!("{output}"),
+                    Err(err) => eprintln!("{location}Couldn't format result: {err}"),
+                }
             }
             if let Err(err) =
                 rt.global_object()
@@ -127,8 +585,119 @@ fn evaluate(input: &str, rt: &mut Runtime, hrt: &mut (impl HostRuntime + 'static
                 println!("Couldn't set '_' property: {err}");
             }
         }
+        Err(_) if rt.timed_out() => {
+            eprintln!("{location}Timed out after {MAX_EVENT_LOOP_TICKS} event loop ticks")
+        }
         Err(e) => {
-            eprintln!("Uncaught {e}")
+            eprintln!("{location}Uncaught {e}")
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Feeds `lines` to the REPL input buffer, returning whether more input
+    /// was expected after each line
+    fn read(lines: &[&str]) -> (Vec<bool>, String) {
+        let mut buffer = String::new();
+        let expects_more = lines
+            .iter()
+            .map(|line| push_line(&mut buffer, line) || is_incomplete(&buffer))
+            .collect();
+
+        (expects_more, buffer)
+    }
+
+    #[test]
+    fn multiline_function() {
+        let (expects_more, _) = read(&["function add(a, b) {", "  return a + b;", "}"]);
+
+        assert_eq!(expects_more, vec![true, true, false]);
+    }
+
+    #[test]
+    fn multiline_object_literal() {
+        let (expects_more, _) =
+            read(&["const point = {", "  x: 1,", "  y: [2,", "3] };"]);
+
+        assert_eq!(expects_more, vec![true, true, true, false]);
+    }
+
+    #[test]
+    fn unterminated_string() {
+        // A string literal cannot span lines...
+        let (expects_more, _) = read(&[r#"const s = "abc"#]);
+        assert_eq!(expects_more, vec![false]);
+
+        // ...unless it is continued with a `\`
+        let (expects_more, buffer) = read(&[r#"const s = "abc\"#, r#"def";"#]);
+        assert_eq!(expects_more, vec![true, false]);
+        assert!(!is_incomplete(&buffer));
+    }
+
+    #[test]
+    fn unterminated_template_literal() {
+        let (expects_more, _) = read(&["const s = `abc", "def`;"]);
+
+        assert_eq!(expects_more, vec![true, false]);
+    }
+
+    #[test]
+    fn explicit_continuation() {
+        let (expects_more, buffer) = read(&["1 + \\", "2"]);
+
+        assert_eq!(expects_more, vec![true, false]);
+        assert_eq!(buffer, "1 + \n2\n");
+    }
+
+    #[test]
+    fn genuine_syntax_error() {
+        let (expects_more, _) = read(&["let 1 = 2;"]);
+        assert_eq!(expects_more, vec![false]);
+
+        let (expects_more, _) = read(&["const x = 1 +* 2;"]);
+        assert_eq!(expects_more, vec![false]);
+    }
+
+    fn eval_json(code: &str) -> String {
+        let context = &mut Context::default();
+        let value = context.eval(Source::from_bytes(code)).unwrap();
+
+        format_result(&value, OutputMode::Json, context).unwrap()
+    }
+
+    #[test]
+    fn json_mode_converts_values() {
+        assert_eq!(
+            eval_json(r#"({ a: [1, 2.5, "x"], b: { c: true, d: null } })"#),
+            r#"{"a":[1,2.5,"x"],"b":{"c":true,"d":null}}"#
+        );
+        assert_eq!(eval_json("[undefined, NaN, 10n]"), r#"[null,null,"10"]"#);
+    }
+
+    #[test]
+    fn json_mode_uses_placeholders() {
+        assert_eq!(
+            eval_json("[function add() {}, Symbol('s')]"),
+            r#"["[Function: add]","[Symbol(s)]"]"#
+        );
+        assert_eq!(
+            eval_json("const o = { x: 1 }; o.self = o; o"),
+            r#"{"x":1,"self":"[Circular]"}"#
+        );
+    }
+
+    #[test]
+    fn brackets_in_strings_and_comments_are_ignored() {
+        assert_eq!(scan(r#"f("(", '[') // {"#), Scan::default());
+        assert_eq!(
+            scan("g(/* ) */"),
+            Scan {
+                unclosed: 1,
+                in_string: false
+            }
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    env,
+    fmt::Write,
+    io::{self, IsTerminal},
+};
+
+use http::header::CONTENT_TYPE;
+use jstz_proto::receipt::RunContract;
+use serde_json::Value as Json;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const MAGENTA: &str = "\x1b[35m";
+
+/// The number of bytes of a binary body shown in its hex dump
+const MAX_HEX_DUMP_LEN: usize = 256;
+
+/// Returns `true` if output to stdout should be colored, i.e. stdout is a
+/// terminal and `NO_COLOR` is not set (see https://no-color.org)
+pub fn use_color() -> bool {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+
+    !no_color && io::stdout().is_terminal()
+}
+
+fn paint(out: &mut String, color: Option<&str>, text: &str) {
+    match color {
+        Some(color) => {
+            let _ = write!(out, "{color}{text}{RESET}");
+        }
+        None => out.push_str(text),
+    }
+}
+
+/// Writes `value` indented by 2 spaces per level, as `serde_json` does
+fn write_json(out: &mut String, value: &Json, depth: usize, color: bool) {
+    let color_of = |code| color.then_some(code);
+    let indent = |depth| "  ".repeat(depth);
+
+    match value {
+        Json::Null | Json::Bool(_) => paint(out, color_of(MAGENTA), &value.to_string()),
+        Json::Number(_) => paint(out, color_of(YELLOW), &value.to_string()),
+        Json::String(_) => paint(out, color_of(GREEN), &value.to_string()),
+        Json::Array(elements) if elements.is_empty() => out.push_str("[]"),
+        Json::Array(elements) => {
+            out.push_str("[\n");
+            for (index, element) in elements.iter().enumerate() {
+                out.push_str(&indent(depth + 1));
+                write_json(out, element, depth + 1, color);
+                if index + 1 < elements.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            out.push_str(&indent(depth));
+            out.push(']');
+        }
+        Json::Object(members) if members.is_empty() => out.push_str("{}"),
+        Json::Object(members) => {
+            out.push_str("{\n");
+            for (index, (key, member)) in members.iter().enumerate() {
+                out.push_str(&indent(depth + 1));
+                paint(out, color_of(BLUE), &Json::from(key.as_str()).to_string());
+                out.push_str(": ");
+                write_json(out, member, depth + 1, color);
+                if index + 1 < members.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            out.push_str(&indent(depth));
+            out.push('}');
+        }
+    }
+}
+
+/// Returns `true` for `application/json` and `+json` media types
+fn is_json(content_type: &str) -> bool {
+    let media_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    media_type == "application/json" || media_type.ends_with("+json")
+}
+
+/// Returns `true` if `text` contains control characters other than
+/// whitespace, which would garble a terminal
+fn is_binary(text: &str) -> bool {
+    text.chars()
+        .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+}
+
+fn write_hex_dump(out: &mut String, body: &[u8]) {
+    let _ = writeln!(out, "<binary body, {} bytes>", body.len());
+
+    for (line, chunk) in body.chunks(16).take(MAX_HEX_DUMP_LEN / 16).enumerate() {
+        let bytes: Vec<String> = chunk.iter().map(|byte| format!("{byte:02x}")).collect();
+        let _ = writeln!(out, "{:08x}  {}", line * 16, bytes.join(" "));
+    }
+
+    if body.len() > MAX_HEX_DUMP_LEN {
+        let _ = writeln!(out, "...");
+    }
+}
+
+fn write_body(out: &mut String, body: &[u8], json: bool, color: bool) {
+    let text = match std::str::from_utf8(body) {
+        Ok(text) if !is_binary(text) => text,
+        _ => return write_hex_dump(out, body),
+    };
+
+    match serde_json::from_str::<Json>(text) {
+        Ok(value) if json => {
+            write_json(out, &value, 0, color);
+            out.push('\n');
+        }
+        _ => {
+            out.push_str(text);
+            if !text.ends_with('\n') {
+                out.push('\n');
+            }
+        }
+    }
+}
+
+/// Renders the status, headers and body of a response. JSON bodies are
+/// pretty-printed and binary bodies are shown as a hex dump. ANSI colors are
+/// used if `color` is set.
+pub fn render_response(response: &RunContract, color: bool) -> String {
+    let mut out = String::new();
+
+    let status_color = if response.status_code.is_success() {
+        GREEN
+    } else {
+        RED
+    };
+    paint(
+        &mut out,
+        color.then_some(status_color),
+        &format!("Status: {}", response.status_code),
+    );
+    out.push('\n');
+
+    for (name, value) in &response.headers {
+        paint(&mut out, color.then_some(BOLD), name.as_str());
+        let _ = writeln!(out, ": {}", String::from_utf8_lossy(value.as_bytes()));
+    }
+
+    if let Some(body) = response.body.as_ref().filter(|body| !body.is_empty()) {
+        let json = response
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(is_json);
+
+        out.push('\n');
+        write_body(&mut out, body, json, color);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use http::{HeaderMap, HeaderValue, StatusCode};
+
+    use super::*;
+
+    fn response(content_type: &str, body: &[u8]) -> RunContract {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap());
+
+        RunContract {
+            body: Some(body.to_vec()),
+            status_code: StatusCode::OK,
+            headers,
+            gas_used: 0,
+            logs: vec![],
+            events: vec![],
+            call_trace: vec![],
+        }
+    }
+
+    #[test]
+    fn json_body_is_pretty_printed() {
+        let body = r#"{"a":[1,{"b":null}],"c":"d","e":{},"f":[]}"#;
+        let response = response("application/json; charset=utf-8", body.as_bytes());
+
+        let expected =
+            serde_json::to_string_pretty(&serde_json::from_str::<Json>(body).unwrap())
+                .unwrap();
+        assert_eq!(
+            render_response(&response, false),
+            format!(
+                "Status: 200 OK\ncontent-type: application/json; charset=utf-8\n\n{expected}\n"
+            )
+        );
+
+        let colored = render_response(&response, true);
+        assert!(colored.contains(&format!("{BLUE}\"c\"{RESET}: {GREEN}\"d\"{RESET}")));
+        assert!(colored.contains(&format!("{MAGENTA}null{RESET}")));
+    }
+
+    #[test]
+    fn text_body_is_printed_as_is() {
+        let response = response("text/plain", b"{not json");
+
+        assert!(render_response(&response, false).ends_with("\n\n{not json\n"));
+    }
+
+    #[test]
+    fn binary_body_is_hex_dumped() {
+        let response = response("application/octet-stream", &[0, 159, 146, 150]);
+
+        assert!(render_response(&response, false)
+            .ends_with("\n\n<binary body, 4 bytes>\n00000000  00 9f 92 96\n"));
+    }
+}
@@ -1,8 +1,14 @@
 use std::str::FromStr;
 
 use anyhow::{anyhow, Result};
-use http::{HeaderMap, Method, Uri};
-use jstz_proto::operation::{Content, Operation, RunContract, SignedOperation};
+use http::{
+    header::{HeaderName, HeaderValue, CONTENT_TYPE},
+    HeaderMap, Method, Uri,
+};
+use jstz_proto::{
+    operation::{Content, Operation, RunContract, SignedOperation},
+    receipt::Content as ReceiptContent,
+};
 use url::Url;
 
 use crate::{
@@ -10,16 +16,65 @@ use crate::{
     config::Config,
     jstz::JstzClient,
     octez::OctezClient,
+    response::{render_response, use_color},
     utils::{from_file_or_id, piped_input},
 };
 
+/// Parses a `KEY:VALUE` header. Whitespace around the key and the value is
+/// ignored.
+fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue)> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or(anyhow!("Invalid header `{header}`, expected KEY:VALUE"))?;
+
+    let name = HeaderName::from_str(name.trim())
+        .map_err(|_| anyhow!("Invalid header name `{}`", name.trim()))?;
+    let value = HeaderValue::from_str(value.trim())
+        .map_err(|_| anyhow!("Invalid value for header `{name}`"))?;
+
+    Ok((name, value))
+}
+
+/// Builds the headers of the request. Repeated headers accumulate, and
+/// requests with a body default to a JSON content type.
+fn request_headers(headers: &[String], has_body: bool) -> Result<HeaderMap> {
+    let mut header_map = HeaderMap::new();
+
+    for header in headers {
+        let (name, value) = parse_header(header)?;
+        header_map.append(name, value);
+    }
+
+    if has_body && !header_map.contains_key(CONTENT_TYPE) {
+        header_map.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+
+    Ok(header_map)
+}
+
+/// The request sent to the smart function, as given on the command line
+pub struct Request {
+    pub url: String,
+    pub http_method: String,
+    pub json_data: Option<String>,
+    /// The headers, as `KEY:VALUE`
+    pub headers: Vec<String>,
+}
+
 pub async fn exec(
     cfg: &mut Config,
     referrer: Option<String>,
-    url: String,
-    http_method: String,
-    json_data: Option<String>,
+    request: Request,
+    amount: u64,
+    gas_limit: u64,
 ) -> Result<()> {
+    let Request {
+        url,
+        http_method,
+        json_data,
+        headers,
+    } = request;
+
     let jstz_client = JstzClient::new(cfg);
 
     // Resolve URL
@@ -71,6 +126,7 @@ pub async fn exec(
         .map(from_file_or_id)
         .or_else(piped_input)
         .map(String::into_bytes);
+    let headers = request_headers(&headers, body.is_some())?;
 
     let op = Operation {
         source: address.clone(),
@@ -78,8 +134,10 @@ pub async fn exec(
         content: Content::RunContract(RunContract {
             uri: url,
             method,
-            headers: HeaderMap::default(),
+            headers,
             body,
+            amount,
+            fuel_limit: gas_limit,
         }),
     };
 
@@ -104,7 +162,80 @@ pub async fn exec(
 
     println!("Receipt: {:?}", receipt);
 
+    match &receipt.inner {
+        Ok(ReceiptContent::RunContract(response)) => {
+            print!("{}", render_response(response, use_color()))
+        }
+        Ok(_) => return Err(anyhow!("Content is not of type 'RunContract'")),
+        Err(error) => println!("Operation failed: {error}"),
+    }
+
     cfg.save()?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use clap::Parser;
+
+    use super::*;
+    use crate::Command;
+
+    #[test]
+    fn headers_accumulate() {
+        let headers = [
+            "Accept: text/plain".to_string(),
+            "X-Tag:a".to_string(),
+            "x-tag: b ".to_string(),
+        ];
+
+        let header_map = request_headers(&headers, false).unwrap();
+
+        assert_eq!(header_map.get("accept").unwrap(), "text/plain");
+        let tags: Vec<_> = header_map.get_all("x-tag").iter().collect();
+        assert_eq!(tags, ["a", "b"]);
+        assert!(!header_map.contains_key(CONTENT_TYPE));
+    }
+
+    #[test]
+    fn invalid_headers_are_rejected() {
+        assert!(parse_header("no-separator").is_err());
+        assert!(parse_header("bad name: value").is_err());
+    }
+
+    #[test]
+    fn body_defaults_to_json_content_type() {
+        let header_map = request_headers(&[], true).unwrap();
+        assert_eq!(header_map.get(CONTENT_TYPE).unwrap(), "application/json");
+
+        let headers = ["Content-Type: text/plain".to_string()];
+        let header_map = request_headers(&headers, true).unwrap();
+        assert_eq!(header_map.get(CONTENT_TYPE).unwrap(), "text/plain");
+    }
+
+    #[test]
+    fn method_defaults_to_get() {
+        let command = Command::try_parse_from([
+            "jstz",
+            "run",
+            "tezos://tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty/",
+            "-H",
+            "X-Tag: a",
+            "--header",
+            "X-Tag: b",
+        ])
+        .unwrap();
+
+        let Command::Run {
+            http_method,
+            headers,
+            ..
+        } = command
+        else {
+            panic!("Expected a run command");
+        };
+        assert_eq!(http_method, "GET");
+        assert_eq!(headers, ["X-Tag: a", "X-Tag: b"]);
+    }
+}
@@ -12,6 +12,22 @@ pub enum Error {
     JsError {
         source: JsError,
     },
+    TransactionConflict,
+    WriteConflict,
+    InvalidSavepoint,
+    ReadOnlyViolation,
+    Timeout,
+    OutOfFuel,
+    #[display(fmt = "ValueTooLarge ({} bytes, limit {})", size, limit)]
+    ValueTooLarge {
+        size: usize,
+        limit: usize,
+    },
+    #[display(fmt = "KeyTooLong ({} bytes, limit {})", length, limit)]
+    KeyTooLong {
+        length: usize,
+        limit: usize,
+    },
 }
 
 impl From<Error> for JsError {
@@ -27,6 +43,30 @@ impl From<Error> for JsError {
                 .with_message("JsError")
                 .with_cause(source)
                 .into(),
+            Error::TransactionConflict => JsNativeError::eval()
+                .with_message("TransactionConflict")
+                .into(),
+            Error::WriteConflict => {
+                JsNativeError::eval().with_message("WriteConflict").into()
+            }
+            Error::InvalidSavepoint => JsNativeError::range()
+                .with_message("InvalidSavepoint")
+                .into(),
+            Error::ReadOnlyViolation => JsNativeError::eval()
+                .with_message("ReadOnlyViolation")
+                .into(),
+            Error::Timeout => JsNativeError::eval().with_message("Timeout").into(),
+            Error::OutOfFuel => JsNativeError::eval().with_message("OutOfFuel").into(),
+            Error::ValueTooLarge { size, limit } => JsNativeError::range()
+                .with_message(format!(
+                    "ValueTooLarge: the value is {size} bytes, the limit is {limit}"
+                ))
+                .into(),
+            Error::KeyTooLong { length, limit } => JsNativeError::range()
+                .with_message(format!(
+                    "KeyTooLong: the key is {length} bytes, the limit is {limit}"
+                ))
+                .into(),
         }
     }
 }
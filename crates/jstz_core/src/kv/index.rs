@@ -0,0 +1,139 @@
+//! # Key index
+//!
+//! The rollup host cannot list the keys of its durable storage, so the store
+//! keeps its own listing of the keys written under the prefixes that a
+//! transaction indexes (see [`Transaction::index_prefix`]).
+//!
+//! Each indexed directory has a listing of the names of its children (keys,
+//! directories or both), stored under [`INDEX_PATH`] at the path of the
+//! directory. Listings are only read and written when committing a
+//! transaction that inserts or removes indexed keys, and when enumerating
+//! them.
+//!
+//! [`Transaction::index_prefix`]: super::Transaction::index_prefix
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use tezos_smart_rollup_host::{
+    path::{self, OwnedPath, RefPath},
+    runtime::Runtime,
+};
+
+use crate::error::Result;
+
+use super::{transaction::is_nested, Storage};
+
+const INDEX_PATH: RefPath = RefPath::assert_from(b"/jstz_index");
+
+/// The names of the children of a directory
+type Listing = BTreeSet<String>;
+
+/// The listings updated while committing a transaction. Each listing is read
+/// at most once, and written once by [`Index::flush`].
+#[derive(Default)]
+pub(super) struct Index {
+    listings: BTreeMap<OwnedPath, Listing>,
+    dirty: BTreeSet<OwnedPath>,
+}
+
+fn listing_path(dir: &OwnedPath) -> Result<OwnedPath> {
+    Ok(path::concat(&INDEX_PATH, dir)?)
+}
+
+/// Splits `key` into its parent directory and its name
+fn split(key: &OwnedPath) -> Result<(OwnedPath, String)> {
+    let key = String::from_utf8_lossy(key.as_bytes());
+    let (dir, name) = key.rsplit_once('/').unwrap_or_default();
+
+    Ok((OwnedPath::try_from(dir.to_string())?, name.to_string()))
+}
+
+/// Returns the persistent children of `dir`, as listed in the index
+pub(super) fn children(rt: &impl Runtime, dir: &OwnedPath) -> Result<Vec<OwnedPath>> {
+    let listing = Storage::get::<Listing>(rt, &listing_path(dir)?)?.unwrap_or_default();
+
+    listing
+        .into_iter()
+        .map(|name| {
+            Ok(path::concat(
+                dir,
+                &OwnedPath::try_from(format!("/{name}"))?,
+            )?)
+        })
+        .collect()
+}
+
+impl Index {
+    fn listing(&mut self, rt: &impl Runtime, dir: &OwnedPath) -> Result<&mut Listing> {
+        if !self.listings.contains_key(dir) {
+            let listing =
+                Storage::get::<Listing>(rt, &listing_path(dir)?)?.unwrap_or_default();
+            self.listings.insert(dir.clone(), listing);
+        }
+
+        Ok(self
+            .listings
+            .get_mut(dir)
+            .expect("Listing should be loaded"))
+    }
+
+    /// Lists `key` in each directory from `root` down to its parent. Does
+    /// nothing unless `key` is nested under `root`.
+    pub(super) fn insert(
+        &mut self,
+        rt: &impl Runtime,
+        root: &OwnedPath,
+        key: &OwnedPath,
+    ) -> Result<()> {
+        let mut key = key.clone();
+        while key != *root && is_nested(&key, root) {
+            let (dir, name) = split(&key)?;
+
+            // A listed directory is itself listed in its parent
+            if !self.listing(rt, &dir)?.insert(name) {
+                break;
+            }
+            self.dirty.insert(dir.clone());
+
+            key = dir;
+        }
+
+        Ok(())
+    }
+
+    /// Unlists `key` and drops the listings of the directories nested under
+    /// it. Does nothing unless `key` is nested under `root`, or `root` under
+    /// `key`.
+    pub(super) fn remove(
+        &mut self,
+        rt: &mut impl Runtime,
+        root: &OwnedPath,
+        key: &OwnedPath,
+    ) -> Result<()> {
+        if !is_nested(key, root) && !is_nested(root, key) {
+            return Ok(());
+        }
+
+        self.listings.retain(|dir, _| !is_nested(dir, key));
+        self.dirty.retain(|dir| !is_nested(dir, key));
+        Storage::remove(rt, &listing_path(key)?)?;
+
+        if key != root && is_nested(key, root) {
+            let (dir, name) = split(key)?;
+            if self.listing(rt, &dir)?.remove(&name) {
+                self.dirty.insert(dir);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the updated listings to the persistent store
+    pub(super) fn flush(self, rt: &mut impl Runtime) -> Result<()> {
+        for dir in self.dirty {
+            Storage::insert(rt, &listing_path(&dir)?, &self.listings[&dir])?;
+        }
+
+        Ok(())
+    }
+}
@@ -14,10 +14,11 @@ use tezos_smart_rollup_host::{
 
 use crate::error::Result;
 
+mod index;
 mod transaction;
 pub mod value;
 
-pub use transaction::{Entry, Transaction};
+pub use transaction::{Entry, SavepointId, Transaction};
 pub use value::Value;
 
 const MAX_TX_COUNT: usize = 16;
@@ -52,11 +53,15 @@ impl Storage {
     where
         V: Value + DeserializeOwned,
     {
+        let bytes = Self::get_bytes(rt, key)?;
+        Ok(bytes.map(|bytes| value::deserialize(&bytes)))
+    }
+
+    /// Retrieve a serialized value from the persistent store if it exists
+    fn get_bytes(rt: &impl Runtime, key: &impl Path) -> Result<Option<Vec<u8>>> {
         match rt.store_has(key)? {
             Some(ValueType::Value | ValueType::ValueWithSubtree) => {
-                let bytes = rt.store_read_all(key)?;
-                let value = value::deserialize(&bytes);
-                Ok(Some(value))
+                Ok(Some(rt.store_read_all(key)?))
             }
             _ => Ok(None),
         }
@@ -80,9 +85,10 @@ impl Storage {
         Ok(())
     }
 
-    /// Remove a key-value pair from the persistent store
+    /// Remove a key-value pair, along with any keys nested under it, from the
+    /// persistent store
     pub fn remove(rt: &mut impl Runtime, key: &impl Path) -> Result<()> {
-        if Self::contains_key(rt, key)? {
+        if rt.store_has(key)?.is_some() {
             rt.store_delete(key)?;
         }
         Ok(())
@@ -112,11 +118,21 @@ impl Kv {
 
     /// Begin a new transaction.
     pub fn begin_transaction(&self) -> Transaction {
-        Transaction::new(self.clock.current_timestamp())
+        Transaction::new(self.clock.current_timestamp(), false)
+    }
+
+    /// Begin a new read-only transaction. Inserting or removing keys in a
+    /// read-only transaction fails with `Error::ReadOnlyViolation`.
+    pub fn begin_read_only_transaction(&self) -> Transaction {
+        Transaction::new(self.clock.current_timestamp(), true)
     }
 
     /// Commit a transaction. Returns `true` if the transaction was successfully
     /// committed to the persistent key-value store.
+    ///
+    /// Fails with `Error::WriteConflict` if the transaction has conflict checking
+    /// enabled (see [`Transaction::with_conflict_check`]) and a value it read has
+    /// changed in the persistent store since.
     pub fn commit_transaction(
         &mut self,
         rt: &mut impl Runtime,
@@ -147,6 +163,11 @@ impl Kv {
             }
         }
 
+        // The above only considers the keys read (and not written) by `tx`, against
+        // the transactions committed through this store. If enabled, the read
+        // versions of `tx` are also checked against the persistent store itself.
+        tx.check_conflicts(rt)?;
+
         // **Commit Phase**
         //
         // The transaction `tx` has now been verified at this point. We can assign it a `commit_timestamp`
@@ -187,3 +208,231 @@ impl Clock {
         timestamp
     }
 }
+
+#[cfg(test)]
+mod test {
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::*;
+    use crate::error::Error;
+
+    fn path(key: &str) -> OwnedPath {
+        OwnedPath::try_from(key.to_string()).unwrap()
+    }
+
+    fn setup() -> (MockHost, Kv) {
+        let mut rt = MockHost::default();
+        let mut kv = Kv::new();
+
+        let mut tx = kv.begin_transaction();
+        tx.insert(path("/a"), 1u64).unwrap();
+        assert!(kv.commit_transaction(&mut rt, tx).unwrap());
+
+        (rt, kv)
+    }
+
+    /// Reads `/a` in `tx`, commits a concurrent write of `/a`, then writes `/a`
+    /// in `tx` and commits it
+    fn commit_after_concurrent_write(mut tx: Transaction) -> Result<bool> {
+        let (mut rt, mut kv) = setup();
+        assert_eq!(tx.get::<u64>(&rt, path("/a")).unwrap(), Some(&1));
+
+        let mut other = kv.begin_transaction();
+        other.insert(path("/a"), 2u64).unwrap();
+        assert!(kv.commit_transaction(&mut rt, other).unwrap());
+
+        *tx.get_mut::<u64>(&rt, path("/a")).unwrap().unwrap() += 10;
+        kv.commit_transaction(&mut rt, tx)
+    }
+
+    #[test]
+    fn test_conflict_check_detects_concurrent_write() {
+        let kv = Kv::new();
+
+        let result =
+            commit_after_concurrent_write(kv.begin_transaction().with_conflict_check());
+
+        assert!(matches!(result, Err(Error::WriteConflict)));
+    }
+
+    #[test]
+    fn test_without_conflict_check_last_writer_wins() {
+        let kv = Kv::new();
+
+        let result = commit_after_concurrent_write(kv.begin_transaction());
+
+        assert!(result.unwrap());
+    }
+
+    #[test]
+    fn test_conflict_check_detects_removed_and_inserted_keys() {
+        let (mut rt, mut kv) = setup();
+
+        let mut tx = kv.begin_transaction().with_conflict_check();
+        assert!(tx.has_conflict_check());
+        assert_eq!(tx.get::<u64>(&rt, path("/a")).unwrap(), Some(&1));
+        assert_eq!(tx.get::<u64>(&rt, path("/b")).unwrap(), None);
+
+        Storage::insert(&mut rt, &path("/b"), &3u64).unwrap();
+
+        assert!(matches!(
+            kv.commit_transaction(&mut rt, tx),
+            Err(Error::WriteConflict)
+        ));
+        assert_eq!(Storage::get::<u64>(&rt, &path("/a")).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_conflict_check_commits_without_conflict() {
+        let (mut rt, mut kv) = setup();
+
+        let mut tx = kv.begin_transaction().with_conflict_check();
+        *tx.get_mut::<u64>(&rt, path("/a")).unwrap().unwrap() += 1;
+
+        // Writes to other keys do not conflict
+        Storage::insert(&mut rt, &path("/b"), &3u64).unwrap();
+
+        assert!(kv.commit_transaction(&mut rt, tx).unwrap());
+        assert_eq!(Storage::get::<u64>(&rt, &path("/a")).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_read_only_rejects_get_mut() {
+        let (rt, kv) = setup();
+
+        let mut tx = kv.begin_read_only_transaction();
+
+        assert!(matches!(
+            tx.get_mut::<u64>(&rt, path("/a")),
+            Err(Error::ReadOnlyViolation)
+        ));
+        assert_eq!(tx.get::<u64>(&rt, path("/a")).unwrap(), Some(&1));
+        assert!(tx.update_set().is_empty());
+    }
+
+    #[test]
+    fn test_read_only_rejects_entry_writes() {
+        let (rt, kv) = setup();
+
+        let mut tx = kv.begin_read_only_transaction();
+
+        match tx.entry::<u64>(&rt, path("/a")).unwrap() {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.get(), &1);
+                assert!(matches!(entry.get_mut(), Err(Error::ReadOnlyViolation)));
+                assert!(matches!(entry.insert(2), Err(Error::ReadOnlyViolation)));
+                assert!(matches!(entry.into_mut(), Err(Error::ReadOnlyViolation)));
+            }
+            Entry::Vacant(_) => panic!("Expected an occupied entry"),
+        }
+
+        match tx.entry::<u64>(&rt, path("/b")).unwrap() {
+            Entry::Vacant(entry) => {
+                assert!(matches!(entry.insert(2), Err(Error::ReadOnlyViolation)));
+            }
+            Entry::Occupied(_) => panic!("Expected a vacant entry"),
+        }
+
+        assert!(matches!(
+            tx.entry::<u64>(&rt, path("/c"))
+                .unwrap()
+                .or_insert_default(),
+            Err(Error::ReadOnlyViolation)
+        ));
+        assert!(tx.update_set().is_empty());
+        assert_eq!(tx.get::<u64>(&rt, path("/a")).unwrap(), Some(&1));
+    }
+
+    fn keys_with_prefix(rt: &MockHost, tx: &Transaction, prefix: &str) -> Vec<OwnedPath> {
+        tx.keys_with_prefix(rt, &path(prefix))
+            .unwrap()
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_prefix_reflects_uncommitted_writes() {
+        let (rt, kv) = setup();
+
+        let mut tx = kv.begin_transaction();
+        tx.insert(path("/p/b"), 2u64).unwrap();
+        tx.insert(path("/p/a"), 1u64).unwrap();
+        tx.insert(path("/p/c/d"), 3u64).unwrap();
+        tx.insert(path("/q"), 4u64).unwrap();
+
+        assert_eq!(
+            tx.scan_prefix::<u64>(&rt, &path("/p")).unwrap(),
+            vec![(path("/p/a"), 1), (path("/p/b"), 2), (path("/p/c/d"), 3)]
+        );
+    }
+
+    #[test]
+    fn test_keys_with_prefix_enumerates_indexed_keys() {
+        let (mut rt, mut kv) = setup();
+
+        let mut tx = kv.begin_transaction();
+        tx.index_prefix(&path("/p"));
+        tx.insert(path("/p/a"), 1u64).unwrap();
+        tx.insert(path("/p/b"), 2u64).unwrap();
+        tx.insert(path("/p/c"), 3u64).unwrap();
+        tx.insert(path("/p/c/d"), 4u64).unwrap();
+        tx.insert(path("/p/e/f"), 5u64).unwrap();
+        assert!(kv.commit_transaction(&mut rt, tx).unwrap());
+
+        let tx = kv.begin_transaction();
+        assert_eq!(
+            keys_with_prefix(&rt, &tx, "/p"),
+            vec![
+                path("/p/a"),
+                path("/p/b"),
+                path("/p/c"),
+                path("/p/c/d"),
+                path("/p/e/f")
+            ]
+        );
+        assert_eq!(keys_with_prefix(&rt, &tx, "/p/c"), vec![path("/p/c/d")]);
+
+        // Removals are reflected before and after they are committed
+        let mut tx = kv.begin_transaction();
+        tx.index_prefix(&path("/p"));
+        tx.remove(&rt, &path("/p/a")).unwrap();
+        tx.remove_prefix(&path("/p/e")).unwrap();
+        assert_eq!(
+            keys_with_prefix(&rt, &tx, "/p"),
+            vec![path("/p/b"), path("/p/c"), path("/p/c/d")]
+        );
+        assert!(kv.commit_transaction(&mut rt, tx).unwrap());
+
+        let mut tx = kv.begin_transaction();
+        assert_eq!(
+            keys_with_prefix(&rt, &tx, "/p"),
+            vec![path("/p/b"), path("/p/c"), path("/p/c/d")]
+        );
+
+        tx.index_prefix(&path("/p"));
+        tx.remove_prefix(&path("/p")).unwrap();
+        assert!(kv.commit_transaction(&mut rt, tx).unwrap());
+
+        let tx = kv.begin_transaction();
+        assert!(keys_with_prefix(&rt, &tx, "/p").is_empty());
+    }
+
+    #[test]
+    fn test_keys_with_prefix_skips_unindexed_keys() {
+        let (mut rt, mut kv) = setup();
+
+        let mut tx = kv.begin_transaction();
+        tx.index_prefix(&path("/p"));
+        tx.insert(path("/p/a"), 1u64).unwrap();
+        tx.insert(path("/q/a"), 2u64).unwrap();
+        assert!(kv.commit_transaction(&mut rt, tx).unwrap());
+
+        let mut tx = kv.begin_transaction();
+        tx.insert(path("/p/b"), 3u64).unwrap();
+        assert!(kv.commit_transaction(&mut rt, tx).unwrap());
+
+        let tx = kv.begin_transaction();
+        assert_eq!(keys_with_prefix(&rt, &tx, "/p"), vec![path("/p/a")]);
+        assert!(keys_with_prefix(&rt, &tx, "/q").is_empty());
+    }
+}
@@ -7,9 +7,10 @@ use boa_gc::{empty_trace, Finalize, Trace};
 use serde::de::DeserializeOwned;
 use tezos_smart_rollup_host::{path::OwnedPath, runtime::Runtime};
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 
-use super::value::{BoxedValue, Value};
+use super::index::{self, Index};
+use super::value::{self, BoxedValue, Value};
 use super::{Storage, Timestamp};
 
 /// A transaction is a 'lazy' snapshot of the persistent key-value store from
@@ -39,11 +40,48 @@ use super::{Storage, Timestamp};
 /// | commit: false |
 /// +---------------+
 /// ```
+///
+/// Savepoints mark points within a transaction that can be rolled back to,
+/// discarding only the writes made after them. They are ordered by creation:
+///
+///   - Rolling back to a savepoint discards all writes made since it was
+///     created, including writes guarded by savepoints created after it.
+///     Those savepoints are released.
+///
+///   - Releasing a savepoint keeps its writes and releases only that
+///     savepoint, so savepoints may be released in any order.
+///
+///   - Rolling back to or releasing a savepoint that has been released fails
+///     with `Error::InvalidSavepoint`.
+///
+/// Each savepoint keeps an undo log of the keys written after it (and before
+/// the next savepoint), holding their state prior to the first write. Creating
+/// a savepoint is therefore cheap, and rolling back only restores the keys
+/// that were written.
+///
+/// Conflict checking is opt-in (see [`Transaction::with_conflict_check`]): the
+/// transaction then records the persistent value of each key it reads, and
+/// fails to commit with `Error::WriteConflict` if any of them has changed.
+///
+/// The keys nested under a prefix can be enumerated with
+/// [`Transaction::scan_prefix`], provided that they were written by
+/// transactions that index the prefix (see [`Transaction::index_prefix`]).
 
 #[must_use]
 pub struct Transaction {
     remove_set: BTreeSet<OwnedPath>,
     snapshot: Snapshot,
+    savepoints: Vec<Savepoint>,
+    next_savepoint_id: SavepointId,
+    read_only: bool,
+    // The serialized persistent values read by the transaction (`None` for an
+    // absent key), if conflict checking is enabled
+    read_versions: Option<BTreeMap<OwnedPath, Option<Vec<u8>>>>,
+    // The keys ever written by the transaction, including writes that were
+    // rolled back, if the write log is enabled
+    write_log: Option<BTreeSet<OwnedPath>>,
+    // The prefixes under which the keys written by the transaction are indexed
+    indexed_prefixes: BTreeSet<OwnedPath>,
     pub(crate) begin_timestamp: Timestamp,
 }
 
@@ -53,6 +91,7 @@ unsafe impl Trace for Transaction {
     empty_trace!();
 }
 
+#[derive(Clone)]
 struct SnapshotEntry {
     dirty: bool,
     value: BoxedValue,
@@ -60,6 +99,20 @@ struct SnapshotEntry {
 
 type Snapshot = BTreeMap<OwnedPath, SnapshotEntry>;
 
+/// Identifies a savepoint within a transaction. Ids are never reused.
+pub type SavepointId = usize;
+
+struct Savepoint {
+    id: SavepointId,
+    undo_log: BTreeMap<OwnedPath, Undo>,
+}
+
+/// The state of a key before it was first written after a savepoint
+struct Undo {
+    entry: Option<SnapshotEntry>,
+    removed: bool,
+}
+
 impl SnapshotEntry {
     fn ephemeral<V>(value: V) -> Self
     where
@@ -106,12 +159,74 @@ impl SnapshotEntry {
 }
 
 impl Transaction {
-    pub(crate) fn new(begin_timestamp: Timestamp) -> Self {
+    pub(crate) fn new(begin_timestamp: Timestamp, read_only: bool) -> Self {
         Self {
             begin_timestamp,
             remove_set: BTreeSet::new(),
             snapshot: BTreeMap::new(),
+            savepoints: Vec::new(),
+            next_savepoint_id: 0,
+            read_only,
+            read_versions: None,
+            write_log: None,
+            indexed_prefixes: BTreeSet::new(),
+        }
+    }
+
+    /// Enables conflict checking. The transaction records the persistent value
+    /// of each key it reads through [`Transaction::get`],
+    /// [`Transaction::get_mut`] or [`Transaction::entry`]. Committing it then
+    /// fails with `Error::WriteConflict` if any of these values has changed in
+    /// the persistent store since it was first read, so that the caller may
+    /// retry.
+    ///
+    /// This detects conflicts on keys that are read and then written, which
+    /// are otherwise last-writer-wins, at the cost of keeping a copy of each
+    /// value read.
+    pub fn with_conflict_check(mut self) -> Self {
+        self.read_versions.get_or_insert_with(BTreeMap::new);
+        self
+    }
+
+    /// Returns `true` if conflict checking is enabled
+    pub fn has_conflict_check(&self) -> bool {
+        self.read_versions.is_some()
+    }
+
+    /// Enables the write log, which records every key written by the
+    /// transaction, including writes that were later rolled back to a
+    /// savepoint (see [`Transaction::write_log`]).
+    pub fn with_write_log(mut self) -> Self {
+        self.write_log.get_or_insert_with(BTreeSet::new);
+        self
+    }
+
+    /// Returns the keys ever written by the transaction, if the write log is
+    /// enabled
+    pub fn write_log(&self) -> Option<&BTreeSet<OwnedPath>> {
+        self.write_log.as_ref()
+    }
+
+    /// Returns `Error::WriteConflict` if conflict checking is enabled and a
+    /// value read by the transaction has changed in the persistent store.
+    pub(crate) fn check_conflicts(&self, rt: &impl Runtime) -> Result<()> {
+        for (key, bytes) in self.read_versions.iter().flatten() {
+            if Storage::get_bytes(rt, key)? != *bytes {
+                return Err(Error::WriteConflict);
+            }
         }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the transaction was begun in read-only mode
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns `Error::ReadOnlyViolation` if the transaction is read-only.
+    pub fn ensure_writable(&self) -> Result<()> {
+        ensure_writable(self.read_only)
     }
 
     pub(crate) fn read_set(&self) -> BTreeSet<OwnedPath> {
@@ -144,24 +259,107 @@ impl Transaction {
             .collect()
     }
 
-    pub(crate) fn update_set(&self) -> BTreeSet<OwnedPath> {
+    /// Returns the keys written (inserted or removed) by the transaction
+    pub fn update_set(&self) -> BTreeSet<OwnedPath> {
         self.insert_set().union(&self.remove_set).cloned().collect()
     }
 
     pub(crate) fn flush(self, rt: &mut impl Runtime) -> Result<()> {
+        let mut index = Index::default();
+
         // Perform deletions
         for key in self.remove_set {
-            Storage::remove(rt, &key)?
+            Storage::remove(rt, &key)?;
+            for prefix in self.indexed_prefixes.iter() {
+                index.remove(rt, prefix, &key)?;
+            }
         }
 
         // Perform insertions
         for (key, entry) in self.snapshot.into_iter() {
             if entry.dirty {
                 Storage::insert(rt, &key, entry.value.as_ref())?;
+                for prefix in self.indexed_prefixes.iter() {
+                    index.insert(rt, prefix, &key)?;
+                }
             }
         }
 
-        Ok(())
+        index.flush(rt)
+    }
+
+    /// Indexes the keys nested under `prefix` that are inserted or removed by
+    /// the transaction once it is committed, so that they can be enumerated
+    /// by later transactions. Keys written by transactions that did not index
+    /// `prefix` are not enumerated.
+    pub fn index_prefix(&mut self, prefix: &OwnedPath) {
+        if !self.indexed_prefixes.contains(prefix) {
+            self.indexed_prefixes.insert(prefix.clone());
+        }
+    }
+
+    /// Returns the keys nested under `prefix` (excluding `prefix` itself), in
+    /// order. These are the indexed keys of the persistent store, and the keys
+    /// held by the transaction, less the keys it has removed.
+    pub fn keys_with_prefix(
+        &self,
+        rt: &impl Runtime,
+        prefix: &OwnedPath,
+    ) -> Result<BTreeSet<OwnedPath>> {
+        let mut keys: BTreeSet<_> = self
+            .snapshot
+            .keys()
+            .filter(|key| *key != prefix && is_nested(key, prefix))
+            .cloned()
+            .collect();
+
+        let mut dirs = vec![prefix.clone()];
+        while let Some(dir) = dirs.pop() {
+            for key in index::children(rt, &dir)? {
+                if self.is_removed(&key) {
+                    continue;
+                }
+
+                if Storage::contains_key(rt, &key)? {
+                    keys.insert(key.clone());
+                }
+                dirs.push(key);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Returns the key-value pairs nested under `prefix` (see
+    /// [`Transaction::keys_with_prefix`]), in key order. Values are read as by
+    /// [`Transaction::get`], so uncommitted writes are reflected.
+    pub fn scan_prefix<V>(
+        &mut self,
+        rt: &impl Runtime,
+        prefix: &OwnedPath,
+    ) -> Result<Vec<(OwnedPath, V)>>
+    where
+        V: Value + DeserializeOwned + Clone,
+    {
+        let keys = self.keys_with_prefix(rt, prefix)?;
+
+        let mut entries = Vec::with_capacity(keys.len());
+        for key in keys {
+            if let Some(value) = self.get::<V>(rt, key.clone())? {
+                entries.push((key, value.clone()));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns `true` if `key`, or any key it is nested under, has been
+    /// removed in this transaction. Removed keys act as tombstones: the
+    /// persistent store is not read for them.
+    fn is_removed(&self, key: &OwnedPath) -> bool {
+        self.remove_set
+            .iter()
+            .any(|removed| is_nested(key, removed))
     }
 
     fn lookup<'a, V>(
@@ -172,11 +370,24 @@ impl Transaction {
     where
         V: Value + DeserializeOwned,
     {
+        let is_removed = self.is_removed(&key);
         let entry = self.snapshot.entry(key);
 
         match entry {
+            btree_map::Entry::Vacant(_) if is_removed => Ok(None),
             btree_map::Entry::Vacant(entry) => {
-                if let Some(value) = Storage::get::<V>(rt, entry.key())? {
+                let bytes = Storage::get_bytes(rt, entry.key())?;
+
+                // Only the first read of a key is recorded, so that any change made
+                // since is detected
+                if let Some(read_versions) = &mut self.read_versions {
+                    read_versions
+                        .entry(entry.key().clone())
+                        .or_insert_with(|| bytes.clone());
+                }
+
+                if let Some(bytes) = bytes {
+                    let value: V = value::deserialize(&bytes);
                     let snapshot_entry = entry.insert(SnapshotEntry::persistent(value));
 
                     return Ok(Some(snapshot_entry));
@@ -199,7 +410,8 @@ impl Transaction {
     }
 
     /// Returns a mutable reference to the value corresponding to the key in the
-    /// key-value store if it exists.
+    /// key-value store if it exists. Fails with `Error::ReadOnlyViolation` if
+    /// the transaction is read-only.
     pub fn get_mut<V>(
         &mut self,
         rt: &impl Runtime,
@@ -208,14 +420,32 @@ impl Transaction {
     where
         V: Value + DeserializeOwned,
     {
-        self.lookup::<V>(rt, key)
-            .map(|entry_opt| entry_opt.map(|entry| entry.as_mut()))
+        self.ensure_writable()?;
+
+        if self.lookup::<V>(rt, key.clone())?.is_none() {
+            return Ok(None);
+        }
+
+        self.record(&key);
+        Ok(self.snapshot.get_mut(&key).map(|entry| entry.as_mut()))
     }
 
     /// Returns `true` if the key-value store contains a key-value pair for the
     /// specified key.
     pub fn contains_key(&self, rt: &impl Runtime, key: &OwnedPath) -> Result<bool> {
-        Ok(self.snapshot.contains_key(key) || Storage::contains_key(rt, key)?)
+        if self.snapshot.contains_key(key) {
+            return Ok(true);
+        }
+
+        Ok(!self.is_removed(key) && Storage::contains_key(rt, key)?)
+    }
+
+    /// Returns the keys of the values held by the transaction, in order. These
+    /// are the keys read or written in the transaction that have not since
+    /// been removed. Keys of the persistent store that the transaction has not
+    /// read are not included (see [`Transaction::keys_with_prefix`]).
+    pub fn keys(&self) -> impl Iterator<Item = &OwnedPath> {
+        self.snapshot.keys()
     }
 
     /// Insert a key-value pair into the key-value store.
@@ -223,12 +453,20 @@ impl Transaction {
     where
         V: Value,
     {
+        self.ensure_writable()?;
+
+        self.record(&key);
         self.snapshot.insert(key, SnapshotEntry::ephemeral(value));
         Ok(())
     }
 
-    /// Removes a key from the key-value store.
+    /// Removes a key from the key-value store. Subsequent reads within the
+    /// transaction do not see the key, until it is inserted again. Removing a
+    /// key that does not exist is a no-op.
     pub fn remove(&mut self, rt: &impl Runtime, key: &OwnedPath) -> Result<()> {
+        self.ensure_writable()?;
+
+        self.record(key);
         self.snapshot.remove(key);
         if Storage::contains_key(rt, key)? {
             self.remove_set.insert(key.clone());
@@ -236,8 +474,200 @@ impl Transaction {
         Ok(())
     }
 
+    /// Creates a savepoint, returning its id. Savepoints are released when
+    /// the transaction is committed or rolled back.
+    pub fn savepoint(&mut self) -> SavepointId {
+        let id = self.next_savepoint_id;
+        self.next_savepoint_id += 1;
+
+        self.savepoints.push(Savepoint {
+            id,
+            undo_log: BTreeMap::new(),
+        });
+
+        id
+    }
+
+    /// Records the current state of `key` in the undo log of the latest
+    /// savepoint, unless it has already been written since that savepoint.
+    /// Must be called before any write to `key`.
+    fn record(&mut self, key: &OwnedPath) {
+        if let Some(write_log) = self.write_log.as_mut() {
+            write_log.insert(key.clone());
+        }
+
+        let Some(savepoint) = self.savepoints.last_mut() else {
+            return;
+        };
+
+        if !savepoint.undo_log.contains_key(key) {
+            savepoint.undo_log.insert(
+                key.clone(),
+                Undo {
+                    entry: self.snapshot.get(key).cloned(),
+                    removed: self.remove_set.contains(key),
+                },
+            );
+        }
+    }
+
+    fn undo(&mut self, undo_log: BTreeMap<OwnedPath, Undo>) {
+        for (key, undo) in undo_log {
+            match undo.entry {
+                Some(entry) => self.snapshot.insert(key.clone(), entry),
+                None => self.snapshot.remove(&key),
+            };
+
+            if undo.removed {
+                self.remove_set.insert(key);
+            } else {
+                self.remove_set.remove(&key);
+            }
+        }
+    }
+
+    fn savepoint_position(&self, id: SavepointId) -> Result<usize> {
+        self.savepoints
+            .iter()
+            .position(|savepoint| savepoint.id == id)
+            .ok_or(Error::InvalidSavepoint)
+    }
+
+    /// Returns whether the savepoint `id` has not been released
+    pub fn has_savepoint(&self, id: SavepointId) -> bool {
+        self.savepoint_position(id).is_ok()
+    }
+
+    /// Returns the number of savepoints
+    pub fn savepoint_count(&self) -> usize {
+        self.savepoints.len()
+    }
+
+    /// Discards all changes made since the savepoint `id` was created. The
+    /// savepoint itself is kept, but any savepoints created after it are
+    /// released.
+    pub fn rollback_to(&mut self, id: SavepointId) -> Result<()> {
+        let position = self.savepoint_position(id)?;
+
+        // Undo the writes of later savepoints first, latest to earliest
+        let later: Vec<_> = self.savepoints.drain(position + 1..).collect();
+        for savepoint in later.into_iter().rev() {
+            self.undo(savepoint.undo_log);
+        }
+
+        let undo_log = std::mem::take(&mut self.savepoints[position].undo_log);
+        self.undo(undo_log);
+
+        Ok(())
+    }
+
+    /// Releases the savepoint `id`, keeping all changes made since it was
+    /// created. Savepoints created after it are kept.
+    pub fn release_savepoint(&mut self, id: SavepointId) -> Result<()> {
+        let position = self.savepoint_position(id)?;
+        let savepoint = self.savepoints.remove(position);
+
+        // The writes guarded by the savepoint are now guarded by the previous
+        // one, which keeps its own (earlier) record of any key written by both
+        if let Some(previous) = position
+            .checked_sub(1)
+            .map(|position| &mut self.savepoints[position])
+        {
+            for (key, undo) in savepoint.undo_log {
+                previous.undo_log.entry(key).or_insert(undo);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes `prefix` and all keys nested under it from the key-value store.
+    ///
+    /// Conflicts are only detected on `prefix` itself, not on the nested keys.
+    pub fn remove_prefix(&mut self, prefix: &OwnedPath) -> Result<()> {
+        self.ensure_writable()?;
+
+        if !self.savepoints.is_empty() {
+            let nested: Vec<_> = self
+                .snapshot
+                .keys()
+                .chain(self.remove_set.iter())
+                .filter(|key| is_nested(key, prefix))
+                .cloned()
+                .collect();
+
+            for key in nested.iter().chain([prefix]) {
+                self.record(key);
+            }
+        } else {
+            self.record(prefix);
+        }
+
+        self.snapshot.retain(|key, _| !is_nested(key, prefix));
+        self.remove_set.retain(|key| !is_nested(key, prefix));
+        self.remove_set.insert(prefix.clone());
+        Ok(())
+    }
+
+    /// Merges the writes of `other` into this transaction.
+    ///
+    /// Values read by `other` are added to this transaction's read set (unless
+    /// already present), so that they are validated when this transaction is
+    /// committed. The same holds for the values recorded by `other` when both
+    /// transactions have conflict checking enabled. If any key is written (inserted or removed) by both
+    /// transactions, the merge is rejected and neither transaction is modified.
+    pub fn merge(&mut self, other: Transaction) -> Result<()> {
+        self.ensure_writable()?;
+
+        if self
+            .update_set()
+            .intersection(&other.update_set())
+            .next()
+            .is_some()
+        {
+            return Err(Error::TransactionConflict);
+        }
+
+        if let (Some(read_versions), Some(other_read_versions)) =
+            (&mut self.read_versions, other.read_versions)
+        {
+            for (key, bytes) in other_read_versions {
+                read_versions.entry(key).or_insert(bytes);
+            }
+        }
+
+        for key in other.remove_set.iter() {
+            self.record(key);
+        }
+        for (key, entry) in other.snapshot.iter() {
+            if entry.dirty {
+                self.record(key);
+            }
+        }
+
+        for key in other.remove_set {
+            self.snapshot.remove(&key);
+            self.remove_set.insert(key);
+        }
+
+        for (key, entry) in other.snapshot.into_iter() {
+            if entry.dirty {
+                self.remove_set.remove(&key);
+                self.snapshot.insert(key, entry);
+            } else {
+                self.snapshot.entry(key).or_insert(entry);
+            }
+        }
+
+        self.indexed_prefixes.extend(other.indexed_prefixes);
+
+        Ok(())
+    }
+
     /// Returns the given key's corresponding entry in the transactional
-    /// snapshot for in-place manipulation.
+    /// snapshot for in-place manipulation. The entry may be read in a
+    /// read-only transaction, but writing to it fails with
+    /// `Error::ReadOnlyViolation`.
     pub fn entry<'a, 'b, V>(
         &'a mut self,
         rt: &impl Runtime,
@@ -248,17 +678,38 @@ impl Transaction {
         'a: 'b,
     {
         self.lookup::<V>(rt, key.clone())?;
+        self.record(&key);
 
         match self.snapshot.entry(key) {
-            btree_map::Entry::Vacant(inner) => Ok(Entry::Vacant(VacantEntry::new(inner))),
+            btree_map::Entry::Vacant(inner) => {
+                Ok(Entry::Vacant(VacantEntry::new(self.read_only, inner)))
+            }
             btree_map::Entry::Occupied(inner) => Ok(Entry::Occupied(OccupiedEntry::new(
                 &mut self.remove_set,
+                self.read_only,
                 inner,
             ))),
         }
     }
 }
 
+/// Returns `Error::ReadOnlyViolation` if writes are made to an entry of a
+/// read-only transaction
+fn ensure_writable(read_only: bool) -> Result<()> {
+    if read_only {
+        return Err(Error::ReadOnlyViolation);
+    }
+
+    Ok(())
+}
+
+/// Returns `true` if `key` is `prefix` or is nested under it
+pub(super) fn is_nested(key: &OwnedPath, prefix: &OwnedPath) -> bool {
+    key.as_bytes()
+        .strip_prefix(prefix.as_bytes())
+        .map_or(false, |rest| rest.is_empty() || rest.starts_with(b"/"))
+}
+
 /// A view into a single entry in the transaction snapshot, which is either
 /// vacant or occupied.
 pub enum Entry<'a, V: 'a> {
@@ -270,7 +721,9 @@ pub enum Entry<'a, V: 'a> {
 }
 
 impl<'a, V> Entry<'a, V> {
-    pub fn or_insert_default(self) -> &'a mut V
+    /// Ensures a value is in the entry by inserting the default value if
+    /// empty, and returns a mutable reference to the value in the entry.
+    pub fn or_insert_default(self) -> Result<&'a mut V>
     where
         V: Value + Default,
     {
@@ -283,13 +736,18 @@ impl<'a, V> Entry<'a, V> {
 
 /// A view into a vacant entry in the transactional snapshot.
 pub struct VacantEntry<'a, V: 'a> {
+    read_only: bool,
     inner: btree_map::VacantEntry<'a, OwnedPath, SnapshotEntry>,
     _marker: PhantomData<V>,
 }
 
 impl<'a, V: 'a> VacantEntry<'a, V> {
-    fn new(inner: btree_map::VacantEntry<'a, OwnedPath, SnapshotEntry>) -> Self {
+    fn new(
+        read_only: bool,
+        inner: btree_map::VacantEntry<'a, OwnedPath, SnapshotEntry>,
+    ) -> Self {
         Self {
+            read_only,
             inner,
             _marker: PhantomData,
         }
@@ -307,13 +765,16 @@ impl<'a, V: 'a> VacantEntry<'a, V> {
 
     /// Set the value of the entry using the entry's key and return a mutable
     /// reference to the value.
-    pub fn insert(self, value: V) -> &'a mut V
+    pub fn insert(self, value: V) -> Result<&'a mut V>
     where
         V: Value,
     {
-        self.inner
+        ensure_writable(self.read_only)?;
+
+        Ok(self
+            .inner
             .insert(SnapshotEntry::ephemeral::<V>(value))
-            .as_mut()
+            .as_mut())
     }
 }
 
@@ -321,6 +782,7 @@ impl<'a, V: 'a> VacantEntry<'a, V> {
 
 pub struct OccupiedEntry<'a, V: 'a> {
     remove_set: &'a mut BTreeSet<OwnedPath>,
+    read_only: bool,
     inner: btree_map::OccupiedEntry<'a, OwnedPath, SnapshotEntry>,
     _marker: PhantomData<V>,
 }
@@ -328,10 +790,12 @@ pub struct OccupiedEntry<'a, V: 'a> {
 impl<'a, V> OccupiedEntry<'a, V> {
     fn new(
         remove_set: &'a mut BTreeSet<OwnedPath>,
+        read_only: bool,
         inner: btree_map::OccupiedEntry<'a, OwnedPath, SnapshotEntry>,
     ) -> Self {
         Self {
             remove_set,
+            read_only,
             inner,
             _marker: PhantomData,
         }
@@ -344,13 +808,15 @@ impl<'a, V> OccupiedEntry<'a, V> {
 
     /// Takes the key-value pair out of the snapshot, returning ownership
     /// to the caller.
-    pub fn remove_entry(self) -> (OwnedPath, V)
+    pub fn remove_entry(self) -> Result<(OwnedPath, V)>
     where
         V: Value,
     {
+        ensure_writable(self.read_only)?;
+
         let (key, entry) = self.inner.remove_entry();
         self.remove_set.insert(key.clone());
-        (key, entry.into_value())
+        Ok((key, entry.into_value()))
     }
 
     /// Gets a reference to the value in the entry.
@@ -362,35 +828,249 @@ impl<'a, V> OccupiedEntry<'a, V> {
     }
 
     /// Get a mutable reference to the value in the entry.
-    pub fn get_mut(&mut self) -> &mut V
+    pub fn get_mut(&mut self) -> Result<&mut V>
     where
         V: Value,
     {
-        self.inner.get_mut().as_mut()
+        ensure_writable(self.read_only)?;
+
+        Ok(self.inner.get_mut().as_mut())
     }
 
     /// Convert the entry into a mutable reference to its value.
-    pub fn into_mut(self) -> &'a mut V
+    pub fn into_mut(self) -> Result<&'a mut V>
     where
         V: Value,
     {
-        self.inner.into_mut().as_mut()
+        ensure_writable(self.read_only)?;
+
+        Ok(self.inner.into_mut().as_mut())
     }
 
     /// Sets the value of the entry and returns the entry's old value.
-    pub fn insert(&mut self, value: V) -> V
+    pub fn insert(&mut self, value: V) -> Result<V>
     where
         V: Value,
     {
-        std::mem::replace(self.get_mut(), value)
+        Ok(std::mem::replace(self.get_mut()?, value))
     }
 
     /// Take the value of the entry out of the snapshot, and return it.
-    pub fn remove(self) -> V
+    pub fn remove(self) -> Result<V>
     where
         V: Value,
     {
+        ensure_writable(self.read_only)?;
+
         self.remove_set.insert(self.key().clone());
-        self.inner.remove().into_value()
+        Ok(self.inner.remove().into_value())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn path(key: &str) -> OwnedPath {
+        OwnedPath::try_from(key.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_merge_applies_writes() {
+        let mut tx = Transaction::new(0, false);
+        let mut other = Transaction::new(0, false);
+
+        tx.insert(path("/a"), 1u64).unwrap();
+        other.insert(path("/b"), 2u64).unwrap();
+        other.remove_set.insert(path("/c"));
+
+        tx.merge(other).unwrap();
+
+        assert_eq!(tx.snapshot.get(&path("/a")).unwrap().as_ref::<u64>(), &1);
+        assert_eq!(tx.snapshot.get(&path("/b")).unwrap().as_ref::<u64>(), &2);
+        assert!(tx.remove_set.contains(&path("/c")));
+        assert_eq!(
+            tx.update_set(),
+            BTreeSet::from([path("/a"), path("/b"), path("/c")])
+        );
+    }
+
+    #[test]
+    fn test_merge_rejects_conflicting_writes() {
+        let mut tx = Transaction::new(0, false);
+        let mut other = Transaction::new(0, false);
+
+        tx.insert(path("/a"), 1u64).unwrap();
+        other.insert(path("/a"), 2u64).unwrap();
+        other.insert(path("/b"), 3u64).unwrap();
+
+        assert!(matches!(tx.merge(other), Err(Error::TransactionConflict)));
+
+        // The original transaction is left untouched
+        assert_eq!(tx.snapshot.get(&path("/a")).unwrap().as_ref::<u64>(), &1);
+        assert!(!tx.snapshot.contains_key(&path("/b")));
+    }
+
+    #[test]
+    fn test_remove_prefix_drops_nested_keys() {
+        let mut tx = Transaction::new(0, false);
+
+        tx.insert(path("/a"), 1u64).unwrap();
+        tx.insert(path("/a/b"), 2u64).unwrap();
+        tx.insert(path("/ab"), 3u64).unwrap();
+        tx.remove_set.insert(path("/a/c"));
+
+        tx.remove_prefix(&path("/a")).unwrap();
+
+        assert!(!tx.snapshot.contains_key(&path("/a")));
+        assert!(!tx.snapshot.contains_key(&path("/a/b")));
+        assert_eq!(tx.snapshot.get(&path("/ab")).unwrap().as_ref::<u64>(), &3);
+        assert_eq!(tx.remove_set, BTreeSet::from([path("/a")]));
+    }
+
+    #[test]
+    fn test_rollback_to() {
+        let mut tx = Transaction::new(0, false);
+
+        tx.insert(path("/a"), 1u64).unwrap();
+        let outer = tx.savepoint();
+        tx.insert(path("/a"), 2u64).unwrap();
+        tx.insert(path("/b"), 3u64).unwrap();
+        let inner = tx.savepoint();
+        tx.remove_prefix(&path("/c")).unwrap();
+
+        tx.rollback_to(inner).unwrap();
+        assert!(tx.remove_set.is_empty());
+        assert_eq!(tx.snapshot.get(&path("/a")).unwrap().as_ref::<u64>(), &2);
+
+        tx.rollback_to(outer).unwrap();
+        assert_eq!(tx.snapshot.get(&path("/a")).unwrap().as_ref::<u64>(), &1);
+        assert!(!tx.snapshot.contains_key(&path("/b")));
+        assert_eq!(tx.savepoint_count(), 1);
+
+        assert!(matches!(
+            tx.rollback_to(inner),
+            Err(Error::InvalidSavepoint)
+        ));
+
+        tx.insert(path("/d"), 4u64).unwrap();
+        tx.release_savepoint(outer).unwrap();
+        assert_eq!(tx.savepoint_count(), 0);
+        assert_eq!(tx.snapshot.get(&path("/d")).unwrap().as_ref::<u64>(), &4);
+    }
+
+    #[test]
+    fn test_rollback_undoes_in_place_writes() {
+        let rt = tezos_smart_rollup_mock::MockHost::default();
+        let mut tx = Transaction::new(0, false);
+
+        tx.insert(path("/a"), 1u64).unwrap();
+        let outer = tx.savepoint();
+        *tx.get_mut::<u64>(&rt, path("/a")).unwrap().unwrap() = 2;
+        let inner = tx.savepoint();
+        *tx.get_mut::<u64>(&rt, path("/a")).unwrap().unwrap() = 3;
+        tx.insert(path("/b"), 4u64).unwrap();
+
+        // Releasing the inner savepoint hands its writes to the outer one
+        tx.release_savepoint(inner).unwrap();
+        tx.rollback_to(outer).unwrap();
+
+        assert_eq!(tx.get::<u64>(&rt, path("/a")).unwrap(), Some(&1));
+        assert_eq!(tx.get::<u64>(&rt, path("/b")).unwrap(), None);
+        assert_eq!(tx.update_set(), BTreeSet::from([path("/a")]));
+    }
+
+    #[test]
+    fn test_write_log_keeps_rolled_back_writes() {
+        let mut tx = Transaction::new(0, false).with_write_log();
+
+        tx.insert(path("/a"), 1u64).unwrap();
+        let savepoint = tx.savepoint();
+        tx.insert(path("/b"), 2u64).unwrap();
+        tx.remove_prefix(&path("/c")).unwrap();
+        tx.rollback_to(savepoint).unwrap();
+
+        assert_eq!(tx.update_set(), BTreeSet::from([path("/a")]));
+        assert_eq!(
+            tx.write_log(),
+            Some(&BTreeSet::from([path("/a"), path("/b"), path("/c")]))
+        );
+        assert!(Transaction::new(0, false).write_log().is_none());
+    }
+
+    #[test]
+    fn test_read_only_rejects_writes() {
+        let mut tx = Transaction::new(0, true);
+        let mut other = Transaction::new(0, false);
+        other.insert(path("/b"), 2u64).unwrap();
+
+        assert!(matches!(
+            tx.insert(path("/a"), 1u64),
+            Err(Error::ReadOnlyViolation)
+        ));
+        assert!(matches!(tx.merge(other), Err(Error::ReadOnlyViolation)));
+        assert!(matches!(
+            tx.remove_prefix(&path("/c")),
+            Err(Error::ReadOnlyViolation)
+        ));
+        assert!(tx.update_set().is_empty());
+    }
+
+    #[test]
+    fn test_savepoints_out_of_order() {
+        let mut tx = Transaction::new(0, false);
+
+        let first = tx.savepoint();
+        tx.insert(path("/a"), 1u64).unwrap();
+        let second = tx.savepoint();
+        tx.insert(path("/b"), 2u64).unwrap();
+
+        // Releasing an earlier savepoint keeps the later ones
+        tx.release_savepoint(first).unwrap();
+        tx.rollback_to(second).unwrap();
+        assert_eq!(tx.snapshot.get(&path("/a")).unwrap().as_ref::<u64>(), &1);
+        assert!(!tx.snapshot.contains_key(&path("/b")));
+        tx.release_savepoint(second).unwrap();
+
+        // Rolling back to an earlier savepoint releases the later ones
+        let third = tx.savepoint();
+        tx.insert(path("/c"), 3u64).unwrap();
+        let fourth = tx.savepoint();
+        tx.insert(path("/d"), 4u64).unwrap();
+
+        tx.rollback_to(third).unwrap();
+        assert!(!tx.snapshot.contains_key(&path("/c")));
+        assert!(!tx.snapshot.contains_key(&path("/d")));
+        assert!(matches!(
+            tx.rollback_to(fourth),
+            Err(Error::InvalidSavepoint)
+        ));
+        assert!(matches!(
+            tx.release_savepoint(fourth),
+            Err(Error::InvalidSavepoint)
+        ));
+
+        tx.release_savepoint(third).unwrap();
+        assert_eq!(tx.savepoint_count(), 0);
+        assert!(matches!(
+            tx.rollback_to(first),
+            Err(Error::InvalidSavepoint)
+        ));
+    }
+
+    #[test]
+    fn test_keys_reflect_pending_writes() {
+        let mut tx = Transaction::new(0, false);
+
+        tx.insert(path("/b"), 1u64).unwrap();
+        tx.insert(path("/a"), 2u64).unwrap();
+        tx.insert(path("/a/c"), 3u64).unwrap();
+        tx.remove_prefix(&path("/a")).unwrap();
+        tx.insert(path("/a"), 4u64).unwrap();
+
+        assert_eq!(
+            tx.keys().cloned().collect::<Vec<_>>(),
+            vec![path("/a"), path("/b")]
+        );
     }
 }
@@ -29,15 +29,16 @@ pub(crate) fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> T {
 }
 
 /// A key-value 'value' is a value that is can be dynamically
-/// coerced (using `Any`) and serialized.
+/// coerced (using `Any`), cloned and serialized.
 pub trait Value: Any + Debug + erased_serde::Serialize {
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn clone_box(&self) -> Box<dyn Value>;
 }
 
 impl<T> Value for T
 where
-    T: Any + Debug + erased_serde::Serialize,
+    T: Any + Debug + Clone + erased_serde::Serialize,
 {
     fn as_any(&self) -> &dyn Any {
         self
@@ -46,11 +47,21 @@ where
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
+
+    fn clone_box(&self) -> Box<dyn Value> {
+        Box::new(self.clone())
+    }
 }
 
 #[derive(Debug, Deref, DerefMut)]
 pub(crate) struct BoxedValue(Box<dyn Value>);
 
+impl Clone for BoxedValue {
+    fn clone(&self) -> Self {
+        BoxedValue(self.0.clone_box())
+    }
+}
+
 impl BoxedValue {
     pub fn new(value: impl Value) -> Self {
         BoxedValue(Box::new(value))
@@ -10,6 +10,8 @@ pub mod kv;
 pub mod native;
 pub mod realm;
 pub mod runtime;
+pub mod structured_clone;
+pub mod timers;
 pub mod value;
 
 /// A generic runtime API
@@ -12,11 +12,13 @@ use boa_engine::{
     property::Attribute,
     Context, JsObject, JsResult, JsValue, Source,
 };
-use boa_gc::{empty_trace, Finalize, GcRef, GcRefCell, GcRefMut, Trace};
+use boa_gc::{empty_trace, Finalize, Gc, GcRef, GcRefCell, GcRefMut, Trace};
 use derive_more::{Deref, DerefMut, From};
 
 use crate::{
     native::{register_global_class, NativeClass},
+    runtime, structured_clone,
+    timers::{self, Timers},
     Api,
 };
 
@@ -169,8 +171,9 @@ unsafe impl Trace for TracedTypeId {
     empty_trace!();
 }
 
-/// Map used to store the host defined objects.
-type HostDefinedMap = HashMap<TracedTypeId, GcRefCell<Box<dyn NativeObject>>>;
+/// Map used to store the host defined objects. Entries are garbage collected
+/// pointers so that they can be shared between realms.
+type HostDefinedMap = HashMap<TracedTypeId, Gc<GcRefCell<Box<dyn NativeObject>>>>;
 
 /// This represents the `ECMAScript` specification notion of 'host defined'
 /// objects.
@@ -181,6 +184,10 @@ pub struct HostDefined {
     env: HostDefinedMap,
 }
 
+/// The value left in a shared entry once its value has been moved out
+#[derive(Trace, Finalize)]
+struct Removed;
+
 unsafe fn downcast_boxed_native_object_unchecked<T: NativeObject>(
     obj: Box<dyn NativeObject>,
 ) -> Box<T> {
@@ -188,6 +195,17 @@ unsafe fn downcast_boxed_native_object_unchecked<T: NativeObject>(
     Box::from_raw(raw as *mut T)
 }
 
+/// Moves the value of type `T` out of `entry`, leaving [`Removed`] in its
+/// place for the other `HostDefined`s sharing the entry
+fn take<T: NativeObject>(entry: &GcRefCell<Box<dyn NativeObject>>) -> Option<Box<T>> {
+    if !entry.borrow().as_ref().as_any().is::<T>() {
+        return None;
+    }
+
+    let obj = std::mem::replace(&mut *entry.borrow_mut(), Box::new(Removed));
+    Some(unsafe { downcast_boxed_native_object_unchecked(obj) })
+}
+
 impl HostDefined {
     pub fn new() -> Self {
         Self {
@@ -198,34 +216,51 @@ impl HostDefined {
     #[track_caller]
     pub fn insert<T: NativeObject>(&mut self, value: T) -> Option<Box<T>> {
         self.env
-            .insert(TracedTypeId::of::<T>(), GcRefCell::new(Box::new(value)))
-            .map(|obj| unsafe {
-                downcast_boxed_native_object_unchecked(obj.into_inner())
-            })
+            .insert(
+                TracedTypeId::of::<T>(),
+                Gc::new(GcRefCell::new(Box::new(value))),
+            )
+            .and_then(|entry| take(&entry))
     }
 
     #[track_caller]
     pub fn remove<T: NativeObject>(&mut self) -> Option<Box<T>> {
-        self.env.remove(&TracedTypeId::of::<T>()).map(|obj| unsafe {
-            downcast_boxed_native_object_unchecked(obj.into_inner())
-        })
+        let entry = self.env.remove(&TracedTypeId::of::<T>())?;
+        take(&entry)
+    }
+
+    /// Shares the value of type `T` with `other`, if any. Both then read and
+    /// modify the same value, until either removes or replaces it. Returns
+    /// `true` if the value was shared.
+    #[track_caller]
+    pub fn share<T: NativeObject>(&self, other: &mut HostDefined) -> bool {
+        match self.env.get(&TracedTypeId::of::<T>()) {
+            Some(entry) if self.has::<T>() => {
+                other.env.insert(TracedTypeId::of::<T>(), entry.clone());
+                true
+            }
+            _ => false,
+        }
     }
 
     #[track_caller]
     pub fn has<T: NativeObject>(&self) -> bool {
-        self.env.contains_key(&TracedTypeId::of::<T>())
+        // A mutably borrowed entry holds a `T`, as `Removed` is never
+        // borrowed mutably
+        self.env.get(&TracedTypeId::of::<T>()).is_some_and(|entry| {
+            entry
+                .try_borrow()
+                .map_or(true, |obj| obj.as_ref().as_any().is::<T>())
+        })
     }
 
     #[track_caller]
     pub fn get<T: NativeObject>(&self) -> Option<GcRef<'_, T>> {
         let entry = self.env.get(&TracedTypeId::of::<T>())?;
 
-        Some(GcRef::map(entry.borrow(), |obj| {
-            obj.as_ref()
-                .as_any()
-                .downcast_ref::<T>()
-                .expect("Why cruel world!")
-        }))
+        GcRef::try_map(entry.borrow(), |obj| {
+            obj.as_ref().as_any().downcast_ref::<T>()
+        })
     }
 
     #[track_caller]
@@ -234,15 +269,9 @@ impl HostDefined {
     ) -> Option<GcRefMut<'_, Box<dyn NativeObject>, T>> {
         let entry = self.env.get(&TracedTypeId::of::<T>())?;
 
-        Some(GcRefMut::map(
-            entry.borrow_mut(),
-            |obj: &mut Box<dyn NativeObject>| {
-                obj.as_mut()
-                    .as_mut_any()
-                    .downcast_mut::<T>()
-                    .expect("Why cruel world!")
-            },
-        ))
+        GcRefMut::try_map(entry.borrow_mut(), |obj: &mut Box<dyn NativeObject>| {
+            obj.as_mut().as_mut_any().downcast_mut::<T>()
+        })
     }
 
     #[track_caller]
@@ -317,10 +346,19 @@ impl Realm {
             inner: context.create_realm()?,
         };
 
-        // 2. Initialize `HostDefined`
+        // 2. Initialize `HostDefined`, sharing the timers of the current realm
+        let mut host_defined = HostDefined::new();
+        if let Some(timers) = Timers::from_context(context) {
+            host_defined.insert(timers);
+        }
+
+        // 3. Register the globals of the runtime
         {
             let mut context = realm.context_handle(context);
-            HostDefined::new().init(&mut context);
+            host_defined.init(&mut context);
+            runtime::register_queue_microtask(&mut context);
+            timers::register_timers(&mut context);
+            structured_clone::register_structured_clone(&mut context);
         }
 
         Ok(realm)
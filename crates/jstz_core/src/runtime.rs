@@ -1,5 +1,5 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::VecDeque,
     future::poll_fn,
     io::Read,
@@ -9,14 +9,18 @@ use std::{
 };
 
 use boa_engine::{
-    builtins::promise::PromiseState, job::NativeJob, object::builtins::JsPromise,
-    Context, JsError, JsNativeError, JsResult, JsValue, Source,
+    builtins::promise::PromiseState, job::NativeJob, js_string,
+    object::builtins::JsPromise, Context, JsArgs, JsError, JsNativeError,
+    JsNativeErrorKind, JsResult, JsValue, NativeFunction, Source,
 };
 
 use crate::{
+    error::{Error, Result},
     future,
     host::{Host, HostRuntime},
+    host_defined,
     realm::{Module, Realm},
+    timers::Timers,
 };
 
 /// A 'pollable' job queue
@@ -32,6 +36,14 @@ impl JobQueue {
         self.0.borrow_mut().pop_front()
     }
 
+    fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    fn clear(&self) {
+        self.0.borrow_mut().clear()
+    }
+
     pub fn call_next(&self, context: &mut Context<'_>) -> Option<JsResult<JsValue>> {
         let job = self.next()?;
         Some(job.call(context))
@@ -66,7 +78,90 @@ impl boa_engine::job::JobQueue for JobQueue {
 
 thread_local! {
     /// Thread-local host
-    static HOST: RefCell<Option<Host>> = RefCell::new(None)
+    static HOST: RefCell<Option<Host>> = RefCell::new(None);
+
+    /// The fuel left to the metered runtime, if any
+    static FUEL: Cell<Option<u64>> = Cell::new(None)
+}
+
+/// The fuel consumed by each job (promise reaction, microtask or timer) run
+/// by the event loop
+pub const JOB_FUEL: u64 = 1_000;
+
+/// The fuel consumed by each access to the host, such as a `Kv` read or write
+pub const HOST_FUEL: u64 = 100;
+
+/// The maximum depth of recursion of a metered runtime
+pub const MAX_RECURSION_DEPTH: usize = 512;
+
+fn consume_fuel(amount: u64) {
+    FUEL.with(|fuel| fuel.set(fuel.get().map(|fuel| fuel.saturating_sub(amount))))
+}
+
+fn exhaust_fuel() {
+    FUEL.with(|fuel| fuel.set(fuel.get().map(|_| 0)))
+}
+
+/// Returns the fuel left to the metered runtime, or `u64::MAX` if no runtime
+/// is metered
+pub fn fuel_remaining() -> u64 {
+    FUEL.with(|fuel| fuel.get().unwrap_or(u64::MAX))
+}
+
+/// Sets the fuel left to the metered runtime, if any, to `fuel`, such as to
+/// refund the fuel consumed by a dry run
+pub fn restore_fuel(fuel: u64) {
+    FUEL.with(|cell| cell.set(cell.get().map(|_| fuel)))
+}
+
+/// Returns `true` if `err`, or one of its causes, was thrown because a loop or
+/// a recursion exceeded the limits of the runtime. In a metered runtime, this
+/// means that the fuel is exhausted.
+pub fn is_runtime_limit(err: &JsError) -> bool {
+    let Some(err) = err.as_native() else {
+        return false;
+    };
+
+    matches!(err.kind, JsNativeErrorKind::RuntimeLimit)
+        || err.cause().is_some_and(is_runtime_limit)
+}
+
+/// `queueMicrotask(callback)`
+///
+/// Enqueues `callback` on the job queue, so that it runs after the current
+/// script and in order with promise reactions. Like a rejected promise without
+/// a handler, an error thrown by `callback` is dropped by the job queue and
+/// does not abort the event loop.
+///
+/// [spec]: https://html.spec.whatwg.org/multipage/timers-and-user-prompts.html#dom-queuemicrotask
+fn queue_microtask(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let callback = args
+        .get_or_undefined(0)
+        .as_callable()
+        .cloned()
+        .ok_or_else(|| {
+            JsNativeError::typ().with_message("The callback provided is not a function")
+        })?;
+
+    let job =
+        NativeJob::new(move |context| callback.call(&JsValue::undefined(), &[], context));
+    context.job_queue().enqueue_promise_job(job, context);
+
+    Ok(JsValue::undefined())
+}
+
+pub(crate) fn register_queue_microtask(context: &mut Context<'_>) {
+    context
+        .register_global_builtin_callable(
+            js_string!("queueMicrotask"),
+            1,
+            NativeFunction::from_fn_ptr(queue_microtask),
+        )
+        .expect("queueMicrotask should only be registered once");
 }
 
 pub fn with_host_runtime<F, R>(hrt: &mut (impl HostRuntime + 'static), f: F) -> R
@@ -82,10 +177,14 @@ where
     result
 }
 
+/// Runs `f` with the host set by [`with_host_runtime`]. Each access consumes
+/// [`HOST_FUEL`] from the metered runtime, if any.
 pub fn with_global_host<F, R>(f: F) -> R
 where
     F: FnOnce(&mut Host) -> R,
 {
+    consume_fuel(HOST_FUEL);
+
     HOST.with(|host| f(host.borrow_mut().as_mut().expect("Host should be set")))
 }
 
@@ -96,6 +195,11 @@ pub struct Runtime<'host> {
     // There will only ever be 2 references to the `job_queue`.
     // The context's internal reference and the runtime's reference.
     job_queue: Rc<JobQueue>,
+    // The timers of all realms, run once the job queue is empty
+    timers: Timers,
+    // The number of jobs the event loop may still run, if limited
+    ticks_remaining: Option<u64>,
+    timed_out: bool,
 }
 
 impl<'host> Deref for Runtime<'host> {
@@ -129,10 +233,23 @@ impl<'host> Runtime<'host> {
 
         context.enter_realm(realm.inner.clone());
 
+        // 4. Initialize timers, which realms created from this one share
+        let timers = Timers::default();
+        {
+            host_defined!(&mut context, mut host_defined);
+            host_defined.insert(timers.clone());
+        }
+
+        // 5. The runtime is not metered until `Runtime::set_fuel` is called
+        FUEL.with(|fuel| fuel.set(None));
+
         Ok(Self {
             context,
             realm,
             job_queue,
+            timers,
+            ticks_remaining: None,
+            timed_out: false,
         })
     }
 
@@ -150,6 +267,38 @@ impl<'host> Runtime<'host> {
         self.realm.eval(src, &mut self.context)
     }
 
+    /// Meters the runtime with `fuel`, which is shared by all its realms.
+    ///
+    /// Each job run by the event loop consumes [`JOB_FUEL`], and each access
+    /// to the host [`HOST_FUEL`]. Once the fuel is exhausted, pending jobs are
+    /// aborted with `Error::OutOfFuel`. Synchronous code is bounded by the
+    /// runtime limits of the engine: no loop may iterate more times than the
+    /// fuel left when it was set, and no recursion may be deeper than
+    /// [`MAX_RECURSION_DEPTH`]. Exceeding them exhausts the fuel (see
+    /// [`is_runtime_limit`]).
+    pub fn set_fuel(&mut self, fuel: u64) {
+        FUEL.with(|cell| cell.set(Some(fuel)));
+
+        let limits = self.context.runtime_limits_mut();
+        limits.set_loop_iteration_limit(fuel);
+        limits.set_recursion_limit(MAX_RECURSION_DEPTH);
+    }
+
+    /// Returns the fuel left to the runtime (see [`Runtime::set_fuel`])
+    pub fn fuel_remaining(&self) -> u64 {
+        fuel_remaining()
+    }
+
+    /// Exhausts the fuel of the runtime if `err` was thrown because a runtime
+    /// limit was exceeded, returning `true` if the fuel is exhausted
+    pub fn exhaust_fuel_on(&mut self, err: &JsError) -> bool {
+        if is_runtime_limit(err) {
+            exhaust_fuel();
+        }
+
+        self.fuel_remaining() == 0
+    }
+
     pub fn context(&mut self) -> &mut Context<'host> {
         self.deref_mut()
     }
@@ -158,19 +307,85 @@ impl<'host> Runtime<'host> {
         &self.realm
     }
 
-    /// Runs the event loop (job queue) to completion
-    pub async fn run_event_loop(&mut self) {
+    /// Returns `true` if the last run of the event loop was aborted with
+    /// `Error::Timeout`
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    fn set_deadline(&mut self, max_ticks: Option<u64>) {
+        self.ticks_remaining = max_ticks;
+        self.timed_out = false;
+    }
+
+    /// Returns the timers of the runtime
+    pub fn timers(&self) -> &Timers {
+        &self.timers
+    }
+
+    /// Aborts all pending jobs and timers
+    fn abort(&mut self) {
+        self.job_queue.clear();
+        self.timers.clear();
+        self.context.clear_kept_objects();
+    }
+
+    fn time_out(&mut self) -> Error {
+        self.abort();
+        self.timed_out = true;
+
+        Error::Timeout
+    }
+
+    /// Runs the event loop (job queue) to completion. If `max_ticks` is set
+    /// and the event loop runs more jobs than that, pending jobs are aborted
+    /// with `Error::Timeout`.
+    ///
+    /// The deadline is counted in ticks rather than time, so that it is
+    /// deterministic (and available in the kernel, which has no clock).
+    /// Likewise, timers run on a virtual clock: once the job queue is empty,
+    /// the timer with the earliest deadline runs and counts as a tick.
+    pub async fn run_event_loop(&mut self, max_ticks: Option<u64>) -> Result<()> {
+        self.set_deadline(max_ticks);
+
         poll_fn(|_| self.poll_event_loop()).await
     }
 
     /// Runs a single tick of the event loop
-    pub fn poll_event_loop(&mut self) -> Poll<()> {
-        match self.job_queue.call_next(&mut self.context) {
+    pub fn poll_event_loop(&mut self) -> Poll<Result<()>> {
+        let pending = !(self.job_queue.is_empty() && self.timers.is_empty());
+        if pending && fuel_remaining() == 0 {
+            self.abort();
+            return Poll::Ready(Err(Error::OutOfFuel));
+        }
+
+        if self.ticks_remaining == Some(0)
+            && !(self.job_queue.is_empty() && self.timers.is_empty())
+        {
+            return Poll::Ready(Err(self.time_out()));
+        }
+
+        let result = self.job_queue.call_next(&mut self.context).or_else(|| {
+            let job = self.timers.next_job()?;
+            Some(job.call(&mut self.context))
+        });
+
+        match result {
             None => {
                 self.context.clear_kept_objects();
-                Poll::Ready(())
+                Poll::Ready(Ok(()))
+            }
+            Some(result) => {
+                consume_fuel(JOB_FUEL);
+                if let Err(err) = result {
+                    self.exhaust_fuel_on(&err);
+                }
+
+                if let Some(ticks) = self.ticks_remaining.as_mut() {
+                    *ticks -= 1;
+                }
+                Poll::Pending
             }
-            Some(_) => Poll::Pending,
         }
     }
 
@@ -190,9 +405,10 @@ impl<'host> Runtime<'host> {
                 match Self::poll_promise(promise) {
                     Poll::Ready(val) => Poll::Ready(val),
                     Poll::Pending => match self.poll_event_loop() {
-                        Poll::Ready(()) => Poll::Ready(Err(JsNativeError::error()
-                            .with_message("Event loop did not resolve the promise")
-                            .into())),
+                        Poll::Ready(Err(err)) => Poll::Ready(Err(err.into())),
+                        // No job is left that could resolve the promise, so
+                        // it would be waited on forever
+                        Poll::Ready(Ok(())) => Poll::Ready(Err(self.time_out().into())),
                         Poll::Pending => Poll::Pending,
                     },
                 }
@@ -203,6 +419,168 @@ impl<'host> Runtime<'host> {
 
     /// Waits for the given value to resolve while polling the event loop
     pub async fn resolve_value(&mut self, value: &JsValue) -> JsResult<JsValue> {
+        self.resolve_value_within(value, None).await
+    }
+
+    /// Waits for the given value to resolve while polling the event loop,
+    /// running at most `max_ticks` jobs. See [`Runtime::run_event_loop`].
+    pub async fn resolve_value_within(
+        &mut self,
+        value: &JsValue,
+        max_ticks: Option<u64>,
+    ) -> JsResult<JsValue> {
+        self.set_deadline(max_ticks);
+
         poll_fn(|_| self.poll_value(value)).await
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(code: &str) -> String {
+        let mut rt = Runtime::new().unwrap();
+        rt.eval(Source::from_bytes(code)).unwrap();
+        future::block_on(rt.run_event_loop(None)).unwrap();
+
+        rt.eval(Source::from_bytes("order.join(',')"))
+            .unwrap()
+            .as_string()
+            .expect("Expected a string")
+            .to_std_string_escaped()
+    }
+
+    #[test]
+    fn microtasks_run_in_order_with_promise_reactions() {
+        let order = run(r#"
+            globalThis.order = [];
+            Promise.resolve().then(() => order.push("then"));
+            queueMicrotask(() => {
+                order.push("microtask");
+                Promise.resolve().then(() => order.push("nested then"));
+            });
+            Promise.resolve().then(() => order.push("second then"));
+            order.push("sync");
+        "#);
+
+        assert_eq!(order, "sync,then,microtask,second then,nested then");
+    }
+
+    #[test]
+    fn throwing_microtask_does_not_abort_event_loop() {
+        let order = run(r#"
+            globalThis.order = [];
+            queueMicrotask(() => { throw new Error("boom"); });
+            queueMicrotask(() => order.push("after"));
+        "#);
+
+        assert_eq!(order, "after");
+    }
+
+    #[test]
+    fn rejects_non_callable() {
+        let mut rt = Runtime::new().unwrap();
+        let error = rt
+            .eval(Source::from_bytes("queueMicrotask(42)"))
+            .expect_err("Expected an error");
+
+        assert!(error.to_string().starts_with("TypeError"), "{error}");
+    }
+
+    #[test]
+    fn timers_run_by_delay_after_microtasks() {
+        let order = run(r#"
+            globalThis.order = [];
+            setTimeout(() => order.push("200ms"), 200);
+            setTimeout((label) => {
+                order.push(label);
+                Promise.resolve().then(() => order.push("then"));
+                setTimeout(() => order.push("50ms + 200ms"), 200);
+            }, 50, "50ms");
+            setTimeout(() => order.push("second 200ms"), 200);
+            queueMicrotask(() => order.push("microtask"));
+        "#);
+
+        assert_eq!(order, "microtask,50ms,then,200ms,second 200ms,50ms + 200ms");
+    }
+
+    #[test]
+    fn clear_timeout_cancels_pending_timer() {
+        let mut rt = Runtime::new().unwrap();
+        rt.eval(Source::from_bytes(
+            r#"
+            globalThis.order = [];
+            const cancelled = setTimeout(() => order.push("cancelled"), 10);
+            setTimeout(() => order.push("kept"), 20);
+            clearTimeout(cancelled);
+            "#,
+        ))
+        .unwrap();
+        future::block_on(rt.run_event_loop(None)).unwrap();
+
+        let order = rt.eval(Source::from_bytes("order.join(',')")).unwrap();
+        assert_eq!(order.as_string().unwrap().to_std_string_escaped(), "kept");
+        assert_eq!(rt.timers().now(), 20);
+    }
+
+    #[test]
+    fn realms_share_timers_of_runtime() {
+        let mut rt = Runtime::new().unwrap();
+        let realm = Realm::new(rt.context()).unwrap();
+        realm
+            .eval(Source::from_bytes("setTimeout(() => {}, 30)"), rt.context())
+            .unwrap();
+
+        future::block_on(rt.run_event_loop(None)).unwrap();
+        assert_eq!(rt.timers().now(), 30);
+    }
+
+    #[test]
+    fn jobs_consume_fuel() {
+        let mut rt = Runtime::new().unwrap();
+        rt.set_fuel(10 * JOB_FUEL);
+        rt.eval(Source::from_bytes(
+            r#"
+            queueMicrotask(() => {});
+            queueMicrotask(() => {});
+            "#,
+        ))
+        .unwrap();
+
+        future::block_on(rt.run_event_loop(None)).unwrap();
+        assert_eq!(rt.fuel_remaining(), 8 * JOB_FUEL);
+    }
+
+    #[test]
+    fn exhausted_fuel_aborts_pending_jobs() {
+        let mut rt = Runtime::new().unwrap();
+        rt.set_fuel(JOB_FUEL);
+        rt.eval(Source::from_bytes(
+            r#"
+            globalThis.order = [];
+            queueMicrotask(() => order.push("first"));
+            queueMicrotask(() => order.push("second"));
+            "#,
+        ))
+        .unwrap();
+
+        let result = future::block_on(rt.run_event_loop(None));
+        assert!(matches!(result, Err(Error::OutOfFuel)));
+
+        let order = rt.eval(Source::from_bytes("order.join(',')")).unwrap();
+        assert_eq!(order.as_string().unwrap().to_std_string_escaped(), "first");
+    }
+
+    #[test]
+    fn unbounded_loop_exhausts_fuel() {
+        let mut rt = Runtime::new().unwrap();
+        rt.set_fuel(1_000);
+
+        let err = rt
+            .eval(Source::from_bytes("while (true) {}"))
+            .expect_err("Expected the loop to be aborted");
+        assert!(rt.exhaust_fuel_on(&err));
+        assert_eq!(rt.fuel_remaining(), 0);
+    }
+}
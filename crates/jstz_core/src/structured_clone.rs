@@ -0,0 +1,397 @@
+//! # Structured clone
+//!
+//! An implementation of the [structured clone algorithm][spec], used to copy
+//! values between realms. The supported values are primitives (except symbols),
+//! plain objects, arrays, `Map`s, `Set`s, `Date`s, `ArrayBuffer`s and typed arrays.
+//! Shared and circular references are preserved in the clone.
+//!
+//! [spec]: https://html.spec.whatwg.org/multipage/structured-data.html#structured-cloning
+
+use boa_engine::{
+    builtins, js_string,
+    object::{
+        builtins::{JsArray, JsArrayBuffer, JsDate, JsMap, JsSet},
+        FunctionObjectBuilder,
+    },
+    value::TryFromJs,
+    Context, JsArgs, JsError, JsNativeError, JsObject, JsResult, JsSymbol, JsValue,
+    NativeFunction,
+};
+
+/// Returns an error named `DataCloneError`, mirroring the `DOMException`
+/// thrown by browsers
+fn data_clone_error(message: &str, context: &mut Context<'_>) -> JsError {
+    let error = JsError::from_native(JsNativeError::error().with_message(message))
+        .to_opaque(context);
+
+    if let Some(object) = error.as_object() {
+        // Setting a property of a fresh error object cannot fail
+        let _ = object.set(
+            js_string!("name"),
+            js_string!("DataCloneError"),
+            false,
+            context,
+        );
+    }
+
+    JsError::from_opaque(error)
+}
+
+/// The objects cloned so far, along with their clones
+#[derive(Default)]
+struct Memory {
+    clones: Vec<(JsObject, JsObject)>,
+}
+
+impl Memory {
+    fn get(&self, object: &JsObject) -> Option<JsObject> {
+        self.clones
+            .iter()
+            .find(|(original, _)| JsObject::equals(original, object))
+            .map(|(_, clone)| clone.clone())
+    }
+
+    fn insert(&mut self, original: JsObject, clone: JsObject) {
+        self.clones.push((original, clone))
+    }
+}
+
+/// Returns a deep copy of `value`, preserving shared and circular references.
+///
+/// Fails with a `DataCloneError` if `value` contains a function, a symbol or
+/// an object that cannot be cloned.
+pub fn structured_clone(value: &JsValue, context: &mut Context<'_>) -> JsResult<JsValue> {
+    clone_value(value, &mut Memory::default(), context)
+}
+
+fn clone_value(
+    value: &JsValue,
+    memory: &mut Memory,
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    if value.is_symbol() {
+        return Err(data_clone_error("Symbols cannot be cloned", context));
+    }
+
+    match value.as_object() {
+        Some(object) => Ok(clone_object(object, memory, context)?.into()),
+        None => Ok(value.clone()),
+    }
+}
+
+fn clone_object(
+    object: &JsObject,
+    memory: &mut Memory,
+    context: &mut Context<'_>,
+) -> JsResult<JsObject> {
+    if let Some(clone) = memory.get(object) {
+        return Ok(clone);
+    }
+
+    if object.is_callable() {
+        return Err(data_clone_error("Functions cannot be cloned", context));
+    }
+
+    if object.is_array_buffer() {
+        return clone_array_buffer(object, memory, context);
+    }
+
+    if object.is_typed_array() {
+        return clone_typed_array(object, memory, context);
+    }
+
+    if object.is_date() {
+        let time = JsDate::from_object(object.clone())?.get_time(context)?;
+        let clone = context
+            .intrinsics()
+            .constructors()
+            .date()
+            .constructor()
+            .construct(&[time], None, context)?;
+        memory.insert(object.clone(), clone.clone());
+        return Ok(clone);
+    }
+
+    if object.is_map() {
+        let clone = JsMap::new(context);
+        memory.insert(object.clone(), clone.clone().into());
+
+        let entries = collect(object, context)?;
+        for entry in entries {
+            let key = clone_value(&entry.get(0, context)?, memory, context)?;
+            let value = clone_value(&entry.get(1, context)?, memory, context)?;
+            clone.set(key, value, context)?;
+        }
+
+        return Ok(clone.into());
+    }
+
+    if object.is_set() {
+        let clone = JsSet::new(context);
+        memory.insert(object.clone(), clone.clone().into());
+
+        let entries = collect(object, context)?;
+        for entry in entries {
+            let value = clone_value(&entry.get(0, context)?, memory, context)?;
+            clone.add(value, context)?;
+        }
+
+        return Ok(clone.into());
+    }
+
+    let clone: JsObject = if object.is_array() {
+        let length = JsArray::from_object(object.clone())?.length(context)?;
+        let clone = JsArray::new(context);
+        clone.set(js_string!("length"), length, true, context)?;
+        clone.into()
+    } else if object.is_ordinary() {
+        JsObject::with_object_proto(context.intrinsics())
+    } else {
+        return Err(data_clone_error("The object cannot be cloned", context));
+    };
+    memory.insert(object.clone(), clone.clone());
+
+    // TODO: Expose `enumerable_own_property_names` in Boa
+    let keys = builtins::object::Object::keys(
+        &JsValue::undefined(),
+        &[object.clone().into()],
+        context,
+    )?;
+    let keys = JsArray::try_from_js(&keys, context)?;
+    for i in 0..keys.length(context)? {
+        let key = keys.get(i, context)?.to_property_key(context)?;
+        let value = object.get(key.clone(), context)?;
+        let value = clone_value(&value, memory, context)?;
+        clone.create_data_property_or_throw(key, value, context)?;
+    }
+
+    Ok(clone)
+}
+
+/// Collects the entries of a `Map` (as `[key, value]` arrays) or of a `Set`
+/// (as `[value, value]` arrays)
+fn collect(object: &JsObject, context: &mut Context<'_>) -> JsResult<Vec<JsArray>> {
+    let entries = JsArray::new(context);
+    let push = FunctionObjectBuilder::new(
+        context.realm(),
+        NativeFunction::from_copy_closure_with_captures(
+            |_, args, entries, context| {
+                let entry = JsArray::from_iter(
+                    [
+                        args.get_or_undefined(1).clone(),
+                        args.get_or_undefined(0).clone(),
+                    ],
+                    context,
+                );
+                entries.push(entry, context)
+            },
+            entries.clone(),
+        ),
+    )
+    .build();
+
+    if object.is_map() {
+        JsMap::from_object(object.clone())?.for_each(
+            push,
+            JsValue::undefined(),
+            context,
+        )?;
+    } else {
+        JsSet::from_object(object.clone())?.for_each(
+            push,
+            JsValue::undefined(),
+            context,
+        )?;
+    }
+
+    let length = entries.length(context)?;
+    (0..length)
+        .map(|i| JsArray::try_from_js(&entries.get(i, context)?, context))
+        .collect()
+}
+
+fn clone_array_buffer(
+    object: &JsObject,
+    memory: &mut Memory,
+    context: &mut Context<'_>,
+) -> JsResult<JsObject> {
+    let bytes = object
+        .borrow()
+        .as_array_buffer()
+        .and_then(|array_buffer| array_buffer.array_buffer_data.clone());
+    let Some(bytes) = bytes else {
+        return Err(data_clone_error(
+            "A detached ArrayBuffer cannot be cloned",
+            context,
+        ));
+    };
+
+    let clone: JsObject = JsArrayBuffer::from_byte_block(bytes, context)?.into();
+    memory.insert(object.clone(), clone.clone());
+
+    Ok(clone)
+}
+
+fn clone_typed_array(
+    object: &JsObject,
+    memory: &mut Memory,
+    context: &mut Context<'_>,
+) -> JsResult<JsObject> {
+    let buffer = object
+        .get(js_string!("buffer"), context)?
+        .as_object()
+        .cloned()
+        .ok_or_else(|| JsNativeError::typ().with_message("Expected an ArrayBuffer"))?;
+    let buffer = clone_object(&buffer, memory, context)?;
+
+    let byte_offset = object.get(js_string!("byteOffset"), context)?;
+    let length = object.get(js_string!("length"), context)?;
+
+    // The name of the typed array is also the name of its constructor
+    let name = object.get(JsSymbol::to_string_tag(), context)?;
+    let constructor = name
+        .as_string()
+        .map(|name| context.global_object().get(name.clone(), context))
+        .transpose()?
+        .and_then(|constructor| constructor.as_object().cloned())
+        .filter(JsObject::is_constructor)
+        .ok_or_else(|| data_clone_error("The typed array cannot be cloned", context))?;
+
+    let clone =
+        constructor.construct(&[buffer.into(), byte_offset, length], None, context)?;
+    memory.insert(object.clone(), clone.clone());
+
+    Ok(clone)
+}
+
+/// `structuredClone(value)`
+fn structured_clone_fn(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    structured_clone(args.get_or_undefined(0), context)
+}
+
+pub(crate) fn register_structured_clone(context: &mut Context<'_>) {
+    context
+        .register_global_builtin_callable(
+            js_string!("structuredClone"),
+            1,
+            NativeFunction::from_fn_ptr(structured_clone_fn),
+        )
+        .expect("structuredClone should only be registered once");
+}
+
+#[cfg(test)]
+mod test {
+    use boa_engine::Source;
+
+    use crate::Runtime;
+
+    fn eval(code: &str) -> String {
+        let mut rt = Runtime::new().unwrap();
+        rt.eval(Source::from_bytes(code))
+            .unwrap()
+            .as_string()
+            .expect("Expected a string")
+            .to_std_string_escaped()
+    }
+
+    #[test]
+    fn cyclic_object_is_preserved() {
+        let result = eval(
+            r#"
+            const obj = { name: "a", list: [1, 2] };
+            obj.self = obj;
+            obj.list.push(obj);
+            const clone = structuredClone(obj);
+            [
+                clone !== obj,
+                clone.self === clone,
+                clone.list[2] === clone,
+                clone.list !== obj.list,
+                clone.name,
+            ].join(",")
+            "#,
+        );
+
+        assert_eq!(result, "true,true,true,true,a");
+    }
+
+    #[test]
+    fn typed_array_clone_is_independent() {
+        let result = eval(
+            r#"
+            const array = new Uint16Array([1, 2, 3]);
+            const clone = structuredClone(array);
+            array[0] = 42;
+            [
+                clone instanceof Uint16Array,
+                clone.buffer !== array.buffer,
+                clone.join(" "),
+            ].join(",")
+            "#,
+        );
+
+        assert_eq!(result, "true,true,1 2 3");
+    }
+
+    #[test]
+    fn typed_arrays_sharing_a_buffer_still_share_it() {
+        let result = eval(
+            r#"
+            const buffer = new ArrayBuffer(8);
+            const [a, b] = structuredClone([new Uint8Array(buffer, 2, 4), new Uint8Array(buffer)]);
+            b[2] = 7;
+            [a.buffer === b.buffer, a.byteOffset, a.length, a[0]].join(",")
+            "#,
+        );
+
+        assert_eq!(result, "true,2,4,7");
+    }
+
+    #[test]
+    fn map_set_and_date_are_cloned() {
+        let result = eval(
+            r#"
+            const key = { id: 1 };
+            const map = new Map([[key, "value"]]);
+            const set = new Set([key, 2]);
+            const date = new Date(86400000);
+            const clone = structuredClone({ map, set, date });
+            const [clonedKey] = clone.map.keys();
+            [
+                clone.map !== map,
+                clonedKey !== key,
+                clonedKey.id,
+                clone.map.get(clonedKey),
+                clone.set.has(clonedKey),
+                clone.set.has(2),
+                clone.date !== date,
+                clone.date.getTime(),
+            ].join(",")
+            "#,
+        );
+
+        assert_eq!(result, "true,true,1,value,true,true,true,86400000");
+    }
+
+    #[test]
+    fn functions_and_symbols_throw_data_clone_error() {
+        for value in ["() => {}", "{ f() {} }", "Symbol()", "[Symbol.iterator]"] {
+            let result = eval(&format!(
+                r#"
+                try {{
+                    structuredClone({value});
+                    "cloned"
+                }} catch (e) {{
+                    e.name
+                }}
+                "#
+            ));
+
+            assert_eq!(result, "DataCloneError", "cloning {value}");
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! `setTimeout` and `clearTimeout` on a virtual clock
+//!
+//! Wall-clock timers would make execution non-deterministic, so timers are
+//! ordered by their virtual deadline instead. Once the job queue is empty, the
+//! event loop runs the timer with the earliest deadline and advances the
+//! virtual clock to it.
+
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use boa_engine::{
+    job::NativeJob, js_string, Context, JsArgs, JsNativeError, JsObject, JsResult,
+    JsValue, NativeFunction,
+};
+use boa_gc::{empty_trace, Finalize, Trace};
+
+use crate::realm::HostDefined;
+
+struct Timer {
+    callback: JsObject,
+    args: Vec<JsValue>,
+}
+
+#[derive(Default)]
+struct TimerQueue {
+    /// The virtual time, in milliseconds
+    now: u64,
+    next_id: u32,
+    /// Pending timers, ordered by deadline and then by registration
+    pending: BTreeMap<(u64, u32), Timer>,
+}
+
+/// The timers of a runtime, shared by all of its realms
+#[derive(Clone, Default)]
+pub struct Timers(Rc<RefCell<TimerQueue>>);
+
+impl Finalize for Timers {}
+
+unsafe impl Trace for Timers {
+    empty_trace!();
+}
+
+impl Timers {
+    /// Returns the timers of the current realm, if any
+    pub fn from_context(context: &mut Context<'_>) -> Option<Self> {
+        let host_defined = context
+            .global_object()
+            .get(js_string!(HostDefined::NAME), context)
+            .ok()?;
+        let host_defined = host_defined.as_object()?.downcast_ref::<HostDefined>()?;
+        let timers = Self::clone(&host_defined.get::<Self>()?);
+
+        Some(timers)
+    }
+
+    /// The current virtual time, in milliseconds
+    pub fn now(&self) -> u64 {
+        self.0.borrow().now
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().pending.is_empty()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.0.borrow_mut().pending.clear()
+    }
+
+    fn insert(&self, delay: u64, callback: JsObject, args: Vec<JsValue>) -> u32 {
+        let mut queue = self.0.borrow_mut();
+
+        queue.next_id += 1;
+        let id = queue.next_id;
+        let deadline = queue.now.saturating_add(delay);
+        queue
+            .pending
+            .insert((deadline, id), Timer { callback, args });
+
+        id
+    }
+
+    fn remove(&self, id: u32) {
+        self.0
+            .borrow_mut()
+            .pending
+            .retain(|&(_, timer_id), _| timer_id != id);
+    }
+
+    /// Removes the timer with the earliest deadline and advances the virtual
+    /// clock to it. Returns the job running the timer's callback.
+    pub(crate) fn next_job(&self) -> Option<NativeJob> {
+        let mut queue = self.0.borrow_mut();
+
+        let ((deadline, _), timer) = queue.pending.pop_first()?;
+        queue.now = queue.now.max(deadline);
+
+        Some(NativeJob::new(move |context| {
+            timer
+                .callback
+                .call(&JsValue::undefined(), &timer.args, context)
+        }))
+    }
+}
+
+fn timers(context: &mut Context<'_>) -> JsResult<Timers> {
+    Timers::from_context(context).ok_or_else(|| {
+        JsNativeError::error()
+            .with_message("Timers are not available in this realm")
+            .into()
+    })
+}
+
+/// `setTimeout(callback, delay, ...args)`
+///
+/// [spec]: https://html.spec.whatwg.org/multipage/timers-and-user-prompts.html#dom-settimeout
+fn set_timeout(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let callback = args
+        .get_or_undefined(0)
+        .as_callable()
+        .cloned()
+        .ok_or_else(|| {
+            JsNativeError::typ().with_message("The callback provided is not a function")
+        })?;
+
+    // Negative and NaN delays are treated as 0
+    let delay = args.get_or_undefined(1).to_number(context)?;
+    let delay = if delay.is_nan() {
+        0
+    } else {
+        delay.max(0.0) as u64
+    };
+
+    let id =
+        timers(context)?.insert(delay, callback, args.iter().skip(2).cloned().collect());
+
+    Ok(id.into())
+}
+
+/// `clearTimeout(id)`
+///
+/// [spec]: https://html.spec.whatwg.org/multipage/timers-and-user-prompts.html#dom-cleartimeout
+fn clear_timeout(
+    _this: &JsValue,
+    args: &[JsValue],
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let id = args.get_or_undefined(0).to_u32(context)?;
+
+    timers(context)?.remove(id);
+
+    Ok(JsValue::undefined())
+}
+
+pub(crate) fn register_timers(context: &mut Context<'_>) {
+    context
+        .register_global_builtin_callable(
+            js_string!("setTimeout"),
+            2,
+            NativeFunction::from_fn_ptr(set_timeout),
+        )
+        .expect("setTimeout should only be registered once");
+    context
+        .register_global_builtin_callable(
+            js_string!("clearTimeout"),
+            1,
+            NativeFunction::from_fn_ptr(clear_timeout),
+        )
+        .expect("clearTimeout should only be registered once");
+}
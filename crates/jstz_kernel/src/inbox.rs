@@ -17,6 +17,11 @@ pub type InternalMessage = ExternalOperation;
 pub enum Message {
     External(ExternalMessage),
     Internal(InternalMessage),
+    /// The start of a new rollup block, at the given Layer 1 level
+    StartOfBlock {
+        level: u32,
+        timestamp: i64,
+    },
 }
 
 // reciever, ticket
@@ -103,7 +108,10 @@ pub fn read_message(
                 info.predecessor,
                 info.predecessor_timestamp
             );
-            None
+            Some(Message::StartOfBlock {
+                level: input.level,
+                timestamp: info.predecessor_timestamp.i64(),
+            })
         }
         InboxMessage::Internal(InternalInboxMessage::EndOfLevel) => {
             // The "End of level" message is pushed by the Layer 1
@@ -1,5 +1,5 @@
 use jstz_core::kv::{Kv, Storage};
-use jstz_proto::{executor, Result};
+use jstz_proto::{context::block::Block, executor, Result};
 use tezos_crypto_rs::hash::ContractKt1Hash;
 use tezos_smart_rollup::{
     kernel_entry,
@@ -31,6 +31,9 @@ fn handle_message(hrt: &mut (impl Runtime + 'static), message: Message) -> Resul
             debug_msg!(hrt, "Receipt: {receipt:?}\n");
             receipt.write(hrt, &mut tx)?
         }
+        Message::StartOfBlock { level, timestamp } => {
+            Block::advance(hrt, &mut tx, level, timestamp)?
+        }
     }
 
     kv.commit_transaction(hrt, tx)?;
@@ -0,0 +1,249 @@
+//! Machine-readable descriptions of smart functions.
+//!
+//! When a smart function is deployed, its source is scanned for JSDoc comments
+//! attached to the default export and to any other exported functions. The
+//! `@param` and `@returns` annotations are collected into an [`Abi`], which is
+//! stored in the smart function's KV store under [`ABI_KEY`].
+
+use serde::{Deserialize, Serialize};
+
+/// The KV key under which a smart function's ABI is stored
+pub const ABI_KEY: &str = "__abi__";
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Abi {
+    pub functions: Vec<Function>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Function {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub description: Option<String>,
+    pub params: Vec<Param>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub returns: Option<Returns>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Param {
+    pub name: String,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none", default)]
+    pub ty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Returns {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none", default)]
+    pub ty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub description: Option<String>,
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    let s = s.trim();
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.to_string())
+    }
+}
+
+/// Splits a leading `{Type}` from `s`, returning the type (if any) and the
+/// remainder.
+fn split_type(s: &str) -> (Option<String>, &str) {
+    let s = s.trim_start();
+    if let Some(rest) = s.strip_prefix('{') {
+        if let Some(end) = rest.find('}') {
+            return (non_empty(&rest[..end]), &rest[end + 1..]);
+        }
+    }
+    (None, s)
+}
+
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(end) => (&s[..end], &s[end..]),
+        None => (s, ""),
+    }
+}
+
+/// Returns the name of the export following a doc comment, if any.
+fn export_name(code: &str) -> Option<String> {
+    let rest = code.trim_start().strip_prefix("export")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let (word, rest) = split_word(rest);
+    let (word, rest) = match word {
+        "default" => return Some("default".to_string()),
+        "async" => split_word(rest),
+        _ => (word, rest),
+    };
+
+    let name = match word {
+        "function" | "function*" | "const" | "let" | "var" => split_word(rest).0,
+        _ => return None,
+    };
+
+    let name: String = name
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+        .collect();
+
+    non_empty(&name)
+}
+
+fn parse_doc_comment(name: String, comment: &str) -> Function {
+    let mut function = Function {
+        name,
+        ..Default::default()
+    };
+    let mut description = Vec::new();
+    let mut in_tags = false;
+
+    for line in comment.lines() {
+        let line = line.trim().trim_start_matches('*').trim();
+        in_tags |= line.starts_with('@');
+
+        if let Some(tag) = line.strip_prefix("@param") {
+            let (ty, rest) = split_type(tag);
+            let (name, rest) = split_word(rest);
+            let name = name.trim_start_matches('[').trim_end_matches(']');
+            let name = name.split('=').next().unwrap_or_default();
+            let rest = rest.trim_start().trim_start_matches('-');
+
+            function.params.push(Param {
+                name: name.to_string(),
+                ty,
+                description: non_empty(rest),
+            });
+        } else if let Some(tag) = line
+            .strip_prefix("@returns")
+            .or_else(|| line.strip_prefix("@return"))
+        {
+            let (ty, rest) = split_type(tag);
+
+            function.returns = Some(Returns {
+                ty,
+                description: non_empty(rest),
+            });
+        } else if !in_tags && !line.is_empty() {
+            description.push(line);
+        }
+    }
+
+    function.description = non_empty(&description.join(" "));
+    function
+}
+
+impl Abi {
+    /// Parses the ABI of a smart function from its source code. Returns `None`
+    /// if no exported function is documented.
+    pub fn parse(code: &str) -> Option<Self> {
+        let mut functions = Vec::new();
+        let mut rest = code;
+
+        while let Some(start) = rest.find("/**") {
+            let Some(end) = rest[start..].find("*/") else {
+                break;
+            };
+            let comment = &rest[start + 3..start + end];
+            rest = &rest[start + end + 2..];
+
+            if let Some(name) = export_name(rest) {
+                functions.push(parse_doc_comment(name, comment));
+            }
+        }
+
+        if functions.is_empty() {
+            None
+        } else {
+            Some(Self { functions })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_documented_exports() {
+        let code = r#"
+            /**
+             * Increments the counter.
+             *
+             * @param {Request} request - The incoming request
+             * @returns {Response} The new counter value
+             */
+            export default async (request) => {
+                return new Response();
+            };
+
+            /**
+             * @param {number} x
+             * @param {number} [y]
+             * @return {number}
+             */
+            export function add(x, y = 0) {
+                return x + y;
+            }
+
+            /** Not exported */
+            function helper() {}
+        "#;
+
+        let abi = Abi::parse(code).unwrap();
+
+        assert_eq!(
+            abi,
+            Abi {
+                functions: vec![
+                    Function {
+                        name: "default".to_string(),
+                        description: Some("Increments the counter.".to_string()),
+                        params: vec![Param {
+                            name: "request".to_string(),
+                            ty: Some("Request".to_string()),
+                            description: Some("The incoming request".to_string()),
+                        }],
+                        returns: Some(Returns {
+                            ty: Some("Response".to_string()),
+                            description: Some("The new counter value".to_string()),
+                        }),
+                    },
+                    Function {
+                        name: "add".to_string(),
+                        description: None,
+                        params: vec![
+                            Param {
+                                name: "x".to_string(),
+                                ty: Some("number".to_string()),
+                                description: None,
+                            },
+                            Param {
+                                name: "y".to_string(),
+                                ty: Some("number".to_string()),
+                                description: None,
+                            },
+                        ],
+                        returns: Some(Returns {
+                            ty: Some("number".to_string()),
+                            description: None,
+                        }),
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_undocumented_code() {
+        assert_eq!(Abi::parse("export default () => new Response()"), None);
+    }
+}
@@ -0,0 +1,102 @@
+use std::ops::{Deref, DerefMut};
+
+use boa_engine::{
+    js_string, object::ObjectInitializer, property::Attribute, Context, JsResult,
+    JsString, JsValue, NativeFunction,
+};
+use jstz_core::{host_defined, kv::Transaction, runtime};
+
+use crate::context::block::Block;
+
+// Block.level()
+// Block.timestamp()
+// Block.hash()
+// Block.parentHash()
+
+pub struct BlockApi;
+
+impl BlockApi {
+    const NAME: &'static str = "Block";
+
+    fn with_current_block(
+        context: &mut Context<'_>,
+        f: impl FnOnce(Block) -> JsValue,
+    ) -> JsResult<JsValue> {
+        runtime::with_global_host(|rt| {
+            host_defined!(context, host_defined);
+
+            let mut tx = host_defined.get_mut::<Transaction>().unwrap();
+
+            let block = Block::current(rt.deref(), tx.deref_mut())?;
+
+            Ok(f(block))
+        })
+    }
+
+    fn level(
+        _this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        Self::with_current_block(context, |block| block.level.into())
+    }
+
+    fn timestamp(
+        _this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        Self::with_current_block(context, |block| (block.timestamp as f64).into())
+    }
+
+    fn hash(
+        _this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        Self::with_current_block(context, |block| {
+            JsString::from(block.hash.to_string()).into()
+        })
+    }
+
+    fn parent_hash(
+        _this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        Self::with_current_block(context, |block| {
+            JsString::from(block.parent_hash.to_string()).into()
+        })
+    }
+}
+
+impl jstz_core::Api for BlockApi {
+    fn init(self, context: &mut Context<'_>) {
+        let block = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::level),
+                js_string!("level"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::timestamp),
+                js_string!("timestamp"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::hash),
+                js_string!("hash"),
+                0,
+            )
+            .function(
+                NativeFunction::from_fn_ptr(Self::parent_hash),
+                js_string!("parentHash"),
+                0,
+            )
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), block, Attribute::all())
+            .expect("The block object shouldn't exist yet");
+    }
+}
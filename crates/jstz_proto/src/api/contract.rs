@@ -1,28 +1,387 @@
-use std::ops::DerefMut;
+use std::{cell::RefCell, collections::BTreeSet, ops::DerefMut, rc::Rc};
 
 use boa_engine::{
     js_string,
-    object::{builtins::JsPromise, Object, ObjectInitializer},
+    object::{
+        builtins::{JsArray, JsPromise},
+        FunctionObjectBuilder, Object, ObjectInitializer,
+    },
     property::Attribute,
     Context, JsArgs, JsError, JsNativeError, JsResult, JsValue, NativeFunction,
 };
-use jstz_api::http::request::Request;
+use jstz_api::{
+    http::{
+        request::Request,
+        response::{Response, ResponseBuilder, ResponseClass},
+    },
+    Kv, KvValue, LogBuffer, LOCK_KEY_PREFIX,
+};
 use jstz_core::{
-    host::HostRuntime, host_defined, kv::Transaction, native::JsNativeObject, runtime,
+    host::HostRuntime,
+    host_defined,
+    kv::{SavepointId, Transaction},
+    native::JsNativeObject,
+    realm::HostDefined,
+    runtime,
     value::IntoJs,
 };
+use jstz_crypto::hash::Blake2b;
 
+use super::ledger::js_value_to_pkh;
 use crate::{
-    context::account::{Account, Address, Amount},
-    executor::contract::{headers, Script},
-    operation::OperationHash,
+    abi::ABI_KEY,
+    context::{
+        account::{Account, Address, Amount},
+        block::Block,
+    },
+    executor::contract::{headers, ModuleCache, OperationContext, Script},
     Error, Result,
 };
 
 use boa_gc::{empty_trace, Finalize, GcRefMut, Trace};
+use serde::{Deserialize, Serialize};
+
+const PAUSED_KEY: &str = "__paused__";
+
+/// The maximum number of nested snapshots per invocation
+const MAX_SNAPSHOTS: usize = 8;
+
+fn is_paused(
+    hrt: &impl HostRuntime,
+    tx: &mut Transaction,
+    contract_address: &Address,
+) -> Result<bool> {
+    let paused = Kv::new(contract_address.to_string())
+        .get(hrt, tx, PAUSED_KEY)?
+        .map_or(false, |value| value.0 == serde_json::Value::Bool(true));
+
+    Ok(paused)
+}
+
+/// Registered in `HostDefined` by `Contract.enablePauseGuard()`. When present,
+/// requests to the contract are rejected with `503 Service Unavailable`
+/// while the contract is paused, except those sent by its admin, who may
+/// still resume it.
+pub struct PauseGuard {
+    contract_address: Address,
+}
+
+impl Finalize for PauseGuard {}
+
+unsafe impl Trace for PauseGuard {
+    empty_trace!();
+}
+
+impl PauseGuard {
+    /// Returns `true` if requests sent by `caller` are to be rejected
+    pub fn rejects(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        caller: Option<&Address>,
+    ) -> Result<bool> {
+        if !is_paused(hrt, tx, &self.contract_address)? {
+            return Ok(false);
+        }
+
+        let admin = Account::admin(hrt, tx, &self.contract_address)?;
+        Ok(caller.is_none() || admin.as_ref() != caller)
+    }
+}
+
+/// A lock held across operations by `Contract.lock()`, until it is released
+/// or the block level reaches `expires_at`
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Lock {
+    lock_id: String,
+    expires_at: u32,
+}
+
+/// The lock released by `Contract.withLock()` once its function settles
+#[derive(Clone)]
+struct LockGuard {
+    contract_address: Address,
+    name: String,
+    lock_id: String,
+}
+
+impl Finalize for LockGuard {}
+
+unsafe impl Trace for LockGuard {
+    empty_trace!();
+}
+
+impl LockGuard {
+    fn unlock(&self, context: &mut Context<'_>) -> JsResult<()> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        runtime::with_global_host(|hrt| {
+            Contract::unlock(
+                hrt,
+                tx.deref_mut(),
+                &self.contract_address,
+                &self.name,
+                &self.lock_id,
+            )
+        })?;
+
+        Ok(())
+    }
+}
+
+/// Registered in `HostDefined` by `Contract.delegateTo()`. When present,
+/// requests the contract responds to with `404 Not Found` are forwarded to
+/// the delegate contract.
+pub struct Delegate {
+    pub address: Address,
+}
+
+impl Finalize for Delegate {}
+
+unsafe impl Trace for Delegate {
+    empty_trace!();
+}
+
+/// Registered in `HostDefined` by `Script::run` with the callers of the
+/// request being handled. Each call runs in its own realm, so the chain of a
+/// nested call is discarded when the call returns or reverts.
+pub struct CallChain {
+    /// The immediate caller (the `Referer` of the request)
+    pub caller: Address,
+    /// The initial source of the operation
+    pub origin: Address,
+}
+
+impl Finalize for CallChain {}
+
+unsafe impl Trace for CallChain {
+    empty_trace!();
+}
+
+/// Registered in `HostDefined` by `Script::run` with the snapshots taken by
+/// the invocation through `Contract.snapshot()`, in order. An invocation may
+/// only roll back to its own snapshots, which are released when its handler
+/// returns.
+#[derive(Default)]
+pub struct Snapshots(pub Vec<SavepointId>);
+
+impl Finalize for Snapshots {}
+
+unsafe impl Trace for Snapshots {
+    empty_trace!();
+}
+
+/// Returns the initial source of the operation being run, if known
+fn origin(host_defined: &HostDefined) -> Option<Address> {
+    host_defined
+        .get::<CallChain>()
+        .map(|call_chain| call_chain.origin.clone())
+}
+
+/// An event emitted by a contract with `Contract.emit()`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub contract_address: Address,
+    pub topic: String,
+    pub data: serde_json::Value,
+}
+
+/// Collects the events emitted by the contracts run by an operation. Held by
+/// its [`OperationContext`]; clones share the same events. The events of a
+/// call are discarded when the call reverts.
+#[derive(Debug, Default, Clone)]
+pub struct EventBuffer(Rc<RefCell<Vec<Event>>>);
+
+impl Finalize for EventBuffer {}
+
+unsafe impl Trace for EventBuffer {
+    empty_trace!();
+}
+
+impl EventBuffer {
+    fn push(&self, event: Event) {
+        self.0.borrow_mut().push(event)
+    }
+
+    /// Returns a checkpoint that the buffer can be rolled back to
+    pub fn checkpoint(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    /// Discards the events emitted since `checkpoint`
+    pub fn rollback_to(&self, checkpoint: usize) {
+        self.0.borrow_mut().truncate(checkpoint)
+    }
+
+    /// Removes and returns the events emitted so far
+    pub fn take(&self) -> Vec<Event> {
+        std::mem::take(&mut self.0.borrow_mut())
+    }
+}
+
+/// A contract call made by an operation, as recorded in its receipt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallFrame {
+    pub contract_address: Address,
+    /// The number of contract calls on the stack, including this one
+    pub call_depth: usize,
+    /// The fuel consumed by the call, including its nested calls
+    pub gas_used: u64,
+    /// The status of the response, or `None` if the call failed without
+    /// responding
+    pub status: Option<u16>,
+    /// Whether the call reverted, that is, failed or did not respond with 2xx
+    pub reverted: bool,
+}
+
+/// Collects the contract calls made by an operation, in the order in which
+/// they are made. Held by its [`OperationContext`]; clones share the same
+/// frames. The frames of reverted calls are kept, and marked as reverted.
+#[derive(Debug, Default, Clone)]
+pub struct CallTrace(Rc<RefCell<Vec<CallFrame>>>);
+
+impl Finalize for CallTrace {}
+
+unsafe impl Trace for CallTrace {
+    empty_trace!();
+}
+
+impl CallTrace {
+    /// Records the start of a call to `contract_address`, returning the index
+    /// of its frame. Calls that never settle are marked as reverted.
+    pub fn push(&self, contract_address: Address, call_depth: usize) -> usize {
+        let mut frames = self.0.borrow_mut();
+        frames.push(CallFrame {
+            contract_address,
+            call_depth,
+            gas_used: 0,
+            status: None,
+            reverted: true,
+        });
+        frames.len() - 1
+    }
+
+    /// Records the end of the call whose frame is at `index`
+    pub fn pop(&self, index: usize, gas_used: u64, status: Option<u16>) {
+        if let Some(frame) = self.0.borrow_mut().get_mut(index) {
+            frame.gas_used = gas_used;
+            frame.status = status;
+            frame.reverted = !status.is_some_and(|status| (200..300).contains(&status));
+        }
+    }
+
+    /// Returns a checkpoint that the trace can be rolled back to
+    pub fn checkpoint(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    /// Discards the frames recorded since `checkpoint`
+    pub fn rollback_to(&self, checkpoint: usize) {
+        self.0.borrow_mut().truncate(checkpoint)
+    }
+
+    /// Removes and returns the frames recorded so far
+    pub fn take(&self) -> Vec<CallFrame> {
+        std::mem::take(&mut self.0.borrow_mut())
+    }
+}
+
+/// The contracts running a `Contract.nonReentrant()` function in the current
+/// operation. Held by its [`OperationContext`]; clones share the same locks.
+#[derive(Debug, Default, Clone)]
+pub struct ReentrancyLocks(Rc<RefCell<BTreeSet<Address>>>);
+
+impl Finalize for ReentrancyLocks {}
+
+unsafe impl Trace for ReentrancyLocks {
+    empty_trace!();
+}
+
+/// The lock of a contract on reentrancy, held while one of its
+/// `Contract.nonReentrant()` functions is running
+#[derive(Clone)]
+struct ReentrancyGuard {
+    address: Address,
+    locks: ReentrancyLocks,
+}
+
+impl Finalize for ReentrancyGuard {}
+
+unsafe impl Trace for ReentrancyGuard {
+    empty_trace!();
+}
+
+impl ReentrancyGuard {
+    /// Acquires the lock, failing if the contract is already locked
+    fn lock(&self) -> Result<()> {
+        if !self.locks.0.borrow_mut().insert(self.address.clone()) {
+            return Err(Error::ReentrancyDetected);
+        }
+
+        Ok(())
+    }
+
+    fn unlock(&self) {
+        self.locks.0.borrow_mut().remove(&self.address);
+    }
+}
+
+/// The dry run of a call by `Contract.estimateGas()`, undone once the call
+/// settles
+struct DryRun {
+    savepoint: SavepointId,
+    events: (EventBuffer, usize),
+    logs: (LogBuffer, usize),
+    trace: (CallTrace, usize),
+    fuel: u64,
+}
+
+impl Finalize for DryRun {}
+
+unsafe impl Trace for DryRun {
+    empty_trace!();
+}
+
+impl DryRun {
+    /// Rolls back the writes, events, logs and call frames of the call, and
+    /// refunds the fuel it consumed, returning that fuel
+    fn rollback(&self, context: &mut Context<'_>) -> JsResult<u64> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        tx.rollback_to(self.savepoint)?;
+        tx.release_savepoint(self.savepoint)?;
+
+        let (events, checkpoint) = &self.events;
+        events.rollback_to(*checkpoint);
+        let (logs, checkpoint) = &self.logs;
+        logs.rollback_to(*checkpoint);
+        let (trace, checkpoint) = &self.trace;
+        trace.rollback_to(*checkpoint);
+
+        // The scripts run by the call may keep state that storage no longer
+        // reflects, so they are not reused
+        if let Some(cache) = host_defined.get::<ModuleCache>() {
+            cache.clear();
+        }
+
+        let gas_used = self.fuel.saturating_sub(runtime::fuel_remaining());
+        runtime::restore_fuel(self.fuel);
+
+        Ok(gas_used)
+    }
+}
+
 struct Contract {
     contract_address: Address,
-    operation_hash: OperationHash,
+    /// The context of the call the contract is handling
+    operation: OperationContext,
 }
 impl Finalize for Contract {}
 
@@ -66,7 +425,7 @@ impl Contract {
         // 3. Increment nonce of current account
         // Deploying a contract requires the nonce to be incremented to avoid a
         // collision with the contract addressing scheme.
-        Account::nonce(hrt, tx, &self.contract_address)?.increment();
+        Account::increment_nonce(hrt, tx, &self.contract_address)?;
 
         // 4. Transfer the balance to the contract
         Account::transfer(hrt, tx, &self.contract_address, &address, initial_balance)?;
@@ -74,10 +433,120 @@ impl Contract {
         Ok(address.to_string())
     }
 
+    fn nonce(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        address: &Address,
+    ) -> Result<u64> {
+        let nonce = Account::nonce(hrt, tx, address)?;
+
+        Ok(nonce.value())
+    }
+
+    fn pause(&self, hrt: &impl HostRuntime, tx: &mut Transaction) -> Result<()> {
+        Kv::new(self.contract_address.to_string()).set(
+            hrt,
+            tx,
+            PAUSED_KEY,
+            KvValue(serde_json::Value::Bool(true)),
+        )?;
+
+        Ok(())
+    }
+
+    fn resume(&self, hrt: &impl HostRuntime, tx: &mut Transaction) -> Result<()> {
+        Kv::new(self.contract_address.to_string()).delete(hrt, tx, PAUSED_KEY)?;
+
+        Ok(())
+    }
+
+    fn is_paused(&self, hrt: &impl HostRuntime, tx: &mut Transaction) -> Result<bool> {
+        is_paused(hrt, tx, &self.contract_address)
+    }
+
+    /// Acquires the lock `name` of the contract for `ttl_blocks` levels,
+    /// returning its id, or `None` if the lock is held and has not expired.
+    fn lock(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        name: &str,
+        ttl_blocks: u32,
+    ) -> Result<Option<String>> {
+        let storage = Kv::new(self.contract_address.to_string());
+        let key = format!("{LOCK_KEY_PREFIX}{name}");
+        let level = Block::current(hrt, tx)?.level;
+
+        let held = storage
+            .get(hrt, tx, &key)?
+            .and_then(|value| serde_json::from_value::<Lock>(value.0.clone()).ok())
+            .is_some_and(|lock| lock.expires_at > level);
+        if held {
+            return Ok(None);
+        }
+
+        let lock = Lock {
+            lock_id: Blake2b::from(
+                format!(
+                    "{}/{}/{name}/{level}",
+                    self.operation.operation_hash.to_string(),
+                    self.contract_address
+                )
+                .as_bytes(),
+            )
+            .to_string(),
+            expires_at: level.saturating_add(ttl_blocks),
+        };
+        let lock_id = lock.lock_id.clone();
+        let value = serde_json::to_value(lock).expect("A lock should serialize to JSON");
+
+        // The lock is read and written in the transaction of the operation, so
+        // no other operation acquires it in between
+        storage.set(tx, &key, KvValue(value))?;
+
+        Ok(Some(lock_id))
+    }
+
+    /// Releases the lock `name` of the contract at `address` if it is held
+    /// with `lock_id`. Returns whether the lock was released.
+    fn unlock(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        address: &Address,
+        name: &str,
+        lock_id: &str,
+    ) -> Result<bool> {
+        let storage = Kv::new(address.to_string());
+        let key = format!("{LOCK_KEY_PREFIX}{name}");
+
+        let held_with_id = storage
+            .get(hrt, tx, &key)?
+            .and_then(|value| serde_json::from_value::<Lock>(value.0.clone()).ok())
+            .is_some_and(|lock| lock.lock_id == lock_id);
+        if !held_with_id {
+            return Ok(false);
+        }
+
+        storage.delete(hrt, tx, &key)?;
+
+        Ok(true)
+    }
+
+    fn abi(
+        &self,
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+    ) -> Result<Option<KvValue>> {
+        let abi = Kv::new(self.contract_address.to_string()).get(hrt, tx, ABI_KEY)?;
+
+        Ok(abi)
+    }
+
     fn call(
         &self,
         tx: &mut Transaction,
         request: &JsNativeObject<Request>,
+        origin: Option<&Address>,
         context: &mut Context<'_>,
     ) -> JsResult<JsValue> {
         // 1. Get address from request
@@ -90,23 +559,278 @@ impl Contract {
                 JsError::from_native(JsNativeError::error().with_message("Invalid host"))
             })?;
 
-        // 2. Set the referer of the request to the current contract address
+        self.call_with_value(tx, &address, request, 0, origin, context)
+    }
+
+    fn call_with_value(
+        &self,
+        tx: &mut Transaction,
+        address: &Address,
+        request: &JsNativeObject<Request>,
+        amount: Amount,
+        origin: Option<&Address>,
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        // 1. Set the referer of the request to the current contract address,
+        //    and pass on the origin of the operation. Contracts run outside
+        //    of an operation (as in the REPL) are the origin of their calls.
         headers::test_and_set_referrer(&request.deref(), &self.contract_address)?;
+        headers::test_and_set_origin(
+            &request.deref(),
+            origin.unwrap_or(&self.contract_address),
+        )?;
+
+        // 2. Guard the call with a savepoint, so that only the work of the
+        //    call is undone if it reverts. The events emitted by the call are
+        //    discarded along with it.
+        let savepoint = tx.savepoint();
+        let events = &self.operation.events;
+        let checkpoint = events.checkpoint();
+
+        // 3. Transfer the amount to the callee
+        if amount > 0 {
+            if let Err(err) = runtime::with_global_host(|hrt| {
+                Account::transfer(hrt, tx, &self.contract_address, address, amount)
+            }) {
+                tx.rollback_to(savepoint)?;
+                tx.release_savepoint(savepoint)?;
+                return Err(err.into());
+            }
+        }
 
-        // 3. Load, init and run!
-        Script::load_init_run(
+        // 4. Load, init and run!
+        let result = Script::load_init_run_in(
             tx,
-            &address,
+            address,
             request.inner(),
-            &self.operation_hash,
+            &self.operation.nested(amount),
             context,
-        )
+        );
+
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                tx.rollback_to(savepoint)?;
+                tx.release_savepoint(savepoint)?;
+                events.rollback_to(checkpoint);
+                return Err(err);
+            }
+        };
+
+        // 5. Roll back to the savepoint if the callee does not respond with 2xx
+        let promise = JsPromise::from_object(
+            result
+                .as_promise()
+                .cloned()
+                .expect("`load_init_run` should return a promise"),
+        )?;
+
+        let promise = promise.then(
+            Some(
+                FunctionObjectBuilder::new(
+                    context.realm(),
+                    NativeFunction::from_copy_closure(move |_, args, context| {
+                        let response = args.get_or_undefined(0);
+                        let ok = Response::try_from_js(response)?.ok();
+
+                        host_defined!(context, host_defined);
+                        let mut tx = host_defined
+                            .get_mut::<Transaction>()
+                            .expect("Curent transaction undefined");
+
+                        if !ok {
+                            tx.rollback_to(savepoint)?;
+                        }
+                        tx.release_savepoint(savepoint)?;
+
+                        Ok(response.clone())
+                    }),
+                )
+                .build(),
+            ),
+            Some(
+                FunctionObjectBuilder::new(context.realm(), unsafe {
+                    NativeFunction::from_closure_with_captures(
+                        move |_, args, events, context| {
+                            host_defined!(context, host_defined);
+                            let mut tx = host_defined
+                                .get_mut::<Transaction>()
+                                .expect("Curent transaction undefined");
+
+                            tx.rollback_to(savepoint)?;
+                            tx.release_savepoint(savepoint)?;
+                            events.rollback_to(checkpoint);
+
+                            Err(JsError::from_opaque(args.get_or_undefined(0).clone()))
+                        },
+                        events.clone(),
+                    )
+                })
+                .build(),
+            ),
+            context,
+        )?;
+
+        Ok(promise.into())
+    }
+
+    fn emit(
+        &self,
+        tx: &Transaction,
+        topic: String,
+        data: serde_json::Value,
+    ) -> Result<()> {
+        if tx.is_read_only() {
+            return Err(jstz_core::Error::ReadOnlyViolation.into());
+        }
+
+        self.operation.events.push(Event {
+            contract_address: self.contract_address.clone(),
+            topic,
+            data,
+        });
+
+        Ok(())
     }
 }
 
+/// Performs the calls of `Contract.multicall()` from `index` onwards, one
+/// after the other. Each call is rolled back by `call_with_value` if it does
+/// not respond with 2xx.
+fn multicall_from(
+    this: JsValue,
+    calls: Rc<Vec<(Address, JsNativeObject<Request>)>>,
+    index: usize,
+    mut responses: Vec<JsValue>,
+    stop_on_error: bool,
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let Some((address, request)) = calls.get(index) else {
+        let responses = JsArray::from_iter(responses, context);
+        return Ok(JsPromise::resolve(responses, context)?.into());
+    };
+
+    let result = {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let contract = Contract::from_js_value(&this)?;
+        let origin = origin(&host_defined);
+
+        contract.call_with_value(
+            tx.deref_mut(),
+            address,
+            request,
+            0,
+            origin.as_ref(),
+            context,
+        )?
+    };
+
+    let promise = JsPromise::from_object(
+        result
+            .as_promise()
+            .cloned()
+            .expect("`load_init_run` should return a promise"),
+    )?;
+
+    let promise = promise.then(
+        Some(
+            FunctionObjectBuilder::new(context.realm(), unsafe {
+                NativeFunction::from_closure(move |_, args, context| {
+                    let response = args.get_or_undefined(0).clone();
+                    let ok = Response::try_from_js(&response)?.ok();
+
+                    let mut responses = responses.clone();
+                    responses.push(response);
+
+                    if !ok && stop_on_error {
+                        return Ok(JsArray::from_iter(responses, context).into());
+                    }
+
+                    multicall_from(
+                        this.clone(),
+                        calls.clone(),
+                        index + 1,
+                        responses,
+                        stop_on_error,
+                        context,
+                    )
+                })
+            })
+            .build(),
+        ),
+        None,
+        context,
+    )?;
+
+    Ok(promise.into())
+}
+
+/// Builds the `{ ok, response }` result of `Contract.tryCall()`. A missing
+/// response stands for a failed call and is replaced with `Response.error()`.
+fn try_call_result(
+    response: Option<JsValue>,
+    context: &mut Context<'_>,
+) -> JsResult<(bool, JsValue)> {
+    let (ok, response) = match response {
+        Some(response) => (Response::try_from_js(&response)?.ok(), response),
+        None => {
+            let response = JsNativeObject::new::<ResponseClass>(
+                ResponseBuilder::error(context)?,
+                context,
+            )?;
+
+            (false, response.inner().clone())
+        }
+    };
+
+    let result = ObjectInitializer::new(context)
+        .property(js_string!("ok"), ok, Attribute::all())
+        .property(js_string!("response"), response, Attribute::all())
+        .build();
+
+    Ok((ok, result.into()))
+}
+
+/// The [`FetchHandler`] of smart functions, which calls `address` on behalf of
+/// the global `Contract`
+///
+/// [`FetchHandler`]: jstz_api::http::fetch::FetchHandler
+pub fn fetch(
+    address: &Address,
+    request: &JsNativeObject<Request>,
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    let this = context
+        .global_object()
+        .get(js_string!(ContractApi::NAME), context)?;
+
+    host_defined!(context, host_defined);
+    let mut tx = host_defined
+        .get_mut::<Transaction>()
+        .expect("Curent transaction undefined");
+
+    let contract = Contract::from_js_value(&this)?;
+
+    let origin = origin(&host_defined);
+    contract.call_with_value(
+        tx.deref_mut(),
+        address,
+        request,
+        0,
+        origin.as_ref(),
+        context,
+    )
+}
+
+/// The `Contract` API of a smart function. The call it handles is described
+/// by the [`OperationContext`] registered in `HostDefined` when the API is
+/// initialized, so the API is registered again for each call.
 pub struct ContractApi {
     pub contract_address: Address,
-    pub operation_hash: OperationHash,
 }
 
 impl ContractApi {
@@ -126,50 +850,723 @@ impl ContractApi {
         let request: JsNativeObject<Request> =
             args.get_or_undefined(0).clone().try_into()?;
 
-        contract.call(tx.deref_mut(), &request, context)
+        let origin = origin(&host_defined);
+        contract.call(tx.deref_mut(), &request, origin.as_ref(), context)
     }
 
-    fn create(
+    fn call_with_value(
         this: &JsValue,
         args: &[JsValue],
         context: &mut Context<'_>,
     ) -> JsResult<JsValue> {
         host_defined!(context, host_defined);
-        let mut tx = host_defined.get_mut::<Transaction>().unwrap();
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
 
         let contract = Contract::from_js_value(this)?;
-        let contract_code: String = args
-            .get(0)
+        let address = js_value_to_pkh(args.get_or_undefined(0))?;
+        let amount = args
+            .get_or_undefined(1)
+            .as_number()
+            .filter(|amount| amount.fract() == 0.0 && *amount >= 0.0)
             .ok_or_else(|| {
                 JsNativeError::typ()
-                    .with_message("Expected at least 1 argument but 0 provided")
-            })?
-            .try_js_into(context)?;
+                    .with_message("Expected a non-negative integer amount")
+            })?;
+        let request: JsNativeObject<Request> =
+            args.get_or_undefined(2).clone().try_into()?;
 
-        let initial_balance = match args.get(1) {
-            None => 0,
-            Some(balance) => balance
-                .to_big_uint64(context)?
-                .iter_u64_digits()
-                .next()
-                .unwrap_or_default(),
-        };
+        let origin = origin(&host_defined);
+        contract.call_with_value(
+            tx.deref_mut(),
+            &address,
+            &request,
+            amount as Amount,
+            origin.as_ref(),
+            context,
+        )
+    }
+
+    fn multicall(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let array = args
+            .get_or_undefined(0)
+            .as_object()
+            .filter(|obj| obj.is_array())
+            .ok_or_else(|| JsNativeError::typ().with_message("Expected an array"))?;
+        let array = JsArray::from_object(array.clone())?;
+
+        let mut calls = Vec::new();
+        for i in 0..array.length(context)? {
+            let call = array.get(i, context)?;
+            let call = call.as_object().ok_or_else(|| {
+                JsNativeError::typ().with_message("Expected `{ address, request }`")
+            })?;
+
+            let address = js_value_to_pkh(&call.get(js_string!("address"), context)?)?;
+            let request: JsNativeObject<Request> =
+                call.get(js_string!("request"), context)?.try_into()?;
+
+            calls.push((address, request));
+        }
+
+        let stop_on_error = match args.get_or_undefined(1).as_object() {
+            Some(options) => options
+                .get(js_string!("stopOnError"), context)?
+                .to_boolean(),
+            None => false,
+        };
+
+        multicall_from(
+            this.clone(),
+            Rc::new(calls),
+            0,
+            Vec::new(),
+            stop_on_error,
+            context,
+        )
+    }
+
+    fn try_call(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let result = {
+            host_defined!(context, host_defined);
+            let mut tx = host_defined
+                .get_mut::<Transaction>()
+                .expect("Curent transaction undefined");
+
+            let contract = Contract::from_js_value(this)?;
+            let address = js_value_to_pkh(args.get_or_undefined(0))?;
+            let request: JsNativeObject<Request> =
+                args.get_or_undefined(1).clone().try_into()?;
+
+            let origin = origin(&host_defined);
+
+            // `call_with_value` rolls back the call if it fails
+            contract
+                .call_with_value(
+                    tx.deref_mut(),
+                    &address,
+                    &request,
+                    0,
+                    origin.as_ref(),
+                    context,
+                )
+                .ok()
+                .map(|result| {
+                    result
+                        .as_promise()
+                        .cloned()
+                        .expect("`load_init_run` should return a promise")
+                })
+        };
+
+        let Some(promise) = result else {
+            let (_, result) = try_call_result(None, context)?;
+            return Ok(JsPromise::resolve(result, context)?.into());
+        };
+
+        let promise = JsPromise::from_object(promise)?.then(
+            Some(
+                FunctionObjectBuilder::new(
+                    context.realm(),
+                    NativeFunction::from_fn_ptr(|_, args, context| {
+                        let (_, result) = try_call_result(
+                            Some(args.get_or_undefined(0).clone()),
+                            context,
+                        )?;
+                        Ok(result)
+                    }),
+                )
+                .build(),
+            ),
+            Some(
+                FunctionObjectBuilder::new(
+                    context.realm(),
+                    NativeFunction::from_fn_ptr(|_, _, context| {
+                        let (_, result) = try_call_result(None, context)?;
+                        Ok(result)
+                    }),
+                )
+                .build(),
+            ),
+            context,
+        )?;
+
+        Ok(promise.into())
+    }
+
+    fn create(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined.get_mut::<Transaction>().unwrap();
+
+        let contract = Contract::from_js_value(this)?;
+        let contract_code: String = args
+            .get(0)
+            .ok_or_else(|| {
+                JsNativeError::typ()
+                    .with_message("Expected at least 1 argument but 0 provided")
+            })?
+            .try_js_into(context)?;
+
+        let initial_balance = match args.get(1) {
+            None => 0,
+            Some(balance) => balance
+                .to_big_uint64(context)?
+                .iter_u64_digits()
+                .next()
+                .unwrap_or_default(),
+        };
+
+        let promise = JsPromise::new(
+            move |resolvers, context| {
+                let address = runtime::with_global_host(|rt| {
+                    contract.create(rt, &mut tx, contract_code, initial_balance as Amount)
+                })?;
+
+                resolvers.resolve.call(
+                    &JsValue::undefined(),
+                    &[address.into_js(context)],
+                    context,
+                )?;
+                Ok(JsValue::undefined())
+            },
+            context,
+        )?;
+
+        Ok(promise.into())
+    }
+
+    fn nonce(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let contract = Contract::from_js_value(this)?;
+        let nonce = runtime::with_global_host(|hrt| {
+            Contract::nonce(hrt, tx.deref_mut(), &contract.contract_address)
+        })?;
+
+        Ok(nonce.into())
+    }
+
+    fn nonce_of(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let address = js_value_to_pkh(args.get_or_undefined(0))?;
+        let nonce = runtime::with_global_host(|hrt| {
+            Contract::nonce(hrt, tx.deref_mut(), &address)
+        })?;
+
+        Ok(nonce.into())
+    }
+
+    fn pause(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let contract = Contract::from_js_value(this)?;
+        runtime::with_global_host(|hrt| contract.pause(hrt, tx.deref_mut()))?;
+
+        Ok(JsValue::undefined())
+    }
+
+    fn resume(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let contract = Contract::from_js_value(this)?;
+        runtime::with_global_host(|hrt| contract.resume(hrt, tx.deref_mut()))?;
+
+        Ok(JsValue::undefined())
+    }
+
+    fn is_paused(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let contract = Contract::from_js_value(this)?;
+        let paused =
+            runtime::with_global_host(|hrt| contract.is_paused(hrt, tx.deref_mut()))?;
+
+        Ok(paused.into())
+    }
+
+    fn selfdestruct(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let contract = Contract::from_js_value(this)?;
+        let beneficiary = js_value_to_pkh(args.get_or_undefined(0))?;
+        runtime::with_global_host(|hrt| {
+            Script::selfdestruct(
+                hrt,
+                tx.deref_mut(),
+                &contract.contract_address,
+                &beneficiary,
+            )
+        })?;
+
+        Ok(JsValue::undefined())
+    }
+
+    fn snapshot(
+        _this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, mut host_defined);
+        if !host_defined.has::<Snapshots>() {
+            host_defined.insert(Snapshots::default());
+        }
+
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+        let mut snapshots = host_defined
+            .get_mut::<Snapshots>()
+            .expect("Rust type `Snapshots` should be defined in `HostDefined`");
+
+        if snapshots.0.len() >= MAX_SNAPSHOTS {
+            return Err(JsNativeError::range()
+                .with_message(format!("Cannot nest more than {MAX_SNAPSHOTS} snapshots"))
+                .into());
+        }
+
+        let id = tx.savepoint();
+        snapshots.0.push(id);
+
+        Ok((id as u32).into())
+    }
+
+    fn rollback_to_snapshot(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let id = args.get_or_undefined(0).try_js_into::<u32>(context)? as SavepointId;
+
+        // Only the snapshots of this invocation may be rolled back to, so
+        // that the writes of its callers are left alone
+        let mut snapshots = host_defined.get_mut::<Snapshots>();
+        let position = snapshots
+            .as_ref()
+            .and_then(|snapshots| snapshots.0.iter().position(|s| *s == id))
+            .ok_or_else(|| {
+                JsNativeError::range().with_message(format!("Unknown snapshot {id}"))
+            })?;
+
+        tx.rollback_to(id)?;
+
+        // Snapshots taken after `id` are released by the rollback
+        if let Some(snapshots) = snapshots.as_mut() {
+            snapshots.0.truncate(position + 1);
+        }
+
+        Ok(JsValue::undefined())
+    }
+
+    fn get_abi(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let contract = Contract::from_js_value(this)?;
+        let abi = runtime::with_global_host(|hrt| contract.abi(hrt, tx.deref_mut()))?;
+
+        match abi {
+            Some(abi) => JsValue::from_json(&abi.0, context),
+            None => Ok(JsValue::null()),
+        }
+    }
+
+    fn delegate_to(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let contract = Contract::from_js_value(this)?;
+        let address = js_value_to_pkh(args.get_or_undefined(0))?;
+
+        if address == contract.contract_address {
+            return Err(JsNativeError::error()
+                .with_message("A contract cannot delegate to itself")
+                .into());
+        }
+
+        let delegate = Delegate { address };
+
+        host_defined!(context, mut host_defined);
+        host_defined.insert(delegate);
+
+        Ok(JsValue::undefined())
+    }
+
+    fn emit(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let contract = Contract::from_js_value(this)?;
+        let topic = args.get_or_undefined(0).as_string().ok_or_else(|| {
+            JsNativeError::typ().with_message("Expected the topic to be a string")
+        })?;
+        let topic = topic.to_std_string_escaped();
+        let data = match args.get_or_undefined(1) {
+            JsValue::Undefined => serde_json::Value::Null,
+            data => data.to_json(context)?,
+        };
+
+        host_defined!(context, host_defined);
+        let tx = host_defined
+            .get::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        contract.emit(&tx, topic, data)?;
+
+        Ok(JsValue::undefined())
+    }
+
+    fn non_reentrant(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let contract = Contract::from_js_value(this)?;
+        let function = args
+            .get_or_undefined(0)
+            .as_callable()
+            .cloned()
+            .ok_or_else(|| JsNativeError::typ().with_message("Expected a function"))?;
+        let guard = ReentrancyGuard {
+            address: contract.contract_address.clone(),
+            locks: contract.operation.locks.clone(),
+        };
+
+        let guarded = FunctionObjectBuilder::new(context.realm(), unsafe {
+            NativeFunction::from_closure_with_captures(
+                |this, args, (function, guard), context| {
+                    guard.lock()?;
+
+                    let result = match function.call(this, args, context) {
+                        Ok(result) => result,
+                        Err(err) => {
+                            guard.unlock();
+                            return Err(err);
+                        }
+                    };
+
+                    // Async functions hold the lock until they settle
+                    let Some(promise) = result.as_promise() else {
+                        guard.unlock();
+                        return Ok(result);
+                    };
+
+                    let unlock = FunctionObjectBuilder::new(
+                        context.realm(),
+                        NativeFunction::from_closure_with_captures(
+                            |_, _, guard, _| {
+                                guard.unlock();
+                                Ok(JsValue::undefined())
+                            },
+                            guard.clone(),
+                        ),
+                    )
+                    .build();
+
+                    let promise = JsPromise::from_object(promise.clone())?
+                        .finally(unlock, context)?;
+
+                    Ok(promise.into())
+                },
+                (function, guard),
+            )
+        })
+        .build();
+
+        Ok(guarded.into())
+    }
+
+    fn enable_pause_guard(
+        this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let contract_address = Contract::from_js_value(this)?.contract_address.clone();
+
+        host_defined!(context, mut host_defined);
+        host_defined.insert(PauseGuard { contract_address });
+
+        Ok(JsValue::undefined())
+    }
+
+    fn estimate_gas(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let OperationContext {
+            events,
+            logs,
+            trace,
+            ..
+        } = Contract::from_js_value(this)?.operation.clone();
+
+        let dry_run = {
+            host_defined!(context, host_defined);
+            let mut tx = host_defined
+                .get_mut::<Transaction>()
+                .expect("Curent transaction undefined");
+
+            DryRun {
+                savepoint: tx.savepoint(),
+                events: (events.clone(), events.checkpoint()),
+                logs: (logs.clone(), logs.checkpoint()),
+                trace: (trace.clone(), trace.checkpoint()),
+                fuel: runtime::fuel_remaining(),
+            }
+        };
+
+        let result = match Self::call(this, args, context) {
+            Ok(result) => result,
+            Err(err) => {
+                dry_run.rollback(context)?;
+                return Err(err);
+            }
+        };
+
+        // The call is undone once it settles, whether or not it succeeds
+        let promise = JsPromise::from_object(
+            result
+                .as_promise()
+                .cloned()
+                .expect("`load_init_run` should return a promise"),
+        )?;
+
+        let rollback = FunctionObjectBuilder::new(context.realm(), unsafe {
+            NativeFunction::from_closure_with_captures(
+                |_, _, dry_run, context| {
+                    let gas_used = dry_run.rollback(context)?;
+                    Ok((gas_used as f64).into())
+                },
+                dry_run,
+            )
+        })
+        .build();
+
+        let promise = promise.then(Some(rollback.clone()), Some(rollback), context)?;
+
+        Ok(promise.into())
+    }
+
+    fn remaining_gas(
+        _this: &JsValue,
+        _args: &[JsValue],
+        _context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        // Contracts run outside of an operation (as in the REPL) are not
+        // metered
+        let remaining = match runtime::fuel_remaining() {
+            u64::MAX => f64::INFINITY,
+            fuel => fuel as f64,
+        };
+
+        Ok(remaining.into())
+    }
+
+    fn lock_name(args: &[JsValue]) -> JsResult<String> {
+        let name = args.get_or_undefined(0).as_string().ok_or_else(|| {
+            JsNativeError::typ().with_message("Expected the lock name to be a string")
+        })?;
+
+        Ok(name.to_std_string_escaped())
+    }
+
+    /// Acquires the lock, returning its id or `null` if it is held
+    fn try_lock(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<Option<(Address, String, String)>> {
+        let contract = Contract::from_js_value(this)?;
+        let name = Self::lock_name(args)?;
+        let ttl_blocks = args.get_or_undefined(1).try_js_into::<u32>(context)?;
+
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let lock_id = runtime::with_global_host(|hrt| {
+            contract.lock(hrt, tx.deref_mut(), &name, ttl_blocks)
+        })?;
+
+        Ok(lock_id.map(|lock_id| (contract.contract_address.clone(), name, lock_id)))
+    }
+
+    fn lock(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let lock_id = Self::try_lock(this, args, context)?;
+
+        let result = ObjectInitializer::new(context)
+            .property(js_string!("acquired"), lock_id.is_some(), Attribute::all())
+            .property(
+                js_string!("lockId"),
+                match lock_id {
+                    Some((_, _, lock_id)) => lock_id.into_js(context),
+                    None => JsValue::null(),
+                },
+                Attribute::all(),
+            )
+            .build();
+
+        Ok(JsPromise::resolve(result, context)?.into())
+    }
+
+    fn unlock(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let contract = Contract::from_js_value(this)?;
+        let name = Self::lock_name(args)?;
+        let lock_id = args
+            .get_or_undefined(1)
+            .as_string()
+            .ok_or_else(|| {
+                JsNativeError::typ().with_message("Expected the lock id to be a string")
+            })?
+            .to_std_string_escaped();
+
+        host_defined!(context, host_defined);
+        let mut tx = host_defined
+            .get_mut::<Transaction>()
+            .expect("Curent transaction undefined");
+
+        let unlocked = runtime::with_global_host(|hrt| {
+            Contract::unlock(
+                hrt,
+                tx.deref_mut(),
+                &contract.contract_address,
+                &name,
+                &lock_id,
+            )
+        })?;
+
+        Ok(unlocked.into())
+    }
+
+    fn with_lock(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let function = args
+            .get_or_undefined(2)
+            .as_callable()
+            .cloned()
+            .ok_or_else(|| JsNativeError::typ().with_message("Expected a function"))?;
+
+        let Some((contract_address, name, lock_id)) =
+            Self::try_lock(this, args, context)?
+        else {
+            let err = JsNativeError::error()
+                .with_message(format!("Lock `{}` is held", Self::lock_name(args)?));
+            return Ok(JsPromise::reject(err, context)?.into());
+        };
+        let guard = LockGuard {
+            contract_address,
+            name,
+            lock_id,
+        };
 
-        let promise = JsPromise::new(
-            move |resolvers, context| {
-                let address = runtime::with_global_host(|rt| {
-                    contract.create(rt, &mut tx, contract_code, initial_balance as Amount)
-                })?;
+        let result = match function.call(&JsValue::undefined(), &[], context) {
+            Ok(result) => result,
+            Err(err) => {
+                guard.unlock(context)?;
+                return Ok(JsPromise::reject(err, context)?.into());
+            }
+        };
 
-                resolvers.resolve.call(
-                    &JsValue::undefined(),
-                    &[address.into_js(context)],
-                    context,
-                )?;
-                Ok(JsValue::undefined())
-            },
-            context,
-        )?;
+        // Async functions hold the lock until they settle
+        let Some(promise) = result.as_promise() else {
+            guard.unlock(context)?;
+            return Ok(JsPromise::resolve(result, context)?.into());
+        };
+
+        let unlock = FunctionObjectBuilder::new(context.realm(), unsafe {
+            NativeFunction::from_closure_with_captures(
+                |_, _, guard, context| {
+                    guard.unlock(context)?;
+                    Ok(JsValue::undefined())
+                },
+                guard,
+            )
+        })
+        .build();
+
+        let promise =
+            JsPromise::from_object(promise.clone())?.finally(unlock, context)?;
 
         Ok(promise.into())
     }
@@ -177,23 +1574,148 @@ impl ContractApi {
 
 impl jstz_core::Api for ContractApi {
     fn init(self, context: &mut Context<'_>) {
+        let operation = {
+            host_defined!(context, host_defined);
+            host_defined
+                .get::<OperationContext>()
+                .map(|operation| operation.clone())
+                .unwrap_or_default()
+        };
+        let call_value = operation.call_value;
+
         let contract = ObjectInitializer::with_native(
             Contract {
                 contract_address: self.contract_address,
-                operation_hash: self.operation_hash,
+                operation,
             },
             context,
         )
+        .property(
+            js_string!("callValue"),
+            call_value,
+            Attribute::READONLY | Attribute::ENUMERABLE,
+        )
         .function(
             NativeFunction::from_fn_ptr(Self::call),
             js_string!("call"),
             2,
         )
+        .function(
+            NativeFunction::from_fn_ptr(Self::call_with_value),
+            js_string!("callWithValue"),
+            3,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::try_call),
+            js_string!("tryCall"),
+            2,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::multicall),
+            js_string!("multicall"),
+            2,
+        )
         .function(
             NativeFunction::from_fn_ptr(Self::create),
             js_string!("create"),
             1,
         )
+        // `Contract.deploy` is an alias of `Contract.create`
+        .function(
+            NativeFunction::from_fn_ptr(Self::create),
+            js_string!("deploy"),
+            2,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::nonce),
+            js_string!("nonce"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::nonce_of),
+            js_string!("nonceOf"),
+            1,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::pause),
+            js_string!("pause"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::resume),
+            js_string!("resume"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::is_paused),
+            js_string!("isPaused"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::selfdestruct),
+            js_string!("selfdestruct"),
+            1,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::snapshot),
+            js_string!("snapshot"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::rollback_to_snapshot),
+            js_string!("rollbackToSnapshot"),
+            1,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::get_abi),
+            js_string!("getABI"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::delegate_to),
+            js_string!("delegateTo"),
+            1,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::emit),
+            js_string!("emit"),
+            2,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::non_reentrant),
+            js_string!("nonReentrant"),
+            1,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::enable_pause_guard),
+            js_string!("enablePauseGuard"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::estimate_gas),
+            js_string!("estimateGas"),
+            1,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::remaining_gas),
+            js_string!("remainingGas"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::lock),
+            js_string!("lock"),
+            2,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::unlock),
+            js_string!("unlock"),
+            2,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::with_lock),
+            js_string!("withLock"),
+            3,
+        )
         .build();
 
         context
@@ -201,3 +1723,1118 @@ impl jstz_core::Api for ContractApi {
             .expect("The contract object shouldn't exist yet")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{
+        call, committed_balance, deploy, execute, other, request, run_contract, source,
+    };
+    use jstz_core::kv::Kv;
+    use tezos_smart_rollup_mock::MockHost;
+
+    #[test]
+    fn test_estimate_gas_undoes_the_call() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let counter_code = r#"
+            export default () => {
+                const count = (Kv.get("count") ?? 0) + 1;
+                Kv.set("count", count);
+                console.log(`Counted ${count}`);
+                return new Response(String(count));
+            };
+        "#;
+        let counter = deploy(hrt, &mut tx, counter_code, 0);
+
+        let code = format!(
+            r#"
+            export default async () => {{
+                const before = Contract.remainingGas();
+                const gas = await Contract.estimateGas(
+                    new Request("tezos://{counter}/"),
+                );
+                const after = Contract.remainingGas();
+                const response = await Contract.call(new Request("tezos://{counter}/"));
+                return new Response(JSON.stringify({{
+                    gas,
+                    estimated: before - after,
+                    called: after - Contract.remainingGas(),
+                    count: await response.text(),
+                }}));
+            }};
+            "#
+        );
+        let address = deploy(hrt, &mut tx, &code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(hrt, &mut tx, &source(), request(&address, "/"))
+            .expect("Could not run script");
+
+        // Assert
+        let result: serde_json::Value =
+            serde_json::from_slice(&receipt.body.unwrap()).unwrap();
+        let gas = result["gas"].as_u64().unwrap();
+
+        // The dry run is undone and its gas refunded
+        assert_eq!(result["count"], "1");
+        assert!(gas > 0);
+        assert!(
+            result["estimated"].as_u64().unwrap() < result["called"].as_u64().unwrap()
+        );
+
+        // Only the actual call is logged and traced
+        assert_eq!(receipt.logs.len(), 1);
+        assert_eq!(receipt.call_trace.len(), 2);
+        assert_eq!(receipt.call_trace[1].contract_address, counter);
+    }
+
+    #[test]
+    fn test_locks_are_held_across_operations() {
+        let mut hrt = MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        // The participant of a two-phase commit, locked between the phases
+        let participant_code = r#"
+            export default async (request) => {
+                const url = new URL(request.url);
+                if (url.pathname === "/prepare") {
+                    const { acquired, lockId } = await Contract.lock("swap", 10);
+                    return acquired
+                        ? new Response(lockId)
+                        : new Response("locked", { status: 409 });
+                }
+                const lockId = url.searchParams.get("lockId");
+                if (!Contract.unlock("swap", lockId)) {
+                    return new Response("not locked", { status: 409 });
+                }
+                Kv.set("commits", (Kv.get("commits") ?? 0) + 1);
+                return new Response("committed");
+            };
+        "#;
+        let participant = deploy(&hrt, &mut tx, participant_code, 0);
+
+        // The coordinator keeps the lock id between the phases
+        let coordinator_code = format!(
+            r#"
+            export default async (request) => {{
+                const url = new URL(request.url);
+                if (url.pathname === "/prepare") {{
+                    const response = await Contract.call(
+                        new Request("tezos://{participant}/prepare"),
+                    );
+                    if (!response.ok) {{
+                        return new Response("aborted");
+                    }}
+                    Kv.set("lockId", await response.text());
+                    return new Response("prepared");
+                }}
+                const response = await Contract.call(
+                    new Request(
+                        `tezos://{participant}/commit?lockId=${{Kv.get("lockId")}}`,
+                    ),
+                );
+                return new Response(await response.text());
+            }};
+            "#
+        );
+        let coordinator = deploy(&hrt, &mut tx, &coordinator_code, 0);
+
+        let with_lock_code = r#"
+            export default async () => {
+                const inner = await Contract.withLock("job", 5, async () => {
+                    try {
+                        await Contract.withLock("job", 5, () => 1);
+                        return "reentered";
+                    } catch (error) {
+                        return error.message;
+                    }
+                });
+                const { acquired } = await Contract.lock("job", 5);
+                return new Response(`${inner}, ${acquired}`);
+            };
+        "#;
+        let with_lock = deploy(&hrt, &mut tx, with_lock_code, 0);
+
+        Block::advance(&hrt, &mut tx, 1, 1_700_000_000).expect("Could not advance block");
+
+        kv.commit_transaction(&mut hrt, tx)
+            .expect("Could not commit tx");
+
+        // Reverted calls are reported with the reason given by the contract
+        let call = |hrt: &mut MockHost, kv: &mut Kv, address: &Address, path: &str| {
+            let mut tx = kv.begin_transaction();
+            let result = execute(hrt, &mut tx, &source(), request(address, path));
+            kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+            match result {
+                Ok(receipt) => {
+                    String::from_utf8(receipt.body.unwrap_or_default()).unwrap()
+                }
+                Err(Error::ContractReverted { message, .. }) => message,
+                Err(err) => panic!("Could not run script: {err:?}"),
+            }
+        };
+
+        // Act & Assert
+        assert_eq!(
+            call(&mut hrt, &mut kv, &coordinator, "/prepare"),
+            "prepared"
+        );
+
+        // The lock outlives the operation that acquired it
+        assert_eq!(call(&mut hrt, &mut kv, &coordinator, "/prepare"), "aborted");
+        assert_eq!(call(&mut hrt, &mut kv, &participant, "/prepare"), "locked");
+        assert_eq!(
+            call(&mut hrt, &mut kv, &participant, "/commit?lockId=forged"),
+            "not locked"
+        );
+
+        assert_eq!(
+            call(&mut hrt, &mut kv, &coordinator, "/commit"),
+            "committed"
+        );
+        assert_eq!(
+            call(&mut hrt, &mut kv, &coordinator, "/commit"),
+            "not locked"
+        );
+
+        // A lock that is not released expires after its ttl
+        assert_eq!(
+            call(&mut hrt, &mut kv, &coordinator, "/prepare"),
+            "prepared"
+        );
+        let mut tx = kv.begin_transaction();
+        Block::advance(&hrt, &mut tx, 11, 1_700_000_150)
+            .expect("Could not advance block");
+        kv.commit_transaction(&mut hrt, tx)
+            .expect("Could not commit tx");
+        assert_ne!(call(&mut hrt, &mut kv, &participant, "/prepare"), "locked");
+
+        assert_eq!(
+            call(&mut hrt, &mut kv, &with_lock, "/"),
+            "Lock `job` is held, true"
+        );
+    }
+
+    #[test]
+    fn test_pause_guard_lets_admin_resume() {
+        let mut hrt = MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let (admin, user) = (source(), other());
+
+        let code = r#"
+            Contract.enablePauseGuard();
+
+            export default (request) => {
+                const path = new URL(request.url).pathname;
+                if (path === "/pause") Contract.pause();
+                if (path === "/resume") Contract.resume();
+                return new Response(String(Contract.isPaused()));
+            };
+        "#;
+        let address = deploy(&hrt, &mut tx, code, 0);
+        kv.commit_transaction(&mut hrt, tx)
+            .expect("Could not commit tx");
+
+        let mut send = |source: &Address, path: &str| {
+            let mut tx = kv.begin_transaction();
+            let result = execute(&mut hrt, &mut tx, source, request(&address, path))
+                .map(|receipt| receipt.body.unwrap_or_default());
+            kv.commit_transaction(&mut hrt, tx)
+                .expect("Could not commit tx");
+            result
+        };
+
+        assert_eq!(send(&admin, "/pause").unwrap(), b"true");
+        assert!(matches!(
+            send(&user, "/"),
+            Err(Error::ContractReverted { status: 503, .. })
+        ));
+
+        // The admin gets through the guard while the contract is paused
+        assert_eq!(send(&admin, "/resume").unwrap(), b"false");
+        assert_eq!(send(&user, "/").unwrap(), b"false");
+    }
+
+    #[test]
+    fn test_snapshot_rollback_on_slippage() {
+        let hrt = &mut MockHost::default();
+        let kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        // A constant product AMM that only applies swaps within tolerance
+        let code = r#"
+            const swap = (dx, tolerance) => {
+                const x = Kv.get("x");
+                const y = Kv.get("y");
+                const id = Contract.snapshot();
+
+                const dy = y - (x * y) / (x + dx);
+                Kv.set("x", x + dx);
+                Kv.set("y", y - dy);
+
+                const slippage = 1 - dy / ((dx * y) / x);
+                if (slippage > tolerance) {
+                    Contract.rollbackToSnapshot(id);
+                    return false;
+                }
+                return true;
+            };
+
+            export default () => {
+                Kv.set("x", 1000);
+                Kv.set("y", 1000);
+
+                const small = swap(10, 0.05);
+                const large = swap(500, 0.05);
+
+                return new Response(JSON.stringify({ small, large }));
+            };
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+
+        // Act
+        let response = call(hrt, &mut tx, &address);
+
+        // Assert
+        assert!(response.status().is_success());
+
+        let storage = jstz_api::Kv::new(address.to_string());
+        let x = storage.get(hrt, &mut tx, "x").unwrap().unwrap().0.as_f64();
+        let y = storage.get(hrt, &mut tx, "y").unwrap().unwrap().0.as_f64();
+
+        // Only the small swap was applied
+        assert_eq!(x, Some(1010.0));
+        assert!(y.unwrap() > 990.0 && y.unwrap() < 991.0);
+    }
+
+    #[test]
+    fn test_snapshots_are_scoped_to_the_invocation() {
+        let child = r#"
+            export default (request) => {
+                const id = Number(new URL(request.url).pathname.slice(1));
+                try {
+                    Contract.rollbackToSnapshot(id);
+                } catch (error) {
+                    return new Response(error.message);
+                }
+                return new Response("rolled back");
+            };
+        "#;
+        let (_, _, result) = run_contract(&format!(
+            r#"
+            export default async () => {{
+                const child = await Contract.deploy({child}, 0);
+                Kv.set("a", 1);
+                const id = Contract.snapshot();
+                Kv.set("b", 2);
+
+                // The callee cannot roll back the writes of its caller
+                const response = await Contract.call(new Request(`tezos://${{child}}/${{id}}`));
+                const message = await response.text();
+
+                // Leftover snapshots are released when the handler returns
+                Contract.snapshot();
+                return Response.json({{ message, id, b: Kv.get("b") }});
+            }};
+            "#,
+            child = serde_json::to_string(child).unwrap(),
+        ));
+
+        let result: serde_json::Value = serde_json::from_slice(&result.unwrap()).unwrap();
+        assert_eq!(
+            result["message"],
+            format!("Unknown snapshot {}", result["id"])
+        );
+        assert_eq!(result["b"], 2);
+    }
+
+    #[test]
+    fn test_delegate_on_not_found() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let fallback = deploy(
+            hrt,
+            &mut tx,
+            "export default () => new Response('fallback')",
+            0,
+        );
+
+        let router_code = format!(
+            r#"
+            Contract.delegateTo("{fallback}");
+
+            export default () => new Response(null, {{ status: 404 }});
+            "#
+        );
+        let router = deploy(hrt, &mut tx, &router_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let (parts, body) = call(hrt, &mut tx, &router).into_parts();
+
+        // Assert
+        assert_eq!(parts.status, 200);
+        assert_eq!(body, Some(b"fallback".to_vec()));
+    }
+
+    #[test]
+    fn test_call_with_value_escrow() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        // A payable escrow that only accepts deposits on `/deposit`
+        let escrow_code = r#"
+            export default (request) => {
+                const url = new URL(request.url);
+                if (url.pathname !== "/deposit") {
+                    return new Response(null, { status: 400 });
+                }
+
+                Kv.set("held", (Kv.get("held") ?? 0) + Contract.callValue);
+                return new Response();
+            };
+        "#;
+        let escrow = deploy(hrt, &mut tx, escrow_code, 0);
+
+        let payer_code = format!(
+            r#"
+            export default async () => {{
+                const accepted = await Contract.callWithValue(
+                    "{escrow}",
+                    30,
+                    new Request("tezos://{escrow}/deposit"),
+                );
+                const rejected = await Contract.callWithValue(
+                    "{escrow}",
+                    50,
+                    new Request("tezos://{escrow}/withdraw"),
+                );
+
+                const invalid = [];
+                for (const amount of [-1, 0.5]) {{
+                    try {{
+                        await Contract.callWithValue(
+                            "{escrow}",
+                            amount,
+                            new Request("tezos://{escrow}/deposit"),
+                        );
+                    }} catch (error) {{
+                        invalid.push(error.message);
+                    }}
+                }}
+
+                return new Response(
+                    JSON.stringify([accepted.status, rejected.status, invalid]),
+                );
+            }};
+            "#
+        );
+        let payer = deploy(hrt, &mut tx, &payer_code, 100);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let (parts, body) = call(hrt, &mut tx, &payer).into_parts();
+
+        // Assert
+        assert_eq!(parts.status, 200);
+        let body: serde_json::Value = serde_json::from_slice(&body.unwrap()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!([
+                200,
+                400,
+                [
+                    "Expected a non-negative integer amount",
+                    "Expected a non-negative integer amount",
+                ],
+            ])
+        );
+
+        // Only the accepted deposit was transferred
+        assert_eq!(Account::balance(hrt, &mut tx, &payer).unwrap(), 70);
+        assert_eq!(Account::balance(hrt, &mut tx, &escrow).unwrap(), 30);
+
+        let held = jstz_api::Kv::new(escrow.to_string())
+            .get(hrt, &mut tx, "held")
+            .unwrap()
+            .unwrap();
+        assert_eq!(held.0.as_f64(), Some(30.0));
+    }
+
+    #[test]
+    fn test_multicall_stops_on_error() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let counter_code = r#"
+            export default (request) => {
+                const url = new URL(request.url);
+                Kv.set("count", (Kv.get("count") ?? 0) + 1);
+
+                if (url.pathname === "/fail") {
+                    return new Response(null, { status: 500 });
+                }
+                return new Response();
+            };
+        "#;
+        let counter = deploy(hrt, &mut tx, counter_code, 0);
+
+        let batcher_code = format!(
+            r#"
+            const call = (path) => ({{
+                address: "{counter}",
+                request: new Request("tezos://{counter}" + path),
+            }});
+
+            export default async () => {{
+                const responses = await Contract.multicall(
+                    [call("/inc"), call("/fail"), call("/inc")],
+                    {{ stopOnError: true }},
+                );
+
+                return new Response(
+                    JSON.stringify(responses.map((response) => response.status)),
+                );
+            }};
+            "#
+        );
+        let batcher = deploy(hrt, &mut tx, &batcher_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let (parts, body) = call(hrt, &mut tx, &batcher).into_parts();
+
+        // Assert
+        assert_eq!(parts.status, 200);
+        assert_eq!(body, Some(b"[200,500]".to_vec()));
+
+        // The failed call's write was rolled back and the third call never ran
+        let count = jstz_api::Kv::new(counter.to_string())
+            .get(hrt, &mut tx, "count")
+            .unwrap()
+            .unwrap();
+        assert_eq!(count.0.as_f64(), Some(1.0));
+    }
+
+    #[test]
+    fn test_try_call_failing_target() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let failing_code = r#"
+            export default (request) => {
+                Kv.set("touched", true);
+
+                const url = new URL(request.url);
+                if (url.pathname === "/throw") {
+                    throw new Error("boom");
+                }
+                return new Response(null, { status: 400 });
+            };
+        "#;
+        let failing = deploy(hrt, &mut tx, failing_code, 0);
+
+        let caller_code = format!(
+            r#"
+            export default async () => {{
+                const rejected = await Contract.tryCall(
+                    "{failing}",
+                    new Request("tezos://{failing}/reject"),
+                );
+                const thrown = await Contract.tryCall(
+                    "{failing}",
+                    new Request("tezos://{failing}/throw"),
+                );
+
+                return new Response(JSON.stringify([
+                    [rejected.ok, rejected.response.status],
+                    [thrown.ok, thrown.response.status],
+                ]));
+            }};
+            "#
+        );
+        let caller = deploy(hrt, &mut tx, &caller_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let (parts, body) = call(hrt, &mut tx, &caller).into_parts();
+
+        // Assert
+        assert_eq!(parts.status, 200);
+        assert_eq!(body, Some(b"[[false,400],[false,500]]".to_vec()));
+
+        // Neither failed call's write was kept
+        let touched = jstz_api::Kv::new(failing.to_string())
+            .has(hrt, &mut tx, "touched")
+            .unwrap();
+        assert!(!touched);
+    }
+
+    #[test]
+    fn test_call_depth_exceeded() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        // Recurses without bound on `/recurse`. On `/`, it recurses, catches
+        // the error and then makes one more call, which only succeeds if the
+        // call depth has been restored.
+        let code = r#"
+            export default async (request) => {
+                const self = Ledger.selfAddress;
+                switch (new URL(request.url).pathname) {
+                    case "/recurse":
+                        return Contract.call(new Request(`tezos://${self}/recurse`));
+                    case "/ping":
+                        return new Response("pong");
+                    default:
+                        let error = null;
+                        try {
+                            await Contract.call(new Request(`tezos://${self}/recurse`));
+                        } catch (e) {
+                            error = String(e);
+                        }
+                        const ping = await Contract.call(
+                            new Request(`tezos://${self}/ping`),
+                        );
+                        return new Response(JSON.stringify([error, ping.status]));
+                }
+            };
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let (parts, body) = call(hrt, &mut tx, &address).into_parts();
+
+        // Assert
+        assert_eq!(parts.status, 200);
+
+        let (error, ping_status): (String, u16) =
+            serde_json::from_slice(&body.unwrap()).unwrap();
+        assert!(error.contains("CallDepthExceeded"));
+        assert_eq!(ping_status, 200);
+    }
+
+    #[test]
+    fn test_factory_deploys_and_calls_child() {
+        let child = r#"
+            export default () => Response.json({
+                balance: Ledger.balance(Ledger.selfAddress),
+                caller: Ledger.caller,
+            });
+        "#;
+        let (hrt, factory, result) = run_contract(&format!(
+            r#"
+            export default async () => {{
+                const address = await Contract.deploy({child}, 20);
+                const response = await Contract.call(new Request(`tezos://${{address}}/`));
+                return Response.json({{ address, ...(await response.json()) }});
+            }};
+            "#,
+            child = serde_json::to_string(child).unwrap(),
+        ));
+
+        let result: serde_json::Value = serde_json::from_slice(&result.unwrap()).unwrap();
+        let address = Address::from_base58(result["address"].as_str().unwrap()).unwrap();
+
+        assert_eq!(result["balance"], 20);
+        assert_eq!(result["caller"], factory.to_string());
+        assert_eq!(committed_balance(&hrt, &factory), 30);
+        assert_eq!(committed_balance(&hrt, &address), 20);
+    }
+
+    #[test]
+    fn test_factory_revert_undoes_deploy() {
+        let child = "export default () => new Response();";
+        let (hrt, factory, result) = run_contract(&format!(
+            r#"
+            export default async () => {{
+                await Contract.deploy({child}, 20);
+                return new Response(null, {{ status: 500 }});
+            }};
+            "#,
+            child = serde_json::to_string(child).unwrap(),
+        ));
+
+        assert!(matches!(
+            result,
+            Err(Error::ContractReverted { status: 500, .. })
+        ));
+
+        // The child would have been deployed with the first nonce of the factory
+        let address = Address::digest(format!("{factory}{child}0").as_bytes())
+            .expect("Could not derive address");
+        let tx = &mut Kv::new().begin_transaction();
+        assert_eq!(
+            Account::kind(&hrt, tx, &address).unwrap(),
+            crate::context::account::AccountKind::Unknown
+        );
+        assert_eq!(Account::nonce(&hrt, tx, &factory).unwrap().value(), 0);
+        assert_eq!(committed_balance(&hrt, &factory), 50);
+    }
+
+    #[test]
+    fn test_nested_call_chain() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let c_code = r#"
+            export default () =>
+                new Response(
+                    JSON.stringify({ caller: Ledger.caller, origin: Ledger.origin }),
+                );
+        "#;
+        let c = deploy(hrt, &mut tx, c_code, 0);
+
+        let b_code = format!(
+            r#"
+            export default () => Contract.call(new Request("tezos://{c}/"));
+            "#
+        );
+        let b = deploy(hrt, &mut tx, &b_code, 0);
+
+        let a_code = format!(
+            r#"
+            export default async () => {{
+                const nested = await Contract.call(new Request("tezos://{b}/"));
+                return new Response(
+                    JSON.stringify({{ caller: Ledger.caller, nested: await nested.json() }}),
+                );
+            }};
+            "#
+        );
+        let a = deploy(hrt, &mut tx, &a_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(hrt, &mut tx, &source, request(&a, "/"))
+            .expect("Could not run script");
+
+        // Assert
+        let body: serde_json::Value =
+            serde_json::from_slice(&receipt.body.expect("Expected a body")).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "caller": source.to_string(),
+                "nested": { "caller": b.to_string(), "origin": source.to_string() },
+            })
+        );
+    }
+
+    #[test]
+    fn test_nested_writes_are_visible_before_commit() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let counter_code = r#"
+            export default (request) => {
+                if (new URL(request.url).pathname === "/increment") {
+                    Kv.set("count", (Kv.get("count") ?? 0) + 1);
+                }
+                return new Response(JSON.stringify(Kv.get("count")));
+            };
+        "#;
+        let counter = deploy(hrt, &mut tx, counter_code, 0);
+
+        let caller_code = format!(
+            r#"
+            export default async () => {{
+                await Contract.call(new Request("tezos://{counter}/increment"));
+                const count = await Contract.call(new Request("tezos://{counter}/"));
+                return new Response(await count.text());
+            }};
+            "#
+        );
+        let caller = deploy(hrt, &mut tx, &caller_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(hrt, &mut tx, &source(), request(&caller, "/"))
+            .expect("Could not run script");
+
+        // Assert
+        assert_eq!(receipt.body, Some(b"1".to_vec()));
+
+        // The write is part of the operation's transaction, and is only
+        // persisted once it is committed
+        let storage = jstz_api::Kv::new(counter.to_string());
+        assert!(storage.has(hrt, &mut tx, "count").unwrap());
+        assert!(!storage
+            .has(hrt, &mut kv.begin_transaction(), "count")
+            .unwrap());
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+        assert!(storage
+            .has(hrt, &mut kv.begin_transaction(), "count")
+            .unwrap());
+    }
+
+    #[test]
+    fn test_fetch_smart_function() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let callee_code = r#"
+            export default (request) =>
+                new Response(
+                    JSON.stringify({
+                        path: new URL(request.url).pathname,
+                        caller: Ledger.caller,
+                    }),
+                );
+        "#;
+        let callee = deploy(hrt, &mut tx, callee_code, 0);
+
+        let caller_code = format!(
+            r#"
+            export default async () => {{
+                const response = await fetch("jstz://{callee}/path");
+                const json = await response.json();
+
+                let rejected = false;
+                try {{
+                    await fetch("tezos://{callee}/path");
+                }} catch (error) {{
+                    rejected = error instanceof TypeError;
+                }}
+
+                return new Response(JSON.stringify({{ ...json, rejected }}));
+            }};
+            "#
+        );
+        let caller = deploy(hrt, &mut tx, &caller_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(hrt, &mut tx, &source(), request(&caller, "/"))
+            .expect("Could not run script");
+
+        // Assert
+        let body: serde_json::Value =
+            serde_json::from_slice(&receipt.body.expect("Expected a body")).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "path": "/path",
+                "caller": caller.to_string(),
+                "rejected": true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_fetch_with_abort_signal() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let callee_code = r#"
+            export default async () => {
+                await new Promise((resolve) => setTimeout(resolve, 10));
+                return new Response("done");
+            };
+        "#;
+        let callee = deploy(hrt, &mut tx, callee_code, 0);
+
+        let caller_code = format!(
+            r#"
+            const outcome = (promise) =>
+                promise.then(
+                    (response) => response.text(),
+                    (error) => `rejected: ${{error.name ?? error}}`,
+                );
+
+            export default async () => {{
+                const before = new AbortController();
+                before.abort();
+                const abortedBefore = await outcome(
+                    fetch("jstz://{callee}/", {{ signal: before.signal }}),
+                );
+
+                const during = new AbortController();
+                setTimeout(() => during.abort("timeout"), 5);
+                const abortedDuring = await outcome(
+                    fetch("jstz://{callee}/", {{ signal: during.signal }}),
+                );
+
+                const never = new AbortController();
+                const completed = await outcome(
+                    fetch("jstz://{callee}/", {{ signal: never.signal }}),
+                );
+                never.abort();
+
+                return new Response(
+                    JSON.stringify({{ abortedBefore, abortedDuring, completed }}),
+                );
+            }};
+            "#
+        );
+        let caller = deploy(hrt, &mut tx, &caller_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(hrt, &mut tx, &source(), request(&caller, "/"))
+            .expect("Could not run script");
+
+        // Assert
+        let body: serde_json::Value =
+            serde_json::from_slice(&receipt.body.expect("Expected a body")).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "abortedBefore": "rejected: AbortError",
+                "abortedDuring": "rejected: timeout",
+                "completed": "done",
+            })
+        );
+    }
+
+    #[test]
+    fn test_events_survive_commit_and_vanish_on_revert() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let callee_code = r#"
+            export default (request) => {
+                Contract.emit("Callee", { path: new URL(request.url).pathname });
+                if (request.url.endsWith("/throw")) {
+                    throw new Error("boom");
+                }
+                const status = request.url.endsWith("/revert") ? 403 : 200;
+                return new Response(null, { status });
+            };
+        "#;
+        let callee = deploy(hrt, &mut tx, callee_code, 0);
+
+        let caller_code = format!(
+            r#"
+            export default async (request) => {{
+                Contract.emit("Caller", 42);
+                await Contract.tryCall("{callee}", new Request("tezos://{callee}/ok"));
+                await Contract.tryCall("{callee}", new Request("tezos://{callee}/revert"));
+                await Contract.tryCall("{callee}", new Request("tezos://{callee}/throw"));
+                const status = request.url.endsWith("/revert") ? 500 : 200;
+                return new Response(null, {{ status }});
+            }};
+            "#
+        );
+        let caller = deploy(hrt, &mut tx, &caller_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(hrt, &mut tx, &source(), request(&caller, "/"))
+            .expect("Could not run script");
+
+        // Assert
+        assert_eq!(
+            receipt.events,
+            vec![
+                Event {
+                    contract_address: caller.clone(),
+                    topic: "Caller".to_string(),
+                    data: serde_json::json!(42),
+                },
+                Event {
+                    contract_address: callee.clone(),
+                    topic: "Callee".to_string(),
+                    data: serde_json::json!({ "path": "/ok" }),
+                },
+            ]
+        );
+
+        // Act
+        let result = execute(hrt, &mut tx, &source(), request(&caller, "/revert"));
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(Error::ContractReverted { status: 500, .. })
+        ));
+    }
+
+    #[test]
+    fn test_call_trace_in_receipt() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let leaf_code = r#"
+            export default (request) => {
+                if (request.url.endsWith("/throw")) {
+                    throw new Error("boom");
+                }
+                const status = request.url.endsWith("/revert") ? 403 : 200;
+                return new Response(null, { status });
+            };
+        "#;
+        let leaf = deploy(hrt, &mut tx, leaf_code, 0);
+
+        let middle_code = format!(
+            r#"
+            export default async () => {{
+                await Contract.call(new Request("tezos://{leaf}/ok"));
+                await Contract.tryCall("{leaf}", new Request("tezos://{leaf}/revert"));
+                await Contract.tryCall("{leaf}", new Request("tezos://{leaf}/throw"));
+                return new Response();
+            }};
+            "#
+        );
+        let middle = deploy(hrt, &mut tx, &middle_code, 0);
+
+        let caller_code = format!(
+            r#"
+            export default () => Contract.call(new Request("tezos://{middle}/"));
+            "#
+        );
+        let caller = deploy(hrt, &mut tx, &caller_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(hrt, &mut tx, &source(), request(&caller, "/"))
+            .expect("Could not run script");
+
+        // Assert
+        let shape: Vec<_> = receipt
+            .call_trace
+            .iter()
+            .map(|frame| {
+                (
+                    frame.contract_address.clone(),
+                    frame.call_depth,
+                    frame.status,
+                    frame.reverted,
+                )
+            })
+            .collect();
+        assert_eq!(
+            shape,
+            vec![
+                (caller, 1, Some(200), false),
+                (middle, 2, Some(200), false),
+                (leaf.clone(), 3, Some(200), false),
+                (leaf.clone(), 3, Some(403), true),
+                (leaf, 3, None, true),
+            ]
+        );
+
+        // Each call consumes fuel, which is counted by its callers too
+        let gas: Vec<u64> = receipt
+            .call_trace
+            .iter()
+            .map(|frame| frame.gas_used)
+            .collect();
+        assert!(gas.iter().all(|gas| *gas > 0));
+        assert!(receipt.gas_used >= gas[0]);
+        assert!(gas[0] > gas[1]);
+        assert!(gas[1] > gas[2] + gas[3] + gas[4]);
+    }
+
+    #[test]
+    fn test_non_reentrant_guard() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        // A's guarded function calls B, which calls back into A
+        let b_code = r#"
+            export default async (request) => {
+                const a = request.headers.get("Referer");
+                try {
+                    await Contract.call(new Request(`tezos://${a}/`));
+                    return new Response("reentered");
+                } catch (error) {
+                    return new Response(error.message);
+                }
+            };
+        "#;
+        let b = deploy(hrt, &mut tx, b_code, 0);
+
+        let a_code = format!(
+            r#"
+            const withdraw = Contract.nonReentrant(async () => {{
+                const response = await Contract.call(new Request("tezos://{b}/"));
+                return new Response(await response.text());
+            }});
+            // The lock is released once the first withdrawal settles
+            export default async () => {{
+                await withdraw();
+                return withdraw();
+            }};
+            "#
+        );
+        let a = deploy(hrt, &mut tx, &a_code, 0);
+
+        // The lock is released when a guarded function throws
+        let c_code = r#"
+            const fail = Contract.nonReentrant(() => {
+                throw new Error("boom");
+            });
+            const succeed = Contract.nonReentrant(() => new Response("released"));
+            export default () => {
+                try {
+                    fail();
+                } catch {}
+                return succeed();
+            };
+        "#;
+        let c = deploy(hrt, &mut tx, c_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let reentered = execute(hrt, &mut tx, &source(), request(&a, "/"))
+            .expect("Could not run script");
+        let released = execute(hrt, &mut tx, &source(), request(&c, "/"))
+            .expect("Could not run script");
+
+        // Assert
+        assert_eq!(reentered.body, Some(b"ReentrancyDetected".to_vec()));
+        assert_eq!(released.body, Some(b"released".to_vec()));
+    }
+}
@@ -13,14 +13,22 @@ use jstz_core::{
     runtime, value::IntoJs,
 };
 
+use super::contract::CallChain;
 use crate::{
-    context::account::{Account, Address, Amount},
+    context::account::{Account, AccountKind, Address, Amount},
     error::Result,
+    Error,
 };
 
 // Ledger.selfAddress
+// Ledger.caller
+// Ledger.origin
 // Ledger.balance(pkh)
+// Ledger.accountKind(pkh)
+// Ledger.nonce(pkh)
 // Ledger.transfer(dst, amount)
+// Ledger.totalSupply()
+// Ledger.circulatingSupply()
 
 struct Ledger {
     contract_address: Address,
@@ -47,6 +55,28 @@ impl Ledger {
         Ok(balance)
     }
 
+    fn account_kind(
+        rt: &impl HostRuntime,
+        tx: &mut Transaction,
+        addr: &Address,
+    ) -> Result<AccountKind> {
+        let kind = Account::kind(rt, tx, addr)?;
+
+        Ok(kind)
+    }
+
+    fn nonce(rt: &impl HostRuntime, tx: &mut Transaction, addr: &Address) -> Result<u64> {
+        let nonce = Account::nonce(rt, tx, addr)?;
+
+        Ok(nonce.value())
+    }
+
+    fn total_supply(rt: &impl HostRuntime, tx: &mut Transaction) -> Result<u64> {
+        let total_supply = Account::total_supply(rt, tx)?;
+
+        Ok(total_supply)
+    }
+
     fn transfer(
         &self,
         rt: &impl HostRuntime,
@@ -54,6 +84,10 @@ impl Ledger {
         dst: &Address,
         amount: Amount,
     ) -> Result<()> {
+        if Account::balance(rt, tx, &self.contract_address)? < amount {
+            return Err(Error::InsufficientFunds);
+        }
+
         Account::transfer(rt, tx, &self.contract_address, dst, amount)?;
 
         Ok(())
@@ -101,6 +135,43 @@ impl LedgerApi {
         )
     }
 
+    fn caller(context: &mut Context<'_>) -> Accessor {
+        accessor!(
+            context,
+            Ledger,
+            "caller",
+            get:((_ledger, context) => Ok(Self::call_chain_address(context, |call_chain| &call_chain.caller)))
+        )
+    }
+
+    fn origin(context: &mut Context<'_>) -> Accessor {
+        accessor!(
+            context,
+            Ledger,
+            "origin",
+            get:((_ledger, context) => Ok(Self::call_chain_address(context, |call_chain| &call_chain.origin)))
+        )
+    }
+
+    /// Returns `null` when the contract is not handling a request
+    fn call_chain_address(
+        context: &mut Context<'_>,
+        f: impl FnOnce(&CallChain) -> &Address,
+    ) -> JsValue {
+        let address = {
+            host_defined!(context, host_defined);
+
+            host_defined
+                .get::<CallChain>()
+                .map(|call_chain| f(&call_chain).to_string())
+        };
+
+        match address {
+            Some(address) => address.into_js(context),
+            None => JsValue::null(),
+        }
+    }
+
     fn balance(
         _this: &JsValue,
         args: &[JsValue],
@@ -119,6 +190,70 @@ impl LedgerApi {
         })
     }
 
+    fn account_kind(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        runtime::with_global_host(|rt| {
+            host_defined!(context, host_defined);
+
+            let mut tx = host_defined.get_mut::<Transaction>().unwrap();
+
+            let pkh = js_value_to_pkh(args.get_or_undefined(0))?;
+
+            let kind = Ledger::account_kind(rt.deref(), tx.deref_mut(), &pkh)?;
+
+            Ok(JsString::from(kind.as_str()).into())
+        })
+    }
+
+    /// Returns the nonce of an account. The address of the next contract
+    /// deployed by the account is derived from it (see `Script::deploy`).
+    fn nonce(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        runtime::with_global_host(|rt| {
+            host_defined!(context, host_defined);
+
+            let mut tx = host_defined.get_mut::<Transaction>().unwrap();
+
+            let pkh = js_value_to_pkh(args.get_or_undefined(0))?;
+
+            let nonce = Ledger::nonce(rt.deref(), tx.deref_mut(), &pkh)?;
+
+            Ok(nonce.into())
+        })
+    }
+
+    fn total_supply(
+        _this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        runtime::with_global_host(|rt| {
+            host_defined!(context, host_defined);
+
+            let mut tx = host_defined.get_mut::<Transaction>().unwrap();
+
+            let total_supply = Ledger::total_supply(rt.deref(), tx.deref_mut())?;
+
+            Ok(total_supply.into())
+        })
+    }
+
+    // No balances are locked or frozen in jstz, so the whole supply
+    // circulates
+    fn circulating_supply(
+        this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        Self::total_supply(this, args, context)
+    }
+
     fn transfer(
         this: &JsValue,
         args: &[JsValue],
@@ -133,7 +268,11 @@ impl LedgerApi {
             let amount = args
                 .get_or_undefined(1)
                 .as_number()
-                .ok_or_else(|| JsNativeError::typ())?;
+                .filter(|amount| amount.fract() == 0.0 && *amount >= 0.0)
+                .ok_or_else(|| {
+                    JsNativeError::typ()
+                        .with_message("Expected a non-negative integer amount")
+                })?;
 
             ledger.transfer(rt.deref(), tx.deref_mut(), &dst, amount as Amount)?;
 
@@ -145,6 +284,8 @@ impl LedgerApi {
 impl jstz_core::Api for LedgerApi {
     fn init(self, context: &mut boa_engine::Context<'_>) {
         let self_address = LedgerApi::self_address(context);
+        let caller = LedgerApi::caller(context);
+        let origin = LedgerApi::origin(context);
 
         let ledger = ObjectInitializer::with_native(
             Ledger {
@@ -158,15 +299,47 @@ impl jstz_core::Api for LedgerApi {
             self_address.set,
             Attribute::all(),
         )
+        .accessor(
+            js_string!(caller.name),
+            caller.get,
+            caller.set,
+            Attribute::all(),
+        )
+        .accessor(
+            js_string!(origin.name),
+            origin.get,
+            origin.set,
+            Attribute::all(),
+        )
         .function(
             NativeFunction::from_fn_ptr(Self::balance),
             js_string!("balance"),
             1,
         )
+        .function(
+            NativeFunction::from_fn_ptr(Self::account_kind),
+            js_string!("accountKind"),
+            1,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::nonce),
+            js_string!("nonce"),
+            1,
+        )
         .function(
             NativeFunction::from_fn_ptr(Self::transfer),
             js_string!("transfer"),
-            3,
+            2,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::total_supply),
+            js_string!("totalSupply"),
+            0,
+        )
+        .function(
+            NativeFunction::from_fn_ptr(Self::circulating_supply),
+            js_string!("circulatingSupply"),
+            0,
         )
         .build();
 
@@ -175,3 +348,110 @@ impl jstz_core::Api for LedgerApi {
             .expect("The ledger object shouldn't exist yet");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{committed_balance, run_contract, source};
+
+    #[test]
+    fn test_ledger_self_transfer() {
+        let (hrt, address, result) = run_contract(
+            r#"
+            export default () => {
+                Ledger.transfer(Ledger.selfAddress, 30);
+                return new Response(JSON.stringify(Ledger.balance(Ledger.selfAddress)));
+            };
+            "#,
+        );
+
+        assert_eq!(result.unwrap(), b"50");
+        assert_eq!(committed_balance(&hrt, &address), 50);
+    }
+
+    #[test]
+    fn test_ledger_account_kind() {
+        let (_, _, result) = run_contract(
+            r#"
+            export default () => {
+                const kinds = [
+                    Ledger.accountKind(Ledger.selfAddress),
+                    Ledger.accountKind("tz1ZXxxkNYSUutm5VZvfudakRxx2mjpWko4J"),
+                ];
+                return new Response(kinds.join(","));
+            };
+            "#,
+        );
+
+        assert_eq!(result.unwrap(), b"SmartFunction,Unknown");
+    }
+
+    #[test]
+    fn test_ledger_nonce_predicts_deploy_address() {
+        let child = "export default () => new Response();";
+        let (_, factory, result) = run_contract(&format!(
+            r#"
+            export default async () => {{
+                const nonce = Ledger.nonce(Ledger.selfAddress);
+                const address = await Contract.create({child});
+                const next = Ledger.nonce(Ledger.selfAddress);
+                return Response.json({{ nonce, address, next }});
+            }};
+            "#,
+            child = serde_json::to_string(child).unwrap(),
+        ));
+
+        let result: serde_json::Value = serde_json::from_slice(&result.unwrap()).unwrap();
+        let nonce = result["nonce"].as_u64().unwrap();
+        let predicted = Address::digest(format!("{factory}{child}{nonce}").as_bytes())
+            .expect("Could not derive address");
+
+        assert_eq!(result["address"], predicted.to_string());
+        assert_eq!(result["next"], nonce + 1);
+    }
+
+    #[test]
+    fn test_ledger_overdraft() {
+        let (hrt, address, result) = run_contract(
+            r#"
+            export default (request) => {
+                try {
+                    Ledger.transfer(request.headers.get("Referer"), 51);
+                } catch (error) {
+                    return new Response(error.message);
+                }
+                return new Response("transferred");
+            };
+            "#,
+        );
+
+        assert_eq!(result.unwrap(), b"InsufficientFunds");
+        assert_eq!(committed_balance(&hrt, &address), 50);
+        assert_eq!(committed_balance(&hrt, &source()), 0);
+    }
+
+    #[test]
+    fn test_ledger_transfer_then_revert() {
+        let (hrt, address, result) = run_contract(
+            r#"
+            export default (request) => {
+                Ledger.transfer(request.headers.get("Referer"), 20);
+                const balance = Ledger.balance(Ledger.selfAddress);
+                return new Response(JSON.stringify(balance), { status: 500 });
+            };
+            "#,
+        );
+
+        let Err(Error::ContractReverted {
+            status, message, ..
+        }) = result
+        else {
+            panic!("Expected the contract to revert");
+        };
+        assert_eq!(status, 500);
+        // The pending debit is visible within the handler
+        assert_eq!(message, "30");
+        assert_eq!(committed_balance(&hrt, &address), 50);
+        assert_eq!(committed_balance(&hrt, &source()), 0);
+    }
+}
@@ -1,5 +1,12 @@
+mod block;
 mod contract;
 mod ledger;
+mod time;
 
-pub use contract::ContractApi;
+pub use block::BlockApi;
+pub use contract::{
+    fetch, CallChain, CallFrame, CallTrace, ContractApi, Delegate, Event, EventBuffer,
+    PauseGuard, ReentrancyLocks, Snapshots,
+};
 pub use ledger::LedgerApi;
+pub use time::BlockTimeApi;
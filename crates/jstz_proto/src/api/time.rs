@@ -0,0 +1,90 @@
+use std::ops::{Deref, DerefMut};
+
+use boa_engine::{
+    js_string, object::ObjectInitializer, property::Attribute, Context, JsArgs,
+    JsNativeError, JsResult, JsString, JsValue, NativeFunction,
+};
+use jstz_core::{host_defined, kv::Transaction, runtime};
+
+use crate::context::block::{format_iso8601, Block};
+
+// Time.format(level, format = "iso8601")
+// Time.now()
+
+pub struct BlockTimeApi;
+
+impl BlockTimeApi {
+    const NAME: &'static str = "Time";
+
+    fn timestamp_at(context: &mut Context<'_>, level: Option<u32>) -> JsResult<i64> {
+        runtime::with_global_host(|rt| {
+            host_defined!(context, host_defined);
+
+            let mut tx = host_defined.get_mut::<Transaction>().unwrap();
+
+            let level = match level {
+                Some(level) => level,
+                None => Block::current(rt.deref(), tx.deref_mut())?.level,
+            };
+
+            Block::timestamp_at(rt.deref(), tx.deref_mut(), level)?.ok_or_else(|| {
+                JsNativeError::range()
+                    .with_message(format!("No known timestamp for block {level}"))
+                    .into()
+            })
+        })
+    }
+
+    fn format(
+        _this: &JsValue,
+        args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let level = args.get_or_undefined(0).to_u32(context)?;
+        let format = match args.get_or_undefined(1) {
+            JsValue::Undefined => "iso8601".to_string(),
+            format => format.to_string(context)?.to_std_string_escaped(),
+        };
+
+        let timestamp = Self::timestamp_at(context, Some(level))?;
+
+        let formatted = match format.as_str() {
+            "iso8601" => format_iso8601(timestamp),
+            "unix" => timestamp.to_string(),
+            _ => {
+                return Err(JsNativeError::range()
+                    .with_message(format!("Unsupported time format: {format}"))
+                    .into())
+            }
+        };
+
+        Ok(JsString::from(formatted).into())
+    }
+
+    fn now(
+        _this: &JsValue,
+        _args: &[JsValue],
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        let timestamp = Self::timestamp_at(context, None)?;
+
+        Ok((timestamp as f64).into())
+    }
+}
+
+impl jstz_core::Api for BlockTimeApi {
+    fn init(self, context: &mut Context<'_>) {
+        let time = ObjectInitializer::new(context)
+            .function(
+                NativeFunction::from_fn_ptr(Self::format),
+                js_string!("format"),
+                1,
+            )
+            .function(NativeFunction::from_fn_ptr(Self::now), js_string!("now"), 0)
+            .build();
+
+        context
+            .register_global_property(js_string!(Self::NAME), time, Attribute::all())
+            .expect("The time object shouldn't exist yet");
+    }
+}
@@ -1,3 +1,8 @@
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display},
+};
+
 use crate::error::{Error, Result};
 use jstz_core::{
     host::HostRuntime,
@@ -23,23 +28,64 @@ impl Nonce {
     pub fn increment(&mut self) {
         self.0 += 1
     }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
 }
 
-impl ToString for Nonce {
-    fn to_string(&self) -> String {
-        self.0.to_string()
+impl Display for Nonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Account {
     pub nonce: Nonce,
     pub amount: Amount,
     pub contract_code: Option<String>,
 }
 
+/// What an address holds, as far as the ledger knows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AccountKind {
+    /// An account without code, e.g. one controlled by a key pair
+    User,
+    /// An account with deployed code
+    SmartFunction,
+    /// An address the ledger has never seen
+    Unknown,
+}
+
+impl AccountKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::User => "User",
+            Self::SmartFunction => "SmartFunction",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
 const ACCOUNTS_PATH: RefPath = RefPath::assert_from(b"/jstz_account");
 
+/// The tombstone of a deleted account, stored under the account's path
+/// rather than in [`Account`], so that the stored layout of accounts is
+/// unchanged
+const DELETED_PATH: RefPath = RefPath::assert_from(b"/deleted");
+
+/// The address allowed to upgrade the contract code, set at deployment.
+/// Stored under the account's path, like [`DELETED_PATH`].
+const ADMIN_PATH: RefPath = RefPath::assert_from(b"/admin");
+
+/// The sum of all account balances. It is absent (i.e. zero) at genesis, when
+/// no account holds any balance, and every later change to a balance other
+/// than a transfer goes through [`Account::mint`] or [`Account::burn`],
+/// including the balances of new accounts and those set with
+/// [`Account::set_balance`].
+const TOTAL_SUPPLY_PATH: &str = "/__total_supply__";
+
 impl Account {
     pub fn path(pkh: &Address) -> Result<OwnedPath> {
         let account_path = OwnedPath::try_from(format!("/{}", pkh))?;
@@ -47,6 +93,14 @@ impl Account {
         Ok(path::concat(&ACCOUNTS_PATH, &account_path)?)
     }
 
+    fn deleted_path(pkh: &Address) -> Result<OwnedPath> {
+        Ok(path::concat(&Self::path(pkh)?, &DELETED_PATH)?)
+    }
+
+    fn admin_path(pkh: &Address) -> Result<OwnedPath> {
+        Ok(path::concat(&Self::path(pkh)?, &ADMIN_PATH)?)
+    }
+
     fn get_mut<'a, 'b>(
         hrt: &impl HostRuntime,
         tx: &'a mut Transaction,
@@ -57,7 +111,40 @@ impl Account {
     {
         let account_entry = tx.entry(hrt, Self::path(addr)?)?;
 
-        Ok(account_entry.or_insert_default())
+        Ok(account_entry.or_insert_default()?)
+    }
+
+    /// Reads the account, if it has been inserted, without requiring a
+    /// writable transaction
+    fn lookup<'a>(
+        hrt: &impl HostRuntime,
+        tx: &'a mut Transaction,
+        addr: &Address,
+    ) -> Result<Option<&'a Account>> {
+        Ok(tx.get::<Self>(hrt, Self::path(addr)?)?)
+    }
+
+    /// Reads the account without inserting a default one. Accounts are
+    /// inserted on first access, so an untouched default account is reported
+    /// as absent, unless it was deleted.
+    fn get(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        addr: &Address,
+    ) -> Result<Option<Account>> {
+        let account = tx.get::<Self>(hrt, Self::path(addr)?)?.cloned();
+
+        match account {
+            Some(account)
+                if account.nonce != Nonce::default()
+                    || account.amount != 0
+                    || account.contract_code.is_some()
+                    || Self::is_deleted(hrt, tx, addr)? =>
+            {
+                Ok(Some(account))
+            }
+            _ => Ok(None),
+        }
     }
 
     fn try_insert(
@@ -66,6 +153,8 @@ impl Account {
         tx: &mut Transaction,
         addr: &Address,
     ) -> Result<()> {
+        tx.ensure_writable()?;
+
         match tx.entry(hrt, Self::path(addr)?)? {
             Entry::Occupied(ntry) => {
                 let acc: &Self = ntry.get();
@@ -73,29 +162,41 @@ impl Account {
                 Err(Error::InvalidAddress)
             }
             Entry::Vacant(entry) => {
-                entry.insert(self);
+                entry.insert(self)?;
                 Ok(())
             }
         }
     }
-    pub fn nonce<'a>(
+
+    pub fn nonce(
         hrt: &impl HostRuntime,
-        tx: &'a mut Transaction,
+        tx: &mut Transaction,
+        addr: &Address,
+    ) -> Result<Nonce> {
+        let account = Self::lookup(hrt, tx, addr)?;
+
+        Ok(account.map(|account| account.nonce).unwrap_or_default())
+    }
+
+    pub fn increment_nonce(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
         addr: &Address,
-    ) -> Result<&'a mut Nonce> {
+    ) -> Result<()> {
         let account = Self::get_mut(hrt, tx, addr)?;
 
-        Ok(&mut account.nonce)
+        account.nonce.increment();
+        Ok(())
     }
 
     pub fn contract_code<'a>(
         hrt: &impl HostRuntime,
         tx: &'a mut Transaction,
         addr: &Address,
-    ) -> Result<Option<&'a mut String>> {
-        let account = Self::get_mut(hrt, tx, addr)?;
+    ) -> Result<Option<&'a String>> {
+        let account = Self::lookup(hrt, tx, addr)?;
 
-        Ok(account.contract_code.as_mut())
+        Ok(account.and_then(|account| account.contract_code.as_ref()))
     }
 
     pub fn set_contract_code(
@@ -104,20 +205,91 @@ impl Account {
         addr: &Address,
         contract_code: String,
     ) -> Result<()> {
+        tx.ensure_writable()?;
+
         let account = Self::get_mut(hrt, tx, addr)?;
 
         account.contract_code = Some(contract_code);
         Ok(())
     }
 
+    pub fn exists(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        addr: &Address,
+    ) -> Result<bool> {
+        Ok(Self::get(hrt, tx, addr)?.is_some())
+    }
+
+    pub fn kind(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        addr: &Address,
+    ) -> Result<AccountKind> {
+        let kind = match Self::get(hrt, tx, addr)? {
+            None => AccountKind::Unknown,
+            Some(Account {
+                contract_code: Some(_),
+                ..
+            }) => AccountKind::SmartFunction,
+            Some(_) => AccountKind::User,
+        };
+
+        Ok(kind)
+    }
+
+    pub fn admin(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        addr: &Address,
+    ) -> Result<Option<Address>> {
+        let admin = tx.get::<Address>(hrt, Self::admin_path(addr)?)?;
+
+        Ok(admin.cloned())
+    }
+
+    pub fn set_admin(
+        tx: &mut Transaction,
+        addr: &Address,
+        admin: &Address,
+    ) -> Result<()> {
+        tx.insert(Self::admin_path(addr)?, admin.clone())?;
+        Ok(())
+    }
+
+    pub fn is_deleted(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        addr: &Address,
+    ) -> Result<bool> {
+        let deleted = tx.get::<bool>(hrt, Self::deleted_path(addr)?)?;
+
+        Ok(deleted.copied().unwrap_or_default())
+    }
+
+    /// Marks the account as deleted and drops its code. This is irreversible.
+    pub fn delete(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        addr: &Address,
+    ) -> Result<()> {
+        tx.ensure_writable()?;
+
+        let account = Self::get_mut(hrt, tx, addr)?;
+
+        account.contract_code = None;
+        tx.insert(Self::deleted_path(addr)?, true)?;
+        Ok(())
+    }
+
     pub fn balance(
         hrt: &impl HostRuntime,
         tx: &mut Transaction,
         addr: &Address,
     ) -> Result<Amount> {
-        let account = Self::get_mut(hrt, tx, addr)?;
+        let account = Self::lookup(hrt, tx, addr)?;
 
-        Ok(account.amount)
+        Ok(account.map(|account| account.amount).unwrap_or_default())
     }
 
     pub fn deposit(
@@ -126,24 +298,89 @@ impl Account {
         addr: &Address,
         amount: Amount,
     ) -> Result<()> {
+        tx.ensure_writable()?;
+
+        Self::mint(hrt, tx, addr, amount)
+    }
+
+    pub fn total_supply(hrt: &impl HostRuntime, tx: &mut Transaction) -> Result<Amount> {
+        let total_supply =
+            tx.get::<Amount>(hrt, OwnedPath::try_from(TOTAL_SUPPLY_PATH.to_string())?)?;
+
+        Ok(total_supply.copied().unwrap_or_default())
+    }
+
+    /// Creates `amount` new tez in the account `addr`, increasing the total
+    /// supply.
+    pub fn mint(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        addr: &Address,
+        amount: Amount,
+    ) -> Result<()> {
+        tx.ensure_writable()?;
+
+        let total_supply: &mut Amount = tx
+            .entry(hrt, OwnedPath::try_from(TOTAL_SUPPLY_PATH.to_string())?)?
+            .or_insert_default()?;
+        *total_supply = total_supply
+            .checked_add(amount)
+            .ok_or(Error::BalanceOverflow)?;
+
         let account = Self::get_mut(hrt, tx, addr)?;
+        account.amount = account
+            .amount
+            .checked_add(amount)
+            .ok_or(Error::BalanceOverflow)?;
 
-        account.amount += amount;
         Ok(())
     }
 
-    pub fn set_balance(
+    /// Destroys `amount` tez held by the account `addr`, decreasing the total
+    /// supply.
+    pub fn burn(
         hrt: &impl HostRuntime,
         tx: &mut Transaction,
         addr: &Address,
         amount: Amount,
     ) -> Result<()> {
+        tx.ensure_writable()?;
+
         let account = Self::get_mut(hrt, tx, addr)?;
+        account.amount = account
+            .amount
+            .checked_sub(amount)
+            .ok_or(Error::BalanceOverflow)?;
+
+        let total_supply: &mut Amount = tx
+            .entry(hrt, OwnedPath::try_from(TOTAL_SUPPLY_PATH.to_string())?)?
+            .or_insert_default()?;
+        *total_supply = total_supply
+            .checked_sub(amount)
+            .ok_or(Error::BalanceOverflow)?;
 
-        account.amount = amount;
         Ok(())
     }
 
+    /// Sets the balance of the account `addr` to `amount`, minting or
+    /// burning the difference.
+    pub fn set_balance(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        addr: &Address,
+        amount: Amount,
+    ) -> Result<()> {
+        tx.ensure_writable()?;
+
+        let balance = Self::balance(hrt, tx, addr)?;
+
+        match amount.cmp(&balance) {
+            Ordering::Greater => Self::mint(hrt, tx, addr, amount - balance),
+            Ordering::Less => Self::burn(hrt, tx, addr, balance - amount),
+            Ordering::Equal => Ok(()),
+        }
+    }
+
     pub fn create(
         hrt: &impl HostRuntime,
         tx: &mut Transaction,
@@ -153,10 +390,16 @@ impl Account {
     ) -> Result<()> {
         Self {
             nonce: Nonce::default(),
-            amount,
+            amount: 0,
             contract_code,
         }
-        .try_insert(hrt, tx, addr)
+        .try_insert(hrt, tx, addr)?;
+
+        if amount > 0 {
+            Self::mint(hrt, tx, addr, amount)?;
+        }
+
+        Ok(())
     }
 
     pub fn transfer(
@@ -166,6 +409,8 @@ impl Account {
         dst: &Address,
         amt: Amount,
     ) -> Result<()> {
+        tx.ensure_writable()?;
+
         let src = Self::get_mut(hrt, tx, src)?;
         match src.amount.checked_sub(amt) {
             Some(amt) => src.amount = amt,
@@ -206,4 +451,230 @@ mod test {
         // Assert
         assert_eq!(amt, 0);
     }
+
+    #[test]
+    fn test_nonce_increments() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+
+        let mut tx = kv.begin_transaction();
+
+        let pkh = PublicKeyHash::from_base58("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty")
+            .expect("Could not parse pkh");
+
+        // Act
+        for _ in 0..3 {
+            Account::increment_nonce(hrt, &mut tx, &pkh)
+                .expect("Could not increment nonce");
+        }
+
+        let nonce = Account::nonce(hrt, &mut tx, &pkh).expect("Could not get nonce");
+
+        // Assert
+        assert_eq!(nonce.value(), 3);
+    }
+
+    #[test]
+    fn test_mint_and_burn_track_total_supply() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+
+        let mut tx = kv.begin_transaction();
+
+        let pkh1 = PublicKeyHash::from_base58("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty")
+            .expect("Could not parse pkh");
+        let pkh2 = PublicKeyHash::from_base58("tz1ZXxxkNYSUutm5VZvfudakRxx2mjpWko4J")
+            .expect("Could not parse pkh");
+
+        // Act
+        assert_eq!(Account::total_supply(hrt, &mut tx).unwrap(), 0);
+
+        Account::mint(hrt, &mut tx, &pkh1, 100).expect("Could not mint");
+        Account::mint(hrt, &mut tx, &pkh2, 50).expect("Could not mint");
+        Account::transfer(hrt, &mut tx, &pkh1, &pkh2, 30).expect("Could not transfer");
+        Account::burn(hrt, &mut tx, &pkh2, 60).expect("Could not burn");
+
+        let burn_too_much = Account::burn(hrt, &mut tx, &pkh1, 71);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Assert
+        let mut tx = kv.begin_transaction();
+
+        assert!(matches!(burn_too_much, Err(Error::BalanceOverflow)));
+        assert_eq!(Account::balance(hrt, &mut tx, &pkh1).unwrap(), 70);
+        assert_eq!(Account::balance(hrt, &mut tx, &pkh2).unwrap(), 20);
+        assert_eq!(Account::total_supply(hrt, &mut tx).unwrap(), 90);
+    }
+
+    #[test]
+    fn test_total_supply_is_sum_of_balances() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+
+        let mut tx = kv.begin_transaction();
+
+        let user = PublicKeyHash::from_base58("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty")
+            .expect("Could not parse pkh");
+        let contract = PublicKeyHash::from_base58("tz1faswCTDciRzE4oJ9jn2Vm2dvjeyA9fUzU")
+            .expect("Could not parse pkh");
+
+        // Act
+        Account::deposit(hrt, &mut tx, &user, 100).expect("Could not deposit");
+        Account::create(
+            hrt,
+            &mut tx,
+            &contract,
+            40,
+            Some("export default () => new Response()".to_string()),
+        )
+        .expect("Could not create contract");
+        Account::set_balance(hrt, &mut tx, &user, 150).expect("Could not set balance");
+        let raised = Account::total_supply(hrt, &mut tx).unwrap();
+        Account::set_balance(hrt, &mut tx, &contract, 10).expect("Could not set balance");
+
+        // Assert
+        assert_eq!(raised, 190);
+        assert_eq!(Account::total_supply(hrt, &mut tx).unwrap(), 160);
+        assert_eq!(
+            Account::balance(hrt, &mut tx, &user).unwrap()
+                + Account::balance(hrt, &mut tx, &contract).unwrap(),
+            160
+        );
+    }
+
+    #[test]
+    fn test_create_on_existing_address_does_not_mint() {
+        let hrt = &mut MockHost::default();
+        let kv = Kv::new();
+
+        let mut tx = kv.begin_transaction();
+
+        let contract = PublicKeyHash::from_base58("tz1faswCTDciRzE4oJ9jn2Vm2dvjeyA9fUzU")
+            .expect("Could not parse pkh");
+        let code = "export default () => new Response()".to_string();
+
+        Account::create(hrt, &mut tx, &contract, 40, Some(code.clone()))
+            .expect("Could not create contract");
+
+        // Act
+        let result = Account::create(hrt, &mut tx, &contract, 40, Some(code));
+
+        // Assert
+        assert!(matches!(result, Err(Error::InvalidAddress)));
+        assert_eq!(Account::balance(hrt, &mut tx, &contract).unwrap(), 40);
+        assert_eq!(Account::total_supply(hrt, &mut tx).unwrap(), 40);
+    }
+
+    #[test]
+    fn test_balance_writes_in_read_only_transaction() {
+        let hrt = &mut MockHost::default();
+        let kv = Kv::new();
+
+        let mut tx = kv.begin_read_only_transaction();
+
+        let pkh = PublicKeyHash::from_base58("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty")
+            .expect("Could not parse pkh");
+
+        // Act
+        let deposit = Account::deposit(hrt, &mut tx, &pkh, 10);
+        // Setting the current balance writes nothing, but is still rejected
+        let set_balance = Account::set_balance(hrt, &mut tx, &pkh, 0);
+
+        // Assert
+        for result in [deposit, set_balance] {
+            assert!(matches!(
+                result,
+                Err(Error::CoreError {
+                    source: jstz_core::Error::ReadOnlyViolation
+                })
+            ));
+        }
+        assert_eq!(Account::balance(hrt, &mut tx, &pkh).unwrap(), 0);
+        assert_eq!(Account::total_supply(hrt, &mut tx).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_account_kind() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+
+        let mut tx = kv.begin_transaction();
+
+        let fresh = PublicKeyHash::from_base58("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty")
+            .expect("Could not parse pkh");
+        let user = PublicKeyHash::from_base58("tz1ZXxxkNYSUutm5VZvfudakRxx2mjpWko4J")
+            .expect("Could not parse pkh");
+        let contract = PublicKeyHash::from_base58("tz1faswCTDciRzE4oJ9jn2Vm2dvjeyA9fUzU")
+            .expect("Could not parse pkh");
+
+        // Act
+        Account::mint(hrt, &mut tx, &user, 100).expect("Could not mint");
+        Account::create(
+            hrt,
+            &mut tx,
+            &contract,
+            0,
+            Some("export default () => new Response()".to_string()),
+        )
+        .expect("Could not create contract");
+
+        // Reading the balance of a fresh address does not create its account
+        Account::balance(hrt, &mut tx, &fresh).expect("Could not get balance");
+
+        // Assert
+        assert!(!Account::exists(hrt, &mut tx, &fresh).unwrap());
+        assert!(Account::exists(hrt, &mut tx, &user).unwrap());
+        assert!(Account::exists(hrt, &mut tx, &contract).unwrap());
+
+        assert_eq!(
+            Account::kind(hrt, &mut tx, &fresh).unwrap(),
+            AccountKind::Unknown
+        );
+        assert_eq!(
+            Account::kind(hrt, &mut tx, &user).unwrap(),
+            AccountKind::User
+        );
+        assert_eq!(
+            Account::kind(hrt, &mut tx, &contract).unwrap(),
+            AccountKind::SmartFunction
+        );
+    }
+
+    #[test]
+    fn test_deleted_account_is_kept() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+
+        let mut tx = kv.begin_transaction();
+
+        let contract = PublicKeyHash::from_base58("tz1faswCTDciRzE4oJ9jn2Vm2dvjeyA9fUzU")
+            .expect("Could not parse pkh");
+
+        // Act
+        Account::create(
+            hrt,
+            &mut tx,
+            &contract,
+            0,
+            Some("export default () => new Response()".to_string()),
+        )
+        .expect("Could not create contract");
+        Account::delete(hrt, &mut tx, &contract).expect("Could not delete contract");
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Assert
+        let mut tx = kv.begin_transaction();
+
+        assert!(Account::is_deleted(hrt, &mut tx, &contract).unwrap());
+        assert!(Account::exists(hrt, &mut tx, &contract).unwrap());
+        assert_eq!(
+            Account::kind(hrt, &mut tx, &contract).unwrap(),
+            AccountKind::User
+        );
+        assert!(Account::contract_code(hrt, &mut tx, &contract)
+            .unwrap()
+            .is_none());
+    }
 }
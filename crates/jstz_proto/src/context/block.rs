@@ -0,0 +1,204 @@
+use crate::error::Result;
+use jstz_core::{host::HostRuntime, kv::Transaction};
+use jstz_crypto::hash::Blake2b;
+use serde::{Deserialize, Serialize};
+use tezos_smart_rollup::storage::path::OwnedPath;
+
+const BLOCK_PATH: &str = "/jstz_block";
+const GENESIS_BLOCK_PATH: &str = "/jstz_block_genesis";
+
+/// The rollup block currently being processed.
+///
+/// The rollup runtime does not expose the hash of the current block, so the
+/// hash of a block is derived from its level and timestamp as
+/// `Blake2b(level || timestamp)` (big-endian). Each block records the hash of
+/// the previous block, chaining blocks together.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Block {
+    pub level: u32,
+    /// The timestamp (in seconds since the Unix epoch) of the predecessor
+    /// Layer 1 block, as reported at the start of the level
+    pub timestamp: i64,
+    pub hash: Blake2b,
+    pub parent_hash: Blake2b,
+}
+
+impl Block {
+    pub fn compute_hash(level: u32, timestamp: i64) -> Blake2b {
+        let mut bytes = level.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&timestamp.to_be_bytes());
+        Blake2b::from(&bytes)
+    }
+
+    fn path() -> Result<OwnedPath> {
+        Ok(OwnedPath::try_from(BLOCK_PATH.to_string())?)
+    }
+
+    fn genesis_path() -> Result<OwnedPath> {
+        Ok(OwnedPath::try_from(GENESIS_BLOCK_PATH.to_string())?)
+    }
+
+    /// Returns the current block. Before the first level has been processed,
+    /// this is the genesis block, whose hashes are all zero.
+    pub fn current(hrt: &impl HostRuntime, tx: &mut Transaction) -> Result<Block> {
+        let block = tx.get::<Block>(hrt, Self::path()?)?;
+
+        Ok(block.cloned().unwrap_or_default())
+    }
+
+    /// Returns the first block processed by the rollup, or the genesis block
+    /// if no level has been processed yet
+    pub fn genesis(hrt: &impl HostRuntime, tx: &mut Transaction) -> Result<Block> {
+        let block = tx.get::<Block>(hrt, Self::genesis_path()?)?;
+
+        Ok(block.cloned().unwrap_or_default())
+    }
+
+    /// Returns the timestamp of the block at `level`.
+    ///
+    /// Only the timestamps of the genesis and current blocks are known
+    /// exactly; the timestamps of the blocks between them are linearly
+    /// interpolated. Returns `None` for levels outside of that range.
+    pub fn timestamp_at(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        level: u32,
+    ) -> Result<Option<i64>> {
+        let genesis = Self::genesis(hrt, tx)?;
+        let current = Self::current(hrt, tx)?;
+
+        if level == current.level {
+            return Ok(Some(current.timestamp));
+        }
+        if level < genesis.level || level > current.level {
+            return Ok(None);
+        }
+
+        let elapsed = (current.timestamp - genesis.timestamp) as i128;
+        let levels = (current.level - genesis.level) as i128;
+        let offset = elapsed * (level - genesis.level) as i128 / levels;
+
+        Ok(Some(genesis.timestamp + offset as i64))
+    }
+
+    /// Starts a new block at `level`, chaining it to the current block
+    pub fn advance(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        level: u32,
+        timestamp: i64,
+    ) -> Result<()> {
+        let parent = Self::current(hrt, tx)?;
+
+        let block = Block {
+            level,
+            timestamp,
+            hash: Self::compute_hash(level, timestamp),
+            parent_hash: parent.hash,
+        };
+
+        if tx.get::<Block>(hrt, Self::genesis_path()?)?.is_none() {
+            tx.insert(Self::genesis_path()?, block.clone())?;
+        }
+
+        tx.insert(Self::path()?, block)?;
+
+        Ok(())
+    }
+}
+
+/// Formats a Unix timestamp (in seconds) as an ISO 8601 UTC date-time,
+/// e.g. `2023-11-14T22:13:20Z`
+pub fn format_iso8601(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(86_400);
+    let seconds = timestamp.rem_euclid(86_400);
+
+    // Civil date from days since the epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3_600,
+        seconds % 3_600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use jstz_core::kv::Kv;
+    use tezos_smart_rollup_mock::MockHost;
+
+    #[test]
+    fn test_blocks_are_chained() {
+        let hrt = &mut MockHost::default();
+        let kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        assert_eq!(Block::current(hrt, &mut tx).unwrap(), Block::default());
+
+        let mut blocks = Vec::new();
+        for level in 1..=5 {
+            Block::advance(hrt, &mut tx, level, 1_700_000_000 + 15 * level as i64)
+                .expect("Could not advance block");
+            blocks.push(Block::current(hrt, &mut tx).unwrap());
+        }
+
+        for block in &blocks {
+            assert_eq!(
+                block.hash,
+                Block::compute_hash(block.level, block.timestamp)
+            );
+        }
+
+        for pair in blocks.windows(2) {
+            assert_eq!(pair[1].parent_hash, pair[0].hash);
+            assert_eq!(pair[1].level, pair[0].level + 1);
+        }
+
+        assert_eq!(blocks[0].parent_hash, Blake2b::default());
+    }
+
+    #[test]
+    fn test_timestamps_are_interpolated() {
+        let hrt = &mut MockHost::default();
+        let kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        Block::advance(hrt, &mut tx, 10, 1_700_000_000).unwrap();
+        Block::advance(hrt, &mut tx, 11, 1_700_000_007).unwrap();
+        Block::advance(hrt, &mut tx, 20, 1_700_000_100).unwrap();
+
+        assert_eq!(Block::genesis(hrt, &mut tx).unwrap().level, 10);
+
+        let at = |hrt: &MockHost, tx: &mut Transaction, level| {
+            Block::timestamp_at(hrt, tx, level).unwrap()
+        };
+        assert_eq!(at(hrt, &mut tx, 10), Some(1_700_000_000));
+        assert_eq!(at(hrt, &mut tx, 20), Some(1_700_000_100));
+        assert_eq!(at(hrt, &mut tx, 15), Some(1_700_000_050));
+        assert_eq!(at(hrt, &mut tx, 9), None);
+        assert_eq!(at(hrt, &mut tx, 21), None);
+    }
+
+    #[test]
+    fn test_format_iso8601() {
+        assert_eq!(format_iso8601(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_iso8601(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_iso8601(1_700_000_000), "2023-11-14T22:13:20Z");
+        assert_eq!(format_iso8601(-1), "1969-12-31T23:59:59Z");
+    }
+}
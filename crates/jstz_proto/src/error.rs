@@ -1,14 +1,60 @@
 use boa_engine::{JsError, JsNativeError};
 use derive_more::{Display, Error, From};
+use jstz_api::LogRecord;
+
+use crate::context::account::Nonce;
 
 #[derive(Display, Debug, Error, From)]
 pub enum Error {
-    CoreError { source: jstz_core::Error },
-    CryptoError { source: jstz_crypto::Error },
+    CoreError {
+        source: jstz_core::Error,
+    },
+    CryptoError {
+        source: jstz_crypto::Error,
+    },
     BalanceOverflow,
-    InvalidNonce,
+    InsufficientFunds,
+    /// The nonce of an operation is not the next nonce of its source
+    #[display(fmt = "InvalidNonce (expected {}, got {})", expected, got)]
+    InvalidNonce {
+        expected: Nonce,
+        got: Nonce,
+    },
     InvalidAddress,
+    /// The signature of an operation does not match its content, or was not
+    /// made by its source
+    InvalidSignature,
     RefererShouldNotBeSet,
+    OutOfGas,
+    CallDepthExceeded,
+    /// A contract was re-entered while a `Contract.nonReentrant()` function
+    /// of the contract was running
+    ReentrancyDetected,
+    /// The caller is not allowed to perform the operation, e.g. upgrading a
+    /// contract it is not the admin of
+    Unauthorized,
+    /// The code of a deployment does not parse as a module with a default
+    /// export
+    #[display(fmt = "InvalidContractCode: {}", message)]
+    InvalidContractCode {
+        message: String,
+    },
+    /// The contract responded with a non-2xx status, rolling back its
+    /// transaction. The records logged before the revert are kept.
+    #[display(fmt = "ContractReverted ({}): {}", status, message)]
+    ContractReverted {
+        status: u16,
+        message: String,
+        logs: Vec<LogRecord>,
+    },
+    /// An operation of a batch failed, rolling back the whole batch
+    #[display(fmt = "BatchFailed ({}): {}", index, source)]
+    BatchFailed {
+        index: usize,
+        source: Box<Error>,
+    },
+    /// A batch contains another batch
+    NestedBatch,
 }
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -22,15 +68,45 @@ impl From<Error> for JsError {
             Error::BalanceOverflow => {
                 JsNativeError::eval().with_message("BalanceOverflow").into()
             }
-            Error::InvalidNonce => {
-                JsNativeError::eval().with_message("InvalidNonce").into()
-            }
+            Error::InsufficientFunds => JsNativeError::eval()
+                .with_message("InsufficientFunds")
+                .into(),
+            Error::InvalidNonce { expected, got } => JsNativeError::eval()
+                .with_message(format!("InvalidNonce (expected {expected}, got {got})"))
+                .into(),
             Error::InvalidAddress => {
                 JsNativeError::eval().with_message("InvalidAddress").into()
             }
+            Error::InvalidSignature => JsNativeError::eval()
+                .with_message("InvalidSignature")
+                .into(),
             Error::RefererShouldNotBeSet => JsNativeError::eval()
                 .with_message("RefererShouldNotBeSet")
                 .into(),
+            Error::OutOfGas => JsNativeError::eval().with_message("OutOfGas").into(),
+            Error::CallDepthExceeded => JsNativeError::eval()
+                .with_message("CallDepthExceeded")
+                .into(),
+            Error::ReentrancyDetected => JsNativeError::eval()
+                .with_message("ReentrancyDetected")
+                .into(),
+            Error::Unauthorized => {
+                JsNativeError::eval().with_message("Unauthorized").into()
+            }
+            Error::InvalidContractCode { message } => JsNativeError::eval()
+                .with_message(format!("InvalidContractCode: {message}"))
+                .into(),
+            Error::ContractReverted {
+                status, message, ..
+            } => JsNativeError::eval()
+                .with_message(format!("ContractReverted ({status}): {message}"))
+                .into(),
+            Error::BatchFailed { index, source } => JsNativeError::eval()
+                .with_message(format!("BatchFailed ({index}): {source}"))
+                .into(),
+            Error::NestedBatch => {
+                JsNativeError::eval().with_message("NestedBatch").into()
+            }
         }
     }
 }
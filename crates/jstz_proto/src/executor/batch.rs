@@ -0,0 +1,249 @@
+use jstz_core::{host::HostRuntime, kv::Transaction};
+use jstz_crypto::hash::Blake2b;
+
+use crate::{
+    context::account::{Account, Address},
+    operation::{Batch, Content, OperationHash},
+    receipt::Receipt,
+    Error, Result,
+};
+
+/// Returns the hash of the `index`-th operation of a batch, with which its
+/// receipt is recorded and its contracts are run
+fn operation_hash(batch_hash: &OperationHash, index: usize) -> OperationHash {
+    Blake2b::from(&[batch_hash.as_ref(), &(index as u64).to_be_bytes()].concat())
+}
+
+/// Executes the operations of `batch` in order. If an operation fails, the
+/// whole batch is rolled back and fails with the error of that operation.
+/// Batches containing a batch are rejected before any operation is executed.
+pub fn execute(
+    hrt: &mut (impl HostRuntime + 'static),
+    tx: &mut Transaction,
+    source: &Address,
+    batch: Batch,
+    batch_hash: &OperationHash,
+) -> Result<Vec<Receipt>> {
+    let Batch(contents) = batch;
+
+    if contents
+        .iter()
+        .any(|content| matches!(content, Content::Batch(_)))
+    {
+        return Err(Error::NestedBatch);
+    }
+
+    let savepoint = tx.savepoint();
+    let mut receipts = Vec::with_capacity(contents.len());
+
+    for (index, content) in contents.into_iter().enumerate() {
+        let hash = operation_hash(batch_hash, index);
+        let is_deployment = matches!(content, Content::DeployContract(_));
+
+        let result =
+            super::execute_content(hrt, tx, source, content, &hash).and_then(|content| {
+                // The address of a deployment is derived from the nonce of
+                // the source, which must be incremented to avoid a collision
+                // with the next deployment of the batch.
+                if is_deployment {
+                    Account::increment_nonce(hrt, tx, source)?;
+                }
+                Ok(content)
+            });
+
+        match result {
+            Ok(content) => receipts.push(Receipt::new(hash, Ok(content))),
+            Err(err) => {
+                tx.rollback_to(savepoint)?;
+                tx.release_savepoint(savepoint)?;
+
+                return Err(Error::BatchFailed {
+                    index,
+                    source: Box::new(err),
+                });
+            }
+        }
+    }
+
+    tx.release_savepoint(savepoint)?;
+
+    Ok(receipts)
+}
+
+#[cfg(test)]
+mod test {
+    use jstz_core::kv::Kv;
+    use jstz_crypto::public_key_hash::PublicKeyHash;
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::*;
+    use crate::{
+        executor::contract::Script,
+        operation::{DeployContract, RunContract},
+        receipt,
+    };
+
+    const COUNTER_CODE: &str = r#"
+        export default (request) => {
+            const count = (Kv.get("count") ?? 0) + 1;
+            Kv.set("count", count);
+
+            const status = new URL(request.url).pathname === "/revert" ? 500 : 200;
+            return new Response(String(count), { status });
+        };
+    "#;
+
+    fn run(address: &Address, path: &str) -> Content {
+        Content::RunContract(RunContract {
+            uri: format!("tezos://{address}{path}").parse().unwrap(),
+            method: http::Method::GET,
+            headers: http::HeaderMap::new(),
+            body: None,
+            amount: 0,
+            fuel_limit: 1_000_000,
+        })
+    }
+
+    fn deploy(contract_credit: u64) -> Content {
+        Content::DeployContract(DeployContract {
+            contract_code: "export default () => new Response()".to_string(),
+            contract_credit,
+        })
+    }
+
+    fn body(receipt: &Receipt) -> String {
+        match receipt
+            .inner
+            .as_ref()
+            .expect("Expected a successful receipt")
+        {
+            receipt::Content::RunContract(run) => {
+                String::from_utf8(run.body.clone().expect("Expected a body")).unwrap()
+            }
+            content => panic!("Expected a run receipt, got {content:?}"),
+        }
+    }
+
+    fn setup(hrt: &mut MockHost, kv: &mut Kv) -> (Address, Address) {
+        let mut tx = kv.begin_transaction();
+
+        let source = PublicKeyHash::from_base58("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty")
+            .expect("Could not parse pkh");
+        Account::mint(hrt, &mut tx, &source, 1_000).expect("Could not mint");
+        let counter = Script::deploy(hrt, &mut tx, &source, COUNTER_CODE.to_string(), 0)
+            .expect("Could not deploy script");
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        (source, counter)
+    }
+
+    #[test]
+    fn test_batch_preserves_receipts_in_order() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let (source, counter) = setup(hrt, &mut kv);
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let batch = Batch(vec![run(&counter, "/"), deploy(0), run(&counter, "/")]);
+        let receipts = execute(hrt, &mut tx, &source, batch, &OperationHash::default())
+            .expect("Could not execute batch");
+
+        // Assert
+        assert_eq!(receipts.len(), 3);
+        assert_eq!(body(&receipts[0]), "1");
+        assert!(matches!(
+            receipts[1].inner,
+            Ok(receipt::Content::DeployContract(_))
+        ));
+        assert_eq!(body(&receipts[2]), "2");
+        assert_ne!(receipts[0].hash(), receipts[2].hash());
+    }
+
+    #[test]
+    fn test_failed_batch_is_rolled_back() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let (source, counter) = setup(hrt, &mut kv);
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let batch = Batch(vec![
+            deploy(100),
+            run(&counter, "/"),
+            run(&counter, "/revert"),
+        ]);
+        let result = execute(hrt, &mut tx, &source, batch, &OperationHash::default());
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(Error::BatchFailed { index: 2, source: ref error })
+                if matches!(**error, Error::ContractReverted { status: 500, .. })
+        ));
+
+        // The credit of the deployment and the first increment are undone
+        assert_eq!(Account::balance(hrt, &mut tx, &source).unwrap(), 1_000);
+
+        let batch = Batch(vec![run(&counter, "/")]);
+        let receipts = execute(hrt, &mut tx, &source, batch, &OperationHash::default())
+            .expect("Could not execute batch");
+        assert_eq!(body(&receipts[0]), "1");
+    }
+
+    #[test]
+    fn test_batch_deployments_have_distinct_addresses() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let (source, _) = setup(hrt, &mut kv);
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let nonce = Account::nonce(hrt, &mut tx, &source).unwrap();
+        let batch = Batch(vec![deploy(0), deploy(0)]);
+        let receipts = execute(hrt, &mut tx, &source, batch, &OperationHash::default())
+            .expect("Could not execute batch");
+
+        // Assert
+        let addresses: Vec<_> = receipts
+            .iter()
+            .map(|receipt| match &receipt.inner {
+                Ok(receipt::Content::DeployContract(deployment)) => {
+                    deployment.contract_address.clone()
+                }
+                content => panic!("Expected a deploy receipt, got {content:?}"),
+            })
+            .collect();
+        assert_ne!(addresses[0], addresses[1]);
+
+        assert_eq!(
+            Account::nonce(hrt, &mut tx, &source).unwrap().value(),
+            nonce.value() + 2
+        );
+    }
+
+    #[test]
+    fn test_nested_batch_is_rejected() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let (source, counter) = setup(hrt, &mut kv);
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let batch = Batch(vec![
+            run(&counter, "/"),
+            Content::Batch(Batch(vec![run(&counter, "/")])),
+        ]);
+        let result = execute(hrt, &mut tx, &source, batch, &OperationHash::default());
+
+        // Assert
+        assert!(matches!(result, Err(Error::NestedBatch)));
+
+        // None of the operations of the batch were executed
+        let batch = Batch(vec![run(&counter, "/")]);
+        let receipts = execute(hrt, &mut tx, &source, batch, &OperationHash::default())
+            .expect("Could not execute batch");
+        assert_eq!(body(&receipts[0]), "1");
+    }
+}
@@ -1,27 +1,40 @@
-use std::io::Read;
+use std::{collections::HashMap, io::Read, mem};
 
 use boa_engine::{
     js_string,
     object::{builtins::JsPromise, FunctionObjectBuilder},
     Context, JsArgs, JsError, JsNativeError, JsResult, JsValue, NativeFunction, Source,
 };
-use boa_gc::{Finalize, Trace};
+use boa_gc::{custom_trace, empty_trace, Finalize, Gc, GcRefCell, Trace};
 use derive_more::{Deref, DerefMut};
 use jstz_api::http::request::Request;
-use jstz_api::http::{body::HttpBody, request::RequestClass, response::Response};
+use jstz_api::http::{
+    body::HttpBody,
+    request::RequestClass,
+    response::{Response, ResponseBuilder, ResponseClass},
+};
+use jstz_api::{
+    time::Clock, ConsoleKind, KvCaller, KvValue, LogBuffer, Subscription, OWNER_KEY,
+};
 use jstz_core::native::JsNativeObject;
 use jstz_core::{
     host::HostRuntime,
     host_defined,
-    kv::{Kv, Transaction},
+    kv::{Kv, SavepointId, Transaction},
+    realm::HostDefined,
     runtime::{self, with_global_host},
     Module, Realm,
 };
+use jstz_crypto::hash::Blake2b;
 use tezos_smart_rollup::prelude::debug_msg;
 
 use crate::{
-    api,
-    context::account::{Account, Address, Amount},
+    abi::{Abi, ABI_KEY},
+    api::{self, CallTrace, EventBuffer, ReentrancyLocks},
+    context::{
+        account::{Account, Address, Amount},
+        block::Block,
+    },
     operation::OperationHash,
     Error, Result,
 };
@@ -30,18 +43,36 @@ pub mod headers {
 
     use super::*;
     pub const REFERRER: &str = "Referer";
+    /// The initial source of the operation a request is part of
+    pub const ORIGIN: &str = "X-JSTZ-Origin";
 
-    pub fn test_and_set_referrer(request: &Request, referer: &Address) -> JsResult<()> {
-        if request.headers().deref().contains_key(REFERRER) {
+    fn test_and_set(request: &Request, name: &str, address: &Address) -> JsResult<()> {
+        if request.headers().deref().contains_key(name) {
             return Err(JsError::from_native(
-                JsNativeError::error().with_message("Referer already set"),
+                JsNativeError::error().with_message(format!("{name} already set")),
             ));
         }
 
         request
             .headers()
             .deref_mut()
-            .set(REFERRER, &referer.to_base58())
+            .set(name, &address.to_base58())
+    }
+
+    pub fn test_and_set_referrer(request: &Request, referer: &Address) -> JsResult<()> {
+        test_and_set(request, REFERRER, referer)
+    }
+
+    pub fn test_and_set_origin(request: &Request, origin: &Address) -> JsResult<()> {
+        test_and_set(request, ORIGIN, origin)
+    }
+
+    /// Returns the address in the header `name` of `request`, if any
+    pub fn get_address(request: &Request, name: &str) -> Option<Address> {
+        let header = request.headers().deref().get(name).ok()?;
+        let value = header.headers.first()?;
+
+        Address::from_base58(value).ok()
     }
 }
 
@@ -77,16 +108,183 @@ fn on_success(
     }
 }
 
+/// Registered in `HostDefined` by `Script::run` with the number of events
+/// emitted before the script was run. The events emitted by a script whose
+/// transaction is rolled back are discarded.
+#[derive(Trace, Finalize)]
+struct EventCheckpoint(usize);
+
+/// Registered in `HostDefined` by `Script::run` with the savepoint taken in
+/// the transaction before the script was run. The writes of a script that
+/// does not respond with 2xx are rolled back to it.
+#[derive(Finalize)]
+struct RunSavepoint(SavepointId);
+
+unsafe impl Trace for RunSavepoint {
+    empty_trace!();
+}
+
+/// The operation being run, registered in `HostDefined` by each contract call
+/// it makes. Its buffers, locks and trace are shared by all the calls of the
+/// operation (clones share them), whereas `call_value` and `call_depth` are
+/// those of the call.
+#[derive(Clone, Default)]
+pub struct OperationContext {
+    pub operation_hash: OperationHash,
+    /// The amount transferred to the contract by the call
+    pub call_value: Amount,
+    /// The number of contract calls on the stack, including the call. Calls
+    /// fail once [`MAX_CALL_DEPTH`] is exceeded.
+    pub call_depth: usize,
+    /// The buffer into which the records logged by the contracts are collected
+    pub logs: LogBuffer,
+    /// The buffer into which the events emitted by the contracts are collected
+    pub events: EventBuffer,
+    /// The contracts guarded against reentrancy
+    pub locks: ReentrancyLocks,
+    /// The trace into which the calls are recorded
+    pub trace: CallTrace,
+    /// The kind of console the contracts log to
+    pub console: ConsoleKind,
+}
+
+impl Finalize for OperationContext {}
+
+unsafe impl Trace for OperationContext {
+    empty_trace!();
+}
+
+impl OperationContext {
+    /// Returns the context of the first call of the operation
+    /// `operation_hash`, whose contracts log to a console of kind `console`
+    pub fn new(operation_hash: OperationHash, console: ConsoleKind) -> Self {
+        Self {
+            operation_hash,
+            call_depth: 1,
+            console,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the context of a call made by the current call, transferring
+    /// `call_value` to the callee
+    pub fn nested(&self, call_value: Amount) -> Self {
+        Self {
+            call_value,
+            call_depth: self.call_depth + 1,
+            ..self.clone()
+        }
+    }
+}
+
+/// Forwards `request` to the contract at `address` if `response` is a
+/// `404 Not Found`
+fn delegate_if_not_found(
+    response: &JsValue,
+    request: &JsValue,
+    address: &Address,
+    operation: &OperationContext,
+    context: &mut Context<'_>,
+) -> JsResult<JsValue> {
+    if Response::try_from_js(response)?.status() != 404 {
+        return Ok(response.clone());
+    }
+
+    // The delegate runs in the transaction of the current script, whose
+    // writes have been rolled back by the 404 response
+    Script::load_init_run_with(address, request, operation, context)
+}
+
 fn register_web_apis(realm: &Realm, context: &mut Context<'_>) {
     realm.register_api(jstz_api::url::UrlApi, context);
     realm.register_api(jstz_api::urlpattern::UrlPatternApi, context);
     realm.register_api(jstz_api::http::HttpApi, context);
+    realm.register_api(
+        jstz_api::http::fetch::FetchApi {
+            handler: api::fetch,
+        },
+        context,
+    );
     realm.register_api(jstz_api::encoding::EncodingApi, context);
+    realm.register_api(jstz_api::net::NetApi, context);
+    realm.register_api(jstz_api::tezos::TezosApi, context);
+    #[cfg(feature = "zk")]
+    realm.register_api(jstz_api::zk::ZkApi, context);
+    realm.register_api(jstz_api::merkle::MerkleApi, context);
+    realm.register_api(jstz_api::time::TimeApi, context);
 }
 
+/// The maximum number of nested contract calls
+pub const MAX_CALL_DEPTH: usize = 1024;
+
+/// The maximum number of event loop ticks (jobs) an operation may run
+pub const MAX_EVENT_LOOP_TICKS: u64 = 100_000;
+
 #[derive(Debug, PartialEq, Eq, Clone, Deref, DerefMut, Trace, Finalize)]
 pub struct Script(Module);
 
+/// A script loaded by [`Script::load_cached`], along with the contract it
+/// belongs to and the hash of its code
+#[derive(Clone, Trace, Finalize)]
+pub struct CachedScript {
+    #[unsafe_ignore_trace]
+    address: Address,
+    #[unsafe_ignore_trace]
+    code_hash: Blake2b,
+    pub script: Script,
+    /// Whether the module of the script was evaluated by a previous call
+    pub evaluated: bool,
+}
+
+#[derive(Default, Finalize)]
+struct ModuleCacheEntries(HashMap<Address, CachedScript>);
+
+unsafe impl Trace for ModuleCacheEntries {
+    custom_trace!(this, {
+        for entry in this.0.values() {
+            mark(entry);
+        }
+    });
+}
+
+/// The scripts run during an operation, keyed by the address of their
+/// contract. Registered in the `HostDefined` of every realm of the operation,
+/// so that a contract called again reuses its parsed module and realm (see
+/// [`Script::load_cached`]).
+#[derive(Clone, Trace, Finalize)]
+pub struct ModuleCache(Gc<GcRefCell<ModuleCacheEntries>>);
+
+impl Default for ModuleCache {
+    fn default() -> Self {
+        Self(Gc::new(GcRefCell::new(ModuleCacheEntries::default())))
+    }
+}
+
+impl ModuleCache {
+    /// Removes the script of the contract at `address` from the cache,
+    /// returning it if its code still hashes to `code_hash`
+    fn take(&self, address: &Address, code_hash: &Blake2b) -> Option<CachedScript> {
+        let entry = self.0.borrow_mut().0.remove(address)?;
+
+        (entry.code_hash == *code_hash).then_some(entry)
+    }
+
+    fn insert(&self, entry: CachedScript) {
+        self.0.borrow_mut().0.insert(entry.address.clone(), entry);
+    }
+
+    /// Drops the script of the contract at `address`, if any
+    pub fn invalidate(&self, address: &Address) {
+        self.0.borrow_mut().0.remove(address);
+    }
+
+    /// Drops all scripts. The realm of each script references the cache, so
+    /// it must be cleared once the operation is over.
+    pub fn clear(&self) {
+        self.0.borrow_mut().0.clear();
+    }
+}
+
 impl Script {
     fn get_default_export(&self, context: &mut Context<'_>) -> JsResult<JsValue> {
         self.namespace(context).get(js_string!("default"), context)
@@ -124,6 +322,111 @@ impl Script {
         Ok(Self::parse(Source::from_bytes(&src), context)?)
     }
 
+    /// Loads the script of the contract at `address`, reusing the script run
+    /// by a previous call in the same operation if the code of the contract
+    /// has not changed since.
+    ///
+    /// The script is taken out of the cache while it runs, so that reentrant
+    /// calls to the contract load a fresh script, and is put back once it has
+    /// run successfully. Outside of an operation, there is no cache and the
+    /// script is loaded as by [`Script::load`].
+    pub fn load_cached(
+        tx: &mut Transaction,
+        address: &Address,
+        context: &mut Context<'_>,
+    ) -> Result<CachedScript> {
+        let code_hash = with_global_host(|hrt| {
+            Account::contract_code(hrt, tx, address)?
+                .map(|src| Blake2b::from(src.as_bytes()))
+                .ok_or(Error::InvalidAddress)
+        })?;
+
+        let cache = {
+            host_defined!(context, host_defined);
+            host_defined.get::<ModuleCache>().map(|cache| cache.clone())
+        };
+
+        if let Some(entry) = cache
+            .as_ref()
+            .and_then(|cache| cache.take(address, &code_hash))
+        {
+            return Ok(entry);
+        }
+
+        let script = Self::load(tx, address, context)?;
+
+        //    Calls made by the script share the cache
+        if let Some(cache) = cache {
+            let context = &mut script.realm().context_handle(context);
+            host_defined!(context, mut host_defined);
+            host_defined.insert(cache);
+        }
+
+        Ok(CachedScript {
+            address: address.clone(),
+            code_hash,
+            script,
+            evaluated: false,
+        })
+    }
+
+    /// Replaces the code of the contract at `address` with `code`, keeping
+    /// its storage intact, and parses the new code into a fresh script
+    pub fn reload(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        address: &Address,
+        code: String,
+        context: &mut Context<'_>,
+    ) -> Result<Self> {
+        if Account::contract_code(hrt, tx, address)?.is_none() {
+            return Err(Error::InvalidAddress);
+        }
+
+        let script = Self::parse(Source::from_bytes(&code), context)?;
+
+        Account::set_contract_code(hrt, tx, address, code)?;
+
+        {
+            host_defined!(context, host_defined);
+            if let Some(cache) = host_defined.get::<ModuleCache>() {
+                cache.invalidate(address);
+            }
+        }
+
+        debug_msg!(hrt, "[📜] Smart function reloaded: {address}\n");
+
+        Ok(script)
+    }
+
+    /// Replaces the code of the contract at `address` with `code`, keeping
+    /// its storage intact. Only the admin of the contract, i.e. its deployer,
+    /// may upgrade it, and `code` must be valid contract code (see
+    /// [`deploy::validate`]).
+    pub fn upgrade(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        caller: &Address,
+        address: &Address,
+        code: String,
+    ) -> Result<()> {
+        if Account::contract_code(hrt, tx, address)?.is_none() {
+            return Err(Error::InvalidAddress);
+        }
+
+        if Account::admin(hrt, tx, address)?.as_ref() != Some(caller) {
+            return Err(Error::Unauthorized);
+        }
+
+        deploy::validate(&code)?;
+
+        Account::set_contract_code(hrt, tx, address, code)?;
+
+        debug_msg!(hrt, "[📜] Smart function upgraded: {address}\n");
+
+        Ok(())
+    }
+
     pub fn parse<R: Read>(
         src: Source<'_, R>,
         context: &mut Context<'_>,
@@ -133,53 +436,80 @@ impl Script {
         Ok(Self(module))
     }
 
-    // TODO: we need to be able to specify the type of console API (Proto vs Cli),
-    // With current implementation, calling a contract in CLI will revert the logging back to Proto
     fn register_apis(
         &self,
         contract_address: Address,
+        operation: &OperationContext,
         context: &mut Context<'_>,
-        operation_hash: &OperationHash,
     ) {
         register_web_apis(self.realm(), context);
-        // TODO: Register console API in `register_web_apis` once `Jstz` object is implemented
+        // Random values are seeded with the operation and the contract, so that
+        // every node computes the same values
+        let seed = [
+            operation.operation_hash.as_ref(),
+            contract_address.to_base58().as_bytes(),
+        ]
+        .concat();
         self.realm().register_api(
-            jstz_api::ConsoleApi::Proto {
-                contract_address: contract_address.clone(),
-                operation_hash: operation_hash.clone(),
+            jstz_api::crypto::CryptoApi {
+                seed: Blake2b::from(&seed),
             },
             context,
         );
+        // TODO: Register console API in `register_web_apis` once `Jstz` object is implemented
+        self.realm().register_api(
+            operation.console.api(
+                contract_address.clone(),
+                operation.operation_hash.clone(),
+                operation.logs.clone(),
+            ),
+            context,
+        );
         self.realm().register_api(
             jstz_api::KvApi {
                 contract_address: contract_address.clone(),
+                max_value_size: jstz_api::MAX_VALUE_SIZE,
+                enable_dump: false,
             },
             context,
         );
         self.realm().register_api(
-            api::LedgerApi {
+            jstz_api::KvMapApi {
                 contract_address: contract_address.clone(),
             },
             context,
         );
         self.realm().register_api(
-            api::ContractApi {
-                contract_address,
-                operation_hash: operation_hash.clone(),
+            api::LedgerApi {
+                contract_address: contract_address.clone(),
             },
             context,
         );
+        self.realm().register_api(api::BlockApi, context);
+        self.realm().register_api(api::BlockTimeApi, context);
+        self.register_contract_api(contract_address, context);
+    }
+
+    /// Registers the `Contract` API, which depends on the call (see
+    /// [`api::ContractApi`])
+    fn register_contract_api(
+        &self,
+        contract_address: Address,
+        context: &mut Context<'_>,
+    ) {
+        self.realm()
+            .register_api(api::ContractApi { contract_address }, context);
     }
 
     /// Initialize the script, registering all associated runtime APIs
-    /// and evaluating the module of the script
+    /// and evaluating the module of the script, for the call of `operation`
     pub fn init(
         &self,
         contract_address: Address,
-        operation_hash: &OperationHash,
+        operation: &OperationContext,
         context: &mut Context<'_>,
     ) -> JsResult<JsPromise> {
-        self.register_apis(contract_address, context, operation_hash);
+        self.register_apis(contract_address, operation, context);
 
         self.realm().eval_module(&self, context)
     }
@@ -204,67 +534,268 @@ impl Script {
             .as_bytes(),
         )?;
 
+        let abi = Abi::parse(&code);
+
         Account::create(hrt, tx, &address, balance, Some(code))?;
+        Account::set_admin(tx, &address, source)?;
+
+        let storage = jstz_api::Kv::new(address.to_string());
+        storage.set(
+            hrt,
+            tx,
+            OWNER_KEY,
+            KvValue(serde_json::Value::String(source.to_string())),
+        )?;
+
+        if let Some(abi) = abi {
+            storage.set(
+                hrt,
+                tx,
+                ABI_KEY,
+                KvValue(serde_json::to_value(abi).expect("ABI should serialize to JSON")),
+            )?;
+        }
 
         debug_msg!(hrt, "[📜] Smart function deployed: {address}\n");
 
         Ok(address)
     }
 
-    /// Runs the script
-    pub fn run(&self, request: &JsValue, context: &mut Context<'_>) -> JsResult<JsValue> {
+    /// Destroys the contract at `address`: its entire balance is transferred
+    /// to `beneficiary`, its storage is cleared and its account is marked as
+    /// deleted. Subsequent requests to the contract return `410 Gone`.
+    pub fn selfdestruct(
+        hrt: &impl HostRuntime,
+        tx: &mut Transaction,
+        address: &Address,
+        beneficiary: &Address,
+    ) -> Result<()> {
+        if address == beneficiary {
+            return Err(Error::InvalidAddress);
+        }
+
+        let balance = Account::balance(hrt, tx, address)?;
+        Account::transfer(hrt, tx, address, beneficiary, balance)?;
+
+        jstz_api::Kv::new(address.to_string()).clear(tx)?;
+
+        Account::delete(hrt, tx, address)?;
+
+        debug_msg!(hrt, "[📜] Smart function destroyed: {address}\n");
+
+        Ok(())
+    }
+
+    /// Returns the callers of `request`. Requests without an origin (such as
+    /// notifications) originate from their caller.
+    fn call_chain(request: &JsValue) -> JsResult<Option<api::CallChain>> {
+        let request: JsNativeObject<Request> = request.clone().try_into()?;
+        let request = request.deref();
+
+        let Some(caller) = headers::get_address(&request, headers::REFERRER) else {
+            return Ok(None);
+        };
+        let origin =
+            headers::get_address(&request, headers::ORIGIN).unwrap_or(caller.clone());
+
+        Ok(Some(api::CallChain { caller, origin }))
+    }
+
+    /// Runs the script in the transaction of its caller, whose `HostDefined`
+    /// is `caller_host_defined`. The script's writes are visible to the
+    /// caller as soon as they are made, and are rolled back if the script
+    /// does not respond with 2xx.
+    pub fn run(
+        &self,
+        request: &JsValue,
+        caller_host_defined: &JsValue,
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
         let context = &mut self.realm().context_handle(context);
+        let call_chain = Self::call_chain(request)?;
 
-        // 1. Register `Kv` and `Transaction` objects in `HostDefined`
-        // FIXME: `Kv` and `Transaction` should be externally provided
+        // 1. Share the transaction of the caller with the script, and
+        //    register the callers of the request in `HostDefined`
         {
             host_defined!(context, mut host_defined);
 
-            let kv = Kv::new();
-            let tx = kv.begin_transaction();
+            let shared = caller_host_defined
+                .as_object()
+                .and_then(|caller| caller.downcast_ref::<HostDefined>())
+                .is_some_and(|caller| caller.share::<Transaction>(&mut host_defined));
+            if !shared {
+                return Err(JsNativeError::error()
+                    .with_message("The script has no transaction to run in")
+                    .into());
+            }
+
+            let caller = call_chain
+                .as_ref()
+                .map(|call_chain| call_chain.caller.clone());
+            if let Some(call_chain) = call_chain {
+                host_defined.insert(KvCaller(call_chain.caller.to_string()));
+                host_defined.insert(call_chain);
+            }
+
+            let checkpoint = host_defined
+                .get::<OperationContext>()
+                .map(|operation| EventCheckpoint(operation.events.checkpoint()));
+            if let Some(checkpoint) = checkpoint {
+                host_defined.insert(checkpoint);
+            }
+
+            let savepoint = {
+                let mut tx = host_defined
+                    .get_mut::<Transaction>()
+                    .expect("Rust type `Transaction` should be defined in `HostDefined`");
 
-            host_defined.insert(kv);
-            host_defined.insert(tx);
+                // 2. If the pause guard is enabled, reject requests while the
+                //    contract is paused, unless they come from its admin
+                if let Some(guard) = host_defined.get::<api::PauseGuard>() {
+                    let rejected = runtime::with_global_host(|hrt| {
+                        guard.rejects(hrt, &mut tx, caller.as_ref())
+                    })?;
+
+                    if rejected {
+                        let response = JsNativeObject::new::<ResponseClass>(
+                            ResponseBuilder::empty(503, context)?,
+                            context,
+                        )?;
+
+                        return Ok(response.inner().clone());
+                    }
+                }
+
+                tx.savepoint()
+            };
+            host_defined.insert(RunSavepoint(savepoint));
+            host_defined.insert(api::Snapshots::default());
         }
 
-        // 2. Invoke the script's handler
+        // 3. Invoke the script's handler
         let result =
             self.invoke_handler(&JsValue::undefined(), &[request.clone()], context)?;
 
-        // 3. Ensure that the transaction is committed
+        //    The delegate, if any, runs as a call nested in the script's
+        let delegate = {
+            host_defined!(context, host_defined);
+            let operation = host_defined
+                .get::<OperationContext>()
+                .map(|operation| operation.nested(0))
+                .unwrap_or_default();
+            host_defined
+                .get::<api::Delegate>()
+                .map(|delegate| (delegate.address.clone(), operation))
+        };
+
+        // 4. Ensure that the writes of the script are rolled back unless it
+        //    responds with 2xx. They are committed along with the transaction.
         let result = on_success(
             result,
             |value, context| {
-                host_defined!(context, mut host_defined);
-
-                runtime::with_global_host(|rt| {
-                    let mut kv = host_defined
-                        .remove::<Kv>()
-                        .expect("Rust type `Kv` should be defined in `HostDefined`");
-
-                    let tx = host_defined.remove::<Transaction>().expect(
-                        "Rust type `Transaction` should be defined in `HostDefined`",
-                    );
-
-                    let response =
-                        Response::try_from_js(&value).expect("Expected valid response");
-
-                    // If status code is 2xx, commit transaction
-                    if response.ok() {
-                        kv.commit_transaction(rt, *tx)
-                            .expect("Failed to commit transaction");
-                    } else {
-                        kv.rollback_transaction(rt, *tx);
+                host_defined!(context, host_defined);
+
+                let mut tx = host_defined
+                    .get_mut::<Transaction>()
+                    .expect("Rust type `Transaction` should be defined in `HostDefined`");
+                let savepoint = host_defined
+                    .get::<RunSavepoint>()
+                    .expect("Rust type `RunSavepoint` should be defined in `HostDefined`")
+                    .0;
+
+                let response =
+                    Response::try_from_js(&value).expect("Expected valid response");
+
+                // Release the snapshots the script left behind. Their writes
+                // are kept or rolled back along with the rest of the script's.
+                if let Some(mut snapshots) = host_defined.get_mut::<api::Snapshots>() {
+                    for id in snapshots.0.drain(..) {
+                        let _ = tx.release_savepoint(id);
                     }
-                })
+                }
+
+                // The savepoint is gone if a caller has already rolled back
+                // past it, along with the writes of the script
+                if !response.ok() {
+                    let _ = tx.rollback_to(savepoint);
+
+                    // The events emitted by the script are rolled back too
+                    let operation = host_defined.get::<OperationContext>();
+                    let checkpoint = host_defined.get::<EventCheckpoint>();
+                    if let (Some(operation), Some(checkpoint)) = (operation, checkpoint) {
+                        operation.events.rollback_to(checkpoint.0);
+                    }
+                }
+                let _ = tx.release_savepoint(savepoint);
             },
             context,
         );
 
-        Ok(result)
+        // 5. Fall through to the delegate, if any, on `404 Not Found`
+        let Some((address, operation)) = delegate else {
+            return Ok(result);
+        };
+
+        match result.as_promise() {
+            Some(promise) => {
+                let request = request.clone();
+                let promise = JsPromise::from_object(promise.clone())?.then(
+                    Some(
+                        FunctionObjectBuilder::new(context.realm(), unsafe {
+                            NativeFunction::from_closure(move |_, args, context| {
+                                delegate_if_not_found(
+                                    args.get_or_undefined(0),
+                                    &request,
+                                    &address,
+                                    &operation,
+                                    context,
+                                )
+                            })
+                        })
+                        .build(),
+                    ),
+                    None,
+                    context,
+                )?;
+
+                Ok(promise.into())
+            }
+            None => {
+                delegate_if_not_found(&result, request, &address, &operation, context)
+            }
+        }
+    }
+
+    /// Makes `tx` the transaction of the current realm of `rt` while running
+    /// `f`. The scripts run by `f`, and the contracts they call, read and write
+    /// through `tx`.
+    pub fn with_transaction<'host, R>(
+        tx: &mut Transaction,
+        rt: &mut jstz_core::Runtime<'host>,
+        f: impl FnOnce(&mut jstz_core::Runtime<'host>) -> R,
+    ) -> R {
+        {
+            let context = rt.context();
+            host_defined!(context, mut host_defined);
+            host_defined.insert(mem::replace(tx, Kv::new().begin_transaction()));
+        }
+
+        let result = f(rt);
+
+        let context = rt.context();
+        host_defined!(context, mut host_defined);
+        *tx = *host_defined
+            .remove::<Transaction>()
+            .expect("Rust type `Transaction` should be defined in `HostDefined`");
+
+        result
     }
 
-    /// Loads, initializes and runs the script
+    /// Loads, initializes and runs the script with the protocol's console. Its
+    /// logs, events and calls are not collected.
+    ///
+    /// `tx` is the transaction of the current realm (see
+    /// [`Script::with_transaction`]).
     pub fn load_init_run(
         tx: &mut Transaction,
         address: &Address,
@@ -272,19 +803,210 @@ impl Script {
         operation_hash: &OperationHash,
         context: &mut Context<'_>,
     ) -> JsResult<JsValue> {
-        // 1. Load script
-        let script = Script::load(tx, address, context)?;
+        Self::load_init_run_in(
+            tx,
+            address,
+            request,
+            &OperationContext::new(operation_hash.clone(), ConsoleKind::Proto),
+            context,
+        )
+    }
+
+    /// Loads, initializes and runs the script as the call of `operation`
+    /// (see [`OperationContext`]): the amount transferred by the call is
+    /// exposed as `Contract.callValue`, the records logged and the events
+    /// emitted by the script are collected into the buffers of `operation`,
+    /// and the call and its nested calls are recorded into its trace.
+    ///
+    /// The script runs in the transaction of the current realm (see
+    /// [`Script::with_transaction`]).
+    pub fn load_init_run_with(
+        address: &Address,
+        request: &JsValue,
+        operation: &OperationContext,
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        host_defined!(context, host_defined);
+        let mut tx = host_defined.get_mut::<Transaction>().ok_or_else(|| {
+            JsNativeError::error().with_message("The script has no transaction to run in")
+        })?;
+
+        Self::load_init_run_in(&mut tx, address, request, operation, context)
+    }
+
+    /// Like [`Script::load_init_run_with`], for callers that already hold
+    /// `tx`, the transaction of the current realm
+    pub(crate) fn load_init_run_in(
+        tx: &mut Transaction,
+        address: &Address,
+        request: &JsValue,
+        operation: &OperationContext,
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        if operation.call_depth > MAX_CALL_DEPTH {
+            return Err(Error::CallDepthExceeded.into());
+        }
+
+        // The frame of the call is pushed when the call starts, and popped
+        // with the fuel it consumed once its response settles
+        let trace = &operation.trace;
+        let frame = trace.push(address.clone(), operation.call_depth);
+        let fuel = runtime::fuel_remaining();
+
+        let result =
+            Self::load_init_run_untraced(tx, address, request, operation, context);
+
+        match result {
+            Ok(result) => Self::pop_frame_on_settle(result, trace, frame, fuel, context),
+            Err(err) => {
+                let gas_used = fuel.saturating_sub(runtime::fuel_remaining());
+                trace.pop(frame, gas_used, None);
+                Err(err)
+            }
+        }
+    }
+
+    /// Pops the frame of a call from `trace` once the call's `result`
+    /// settles, recording the fuel consumed since `fuel` was read
+    fn pop_frame_on_settle(
+        result: JsValue,
+        trace: &CallTrace,
+        frame: usize,
+        fuel: u64,
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        fn pop(trace: &CallTrace, frame: usize, fuel: u64, response: Option<&JsValue>) {
+            let gas_used = fuel.saturating_sub(runtime::fuel_remaining());
+            let status = response
+                .and_then(|response| Response::try_from_js(response).ok())
+                .map(|response| response.status());
+            trace.pop(frame, gas_used, status);
+        }
+
+        let Some(promise) = result.as_promise() else {
+            pop(trace, frame, fuel, Some(&result));
+            return Ok(result);
+        };
+
+        let on_fulfilled = {
+            let trace = trace.clone();
+            FunctionObjectBuilder::new(context.realm(), unsafe {
+                NativeFunction::from_closure(move |_, args, _| {
+                    let response = args.get_or_undefined(0);
+                    pop(&trace, frame, fuel, Some(response));
+                    Ok(response.clone())
+                })
+            })
+            .build()
+        };
+        let on_rejected = {
+            let trace = trace.clone();
+            FunctionObjectBuilder::new(context.realm(), unsafe {
+                NativeFunction::from_closure(move |_, args, _| {
+                    pop(&trace, frame, fuel, None);
+                    Err(JsError::from_opaque(args.get_or_undefined(0).clone()))
+                })
+            })
+            .build()
+        };
+
+        let promise = JsPromise::from_object(promise.clone())?.then(
+            Some(on_fulfilled),
+            Some(on_rejected),
+            context,
+        )?;
+
+        Ok(promise.into())
+    }
+
+    fn load_init_run_untraced(
+        tx: &mut Transaction,
+        address: &Address,
+        request: &JsValue,
+        operation: &OperationContext,
+        context: &mut Context<'_>,
+    ) -> JsResult<JsValue> {
+        // 0. Destroyed contracts are gone for good
+        if with_global_host(|hrt| Account::is_deleted(hrt, tx, address))? {
+            let response = JsNativeObject::new::<ResponseClass>(
+                ResponseBuilder::empty(410, context)?,
+                context,
+            )?;
+
+            return Ok(response.inner().clone());
+        }
 
-        // 2. Evaluate the script's module
-        let script_promise = script.init(address.clone(), operation_hash, context)?;
+        // 1. Load script, reusing the script of a previous call if any
+        let cached = Script::load_cached(tx, address, context)?;
+        let script = cached.script.clone();
+        let block = with_global_host(|hrt| Block::current(hrt, tx))?;
+
+        //    All calls of an operation share its transaction (see
+        //    `Script::run`), its context and the timestamp of its block
+        let caller_host_defined = context
+            .global_object()
+            .get(js_string!(HostDefined::NAME), context)?;
+        {
+            let context = &mut script.realm().context_handle(context);
+            host_defined!(context, mut host_defined);
+            host_defined.insert(operation.clone());
+            host_defined.insert(Clock::from_unix_seconds(block.timestamp));
+        }
+
+        let cache = {
+            host_defined!(context, host_defined);
+            host_defined.get::<ModuleCache>().map(|cache| cache.clone())
+        };
+
+        // 2. Evaluate the script's module, unless a previous call did
+        let script_promise = if cached.evaluated {
+            script.register_contract_api(address.clone(), context);
+
+            JsPromise::resolve(JsValue::undefined(), context)?
+        } else {
+            script.init(address.clone(), operation, context)?
+        };
 
         // 3. Once evaluated, call the script's handler
         let result = script_promise.then(
             Some(
                 FunctionObjectBuilder::new(context.realm(), unsafe {
                     NativeFunction::from_closure_with_captures(
-                        |_, _, (script, request), context| script.run(request, context),
-                        (script, request.clone()),
+                        |_, _, (script, request, caller_host_defined), context| {
+                            script.run(request, caller_host_defined, context)
+                        },
+                        (script, request.clone(), caller_host_defined),
+                    )
+                })
+                .build(),
+            ),
+            None,
+            context,
+        )?;
+
+        // 4. Once the script has responded with 2xx, put it back in the cache
+        let Some(cache) = cache else {
+            return Ok(result.into());
+        };
+
+        let result = result.then(
+            Some(
+                FunctionObjectBuilder::new(context.realm(), unsafe {
+                    NativeFunction::from_closure_with_captures(
+                        |_, args, (cache, cached), _| {
+                            let response = args.get_or_undefined(0);
+                            if Response::try_from_js(response)
+                                .is_ok_and(|response| response.ok())
+                            {
+                                cache.insert(CachedScript {
+                                    evaluated: true,
+                                    ..cached.clone()
+                                });
+                            }
+
+                            Ok(response.clone())
+                        },
+                        (cache, cached),
                     )
                 })
                 .build(),
@@ -299,6 +1021,8 @@ impl Script {
 
 pub mod run {
 
+    use tezos_smart_rollup::storage::path::Path;
+
     use super::*;
     use crate::{
         operation::{self, OperationHash},
@@ -318,27 +1042,144 @@ pub mod run {
         builder.body(body).expect("Expected valid http request")
     }
 
-    pub fn execute(
-        hrt: &mut (impl HostRuntime + 'static),
+    /// Returns the body of a non-2xx response as text, or the canonical reason
+    /// of its status if the body is empty
+    fn revert_reason(status: http::StatusCode, body: Option<&[u8]>) -> String {
+        match body {
+            Some(body) if !body.is_empty() => String::from_utf8_lossy(body).into_owned(),
+            _ => status.canonical_reason().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Reads the subscriptions to the keys of `address`, with the current
+    /// value of each key in `tx`
+    fn watched_values(
+        hrt: &impl HostRuntime,
         tx: &mut Transaction,
-        source: &Address,
-        run: operation::RunContract,
-        operation_hash: &OperationHash,
-    ) -> Result<receipt::RunContract> {
-        let operation::RunContract {
-            uri,
-            method,
-            headers,
-            body,
-        } = run;
-        // 1. Initialize runtime (with Web APIs to construct request)
-        let rt = &mut jstz_core::Runtime::new()?;
-        register_web_apis(&rt.realm().clone(), rt);
+        address: &Address,
+    ) -> Result<Vec<(Subscription, Option<serde_json::Value>)>> {
+        let storage = jstz_api::Kv::new(address.to_string());
+
+        let mut watched = Vec::new();
+        for subscription in storage.subscriptions(hrt, tx)? {
+            let value = storage
+                .get(hrt, tx, &subscription.key)?
+                .map(|value| value.0.clone());
+
+            watched.push((subscription, value));
+        }
+
+        Ok(watched)
+    }
+
+    /// Calls the subscribers of the keys of `address` whose values have
+    /// changed since `watched` was read. Notifications are not recursive:
+    /// writes made by the subscribers are not watched. Subscribers draw from
+    /// the remaining `fuel` of the operation.
+    fn notify_subscribers(
+        hrt: &mut (impl HostRuntime + 'static),
+        tx: &mut Transaction,
+        address: &Address,
+        watched: Vec<(Subscription, Option<serde_json::Value>)>,
+        fuel: &mut u64,
+        operation_hash: &OperationHash,
+    ) -> Result<()> {
+        let storage = jstz_api::Kv::new(address.to_string());
+
+        for (subscription, old_value) in watched {
+            if *fuel == 0 {
+                return Err(Error::OutOfGas);
+            }
+
+            let new_value = storage
+                .get(hrt, tx, &subscription.key)?
+                .map(|value| value.0.clone());
+
+            if new_value == old_value {
+                continue;
+            }
+
+            // The watched contract may have overwritten its subscriptions
+            let Ok(callback) = Address::from_base58(&subscription.callback) else {
+                continue;
+            };
+            let body = serde_json::json!({
+                "contract": address.to_string(),
+                "key": subscription.key,
+                "oldValue": old_value,
+                "newValue": new_value,
+            });
+
+            let http_request = create_http_request(
+                format!("tezos://{callback}/")
+                    .parse()
+                    .expect("Expected valid uri"),
+                http::Method::POST,
+                http::HeaderMap::new(),
+                Some(body.to_string().into_bytes()),
+            );
+
+            let rt = &mut jstz_core::Runtime::new()?;
+            register_web_apis(&rt.realm().clone(), rt);
+
+            let request = JsNativeObject::new::<RequestClass>(
+                Request::from_http_request(http_request, rt)?,
+                rt,
+            )?;
+
+            // The watched contract is the referer of the notification
+            headers::test_and_set_referrer(&request.deref(), address)?;
+
+            // A failing subscriber does not affect the operation
+            rt.set_fuel(*fuel);
+
+            let savepoint = tx.savepoint();
+            let result = runtime::with_host_runtime(hrt, || {
+                Script::with_transaction(tx, rt, |rt| {
+                    jstz_core::future::block_on(async move {
+                        let result = Script::load_init_run_with(
+                            &callback,
+                            request.inner(),
+                            &OperationContext::new(
+                                operation_hash.clone(),
+                                ConsoleKind::Proto,
+                            ),
+                            rt,
+                        )?;
+
+                        rt.resolve_value_within(&result, Some(MAX_EVENT_LOOP_TICKS))
+                            .await
+                    })
+                })
+            });
+
+            *fuel = rt.fuel_remaining();
+
+            if let Err(error) = result {
+                tx.rollback_to(savepoint)?;
+                debug_msg!(hrt, "[🔔] Subscriber {callback} failed: {error}\n");
+            }
+            tx.release_savepoint(savepoint)?;
+        }
 
-        // 2. Extract address from request
+        Ok(())
+    }
+
+    /// Deserializes the request of an operation, with the source of the
+    /// operation as its referer, returning it with the address of the target
+    /// contract
+    fn create_request(
+        rt: &mut jstz_core::Runtime<'_>,
+        source: &Address,
+        uri: http::Uri,
+        method: http::Method,
+        headers: http::HeaderMap,
+        body: HttpBody,
+    ) -> Result<(Address, JsNativeObject<Request>)> {
+        // 1. Extract address from request
         let address = Address::from_base58(&uri.host().expect("Expected host"))?;
 
-        // 3. Deserialize request
+        // 2. Deserialize request
         let http_request = create_http_request(uri, method, headers, body);
 
         let request = JsNativeObject::new::<RequestClass>(
@@ -346,41 +1187,325 @@ pub mod run {
             rt,
         )?;
 
-        // 4. Set referer as the source address of the operation
+        // 3. Set referer and origin as the source address of the operation
         headers::test_and_set_referrer(&request.deref(), source)?;
+        headers::test_and_set_origin(&request.deref(), source)?;
 
-        // 5. Run :)
-        let result: JsValue = runtime::with_host_runtime(hrt, || {
-            jstz_core::future::block_on(async move {
-                let result = Script::load_init_run(
-                    tx,
-                    &address,
-                    request.inner(),
-                    operation_hash,
-                    rt,
-                )?;
+        Ok((address, request))
+    }
 
-                rt.resolve_value(&result).await
-            })
-        })?;
+    pub fn execute(
+        hrt: &mut (impl HostRuntime + 'static),
+        tx: &mut Transaction,
+        source: &Address,
+        run: operation::RunContract,
+        operation_hash: &OperationHash,
+    ) -> Result<receipt::RunContract> {
+        // 1. Initialize runtime (with Web APIs to construct request)
+        let rt = &mut jstz_core::Runtime::new()?;
+        register_web_apis(&rt.realm().clone(), rt);
 
-        // 6. Serialize response
-        let response = Response::try_from_js(&result)?;
+        let operation = OperationContext::new(operation_hash.clone(), ConsoleKind::Proto);
 
-        let (http_parts, body) = Response::to_http_response(&response).into_parts();
+        execute_in(hrt, tx, rt, source, run, &operation)
+    }
+
+    /// Runs `run` as the operation `operation`. The records logged, the events
+    /// emitted and the calls made are kept in the buffers of `operation` if
+    /// the operation fails.
+    fn execute_in(
+        hrt: &mut (impl HostRuntime + 'static),
+        tx: &mut Transaction,
+        rt: &mut jstz_core::Runtime<'_>,
+        source: &Address,
+        run: operation::RunContract,
+        operation: &OperationContext,
+    ) -> Result<receipt::RunContract> {
+        let operation::RunContract {
+            uri,
+            method,
+            headers,
+            body,
+            amount,
+            fuel_limit,
+        } = run;
+
+        // 2. Deserialize request
+        let (address, request) = create_request(rt, source, uri, method, headers, body)?;
+
+        // 3. Transfer the amount to the contract, undoing the transfer if the
+        //    contract fails or reverts
+        if Account::balance(hrt, tx, source)? < amount {
+            return Err(Error::InsufficientFunds);
+        }
+
+        let operation = OperationContext {
+            call_value: amount,
+            ..operation.clone()
+        };
+
+        let savepoint = tx.savepoint();
+        let result =
+            Account::transfer(hrt, tx, source, &address, amount).and_then(|()| {
+                run_script(hrt, tx, rt, &address, request, fuel_limit, &operation)
+            });
+
+        if result.is_err() {
+            tx.rollback_to(savepoint)?;
+        }
+        tx.release_savepoint(savepoint)?;
+
+        result
+    }
+
+    /// Simulates a `RunContract` operation: the request is handled as by
+    /// [`execute`], writes and transfers included, but in a transaction that
+    /// is never committed. Returns the receipt the operation would have had,
+    /// or the reason it would have failed, along with its logs, events, calls,
+    /// fuel consumed and the keys it wrote, even if it failed.
+    ///
+    /// The run is seeded with the hash the operation would have if `source`
+    /// sent it with its next nonce, so that `crypto.getRandomValues()`
+    /// returns the same values as in the real run.
+    pub fn execute_simulate(
+        hrt: &mut (impl HostRuntime + 'static),
+        source: &Address,
+        run: operation::RunContract,
+    ) -> Result<receipt::SimulateRunContract> {
+        let operation_hash = operation::Operation {
+            source: source.clone(),
+            nonce: Account::nonce(hrt, &mut Kv::new().begin_transaction(), source)?,
+            content: operation::Content::RunContract(run.clone()),
+        }
+        .hash();
+        let fuel_limit = run.fuel_limit;
+
+        let rt = &mut jstz_core::Runtime::new()?;
+        register_web_apis(&rt.realm().clone(), rt);
+
+        let mut tx = Kv::new().begin_transaction().with_write_log();
+        let operation = OperationContext::new(operation_hash, ConsoleKind::Proto);
+        let result = execute_in(hrt, &mut tx, rt, source, run, &operation);
+
+        let changed_keys = tx
+            .write_log()
+            .into_iter()
+            .flatten()
+            .map(|key| String::from_utf8_lossy(key.as_bytes()).into_owned())
+            .collect();
+
+        // The transaction is dropped without being committed
+        let simulation = match result {
+            Ok(receipt) => receipt::SimulateRunContract {
+                gas_used: receipt.gas_used,
+                logs: receipt.logs.clone(),
+                events: receipt.events.clone(),
+                call_trace: receipt.call_trace.clone(),
+                result: Ok(receipt),
+                changed_keys,
+            },
+            Err(error) => {
+                // Reverted operations carry their logs in the error
+                let logs = match &error {
+                    Error::ContractReverted { logs, .. } => logs.clone(),
+                    _ => operation.logs.take(),
+                };
+
+                receipt::SimulateRunContract {
+                    gas_used: fuel_limit.saturating_sub(rt.fuel_remaining()),
+                    logs,
+                    events: operation.events.take(),
+                    call_trace: operation.trace.take(),
+                    result: Err(error.into()),
+                    changed_keys,
+                }
+            }
+        };
+
+        Ok(simulation)
+    }
+
+    /// Runs a view: the request is handled like a `RunContract` operation,
+    /// but in a read-only transaction that is never committed. Writes to the
+    /// key-value store and transfers fail with `ReadOnlyViolation`.
+    pub fn execute_view(
+        hrt: &mut (impl HostRuntime + 'static),
+        source: &Address,
+        view: operation::RunView,
+    ) -> Result<receipt::RunContract> {
+        let operation::RunView {
+            uri,
+            method,
+            headers,
+            body,
+            fuel_limit,
+        } = view;
+        let rt = &mut jstz_core::Runtime::new()?;
+        register_web_apis(&rt.realm().clone(), rt);
+
+        let (address, request) = create_request(rt, source, uri, method, headers, body)?;
+
+        let mut tx = Kv::new().begin_read_only_transaction();
+        let operation =
+            OperationContext::new(OperationHash::default(), ConsoleKind::Proto);
+        let (http_parts, body) =
+            eval_request(hrt, &mut tx, rt, &address, request, fuel_limit, &operation)?;
 
         Ok(receipt::RunContract {
             body,
             status_code: http_parts.status,
             headers: http_parts.headers,
+            gas_used: fuel_limit - rt.fuel_remaining(),
+            logs: operation.logs.take(),
+            events: operation.events.take(),
+            call_trace: operation.trace.take(),
         })
     }
+
+    fn run_script(
+        hrt: &mut (impl HostRuntime + 'static),
+        tx: &mut Transaction,
+        rt: &mut jstz_core::Runtime<'_>,
+        address: &Address,
+        request: JsNativeObject<Request>,
+        fuel_limit: u64,
+        operation: &OperationContext,
+    ) -> Result<receipt::RunContract> {
+        // 1. Read the watched keys of the contract
+        let watched = watched_values(hrt, tx, address)?;
+
+        // 2. Run :)
+        let (http_parts, body) =
+            eval_request(hrt, tx, rt, address, request, fuel_limit, operation)?;
+
+        // 3. Notify the subscribers of the watched keys that have changed
+        //    The operation has already been applied, so failures are only logged
+        let mut fuel_remaining = rt.fuel_remaining();
+        if !watched.is_empty() {
+            if let Err(error) = notify_subscribers(
+                hrt,
+                tx,
+                address,
+                watched,
+                &mut fuel_remaining,
+                &operation.operation_hash,
+            ) {
+                debug_msg!(hrt, "[🔔] Failed to notify subscribers: {error}\n");
+            }
+        }
+
+        Ok(receipt::RunContract {
+            body,
+            status_code: http_parts.status,
+            headers: http_parts.headers,
+            gas_used: fuel_limit - fuel_remaining,
+            logs: operation.logs.take(),
+            events: operation.events.take(),
+            call_trace: operation.trace.take(),
+        })
+    }
+
+    /// Loads, initializes and runs the contract at `address` as the first call
+    /// of `operation`, with at most `fuel_limit` fuel, returning its (2xx)
+    /// response. The records logged and the events emitted by the contract
+    /// and its nested calls are collected into the buffers of `operation`, and
+    /// the calls are recorded into its trace.
+    fn eval_request(
+        hrt: &mut (impl HostRuntime + 'static),
+        tx: &mut Transaction,
+        rt: &mut jstz_core::Runtime<'_>,
+        address: &Address,
+        request: JsNativeObject<Request>,
+        fuel_limit: u64,
+        operation: &OperationContext,
+    ) -> Result<(http::response::Parts, HttpBody)> {
+        // 1. Run :)
+        //    Nested calls run in the same runtime, so they draw from the same
+        //    fuel
+        rt.set_fuel(fuel_limit);
+
+        //    Calls to the same contract share its script
+        let cache = ModuleCache::default();
+        {
+            let context = rt.context();
+            host_defined!(context, mut host_defined);
+            host_defined.insert(cache.clone());
+        }
+
+        let result = runtime::with_host_runtime(hrt, || {
+            Script::with_transaction(tx, rt, |rt| {
+                jstz_core::future::block_on(async move {
+                    let result = Script::load_init_run_with(
+                        address,
+                        request.inner(),
+                        operation,
+                        rt,
+                    )?;
+
+                    rt.resolve_value_within(&result, Some(MAX_EVENT_LOOP_TICKS))
+                        .await
+                })
+            })
+        });
+        cache.clear();
+
+        // If the fuel is exhausted or the response never settles, the script
+        // is aborted and its writes are rolled back by the caller
+        let result: JsValue = match result {
+            Err(Error::CoreError {
+                source: jstz_core::Error::JsError { source },
+            }) if rt.exhaust_fuel_on(&source) => return Err(Error::OutOfGas),
+            Err(_) if rt.fuel_remaining() == 0 => return Err(Error::OutOfGas),
+            Err(_) if rt.timed_out() => return Err(jstz_core::Error::Timeout.into()),
+            result => result?,
+        };
+
+        // 2. Serialize response
+        let response = Response::try_from_js(&result)?;
+        let (http_parts, body) = Response::to_http_response(&response).into_parts();
+
+        // 3. Report non-2xx responses, whose transaction has been rolled back,
+        //    with the reason given by the contract
+        if !http_parts.status.is_success() {
+            return Err(Error::ContractReverted {
+                status: http_parts.status.as_u16(),
+                message: revert_reason(http_parts.status, body.as_deref()),
+                logs: operation.logs.take(),
+            });
+        }
+
+        Ok((http_parts, body))
+    }
 }
 
 pub mod deploy {
+    use boa_interner::Sym;
+    use boa_parser::Parser;
+
     use super::*;
     use crate::{operation, receipt};
 
+    /// Checks that `code` parses as a module with a `default` export, without
+    /// evaluating it. Parsing happens in a throwaway context, so that the
+    /// interned names of rejected code are dropped with it.
+    pub fn validate(code: &str) -> Result<()> {
+        let context = &mut Context::default();
+
+        let module = Parser::new(Source::from_bytes(code))
+            .parse_module(context.interner_mut())
+            .map_err(|error| Error::InvalidContractCode {
+                message: error.to_string(),
+            })?;
+
+        if !module.exported_names().contains(&Sym::DEFAULT) {
+            return Err(Error::InvalidContractCode {
+                message: "The module has no default export".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
     pub fn execute(
         hrt: &impl HostRuntime,
         tx: &mut Transaction,
@@ -392,6 +1517,8 @@ pub mod deploy {
             contract_credit,
         } = deployment;
 
+        validate(&contract_code)?;
+
         let address = Script::deploy(hrt, tx, source, contract_code, contract_credit)?;
 
         Ok(receipt::DeployContract {
@@ -399,3 +1526,1435 @@ pub mod deploy {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        context::block::Block,
+        operation::RunContract,
+        test_utils::{call, deploy, execute, other, request, run_contract, source},
+    };
+    use tezos_smart_rollup_mock::MockHost;
+
+    #[test]
+    fn test_reload_preserves_account_state() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+        let rt = &mut jstz_core::Runtime::new().expect("Could not create runtime");
+
+        let counter_code = |step: u32| {
+            format!(
+                r#"
+                export default () => {{
+                    const count = (Kv.get("count") ?? 0) + {step};
+                    Kv.set("count", count);
+                    return new Response(`${{count}}`);
+                }};
+                "#
+            )
+        };
+        let old_code = counter_code(1);
+        let new_code = counter_code(10);
+
+        let address = deploy(hrt, &mut tx, &old_code, 42);
+
+        fn run(hrt: &mut MockHost, tx: &mut Transaction, address: &Address) -> String {
+            let (_, body) = call(hrt, tx, address).into_parts();
+
+            String::from_utf8(body.unwrap_or_default()).unwrap()
+        }
+
+        fn count(hrt: &MockHost, tx: &mut Transaction, address: &Address) -> f64 {
+            jstz_api::Kv::new(address.to_string())
+                .get(hrt, tx, "count")
+                .expect("Could not read count")
+                .and_then(|count| count.0.as_f64())
+                .expect("Expected a count")
+        }
+
+        assert_eq!(run(hrt, &mut tx, &address), "1");
+        assert_eq!(count(hrt, &mut tx, &address), 1.0);
+
+        // Act
+        Script::reload(hrt, &mut tx, &address, new_code.clone(), rt)
+            .expect("Could not reload script");
+
+        // Assert
+        let code = Account::contract_code(hrt, &mut tx, &address)
+            .expect("Could not get contract code")
+            .cloned();
+        let balance =
+            Account::balance(hrt, &mut tx, &address).expect("Could not get balance");
+
+        assert_eq!(code, Some(new_code));
+        assert_eq!(balance, 42);
+        assert_eq!(count(hrt, &mut tx, &address), 1.0);
+
+        // The new logic increments the preserved count
+        assert_eq!(run(hrt, &mut tx, &address), "11");
+        assert_eq!(count(hrt, &mut tx, &address), 11.0);
+    }
+
+    #[test]
+    fn test_reload_fails_for_non_contract_address() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+        let rt = &mut jstz_core::Runtime::new().expect("Could not create runtime");
+
+        let address = source();
+
+        let result = Script::reload(
+            hrt,
+            &mut tx,
+            &address,
+            "export default () => new Response()".to_string(),
+            rt,
+        );
+
+        assert!(matches!(result, Err(Error::InvalidAddress)));
+    }
+
+    fn deploy_code(code: &str) -> Result<crate::receipt::DeployContract> {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        deploy::execute(
+            hrt,
+            &mut tx,
+            &source,
+            crate::operation::DeployContract {
+                contract_code: code.to_string(),
+                contract_credit: 0,
+            },
+        )
+    }
+
+    #[test]
+    fn test_deploy_rejects_syntax_error() {
+        let result = deploy_code("export default () => new Response(");
+
+        assert!(matches!(result, Err(Error::InvalidContractCode { .. })));
+    }
+
+    #[test]
+    fn test_deploy_rejects_missing_default_export() {
+        let result = deploy_code("export const handler = () => new Response();");
+
+        let Err(Error::InvalidContractCode { message }) = result else {
+            panic!("Expected the deployment to be rejected");
+        };
+        assert_eq!(message, "The module has no default export");
+        assert!(deploy_code("export default () => new Response()").is_ok());
+    }
+
+    #[test]
+    fn test_cached_script_is_reloaded_after_redeploy() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+        let rt = &mut jstz_core::Runtime::new().expect("Could not create runtime");
+
+        let old_code = r#"
+            let calls = 0;
+            export default () => new Response(`old ${++calls}`);
+        "#;
+        let new_code = "export default () => new Response('new')";
+
+        let address = deploy(hrt, &mut tx, old_code, 0);
+
+        {
+            let context = rt.context();
+            host_defined!(context, mut host_defined);
+            host_defined.insert(ModuleCache::default());
+        }
+
+        fn run(
+            hrt: &mut MockHost,
+            tx: &mut Transaction,
+            rt: &mut jstz_core::Runtime<'_>,
+            address: &Address,
+        ) -> String {
+            let result = runtime::with_host_runtime(hrt, || {
+                Script::with_transaction(tx, rt, |rt| {
+                    jstz_core::future::block_on(async move {
+                        let result = Script::load_init_run_with(
+                            address,
+                            &JsValue::undefined(),
+                            &OperationContext::new(
+                                OperationHash::default(),
+                                ConsoleKind::Proto,
+                            ),
+                            rt,
+                        )?;
+
+                        rt.resolve_value(&result).await
+                    })
+                })
+            })
+            .expect("Could not run script");
+
+            let response = Response::try_from_js(&result).expect("Expected a response");
+            let (_, body) = Response::to_http_response(&response).into_parts();
+
+            String::from_utf8(body.unwrap_or_default()).unwrap()
+        }
+
+        // Act & Assert
+        assert_eq!(run(hrt, &mut tx, rt, &address), "old 1");
+
+        // The module is not evaluated again
+        assert_eq!(run(hrt, &mut tx, rt, &address), "old 2");
+
+        Account::set_contract_code(hrt, &mut tx, &address, new_code.to_string())
+            .expect("Could not set contract code");
+        assert_eq!(run(hrt, &mut tx, rt, &address), "new");
+    }
+
+    #[test]
+    fn test_admin_upgrade() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let old_code = "export default () => new Response('1')".to_string();
+        let new_code = "export default () => new Response('2')".to_string();
+
+        let address = deploy(hrt, &mut tx, &old_code, 42);
+
+        // Act
+        Script::upgrade(hrt, &mut tx, &source, &address, new_code.clone())
+            .expect("Could not upgrade script");
+
+        // Assert
+        let code = Account::contract_code(hrt, &mut tx, &address)
+            .expect("Could not get contract code")
+            .cloned();
+        let balance =
+            Account::balance(hrt, &mut tx, &address).expect("Could not get balance");
+
+        assert_eq!(code, Some(new_code));
+        assert_eq!(balance, 42);
+    }
+
+    #[test]
+    fn test_non_admin_upgrade_is_rejected() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let other = other();
+
+        let old_code = "export default () => new Response('1')".to_string();
+
+        let address = deploy(hrt, &mut tx, &old_code, 0);
+
+        // Act
+        let result = Script::upgrade(
+            hrt,
+            &mut tx,
+            &other,
+            &address,
+            "export default () => new Response('2')".to_string(),
+        );
+
+        // Assert
+        assert!(matches!(result, Err(Error::Unauthorized)));
+
+        let code = Account::contract_code(hrt, &mut tx, &address)
+            .expect("Could not get contract code")
+            .cloned();
+        assert_eq!(code, Some(old_code));
+    }
+
+    #[test]
+    fn test_upgrade_rejects_invalid_code() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let old_code = "export default () => new Response('1')".to_string();
+
+        let address = deploy(hrt, &mut tx, &old_code, 0);
+
+        // Act
+        let syntax_error = Script::upgrade(
+            hrt,
+            &mut tx,
+            &source,
+            &address,
+            "export default () => new Response(".to_string(),
+        );
+        let no_default_export = Script::upgrade(
+            hrt,
+            &mut tx,
+            &source,
+            &address,
+            "export const handler = () => new Response();".to_string(),
+        );
+
+        // Assert
+        assert!(matches!(
+            syntax_error,
+            Err(Error::InvalidContractCode { .. })
+        ));
+        assert!(matches!(
+            no_default_export,
+            Err(Error::InvalidContractCode { .. })
+        ));
+
+        let code = Account::contract_code(hrt, &mut tx, &address)
+            .expect("Could not get contract code")
+            .cloned();
+        assert_eq!(code, Some(old_code));
+    }
+
+    #[test]
+    fn test_selfdestruct_transfers_balance_and_blocks_calls() {
+        let hrt = &mut MockHost::default();
+        let kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let beneficiary = other();
+
+        let address = deploy(hrt, &mut tx, "export default () => new Response()", 42);
+
+        // Act
+        Script::selfdestruct(hrt, &mut tx, &address, &beneficiary)
+            .expect("Could not destroy script");
+
+        // Assert
+        let balance =
+            Account::balance(hrt, &mut tx, &beneficiary).expect("Could not get balance");
+        assert_eq!(balance, 42);
+        assert_eq!(Account::balance(hrt, &mut tx, &address).unwrap(), 0);
+        assert!(Account::is_deleted(hrt, &mut tx, &address).unwrap());
+
+        assert_eq!(call(hrt, &mut tx, &address).status(), 410);
+    }
+
+    #[test]
+    fn test_watch_global_notifies_subscriber() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let oracle_code = r#"
+            export default (request) => {
+                const url = new URL(request.url);
+                if (url.pathname === "/allow") {
+                    Kv.allowWatcher(url.searchParams.get("watcher"));
+                }
+                if (url.pathname === "/set") {
+                    Kv.set("price", 42);
+                }
+                return new Response();
+            };
+        "#;
+        let oracle = deploy(hrt, &mut tx, oracle_code, 0);
+
+        let subscriber_code = format!(
+            r#"
+            export default async (request) => {{
+                const url = new URL(request.url);
+                if (url.pathname === "/subscribe") {{
+                    Kv.watchGlobal("{oracle}", "price", Ledger.selfAddress);
+                    return new Response();
+                }}
+
+                const {{ contract, key, oldValue, newValue }} = await request.json();
+                Kv.set("notifications", (Kv.get("notifications") ?? 0) + 1);
+                Kv.set("last", [
+                    request.headers.get("Referer"),
+                    contract,
+                    key,
+                    oldValue,
+                    newValue,
+                ]);
+                return new Response();
+            }};
+            "#
+        );
+        let subscriber = deploy(hrt, &mut tx, &subscriber_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        let mut run = |address: &Address, path: &str| {
+            let mut tx = kv.begin_transaction();
+            let receipt = execute(hrt, &mut tx, &source, request(address, path))
+                .expect("Could not run script");
+            assert_eq!(receipt.status_code, 200);
+            assert!(receipt.gas_used > 0);
+            kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+        };
+
+        // Act
+        run(&oracle, &format!("/allow?watcher={subscriber}"));
+        run(&subscriber, "/subscribe");
+        run(&oracle, "/noop");
+        run(&oracle, "/set");
+        run(&oracle, "/set");
+
+        // Assert
+        let mut tx = kv.begin_transaction();
+        let storage = jstz_api::Kv::new(subscriber.to_string());
+
+        // Only the first `/set` changed the watched key
+        let notifications = storage.get(hrt, &mut tx, "notifications").unwrap().unwrap();
+        assert_eq!(notifications.0.as_f64(), Some(1.0));
+
+        let last = storage.get(hrt, &mut tx, "last").unwrap().unwrap();
+        assert_eq!(
+            last.0,
+            serde_json::json!([
+                oracle.to_string(),
+                oracle.to_string(),
+                "price",
+                null,
+                42
+            ])
+        );
+
+        let subscriptions = jstz_api::Kv::new(oracle.to_string())
+            .subscriptions(hrt, &mut tx)
+            .unwrap();
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0].subscriber, subscriber.to_string());
+    }
+
+    #[test]
+    fn test_watch_global_requires_opt_in() {
+        let mut hrt = MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let target_code = "export default () => new Response();";
+        let target = deploy(&hrt, &mut tx, target_code, 0);
+
+        // A third party tries to fill all the subscription slots of the target
+        let attacker_code = format!(
+            r#"
+            export default () => {{
+                let failures = 0;
+                for (let i = 0; i < 16; i++) {{
+                    try {{
+                        Kv.watchGlobal("{target}", `key${{i}}`, Ledger.selfAddress);
+                    }} catch {{
+                        failures++;
+                    }}
+                }}
+                return new Response(String(failures));
+            }};
+            "#
+        );
+        let attacker = deploy(&hrt, &mut tx, &attacker_code, 0);
+
+        kv.commit_transaction(&mut hrt, tx)
+            .expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(&mut hrt, &mut tx, &source, request(&attacker, "/"))
+            .expect("Could not run script");
+        kv.commit_transaction(&mut hrt, tx)
+            .expect("Could not commit tx");
+
+        // Assert
+        assert_eq!(receipt.body.unwrap(), b"16");
+
+        let subscriptions = jstz_api::Kv::new(target.to_string())
+            .subscriptions(&hrt, &mut kv.begin_transaction())
+            .unwrap();
+        assert!(subscriptions.is_empty());
+    }
+
+    #[test]
+    fn test_out_of_gas_rolls_back() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let looping_code = r#"
+            export default () => {
+                Kv.set("touched", true);
+                while (true) {}
+            };
+        "#;
+        let looping = deploy(hrt, &mut tx, looping_code, 0);
+
+        let caller_code = format!(
+            r#"
+            export default async () => {{
+                Kv.set("touched", true);
+                try {{
+                    await Contract.call(new Request("tezos://{looping}/"));
+                }} catch {{}}
+                return new Response();
+            }};
+            "#
+        );
+        let caller = deploy(hrt, &mut tx, &caller_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        for address in [&looping, &caller] {
+            // Act
+            let mut tx = kv.begin_transaction();
+            let result = execute(
+                hrt,
+                &mut tx,
+                &source,
+                RunContract {
+                    fuel_limit: 100_000,
+                    ..request(address, "/")
+                },
+            );
+
+            // Assert
+            assert!(matches!(result, Err(Error::OutOfGas)));
+
+            let touched = jstz_api::Kv::new(address.to_string())
+                .has(hrt, &mut tx, "touched")
+                .unwrap();
+            assert!(!touched);
+        }
+    }
+
+    #[test]
+    fn test_revert_reason_in_receipt() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let code = r#"
+            export default () => {
+                Kv.set("touched", true);
+                return new Response("Insufficient funds", { status: 402 });
+            };
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let result = execute(hrt, &mut tx, &source, request(&address, "/"));
+
+        // Assert
+        let Err(Error::ContractReverted {
+            status, message, ..
+        }) = result
+        else {
+            panic!("Expected the contract to revert");
+        };
+        assert_eq!(status, 402);
+        assert_eq!(message, "Insufficient funds");
+
+        let receipt = crate::receipt::Receipt::new(
+            OperationHash::default(),
+            Err(Error::ContractReverted {
+                status,
+                message,
+                logs: vec![],
+            }),
+        );
+        assert_eq!(
+            receipt.inner.unwrap_err().to_string(),
+            "ContractReverted (402): Insufficient funds"
+        );
+
+        let touched = jstz_api::Kv::new(address.to_string())
+            .has(hrt, &mut tx, "touched")
+            .unwrap();
+        assert!(!touched);
+    }
+
+    #[test]
+    fn test_export_import_json_requires_owner() {
+        let mut hrt = MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let owner = source();
+        let user = other();
+
+        let code = r#"
+            export default (request) => {
+                const path = new URL(request.url).pathname;
+                try {
+                    if (path === "/import") Kv.importJSON({ counter: 42 });
+                    return Response.json(Kv.exportJSON());
+                } catch (error) {
+                    return new Response(error.message);
+                }
+            };
+        "#;
+        let address = deploy(&hrt, &mut tx, code, 0);
+        kv.commit_transaction(&mut hrt, tx)
+            .expect("Could not commit tx");
+
+        let mut send = |source: &Address, path: &str| {
+            let mut tx = kv.begin_transaction();
+            let result = execute(&mut hrt, &mut tx, source, request(&address, path))
+                .map(|receipt| receipt.body.unwrap_or_default());
+            kv.commit_transaction(&mut hrt, tx)
+                .expect("Could not commit tx");
+            String::from_utf8(result.unwrap()).unwrap()
+        };
+
+        assert_eq!(
+            send(&user, "/import"),
+            "Kv.importJSON() may only be called by the owner"
+        );
+        assert_eq!(
+            send(&user, "/"),
+            "Kv.exportJSON() may only be called by the owner"
+        );
+
+        assert_eq!(send(&owner, "/import"), r#"{"counter":42}"#);
+        assert_eq!(send(&owner, "/"), r#"{"counter":42}"#);
+    }
+
+    #[test]
+    fn test_run_with_amount() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+        Account::deposit(hrt, &mut tx, &source, 100).expect("Could not deposit");
+
+        let code = r#"
+            export default () => new Response(JSON.stringify(Contract.callValue));
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(
+            hrt,
+            &mut tx,
+            &source,
+            RunContract {
+                amount: 40,
+                ..request(&address, "/")
+            },
+        )
+        .expect("Could not run contract");
+
+        // Assert
+        assert_eq!(receipt.body, Some(b"40".to_vec()));
+        assert_eq!(Account::balance(hrt, &mut tx, &source).unwrap(), 60);
+        assert_eq!(Account::balance(hrt, &mut tx, &address).unwrap(), 40);
+    }
+
+    #[test]
+    fn test_run_with_insufficient_funds() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+        Account::deposit(hrt, &mut tx, &source, 10).expect("Could not deposit");
+
+        let code = r#"
+            export default () => {
+                Kv.set("touched", true);
+                return new Response();
+            };
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let result = execute(
+            hrt,
+            &mut tx,
+            &source,
+            RunContract {
+                amount: 11,
+                ..request(&address, "/")
+            },
+        );
+
+        // Assert
+        assert!(matches!(result, Err(Error::InsufficientFunds)));
+        assert_eq!(Account::balance(hrt, &mut tx, &source).unwrap(), 10);
+        assert_eq!(Account::balance(hrt, &mut tx, &address).unwrap(), 0);
+
+        let touched = jstz_api::Kv::new(address.to_string())
+            .has(hrt, &mut tx, "touched")
+            .unwrap();
+        assert!(!touched);
+    }
+
+    #[test]
+    fn test_revert_returns_amount() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+        Account::deposit(hrt, &mut tx, &source, 100).expect("Could not deposit");
+
+        let code = r#"
+            export default () => new Response("Not today", { status: 403 });
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let result = execute(
+            hrt,
+            &mut tx,
+            &source,
+            RunContract {
+                amount: 40,
+                ..request(&address, "/")
+            },
+        );
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(Error::ContractReverted { status: 403, .. })
+        ));
+        assert_eq!(Account::balance(hrt, &mut tx, &source).unwrap(), 100);
+        assert_eq!(Account::balance(hrt, &mut tx, &address).unwrap(), 0);
+    }
+
+    fn view(address: &Address) -> crate::operation::RunView {
+        crate::operation::RunView {
+            uri: format!("tezos://{address}/").parse().unwrap(),
+            method: http::Method::GET,
+            headers: http::HeaderMap::new(),
+            body: None,
+            fuel_limit: 1_000_000,
+        }
+    }
+
+    #[test]
+    fn test_view_reads_state() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let code = r#"
+            export default () => new Response(JSON.stringify(Kv.get("counter")));
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+        jstz_api::Kv::new(address.to_string())
+            .set(hrt, &mut tx, "counter", KvValue(serde_json::json!(42)))
+            .expect("Could not set counter");
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let receipt =
+            run::execute_view(hrt, &source, view(&address)).expect("Could not run view");
+
+        // Assert
+        assert_eq!(receipt.body, Some(b"42".to_vec()));
+    }
+
+    #[test]
+    fn test_view_rejects_writes() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let code = r#"
+            export default () => {
+                try {
+                    Kv.set("touched", true);
+                } catch (error) {
+                    return new Response(error.message, { status: 500 });
+                }
+                return new Response();
+            };
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let result = run::execute_view(hrt, &source, view(&address));
+
+        // Assert
+        let Err(Error::ContractReverted {
+            status, message, ..
+        }) = result
+        else {
+            panic!("Expected the view to fail");
+        };
+        assert_eq!(status, 500);
+        assert_eq!(message, "ReadOnlyViolation");
+
+        let touched = jstz_api::Kv::new(address.to_string())
+            .has(hrt, &mut kv.begin_transaction(), "touched")
+            .unwrap();
+        assert!(!touched);
+    }
+
+    #[test]
+    fn test_simulate_does_not_persist_writes() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+        Account::deposit(hrt, &mut tx, &source, 100).expect("Could not deposit");
+
+        let code = r#"
+            export default () => {
+                const count = (Kv.get("count") ?? 0) + 1;
+                Kv.set("count", count);
+                Contract.emit("Counted", count);
+                return new Response(JSON.stringify(count));
+            };
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let simulate = |hrt: &mut MockHost| {
+            run::execute_simulate(
+                hrt,
+                &source,
+                RunContract {
+                    amount: 10,
+                    ..RunContract::json(
+                        format!("tezos://{address}/").parse().unwrap(),
+                        http::Method::POST,
+                        &serde_json::Value::Null,
+                    )
+                },
+            )
+            .expect("Could not simulate")
+        };
+        let first = simulate(hrt);
+        let second = simulate(hrt);
+
+        // Assert
+        assert_eq!(first.result.unwrap().body, Some(b"1".to_vec()));
+        assert_eq!(second.result.unwrap().body, Some(b"1".to_vec()));
+        assert!(first.gas_used > 0);
+        assert_eq!(
+            first.events,
+            vec![api::Event {
+                contract_address: address.clone(),
+                topic: "Counted".to_string(),
+                data: serde_json::json!(1),
+            }]
+        );
+        assert!(first
+            .changed_keys
+            .contains(&format!("/jstz_kv/{address}/count")));
+        assert!(first
+            .changed_keys
+            .contains(&format!("/jstz_account/{address}")));
+
+        let mut tx = kv.begin_transaction();
+        let count = jstz_api::Kv::new(address.to_string())
+            .has(hrt, &mut tx, "count")
+            .unwrap();
+        assert!(!count);
+        assert_eq!(Account::balance(hrt, &mut tx, &source).unwrap(), 100);
+        assert_eq!(Account::balance(hrt, &mut tx, &address).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_simulate_reports_failures() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let code = r#"
+            export default (request) => {
+                Kv.set("touched", true);
+                console.log("before failing");
+                if (new URL(request.url).pathname === "/loop") {
+                    while (true) {}
+                }
+                return new Response("Nope", { status: 400 });
+            };
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        let simulate = |hrt: &mut MockHost, path: &str| {
+            run::execute_simulate(
+                hrt,
+                &source,
+                RunContract {
+                    fuel_limit: 100_000,
+                    ..request(&address, path)
+                },
+            )
+            .expect("Could not simulate")
+        };
+        let touched = format!("/jstz_kv/{address}/touched");
+
+        // Act
+        let reverted = simulate(hrt, "/");
+        let out_of_gas = simulate(hrt, "/loop");
+
+        // Assert
+        assert!(matches!(
+            reverted.result,
+            Err(crate::receipt::ReceiptError::ContractReverted { status: 400, .. })
+        ));
+        assert_eq!(reverted.logs.len(), 1);
+        assert!(reverted.gas_used > 0);
+        assert_eq!(reverted.call_trace.len(), 1);
+        assert!(reverted.changed_keys.contains(&touched));
+
+        assert!(matches!(
+            out_of_gas.result,
+            Err(crate::receipt::ReceiptError::OutOfGas)
+        ));
+        assert_eq!(out_of_gas.logs.len(), 1);
+        assert_eq!(out_of_gas.gas_used, 100_000);
+        assert!(out_of_gas.changed_keys.contains(&touched));
+    }
+
+    #[test]
+    fn test_simulate_random_values_match_real_run() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let code = r#"
+            export default () => {
+                const bytes = crypto.getRandomValues(new Uint8Array(8));
+                return Response.json(Array.from(bytes));
+            };
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        let run = request(&address, "/");
+
+        // Act
+        let simulated = run::execute_simulate(hrt, &source, run.clone())
+            .expect("Could not simulate")
+            .result
+            .unwrap();
+
+        let mut tx = kv.begin_transaction();
+        let operation_hash = crate::operation::Operation {
+            source: source.clone(),
+            nonce: Account::nonce(hrt, &mut tx, &source).unwrap(),
+            content: crate::operation::Content::RunContract(run.clone()),
+        }
+        .hash();
+        let executed = run::execute(hrt, &mut tx, &source, run, &operation_hash).unwrap();
+
+        // Assert
+        assert_eq!(simulated.body, executed.body);
+    }
+
+    #[test]
+    fn test_reserved_keys_are_read_only() {
+        let (hrt, address, result) = run_contract(
+            r#"
+            export default () => {
+                const writes = [
+                    () => Kv.set("__owner__", Ledger.selfAddress),
+                    () => Kv.delete("__abi__"),
+                    () => Kv.compareAndSwap("__paused__", null, true),
+                    () => Kv.delete("__lock__/swap"),
+                ];
+                const errors = writes.map((write) => {
+                    try {
+                        write();
+                    } catch (error) {
+                        return error.message;
+                    }
+                });
+                return Response.json({ errors, owner: Kv.get("__owner__") });
+            };
+            "#,
+        );
+
+        let result: serde_json::Value = serde_json::from_slice(&result.unwrap()).unwrap();
+        assert_eq!(
+            result["errors"],
+            serde_json::json!([
+                "The key `__owner__` is reserved",
+                "The key `__abi__` is reserved",
+                "The key `__paused__` is reserved",
+                "The key `__lock__/swap` is reserved",
+            ])
+        );
+        assert_eq!(result["owner"], source().to_string());
+
+        let owner = jstz_api::Kv::new(address.to_string())
+            .get(&hrt, &mut Kv::new().begin_transaction(), OWNER_KEY)
+            .unwrap()
+            .map(|value| value.0.clone());
+        assert_eq!(owner, Some(serde_json::json!(source().to_string())));
+    }
+
+    #[test]
+    fn test_scan_prefix_in_smart_function() {
+        let (hrt, address, result) = run_contract(
+            r#"
+            export default () => {
+                Kv.set("users/1", "alice");
+                Kv.set("users/2", "bob");
+                Kv.set("users/3", "carol");
+                Kv.set("posts/1", "hello");
+                Kv.delete("users/2");
+                return Response.json(Kv.scanPrefix("users/"));
+            };
+            "#,
+        );
+
+        let result: serde_json::Value = serde_json::from_slice(&result.unwrap()).unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!([["users/1", "alice"], ["users/3", "carol"]])
+        );
+
+        let entries = jstz_api::Kv::new(address.to_string())
+            .scan_prefix(&hrt, &mut Kv::new().begin_transaction(), "users/", 0)
+            .unwrap()
+            .into_iter()
+            .map(|(key, value)| (key, value.0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                ("users/1".to_string(), serde_json::json!("alice")),
+                ("users/3".to_string(), serde_json::json!("carol")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_kv_transactions() {
+        let (_, _, result) = run_contract(
+            r#"
+            export default async () => {
+                // A failed inner transaction keeps the writes of the outer one
+                await Kv.transaction(async (kv) => {
+                    kv.set("outer", 1);
+                    try {
+                        await Kv.transaction(async (kv) => {
+                            kv.set("inner", 1);
+                            throw new Error("inner");
+                        });
+                    } catch {}
+                });
+
+                // The outer transaction is rolled back before the inner one
+                // settles, which discards the savepoint of the inner one
+                let inner;
+                try {
+                    await Kv.transaction(async (kv) => {
+                        kv.set("a", 1);
+                        inner = Kv.transaction(async (kv) => {
+                            await null;
+                            kv.set("b", 1);
+                        });
+                        throw new Error("outer");
+                    });
+                } catch {}
+                await inner;
+
+                const keys = ["outer", "inner", "a", "b"];
+                return Response.json(keys.map((key) => Kv.get(key)));
+            };
+            "#,
+        );
+
+        assert_eq!(result.unwrap(), b"[1,null,null,null]");
+    }
+
+    #[test]
+    fn test_unresolved_response_times_out() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+        Account::deposit(hrt, &mut tx, &source, 10).expect("Could not deposit");
+
+        let code = r#"
+            export default () => {
+                Kv.set("touched", true);
+                return new Promise(() => {});
+            };
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let result = execute(
+            hrt,
+            &mut tx,
+            &source,
+            RunContract {
+                amount: 10,
+                ..request(&address, "/")
+            },
+        );
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(Error::CoreError {
+                source: jstz_core::Error::Timeout
+            })
+        ));
+        assert_eq!(Account::balance(hrt, &mut tx, &source).unwrap(), 10);
+
+        let touched = jstz_api::Kv::new(address.to_string())
+            .has(hrt, &mut tx, "touched")
+            .unwrap();
+        assert!(!touched);
+    }
+
+    fn log_lines(logs: &[jstz_api::LogRecord]) -> Vec<(String, String, String)> {
+        logs.iter()
+            .map(|log| {
+                (
+                    log.contract_address.to_string(),
+                    log.level.clone(),
+                    log.text.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_logs_in_receipt() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let callee_code = r#"
+            export default () => {
+                console.warn("callee");
+                return new Response();
+            };
+        "#;
+        let callee = deploy(hrt, &mut tx, callee_code, 0);
+
+        let caller_code = format!(
+            r#"
+            export default async (request) => {{
+                console.log("before");
+                await Contract.call(new Request("tezos://{callee}/"));
+                console.error("after");
+                const status = request.url.endsWith("/revert") ? 500 : 200;
+                return new Response(null, {{ status }});
+            }};
+            "#
+        );
+        let caller = deploy(hrt, &mut tx, &caller_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        let expected = vec![
+            (caller.to_string(), "log".to_string(), "before".to_string()),
+            (callee.to_string(), "warn".to_string(), "callee".to_string()),
+            (caller.to_string(), "error".to_string(), "after".to_string()),
+        ];
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(hrt, &mut tx, &source, request(&caller, "/"))
+            .expect("Could not run script");
+
+        // Assert
+        assert_eq!(log_lines(&receipt.logs), expected);
+
+        // Act
+        let result = execute(hrt, &mut tx, &source, request(&caller, "/revert"));
+
+        // Assert
+        let Err(Error::ContractReverted { status, logs, .. }) = result else {
+            panic!("Expected the contract to revert");
+        };
+        assert_eq!(status, 500);
+        assert_eq!(log_lines(&logs), expected);
+    }
+
+    #[test]
+    fn test_console_groups_across_nested_calls() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        // The callee throws without closing its group
+        let callee_code = r#"
+            export default () => {
+                console.group("inner");
+                console.log("callee");
+                throw new Error("boom");
+            };
+        "#;
+        let callee = deploy(hrt, &mut tx, callee_code, 0);
+
+        let caller_code = format!(
+            r#"
+            export default async () => {{
+                console.group("outer");
+                console.log("first\nsecond");
+                await Contract.tryCall("{callee}", new Request("tezos://{callee}/"));
+                await Contract.tryCall("{callee}", new Request("tezos://{callee}/"));
+                console.log("nested");
+                console.groupEnd();
+                console.log("done");
+                return new Response();
+            }};
+            "#
+        );
+        let caller = deploy(hrt, &mut tx, &caller_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(hrt, &mut tx, &source, request(&caller, "/"))
+            .expect("Could not run script");
+
+        // Assert
+        let texts: Vec<_> = log_lines(&receipt.logs)
+            .into_iter()
+            .map(|(address, _, text)| (address, text))
+            .collect();
+        let (caller, callee) = (caller.to_string(), callee.to_string());
+        assert_eq!(
+            texts,
+            vec![
+                (caller.clone(), "group: outer".to_string()),
+                (caller.clone(), "  first\n  second".to_string()),
+                (callee.clone(), "group: inner".to_string()),
+                (callee.clone(), "  callee".to_string()),
+                (callee.clone(), "group: inner".to_string()),
+                (callee.clone(), "  callee".to_string()),
+                (caller.clone(), "  nested".to_string()),
+                (caller.clone(), "done".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_random_values_are_deterministic_per_operation() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        let code = r#"
+            export default () => {
+                const bytes = crypto.getRandomValues(new Uint8Array(16));
+                return new Response(Hex.encode(bytes));
+            };
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        let mut run = |operation_hash: &OperationHash| {
+            let mut tx = kv.begin_transaction();
+            run::execute(
+                hrt,
+                &mut tx,
+                &source,
+                request(&address, "/"),
+                operation_hash,
+            )
+            .expect("Could not run script")
+            .body
+        };
+
+        // Act
+        let operation_hash = OperationHash::from(b"operation".as_slice());
+        let first = run(&operation_hash);
+        let second = run(&operation_hash);
+        let other = run(&OperationHash::from(b"other operation".as_slice()));
+
+        // Assert
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn test_date_is_consistent_within_operation() {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let source = source();
+
+        Block::advance(hrt, &mut tx, 1, 1_700_000_000).expect("Could not advance block");
+
+        let b_code = r#"
+            export default () => new Response(String(Date.now()));
+        "#;
+        let b = deploy(hrt, &mut tx, b_code, 0);
+
+        let a_code = format!(
+            r#"
+            export default async () => {{
+                const first = Date.now();
+                const second = new Date().getTime();
+                const nested = await Contract.call(new Request("tezos://{b}/"));
+                return new Response(
+                    JSON.stringify({{
+                        first,
+                        second,
+                        nested: Number(await nested.text()),
+                        elapsed: performance.now(),
+                    }}),
+                );
+            }};
+            "#
+        );
+        let a = deploy(hrt, &mut tx, &a_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let receipt = execute(hrt, &mut tx, &source, request(&a, "/"))
+            .expect("Could not run script");
+
+        // Assert
+        let body: serde_json::Value =
+            serde_json::from_slice(&receipt.body.expect("Expected a body")).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "first": 1_700_000_000_000u64,
+                "second": 1_700_000_000_000u64,
+                "nested": 1_700_000_000_000u64,
+                "elapsed": 0,
+            })
+        );
+    }
+
+    /// Runs a contract that logs and calls another contract that logs, with a
+    /// console of kind `console`. Returns the log records collected.
+    fn logs_of_nested_call(console: ConsoleKind) -> Vec<jstz_api::LogRecord> {
+        let hrt = &mut MockHost::default();
+        let mut kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+        let rt = &mut jstz_core::Runtime::new().expect("Could not create runtime");
+
+        let b_code = r#"
+            export default () => {
+                console.log("callee");
+                return new Response();
+            };
+        "#;
+        let b = deploy(hrt, &mut tx, b_code, 0);
+
+        let a_code = format!(
+            r#"
+            export default async () => {{
+                console.log("caller");
+                return Contract.call(new Request("tezos://{b}/"));
+            }};
+            "#
+        );
+        let a = deploy(hrt, &mut tx, &a_code, 0);
+
+        kv.commit_transaction(hrt, tx).expect("Could not commit tx");
+
+        // Act
+        let mut tx = kv.begin_transaction();
+        let operation = OperationContext::new(OperationHash::default(), console);
+        let result = runtime::with_host_runtime(hrt, || {
+            Script::with_transaction(&mut tx, rt, |rt| {
+                jstz_core::future::block_on(async {
+                    let request = JsNativeObject::new::<RequestClass>(
+                        Request::from_http_request(
+                            http::Request::builder()
+                                .uri(format!("tezos://{a}/"))
+                                .body(None)
+                                .unwrap(),
+                            rt,
+                        )?,
+                        rt,
+                    )?;
+
+                    let result =
+                        Script::load_init_run_with(&a, request.inner(), &operation, rt)?;
+
+                    rt.resolve_value(&result).await
+                })
+            })
+        })
+        .expect("Could not run script");
+
+        let response = Response::try_from_js(&result).expect("Expected a response");
+        assert_eq!(response.status(), 200);
+
+        operation.logs.take()
+    }
+
+    #[test]
+    fn test_proto_console_collects_nested_logs() {
+        let texts: Vec<_> = logs_of_nested_call(ConsoleKind::Proto)
+            .into_iter()
+            .map(|record| record.text)
+            .collect();
+
+        assert_eq!(texts, vec!["caller", "callee"]);
+    }
+
+    #[test]
+    fn test_cli_console_is_kept_by_nested_calls() {
+        // The CLI console prints its messages instead of collecting them, both
+        // for the contract run from the CLI and for the contract it calls
+        let logs = logs_of_nested_call(ConsoleKind::Cli {
+            level: jstz_api::LogLevel::default(),
+        });
+
+        assert!(logs.is_empty());
+    }
+}
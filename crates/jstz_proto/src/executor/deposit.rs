@@ -9,5 +9,5 @@ pub fn execute(
 ) -> Result<()> {
     let Deposit { amount, reciever } = deposit;
 
-    Account::deposit(hrt, tx, &reciever, amount)
+    Account::mint(hrt, tx, &reciever, amount)
 }
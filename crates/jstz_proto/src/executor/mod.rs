@@ -1,14 +1,45 @@
 use jstz_core::{host::HostRuntime, kv::Transaction};
 
 use crate::{
-    operation::{self, ExternalOperation, Operation, SignedOperation},
+    context::account::{Account, Address},
+    operation::{self, ExternalOperation, Operation, OperationHash, SignedOperation},
     receipt::{self, Receipt},
     Result,
 };
 
+pub mod batch;
 pub mod contract;
 pub mod deposit;
 
+/// Executes the content of an operation of `source`
+fn execute_content(
+    hrt: &mut (impl HostRuntime + 'static),
+    tx: &mut Transaction,
+    source: &Address,
+    content: operation::Content,
+    operation_hash: &OperationHash,
+) -> Result<receipt::Content> {
+    match content {
+        operation::Content::DeployContract(deployment) => {
+            let result = contract::deploy::execute(hrt, tx, source, deployment)?;
+
+            Ok(receipt::Content::DeployContract(result))
+        }
+
+        operation::Content::RunContract(run) => {
+            let result = contract::run::execute(hrt, tx, source, run, operation_hash)?;
+
+            Ok(receipt::Content::RunContract(result))
+        }
+
+        operation::Content::Batch(operations) => {
+            let result = batch::execute(hrt, tx, source, operations, operation_hash)?;
+
+            Ok(receipt::Content::Batch(result))
+        }
+    }
+}
+
 fn execute_operation_inner(
     hrt: &mut (impl HostRuntime + 'static),
     tx: &mut Transaction,
@@ -17,28 +48,28 @@ fn execute_operation_inner(
     let operation = signed_operation.verify()?;
     let operation_hash = operation.hash();
 
+    // Replayed (and future) operations are rejected
     operation.verify_nonce(hrt, tx)?;
-    match operation {
-        Operation {
-            source,
-            content: operation::Content::DeployContract(deployment),
-            ..
-        } => {
-            let result = contract::deploy::execute(hrt, tx, &source, deployment)?;
 
-            Ok(receipt::Content::DeployContract(result))
-        }
+    let Operation {
+        source, content, ..
+    } = operation;
 
-        Operation {
-            content: operation::Content::RunContract(run),
-            source,
-            ..
-        } => {
-            let result = contract::run::execute(hrt, tx, &source, run, &operation_hash)?;
+    // The nonce is only consumed by operations that succeed. The writes of
+    // failed operations are rolled back, along with the nonce.
+    let savepoint = tx.savepoint();
+    let result =
+        execute_content(hrt, tx, &source, content, &operation_hash).and_then(|receipt| {
+            Account::increment_nonce(hrt, tx, &source)?;
+            Ok(receipt)
+        });
 
-            Ok(receipt::Content::RunContract(result))
-        }
+    if result.is_err() {
+        tx.rollback_to(savepoint)?;
     }
+    tx.release_savepoint(savepoint)?;
+
+    result
 }
 
 pub fn execute_external_operation(
@@ -60,3 +91,211 @@ pub fn execute_operation(
     let inner = execute_operation_inner(hrt, tx, signed_operation);
     Receipt::new(hash, inner)
 }
+
+#[cfg(test)]
+mod test {
+    use jstz_core::kv::Kv;
+    use jstz_crypto::{public_key::PublicKey, secret_key::SecretKey};
+    use tezos_smart_rollup_mock::MockHost;
+
+    use super::*;
+    use crate::{
+        context::account::Nonce,
+        executor::contract::Script,
+        operation::{Content, DeployContract, RunContract},
+        receipt::ReceiptError,
+        Error,
+    };
+
+    fn keypair(passphrase: &str) -> (SecretKey, PublicKey) {
+        jstz_crypto::keypair_from_passphrase(passphrase).expect("Could not create keys")
+    }
+
+    fn source() -> Address {
+        Address::try_from(&keypair("source").1).expect("Could not hash public key")
+    }
+
+    fn deploy() -> Content {
+        Content::DeployContract(DeployContract {
+            contract_code: "export default () => new Response()".to_string(),
+            contract_credit: 0,
+        })
+    }
+
+    fn operation(nonce: Nonce, content: Content) -> Operation {
+        Operation {
+            source: source(),
+            nonce,
+            content,
+        }
+    }
+
+    fn sign(nonce: Nonce, content: Content) -> SignedOperation {
+        let (secret_key, public_key) = keypair("source");
+        let operation = operation(nonce, content);
+        let signature = secret_key.sign(operation.hash()).unwrap();
+
+        SignedOperation::new(public_key, signature, operation)
+    }
+
+    fn nonce(hrt: &MockHost, tx: &mut Transaction) -> Nonce {
+        Account::nonce(hrt, tx, &source()).expect("Could not get nonce")
+    }
+
+    #[test]
+    fn test_next_nonce_is_accepted_and_consumed() {
+        let hrt = &mut MockHost::default();
+        let mut tx = Kv::new().begin_transaction();
+
+        // Act
+        let receipt = execute_operation(hrt, &mut tx, sign(Nonce::default(), deploy()));
+
+        // Assert
+        assert!(receipt.inner.is_ok());
+        assert_eq!(nonce(hrt, &mut tx), Nonce::default().next());
+    }
+
+    #[test]
+    fn test_replayed_operation_is_rejected() {
+        let hrt = &mut MockHost::default();
+        let mut tx = Kv::new().begin_transaction();
+
+        let receipt = execute_operation(hrt, &mut tx, sign(Nonce::default(), deploy()));
+        assert!(receipt.inner.is_ok());
+
+        // Act
+        let receipt = execute_operation(hrt, &mut tx, sign(Nonce::default(), deploy()));
+
+        // Assert
+        assert_eq!(
+            receipt.inner.unwrap_err(),
+            ReceiptError::InvalidNonce {
+                expected: Nonce::default().next(),
+                got: Nonce::default(),
+            }
+        );
+        assert_eq!(nonce(hrt, &mut tx), Nonce::default().next());
+    }
+
+    #[test]
+    fn test_future_nonce_is_rejected() {
+        let hrt = &mut MockHost::default();
+        let mut tx = Kv::new().begin_transaction();
+
+        // Act
+        let receipt =
+            execute_operation(hrt, &mut tx, sign(Nonce::default().next(), deploy()));
+
+        // Assert
+        assert_eq!(
+            receipt.inner.unwrap_err(),
+            ReceiptError::InvalidNonce {
+                expected: Nonce::default(),
+                got: Nonce::default().next(),
+            }
+        );
+        assert_eq!(nonce(hrt, &mut tx), Nonce::default());
+    }
+
+    #[test]
+    fn test_reverted_operation_does_not_consume_nonce() {
+        let hrt = &mut MockHost::default();
+        let mut tx = Kv::new().begin_transaction();
+
+        let code = "export default () => new Response(null, { status: 500 })";
+        let address = Script::deploy(hrt, &mut tx, &source(), code.to_string(), 0)
+            .expect("Could not deploy script");
+        let run = Content::RunContract(RunContract {
+            uri: format!("tezos://{address}/").parse().unwrap(),
+            method: http::Method::GET,
+            headers: http::HeaderMap::new(),
+            body: None,
+            amount: 0,
+            fuel_limit: 1_000_000,
+        });
+
+        // Act
+        let receipt = execute_operation(hrt, &mut tx, sign(Nonce::default(), run));
+
+        // Assert
+        assert!(matches!(
+            receipt.inner,
+            Err(ReceiptError::ContractReverted { status: 500, .. })
+        ));
+        assert_eq!(nonce(hrt, &mut tx), Nonce::default());
+    }
+
+    #[test]
+    fn test_valid_signature_is_accepted() {
+        let (_, public_key) = keypair("source");
+
+        let verified = sign(Nonce::default(), deploy())
+            .verify()
+            .expect("Expected a valid signature");
+
+        assert_eq!(verified, operation(Nonce::default(), deploy()));
+        assert_eq!(Address::try_from(&public_key).unwrap(), verified.source);
+    }
+
+    #[test]
+    fn test_tampered_operation_is_rejected() {
+        let hrt = &mut MockHost::default();
+        let mut tx = Kv::new().begin_transaction();
+
+        let (secret_key, public_key) = keypair("source");
+        let signed = operation(Nonce::default(), deploy());
+        let signature = secret_key.sign(signed.hash()).unwrap();
+
+        // The signed deployment is swapped for another one
+        let tampered = operation(
+            Nonce::default(),
+            Content::DeployContract(DeployContract {
+                contract_code: "export default () => new Response('pwned')".to_string(),
+                contract_credit: 0,
+            }),
+        );
+
+        // Act
+        let receipt = execute_operation(
+            hrt,
+            &mut tx,
+            SignedOperation::new(public_key, signature, tampered),
+        );
+
+        // Assert
+        assert_eq!(receipt.inner.unwrap_err(), ReceiptError::InvalidSignature);
+        assert_eq!(nonce(hrt, &mut tx), Nonce::default());
+    }
+
+    #[test]
+    fn test_wrong_key_is_rejected() {
+        let hash = operation(Nonce::default(), deploy()).hash();
+        let (source_secret_key, source_public_key) = keypair("source");
+        let (other_secret_key, other_public_key) = keypair("other");
+
+        // Signed and declared by another key than the source's
+        let signature = other_secret_key.sign(&hash).unwrap();
+        let signed = SignedOperation::new(
+            other_public_key.clone(),
+            signature,
+            operation(Nonce::default(), deploy()),
+        );
+        assert!(matches!(signed.verify(), Err(Error::InvalidSignature)));
+
+        // Signed by another key, but declared with the source's
+        let signed = SignedOperation::new(
+            source_public_key.clone(),
+            other_secret_key.sign(&hash).unwrap(),
+            operation(Nonce::default(), deploy()),
+        );
+        assert!(matches!(signed.verify(), Err(Error::InvalidSignature)));
+
+        // Signed by the source, but declared with another key
+        let signed = SignedOperation::new(
+            other_public_key,
+            source_secret_key.sign(&hash).unwrap(),
+            operation(Nonce::default(), deploy()),
+        );
+        assert!(matches!(signed.verify(), Err(Error::InvalidSignature)));
+    }
+}
@@ -1,3 +1,4 @@
+pub mod abi;
 pub mod api;
 mod error;
 
@@ -5,5 +6,7 @@ pub mod context;
 pub mod executor;
 pub mod operation;
 pub mod receipt;
+#[cfg(test)]
+mod test_utils;
 
 pub use error::{Error, Result};
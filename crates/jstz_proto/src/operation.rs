@@ -1,4 +1,7 @@
-use http::{HeaderMap, Method, Uri};
+use http::{
+    header::{HeaderValue, CONTENT_TYPE},
+    HeaderMap, Method, Uri,
+};
 use jstz_api::http::body::HttpBody;
 use jstz_core::{host::HostRuntime, kv::Transaction};
 use jstz_crypto::{hash::Blake2b, public_key::PublicKey, signature::Signature};
@@ -29,8 +32,8 @@ impl Operation {
         &self.nonce
     }
 
-    /// Verify the nonce of the operation
-    /// Returns the operation's
+    /// Verifies that the nonce of the operation is the next nonce of its
+    /// source. The nonce is not consumed.
     pub fn verify_nonce(
         &self,
         rt: &impl HostRuntime,
@@ -38,11 +41,13 @@ impl Operation {
     ) -> Result<()> {
         let next_nonce = Account::nonce(rt, tx, &self.source)?;
 
-        if self.nonce == *next_nonce {
-            next_nonce.increment();
+        if self.nonce == next_nonce {
             Ok(())
         } else {
-            Err(Error::InvalidNonce)
+            Err(Error::InvalidNonce {
+                expected: next_nonce,
+                got: self.nonce,
+            })
         }
     }
 
@@ -73,19 +78,41 @@ impl Operation {
                 method,
                 headers,
                 body,
-                ..
+                amount,
+                fuel_limit,
             }) => Blake2b::from(
                 format!(
-                    "{}{}{}{}{:?}{:?}",
+                    "{}{}{}{}{:?}{:?}{}{}",
                     source.to_string(),
                     nonce.to_string(),
                     uri,
                     method,
                     headers,
-                    body
+                    body,
+                    amount,
+                    fuel_limit
                 )
                 .as_bytes(),
             ),
+            Content::Batch(Batch(contents)) => {
+                let hashes: String = contents
+                    .iter()
+                    .map(|content| {
+                        Operation {
+                            source: source.clone(),
+                            nonce: *nonce,
+                            content: content.clone(),
+                        }
+                        .hash()
+                        .to_string()
+                    })
+                    .collect();
+
+                Blake2b::from(
+                    format!("{}{}{}", source.to_string(), nonce.to_string(), hashes)
+                        .as_bytes(),
+                )
+            }
         }
     }
 }
@@ -105,12 +132,69 @@ pub struct RunContract {
     #[serde(with = "http_serde::header_map")]
     pub headers: HeaderMap,
     pub body: HttpBody,
+    /// The amount transferred from the source to the contract, exposed to the
+    /// contract as `Contract.callValue`
+    #[serde(default)]
+    pub amount: Amount,
+    /// The maximum fuel the operation may consume (see `Runtime::set_fuel`)
+    #[serde(default = "default_fuel_limit")]
+    pub fuel_limit: u64,
+}
+
+fn default_fuel_limit() -> u64 {
+    RunContract::DEFAULT_FUEL_LIMIT
+}
+
+impl RunContract {
+    /// The fuel limit of operations that don't specify one
+    pub const DEFAULT_FUEL_LIMIT: u64 = 1_000_000;
+
+    /// Creates a request to `uri` whose body is the JSON serialization of
+    /// `value`, with an `application/json` content type. The request
+    /// transfers no amount and uses the default fuel limit.
+    pub fn json(uri: Uri, method: Method, value: &serde_json::Value) -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        Self {
+            uri,
+            method,
+            headers,
+            body: Some(value.to_string().into_bytes()),
+            amount: 0,
+            fuel_limit: Self::DEFAULT_FUEL_LIMIT,
+        }
+    }
 }
 
+/// A read-only request to a contract. Views are not signed operations: they
+/// are run against the current state and their effects are never committed.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct RunView {
+    #[serde(with = "http_serde::uri")]
+    pub uri: Uri,
+    #[serde(with = "http_serde::method")]
+    pub method: Method,
+    #[serde(with = "http_serde::header_map")]
+    pub headers: HeaderMap,
+    pub body: HttpBody,
+    /// The maximum fuel the view may consume (see `Runtime::set_fuel`)
+    #[serde(default = "default_fuel_limit")]
+    pub fuel_limit: u64,
+}
+
+/// Operations that either all succeed or are all rolled back. The operations
+/// of a batch are signed once, and share the source and nonce of the batch.
+/// Each deployment of a batch advances the nonce of the source, so that the
+/// contracts it deploys have distinct addresses. Batches may not be nested.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Batch(pub Vec<Content>);
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub enum Content {
     DeployContract(DeployContract),
     RunContract(RunContract),
+    Batch(Batch),
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -133,10 +217,18 @@ impl SignedOperation {
         self.inner.hash()
     }
 
+    /// Checks that the operation was signed by its source, returning the
+    /// operation. Fails with `Error::InvalidSignature` if the public key is not
+    /// the source's or the signature does not match the operation.
     pub fn verify(self) -> Result<Operation> {
-        // FIXME: Adding signature verification kills to the rollup???!??!?!?!
+        if Address::try_from(&self.public_key)? != self.inner.source {
+            return Err(Error::InvalidSignature);
+        }
+
         let hash = self.inner.hash();
-        self.signature.verify(&self.public_key, hash.as_ref())?;
+        self.signature
+            .verify(&self.public_key, hash.as_ref())
+            .map_err(|_| Error::InvalidSignature)?;
 
         Ok(self.inner)
     }
@@ -156,3 +248,56 @@ pub mod external {
 pub enum ExternalOperation {
     Deposit(external::Deposit),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_utils::{deploy, execute, source};
+    use jstz_core::kv::Kv;
+    use tezos_smart_rollup_mock::MockHost;
+
+    #[test]
+    fn test_run_with_json_body() {
+        let hrt = &mut MockHost::default();
+        let kv = Kv::new();
+        let mut tx = kv.begin_transaction();
+
+        let code = r#"
+            export default async (request) => {
+                const { name, tags } = await request.json();
+                return Response.json({
+                    name,
+                    tags,
+                    method: request.method,
+                    contentType: request.headers.get("Content-Type"),
+                    custom: request.headers.get("X-Custom"),
+                });
+            };
+        "#;
+        let address = deploy(hrt, &mut tx, code, 0);
+
+        let mut run = RunContract::json(
+            format!("tezos://{address}/").parse().unwrap(),
+            http::Method::POST,
+            &serde_json::json!({ "name": "jstz", "tags": ["a", "b"] }),
+        );
+        run.headers
+            .insert("X-Custom", http::HeaderValue::from_static("custom"));
+
+        let receipt =
+            execute(hrt, &mut tx, &source(), run).expect("Could not run contract");
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&receipt.body.expect("Expected a body")).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "name": "jstz",
+                "tags": ["a", "b"],
+                "method": "POST",
+                "contentType": "application/json",
+                "custom": "custom",
+            })
+        );
+    }
+}
@@ -1,12 +1,102 @@
+use derive_more::Display;
 use http::{HeaderMap, StatusCode};
-use jstz_api::http::body::HttpBody;
+use jstz_api::{http::body::HttpBody, LogRecord};
 use serde::{Deserialize, Serialize};
 
-use crate::{context::account::Address, operation::OperationHash, Result};
+use crate::{
+    api::{CallFrame, Event},
+    context::account::{Address, Nonce},
+    operation::OperationHash,
+    Error, Result,
+};
 
-pub type ReceiptResult<T> = std::result::Result<T, String>;
+pub type ReceiptResult<T> = std::result::Result<T, ReceiptError>;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The reason an operation failed, as recorded in its receipt
+#[derive(Display, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReceiptError {
+    BalanceOverflow,
+    InsufficientFunds,
+    #[display(fmt = "InvalidNonce (expected {}, got {})", expected, got)]
+    InvalidNonce {
+        expected: Nonce,
+        got: Nonce,
+    },
+    InvalidAddress,
+    InvalidSignature,
+    RefererShouldNotBeSet,
+    OutOfGas,
+    CallDepthExceeded,
+    ReentrancyDetected,
+    Unauthorized,
+    #[display(fmt = "InvalidContractCode: {}", message)]
+    InvalidContractCode {
+        message: String,
+    },
+    #[display(fmt = "ContractReverted ({}): {}", status, message)]
+    ContractReverted {
+        status: u16,
+        message: String,
+        logs: Vec<LogRecord>,
+    },
+    Timeout,
+    ReadOnlyViolation,
+    #[display(fmt = "BatchFailed ({}): {}", index, error)]
+    BatchFailed {
+        index: usize,
+        error: Box<ReceiptError>,
+    },
+    NestedBatch,
+    #[display(fmt = "CryptoError: {}", _0)]
+    CryptoError(String),
+    /// Any other failure, such as an uncaught JavaScript exception
+    #[display(fmt = "RuntimeError: {}", _0)]
+    RuntimeError(String),
+}
+
+impl From<Error> for ReceiptError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::BalanceOverflow => Self::BalanceOverflow,
+            Error::InsufficientFunds => Self::InsufficientFunds,
+            Error::InvalidNonce { expected, got } => Self::InvalidNonce { expected, got },
+            Error::InvalidAddress => Self::InvalidAddress,
+            Error::InvalidSignature => Self::InvalidSignature,
+            Error::RefererShouldNotBeSet => Self::RefererShouldNotBeSet,
+            Error::OutOfGas => Self::OutOfGas,
+            Error::CallDepthExceeded => Self::CallDepthExceeded,
+            Error::ReentrancyDetected => Self::ReentrancyDetected,
+            Error::Unauthorized => Self::Unauthorized,
+            Error::InvalidContractCode { message } => {
+                Self::InvalidContractCode { message }
+            }
+            Error::ContractReverted {
+                status,
+                message,
+                logs,
+            } => Self::ContractReverted {
+                status,
+                message,
+                logs,
+            },
+            Error::BatchFailed { index, source } => Self::BatchFailed {
+                index,
+                error: Box::new(Self::from(*source)),
+            },
+            Error::NestedBatch => Self::NestedBatch,
+            Error::CoreError {
+                source: jstz_core::Error::Timeout,
+            } => Self::Timeout,
+            Error::CoreError {
+                source: jstz_core::Error::ReadOnlyViolation,
+            } => Self::ReadOnlyViolation,
+            Error::CoreError { source } => Self::RuntimeError(source.to_string()),
+            Error::CryptoError { source } => Self::CryptoError(source.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Receipt {
     hash: OperationHash,
     pub inner: ReceiptResult<Content>,
@@ -14,7 +104,7 @@ pub struct Receipt {
 
 impl Receipt {
     pub fn new(hash: OperationHash, inner: Result<Content>) -> Self {
-        let inner = inner.map_err(|e| e.to_string());
+        let inner = inner.map_err(ReceiptError::from);
         Self { hash, inner }
     }
 
@@ -23,22 +113,163 @@ impl Receipt {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeployContract {
     pub contract_address: Address,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RunContract {
     pub body: HttpBody,
     #[serde(with = "http_serde::status_code")]
     pub status_code: StatusCode,
     #[serde(with = "http_serde::header_map")]
     pub headers: HeaderMap,
+    /// The fuel consumed by the operation, including nested calls
+    pub gas_used: u64,
+    /// The records logged by the contract and its nested calls
+    pub logs: Vec<LogRecord>,
+    /// The events emitted by the contract and its nested calls that have
+    /// not been reverted
+    pub events: Vec<Event>,
+    /// The call to the contract and its nested calls, including reverted
+    /// ones, in the order in which they were made
+    pub call_trace: Vec<CallFrame>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The outcome of a simulated `RunContract` operation, whose effects are
+/// never persisted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulateRunContract {
+    /// The receipt the operation would have had, or the reason it would have
+    /// failed
+    pub result: ReceiptResult<RunContract>,
+    /// The fuel consumed by the operation, including nested calls, until it
+    /// completed or failed
+    pub gas_used: u64,
+    /// The records logged by the contract and its nested calls, even if the
+    /// operation failed
+    pub logs: Vec<LogRecord>,
+    /// The events emitted by the contract and its nested calls that have
+    /// not been reverted
+    pub events: Vec<Event>,
+    /// The call to the contract and its nested calls, including reverted
+    /// ones, in the order in which they were made
+    pub call_trace: Vec<CallFrame>,
+    /// The keys of the key-value store the operation wrote, including writes
+    /// that were rolled back because a call reverted or the operation failed
+    pub changed_keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Content {
     DeployContract(DeployContract),
     RunContract(RunContract),
+    /// The receipts of the operations of a batch, in order
+    Batch(Vec<Receipt>),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip(error: Error) -> ReceiptError {
+        let receipt = Receipt::new(OperationHash::default(), Err(error));
+        let json = serde_json::to_string(&receipt).expect("Could not serialize");
+        let receipt: Receipt =
+            serde_json::from_str(&json).expect("Could not deserialize");
+
+        receipt.inner.expect_err("Expected an error")
+    }
+
+    #[test]
+    fn test_error_variants_round_trip() {
+        let cases = [
+            (Error::BalanceOverflow, ReceiptError::BalanceOverflow),
+            (Error::InsufficientFunds, ReceiptError::InsufficientFunds),
+            (
+                Error::InvalidNonce {
+                    expected: Nonce::default().next(),
+                    got: Nonce::default(),
+                },
+                ReceiptError::InvalidNonce {
+                    expected: Nonce::default().next(),
+                    got: Nonce::default(),
+                },
+            ),
+            (Error::InvalidAddress, ReceiptError::InvalidAddress),
+            (Error::InvalidSignature, ReceiptError::InvalidSignature),
+            (
+                Error::RefererShouldNotBeSet,
+                ReceiptError::RefererShouldNotBeSet,
+            ),
+            (Error::OutOfGas, ReceiptError::OutOfGas),
+            (Error::CallDepthExceeded, ReceiptError::CallDepthExceeded),
+            (Error::ReentrancyDetected, ReceiptError::ReentrancyDetected),
+            (Error::Unauthorized, ReceiptError::Unauthorized),
+            (
+                Error::InvalidContractCode {
+                    message: "no default export".to_string(),
+                },
+                ReceiptError::InvalidContractCode {
+                    message: "no default export".to_string(),
+                },
+            ),
+            (
+                Error::BatchFailed {
+                    index: 1,
+                    source: Box::new(Error::OutOfGas),
+                },
+                ReceiptError::BatchFailed {
+                    index: 1,
+                    error: Box::new(ReceiptError::OutOfGas),
+                },
+            ),
+            (
+                Error::ContractReverted {
+                    status: 403,
+                    message: "forbidden".to_string(),
+                    logs: vec![],
+                },
+                ReceiptError::ContractReverted {
+                    status: 403,
+                    message: "forbidden".to_string(),
+                    logs: vec![],
+                },
+            ),
+            (
+                Error::CoreError {
+                    source: jstz_core::Error::Timeout,
+                },
+                ReceiptError::Timeout,
+            ),
+            (
+                Error::CoreError {
+                    source: jstz_core::Error::ReadOnlyViolation,
+                },
+                ReceiptError::ReadOnlyViolation,
+            ),
+            (
+                Error::CoreError {
+                    source: jstz_core::Error::TransactionConflict,
+                },
+                ReceiptError::RuntimeError("TransactionConflict".to_string()),
+            ),
+        ];
+
+        for (error, expected) in cases {
+            assert_eq!(round_trip(error), expected);
+        }
+    }
+
+    #[test]
+    fn test_error_display() {
+        let error = ReceiptError::from(Error::ContractReverted {
+            status: 500,
+            message: "boom".to_string(),
+            logs: vec![],
+        });
+
+        assert_eq!(error.to_string(), "ContractReverted (500): boom");
+    }
 }
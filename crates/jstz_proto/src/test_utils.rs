@@ -0,0 +1,140 @@
+//! Helpers shared by the tests of the crate
+
+use jstz_api::{
+    http::{
+        body::HttpBody,
+        request::{Request, RequestClass},
+        response::Response,
+    },
+    ConsoleKind,
+};
+use jstz_core::{
+    host::HostRuntime,
+    kv::{Kv, Transaction},
+    native::JsNativeObject,
+    runtime,
+};
+use jstz_crypto::public_key_hash::PublicKeyHash;
+use tezos_smart_rollup_mock::MockHost;
+
+use crate::{
+    context::account::{Account, Address, Amount},
+    executor::contract::{run, OperationContext, Script},
+    operation::{OperationHash, RunContract},
+    receipt, Result,
+};
+
+/// The account that deploys and calls the contracts of the tests
+pub fn source() -> Address {
+    PublicKeyHash::from_base58("tz1XQjK1b3P72kMcHsoPhnAg3dvX1n8Ainty")
+        .expect("Could not parse pkh")
+}
+
+/// An account other than [`source`], with no rights over its contracts
+pub fn other() -> Address {
+    PublicKeyHash::from_base58("tz1ZXxxkNYSUutm5VZvfudakRxx2mjpWko4J")
+        .expect("Could not parse pkh")
+}
+
+/// Deploys `code` from [`source`] with an initial balance of `balance`
+pub fn deploy(
+    hrt: &impl HostRuntime,
+    tx: &mut Transaction,
+    code: &str,
+    balance: Amount,
+) -> Address {
+    Script::deploy(hrt, tx, &source(), code.to_string(), balance)
+        .expect("Could not deploy script")
+}
+
+/// Returns a `GET` request to `path` of the contract at `address`, which
+/// transfers nothing and has enough fuel for the contracts of the tests
+pub fn request(address: &Address, path: &str) -> RunContract {
+    RunContract {
+        uri: format!("tezos://{address}{path}").parse().unwrap(),
+        method: http::Method::GET,
+        headers: http::HeaderMap::new(),
+        body: None,
+        amount: 0,
+        fuel_limit: 1_000_000,
+    }
+}
+
+/// Runs `run` in `tx` as an operation of `source`
+pub fn execute(
+    hrt: &mut MockHost,
+    tx: &mut Transaction,
+    source: &Address,
+    run: RunContract,
+) -> Result<receipt::RunContract> {
+    run::execute(hrt, tx, source, run, &OperationHash::default())
+}
+
+/// Calls the contract at `address` with a `GET` request in `tx`, outside of
+/// an operation, returning its response
+pub fn call(
+    hrt: &mut MockHost,
+    tx: &mut Transaction,
+    address: &Address,
+) -> http::Response<HttpBody> {
+    let rt = &mut jstz_core::Runtime::new().expect("Could not create runtime");
+
+    let result = runtime::with_host_runtime(hrt, || {
+        Script::with_transaction(tx, rt, |rt| {
+            jstz_core::future::block_on(async move {
+                let request = JsNativeObject::new::<RequestClass>(
+                    Request::from_http_request(
+                        http::Request::builder()
+                            .uri(format!("tezos://{address}/"))
+                            .body(None)
+                            .unwrap(),
+                        rt,
+                    )?,
+                    rt,
+                )?;
+
+                let result = Script::load_init_run_with(
+                    address,
+                    request.inner(),
+                    &OperationContext::new(OperationHash::default(), ConsoleKind::Proto),
+                    rt,
+                )?;
+
+                rt.resolve_value(&result).await
+            })
+        })
+    })
+    .expect("Could not run script");
+
+    Response::try_from_js(&result)
+        .expect("Expected a response")
+        .to_http_response()
+}
+
+/// Deploys `code` with a balance of 50 and runs it as an operation of
+/// [`source`]. The operation is committed whatever its outcome, as in the
+/// kernel. Returns the host, the address of the contract and the body of its
+/// response.
+pub fn run_contract(code: &str) -> (MockHost, Address, Result<Vec<u8>>) {
+    let mut hrt = MockHost::default();
+    let mut kv = Kv::new();
+
+    let mut tx = kv.begin_transaction();
+    let address = deploy(&hrt, &mut tx, code, 50);
+    kv.commit_transaction(&mut hrt, tx)
+        .expect("Could not commit tx");
+
+    let mut tx = kv.begin_transaction();
+    let result = execute(&mut hrt, &mut tx, &source(), request(&address, "/"))
+        .map(|receipt| receipt.body.unwrap_or_default());
+    kv.commit_transaction(&mut hrt, tx)
+        .expect("Could not commit tx");
+
+    (hrt, address, result)
+}
+
+/// Returns the committed balance of `address`
+pub fn committed_balance(hrt: &MockHost, address: &Address) -> Amount {
+    Account::balance(hrt, &mut Kv::new().begin_transaction(), address)
+        .expect("Could not get balance")
+}